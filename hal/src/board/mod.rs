@@ -10,6 +10,28 @@ mod loongarch64;
 #[cfg(target_arch = "loongarch64")]
 pub use loongarch64::*;
 
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::constant::{Constant, ConstantsHal};
+
+/// physical address of the device tree the firmware passed, 0 if none
+static BOOT_DTB: AtomicUsize = AtomicUsize::new(0);
+
+/// record the device tree the firmware passed at boot
+#[allow(unused)]
+pub(crate) fn set_boot_device_tree(pa: usize) {
+    BOOT_DTB.store(pa, Ordering::Relaxed);
+}
+
+/// the device tree the firmware passed at boot, it tells what the boot
+/// loader did, e.g. where it left the initrd
+pub fn get_boot_device_tree_addr() -> Option<usize> {
+    match BOOT_DTB.load(Ordering::Relaxed) {
+        0 => None,
+        pa => Some(pa | Constant::KERNEL_ADDR_SPACE.start),
+    }
+}
+
 pub fn get_device_tree_addr() -> usize {
     unsafe extern "C" {
         fn _dtb_start();
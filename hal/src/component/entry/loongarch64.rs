@@ -12,6 +12,7 @@ const VIRT_RAM_OFFSET: usize = Constant::KERNEL_ADDR_SPACE.start;
 unsafe extern "C" fn _start() -> ! {
     core::arch::naked_asm!(
         r"
+        move         $a1, $a2                     # efi system table from the firmware
         csrrd        $a0, 0x20                    # cpuid
         move         $tp, $a0
         addi.d       $t0, $a0, 1                  # t0 = hart_id + 1
@@ -37,9 +38,9 @@ unsafe extern "C" fn _start() -> ! {
 
         li.d         $t2, {virt_ram_offset}       
         or           $sp, $sp, $t2
-        la.global    $a2, {entry}
-        or           $a2, $a2, $t2
-        jirl         $zero, $a2, 0                # call rust_main
+        la.global    $t3, {entry}
+        or           $t3, $t3, $t2
+        jirl         $zero, $t3, 0                # call rust_main
         ",
         boot_stack_size = const Constant::KERNEL_STACK_SIZE,
         boot_stack = sym BOOT_STACK,
@@ -48,12 +49,17 @@ unsafe extern "C" fn _start() -> ! {
     );
 }
 
-pub(crate) fn rust_main(id: usize) {
+/// `systab` is the efi system table the firmware passes in a2, the device
+/// tree is one of its config tables
+pub(crate) fn rust_main(id: usize, systab: usize) {
     let is_first = RUNNING_PROCESSOR.fetch_add(1, Ordering::AcqRel) == 0;
     Instruction::set_tp(id);
     tlb_init();
     if is_first {
         super::clear_bss();
+        if let Some(dtb) = find_boot_dtb(systab) {
+            crate::board::set_boot_device_tree(dtb);
+        }
         crate::console::init();
         print_info();
         let _ = unsafe { super::_main_for_arch(id, true) };
@@ -67,6 +73,40 @@ pub(crate) fn rust_main(id: usize) {
 }
 
 
+/// guid of the device tree in the efi config tables
+const DEVICE_TREE_GUID: [u8; 16] = [
+    0xd5, 0x21, 0xb6, 0xb1, 0x9c, 0xf1, 0xa5, 0x41,
+    0x83, 0x0b, 0xd9, 0x15, 0x2c, 0x69, 0xaa, 0xe0,
+];
+
+/// physical address of the device tree in the efi system table at `systab`,
+/// checked against the fdt magic so a bogus a2 is ignored
+fn find_boot_dtb(systab: usize) -> Option<usize> {
+    const FDT_MAGIC: u32 = 0xd00d_feed;
+    // offsets of nr_tables and tables in efi_system_table
+    const NR_TABLES: usize = 104;
+    const TABLES: usize = 112;
+    const MAX_TABLES: usize = 64;
+    // the tables live in ram, below the cached window
+    let window = |pa: usize| (pa < VIRT_RAM_OFFSET && pa != 0).then(|| pa | VIRT_RAM_OFFSET);
+    let systab = window(systab)?;
+    unsafe {
+        let nr = ((systab + NR_TABLES) as *const usize).read_volatile();
+        let tables = window(((systab + TABLES) as *const usize).read_volatile())?;
+        for i in 0..nr.min(MAX_TABLES) {
+            // efi_config_table: a guid and a pointer
+            let entry = tables + i * 24;
+            if (entry as *const [u8; 16]).read_volatile() != DEVICE_TREE_GUID {
+                continue;
+            }
+            let dtb = ((entry + 16) as *const usize).read_volatile();
+            let va = window(dtb)?;
+            return (u32::from_be((va as *const u32).read_volatile()) == FDT_MAGIC).then_some(dtb);
+        }
+    }
+    None
+}
+
 fn print_info() {
     println!("\u{1B}[36m\n{}\u{1B}[0m", super::BANNER);
     println!("[CINPHAL] IOCSR Support: {}", loongArch64::cpu::get_support_iocsr());
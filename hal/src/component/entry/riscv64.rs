@@ -65,10 +65,12 @@ unsafe extern "C" fn _start(id: usize) -> ! {
     )
 }
 
-pub(crate) fn rust_main(id: usize) {
+/// `dtb` is the device tree sbi passes in a1, untouched by `_start`
+pub(crate) fn rust_main(id: usize, dtb: usize) {
     Instruction::set_tp(id);
     if RUNNING_PROCESSOR.fetch_add(1, Ordering::AcqRel) == 0 {
         super::clear_bss();
+        crate::board::set_boot_device_tree(dtb);
         crate::console::init();
         print_info();
         let _ = unsafe { super::_main_for_arch(id, true) };
@@ -135,6 +135,11 @@ bitflags::bitflags! {
         const W = 1 << 8;
         /// Page is CoW
         const C = 1 << 9;
+        /// Swap entry, only when not valid
+        const S = 1 << 10;
+        /// Mapped but not accessed since the accessed bit was cleared, V is
+        /// off meanwhile so the next access faults, there is no hardware A bit
+        const OLD = 1 << 11;
         // /// Is a Global Page if using huge page(GH bit).
         // const G = 1 << 12;
         /// Page is not readable.
@@ -244,11 +249,29 @@ impl PageTableEntryHal for PageTableEntry {
         }
     }
 
+    /// emulated with `OLD`, the fault handler sets it again
+    fn is_accessed(&self) -> bool {
+        !self.pteflags().contains(PTEFlags::OLD)
+    }
+
+    fn set_accessed(&mut self, val: bool) {
+        let flags = self.pteflags();
+        if val && flags.contains(PTEFlags::OLD) {
+            self.bits &= !(PTEFlags::OLD.bits as usize);
+            self.bits |= PTEFlags::V.bits as usize;
+        } else if !val && flags.contains(PTEFlags::V) {
+            self.bits &= !(PTEFlags::V.bits as usize);
+            self.bits |= PTEFlags::OLD.bits as usize;
+        }
+    }
+
+    /// an old page is still mapped, only hidden from the hardware
     fn is_valid(&self) -> bool {
-        self.pteflags().contains(PTEFlags::V)
+        self.pteflags().intersects(PTEFlags::V | PTEFlags::OLD)
     }
     
     fn set_valid(&mut self, val: bool) {
+        self.bits &= !(PTEFlags::OLD.bits as usize);
         if val {
             self.bits |= PTEFlags::V.bits as usize
         } else {
@@ -306,6 +329,13 @@ impl<A: FrameAllocatorHal + Clone> PageTable<A> {
         }
         result
     }
+
+    fn swap_entry(pte: &PageTableEntry) -> Option<usize> {
+        if pte.is_valid() || !pte.pteflags().contains(PTEFlags::S) {
+            return None;
+        }
+        Some((pte.bits & PageTableEntry::PPN_MASK) >> 12)
+    }
 }
 
 impl<A: FrameAllocatorHal + Clone> PageTableHal<PageTableEntry, A> for PageTable<A> {
@@ -395,6 +425,28 @@ impl<A: FrameAllocatorHal + Clone> PageTableHal<PageTableEntry, A> for PageTable
         }
     }
 
+    fn map_swap(&mut self, vpn: VirtPageNum, entry: usize) -> Result<(), ()> {
+        let pte = self.find_pte_create(vpn, PageLevel::Small).ok_or(())?;
+        if pte.is_valid() {
+            return Err(());
+        }
+        // not readable nor executable, so it is never taken as a mapping
+        pte.bits = ((entry << 12) & PageTableEntry::PPN_MASK)
+            | (PTEFlags::S | PTEFlags::NR | PTEFlags::NX).bits;
+        Ok(())
+    }
+
+    fn find_swap(&self, vpn: VirtPageNum) -> Option<usize> {
+        self.find_pte(vpn).and_then(|(pte, _)| Self::swap_entry(pte))
+    }
+
+    fn unmap_swap(&mut self, vpn: VirtPageNum) -> Option<usize> {
+        let (pte, _) = self.find_pte(vpn)?;
+        let entry = Self::swap_entry(pte)?;
+        pte.bits = 0;
+        Some(entry)
+    }
+
     unsafe fn enable_high(&self) {
         register::asid::set_asid(0);
         register::pgdh::set_base(self.get_token());
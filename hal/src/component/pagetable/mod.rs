@@ -86,6 +86,10 @@ pub trait PageTableEntryHal {
     fn is_dirty(&self) -> bool;
 
     fn set_dirty(&mut self, val: bool);
+
+    fn is_accessed(&self) -> bool;
+
+    fn set_accessed(&mut self, val: bool);
     
     fn is_leaf(&self) -> bool;
 }
@@ -99,6 +103,12 @@ pub trait PageTableHal<PTE: PageTableEntryHal, A: FrameAllocatorHal> {
     fn find_pte(&self, vpn: VirtPageNum) -> Option<(&mut PTE, usize)>;
     fn map(&mut self, vpn: VirtPageNum, ppn: PhysPageNum, perm: MapPerm, level: PageLevel) -> Result<&mut PTE, ()>;
     fn unmap(&mut self, vpn: VirtPageNum) -> Result<PTE, ()>;
+    /// leave the swap entry `entry` at `vpn`, the hardware takes it as unmapped
+    fn map_swap(&mut self, vpn: VirtPageNum, entry: usize) -> Result<(), ()>;
+    /// the swap entry left at `vpn`
+    fn find_swap(&self, vpn: VirtPageNum) -> Option<usize>;
+    /// remove the swap entry left at `vpn`
+    fn unmap_swap(&mut self, vpn: VirtPageNum) -> Option<usize>;
     fn clear(&mut self);
     unsafe fn enable_high(&self);
     unsafe fn enable_low(&self);
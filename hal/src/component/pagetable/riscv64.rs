@@ -123,6 +123,8 @@ bitflags! {
         const D = 1 << 7;
         /// Copy On Write
         const C = 1 << 8;
+        /// Swap entry, only when not valid
+        const S = 1 << 9;
     }
 }
 
@@ -232,6 +234,18 @@ impl PageTableEntryHal for PageTableEntry {
         }
     }
     
+    fn is_accessed(&self) -> bool {
+        self.pteflags().contains(PTEFlags::A)
+    }
+
+    fn set_accessed(&mut self, val: bool) {
+        if val {
+            self.bits |= PTEFlags::A.bits as usize;
+        } else {
+            self.bits &= !(PTEFlags::A.bits as usize);
+        }
+    }
+
     fn is_valid(&self) -> bool {
         self.pteflags().contains(PTEFlags::V)
     }
@@ -289,6 +303,30 @@ impl<A: FrameAllocatorHal + Clone> PageTable<A> {
         result
     }
 
+    /// the last level entry of `vpn` even if not valid
+    fn find_leaf_pte(&self, vpn: VirtPageNum) -> Option<&mut PageTableEntry> {
+        let idxs = vpn.indexes();
+        let mut ppn = self.root_ppn;
+        for (i, idx) in idxs.iter().enumerate() {
+            let pte = &mut ppn.start_addr().get_mut::<[PageTableEntry; 512]>()[*idx];
+            if i == 2 {
+                return Some(pte);
+            }
+            if !pte.is_valid() || pte.is_leaf() {
+                return None;
+            }
+            ppn = pte.ppn();
+        }
+        None
+    }
+
+    fn swap_entry(pte: &PageTableEntry) -> Option<usize> {
+        if pte.is_valid() || !pte.pteflags().contains(PTEFlags::S) {
+            return None;
+        }
+        Some((pte.bits & PageTableEntry::PPN_MASK) >> 10)
+    }
+
 }
 
 impl<A: FrameAllocatorHal + Clone> PageTableHal<PageTableEntry, A> for PageTable<A> {
@@ -356,6 +394,26 @@ impl<A: FrameAllocatorHal + Clone> PageTableHal<PageTableEntry, A> for PageTable
         }
     }
     
+    fn map_swap(&mut self, vpn: VirtPageNum, entry: usize) -> Result<(), ()> {
+        let pte = self.find_pte_create(vpn, PageLevel::Small).ok_or(())?;
+        if pte.is_valid() {
+            return Err(());
+        }
+        pte.bits = ((entry << 10) & PageTableEntry::PPN_MASK) | PTEFlags::S.bits as usize;
+        Ok(())
+    }
+
+    fn find_swap(&self, vpn: VirtPageNum) -> Option<usize> {
+        self.find_leaf_pte(vpn).and_then(|pte| Self::swap_entry(pte))
+    }
+
+    fn unmap_swap(&mut self, vpn: VirtPageNum) -> Option<usize> {
+        let pte = self.find_leaf_pte(vpn)?;
+        let entry = Self::swap_entry(pte)?;
+        pte.bits = 0;
+        Some(entry)
+    }
+
     unsafe fn enable_high(&self) {
         asm!("csrw satp, {}", in(reg)(self.get_token()), options(nostack));
     }
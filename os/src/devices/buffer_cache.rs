@@ -37,7 +37,9 @@ pub struct BlockPage {
     pub frame: StrongArc<FrameTracker>,
     pub page_index: usize,
     /// maybe we can support pre-read
-    pub is_loaded: SpinNoIrqLock<[bool; 8]>
+    pub is_loaded: SpinNoIrqLock<[bool; 8]>,
+    /// blocks written here and not yet to the device
+    pub is_dirty: SpinNoIrqLock<[bool; 8]>,
 }
 
 impl BlockPage {
@@ -49,6 +51,7 @@ impl BlockPage {
             page_index,
             frame: StrongArc::new(frame),
             is_loaded: SpinNoIrqLock::new([false; 8]),
+            is_dirty: SpinNoIrqLock::new([false; 8]),
         })
     }
 
@@ -131,19 +134,40 @@ impl dyn BlockDevice {
                     page.is_loaded.lock()[in_page_offset] = true;
                 }
                 page.write_block_one(in_page_offset, buf);
+                page.is_dirty.lock()[in_page_offset] = true;
             } else {
                 // page not exist
-                // same as page exist but block miss
+                // the whole block is written, nothing to read first
                 let page = BlockPage::new(page_index);
-                let frame = page.frame();
-                let slice = &mut frame.range_ppn.get_slice_mut::<u8>()[start..end];
-                self.direct_write_block(block_id, slice);
                 page.is_loaded.lock()[in_page_offset] = true;
                 page.write_block_one(in_page_offset, buf);
+                page.is_dirty.lock()[in_page_offset] = true;
                 buffer_cache.insert_block_page(page_index, page);
             }
         } else {
             self.direct_write_block(block_id, buf);
         }
     }
+
+    /// write the dirty blocks of the buffer cache to the device
+    pub fn sync(&self) {
+        let Some(buffer_cache) = self.buffer_cache() else {
+            return;
+        };
+        let pages: Vec<_> = buffer_cache.get_block_pages().lock().values().cloned().collect();
+        let blocks_per_page = PAGE_SIZE / BLOCK_SIZE;
+        for page in pages {
+            let frame = page.frame();
+            for in_page_offset in 0..blocks_per_page {
+                // cleaned first, a write meanwhile dirties it again
+                if !core::mem::replace(&mut page.is_dirty.lock()[in_page_offset], false) {
+                    continue;
+                }
+                let start = BLOCK_SIZE * in_page_offset;
+                let end = BLOCK_SIZE * (in_page_offset + 1);
+                let slice = &frame.range_ppn.get_slice::<u8>()[start..end];
+                self.direct_write_block(page.page_index * blocks_per_page + in_page_offset, slice);
+            }
+        }
+    }
 }
\ No newline at end of file
@@ -1,6 +1,6 @@
 //! Device Manager 
 
-use alloc::{collections::btree_map::BTreeMap, sync::Arc, vec::Vec};
+use alloc::{collections::btree_map::BTreeMap, string::{String, ToString}, sync::Arc, vec::Vec};
 use fdt::Fdt;
 use hal::{board::MAX_PROCESSORS, constant::{Constant, ConstantsHal}, instruction::{Instruction, InstructionHal}, irq::{IrqCtrl, IrqCtrlHal}, pagetable::MapPerm, println};
 use virtio_drivers::transport::Transport;
@@ -11,6 +11,18 @@ use super::{mmio::MmioManager, pci::{PciDeviceClass, PciManager}, plic::{scan_pl
 
 type IrqNo = usize;
 
+/// a hart found in the device tree
+pub struct CpuInfo {
+    /// hart id
+    pub hart_id: usize,
+    /// isa string, e.g. rv64imafdc
+    pub isa: String,
+    /// mmu type, e.g. riscv,sv39
+    pub mmu: String,
+    /// clock frequency in Hz, 0 if unknown
+    pub freq: usize,
+}
+
 /// Chronix's device manager
 /// responsible for:
 /// Creates device instance from device tree,
@@ -26,7 +38,9 @@ pub struct DeviceManager {
     /// mapping from device id to device instance
     pub devices: BTreeMap<DevId, Arc<dyn Device>>,
     /// mapping from irq no to device instance
-    pub irq_map: BTreeMap<IrqNo, Arc<dyn Device>>
+    pub irq_map: BTreeMap<IrqNo, Arc<dyn Device>>,
+    /// harts described by the device tree
+    pub cpus: Vec<CpuInfo>,
 }
 
 impl DeviceManager {
@@ -38,6 +52,7 @@ impl DeviceManager {
             mmio: None,
             devices: BTreeMap::new(),
             irq_map: BTreeMap::new(),
+            cpus: Vec::new(),
         }
     }
 
@@ -57,6 +72,20 @@ impl DeviceManager {
     /// Device Init Stage1: scan the whole device tree and create instances
     /// map DevId to device, map IrqNo to device
     pub fn map_devices(&mut self, device_tree: &Fdt) {
+        // record the harts
+        for cpu in device_tree.cpus() {
+            let prop_str = |name: &str| cpu.property(name)
+                .and_then(|p| p.as_str())
+                .unwrap_or("")
+                .to_string();
+            self.cpus.push(CpuInfo {
+                hart_id: cpu.ids().first(),
+                isa: prop_str("riscv,isa"),
+                mmu: prop_str("mmu-type"),
+                freq: cpu.property("clock-frequency").and_then(|p| p.as_usize()).unwrap_or(0),
+            });
+        }
+
         // map char device
         let serial = scan_char_device(device_tree);
         self.devices.insert(serial.dev_id(), serial.clone());
@@ -220,6 +220,26 @@ pub fn get_device_tree_addr() -> usize {
     hal::board::get_device_tree_addr()
 }
 
+static INITRD: Once<Option<Range<usize>>> = Once::new();
+
+/// the physical range of the initrd the boot loader left in memory, from
+/// /chosen of the device tree passed at boot, or else of the built in one.
+/// first asked before the memory of the device tree can be reused
+pub fn initrd_range() -> Option<Range<usize>> {
+    let find = |addr: usize| -> Option<Range<usize>> {
+        let device_tree = unsafe { fdt::Fdt::from_ptr(addr as _).ok()? };
+        let chosen = device_tree.find_node("/chosen")?;
+        let start = chosen.property("linux,initrd-start")?.as_usize()?;
+        let end = chosen.property("linux,initrd-end")?.as_usize()?;
+        (start < end).then(|| start..end)
+    };
+    INITRD.call_once(|| {
+        hal::board::get_boot_device_tree_addr()
+            .and_then(find)
+            .or_else(|| find(get_device_tree_addr()))
+    }).clone()
+}
+
 lazy_static! {
     pub static ref DEVICE_MANAGER: SpinNoIrqLock<DeviceManager> = SpinNoIrqLock::new(DeviceManager::new());
 }
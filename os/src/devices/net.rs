@@ -1,4 +1,4 @@
-use core::{panic, ptr::NonNull};
+use core::{panic, ptr::NonNull, sync::atomic::{AtomicUsize, Ordering}};
 
 use alloc::{boxed::Box, sync::Arc, vec::{self, Vec}};
 use log::info;
@@ -189,6 +189,24 @@ pub type NetBufBox = Box<NetBuf>;
 pub struct NetDeviceWrapper {
     /// the inner device wrapped by UPSafeCell
     inner: UPSafeCell<Box<dyn NetDevice>>,
+    /// packet counters
+    stats: NetStats,
+}
+
+/// packet counters of a net device
+#[derive(Default)]
+pub struct NetStats {
+    pub rx_packets: AtomicUsize,
+    pub rx_bytes: AtomicUsize,
+    pub tx_packets: AtomicUsize,
+    pub tx_bytes: AtomicUsize,
+}
+
+impl NetStats {
+    fn add(packets: &AtomicUsize, bytes: &AtomicUsize, len: usize) {
+        packets.fetch_add(1, Ordering::Relaxed);
+        bytes.fetch_add(len, Ordering::Relaxed);
+    }
 }
 
 impl NetDeviceWrapper {
@@ -196,12 +214,17 @@ impl NetDeviceWrapper {
     pub fn new(dev: Box<dyn NetDevice>) -> Self {
         Self {
             inner: UPSafeCell::new(dev),
+            stats: NetStats::default(),
         }
     }
+    /// packet counters of the device
+    pub fn stats(&self) -> &NetStats {
+        &self.stats
+    }
 }
 /// rx token and tx token needed for smoltcp
-pub struct NetRxToken<'a>(&'a UPSafeCell<Box<dyn NetDevice>>, Box<dyn NetBufPtrTrait>);
-pub struct NetTxToken<'a>(&'a UPSafeCell<Box<dyn NetDevice>>);
+pub struct NetRxToken<'a>(&'a UPSafeCell<Box<dyn NetDevice>>, Box<dyn NetBufPtrTrait>, &'a NetStats);
+pub struct NetTxToken<'a>(&'a UPSafeCell<Box<dyn NetDevice>>, &'a NetStats);
 
 impl <'a> RxToken for NetRxToken<'a> {
     /// receive a packet than call the closure with the packet bytes
@@ -216,6 +239,7 @@ impl <'a> RxToken for NetRxToken<'a> {
         //     rx_buf.packet_len(),
             // rx_buf.packet()
         // );
+        NetStats::add(&self.2.rx_packets, &self.2.rx_bytes, rx_buf.packet_len());
         let result = f(rx_buf.packet_mut());
         self.0.exclusive_access().recycle_rx_buffer(rx_buf).unwrap();
         result
@@ -242,6 +266,7 @@ impl <'a> TxToken for NetTxToken<'a> {
              // tx_buf.packet()
         // );
         self.0.exclusive_access().transmit(tx_buf).unwrap();
+        NetStats::add(&self.1.tx_packets, &self.1.tx_bytes, len);
         result
     }
 }
@@ -268,7 +293,7 @@ impl Device for NetDeviceWrapper {
                 return None;
             }
         };
-        Some((NetRxToken(&self.inner, rx_buf), NetTxToken(&self.inner)))
+        Some((NetRxToken(&self.inner, rx_buf, &self.stats), NetTxToken(&self.inner, &self.stats)))
     }
     fn transmit(&mut self, _: Instant) -> Option<Self::TxToken<'_>> {
         let inner = self.inner.exclusive_access();
@@ -278,7 +303,7 @@ impl Device for NetDeviceWrapper {
                 return None;    
             }
             Ok(_) => {
-                Some(NetTxToken(&self.inner))
+                Some(NetTxToken(&self.inner, &self.stats))
             },
         }
     }
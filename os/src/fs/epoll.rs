@@ -0,0 +1,326 @@
+//! epoll file object
+//!
+//! the interest list holds weak references to the watched files, an entry
+//! goes away by itself once the file is released. waiting goes through
+//! `File::base_poll`, which registers the waker of the current task on
+//! every watched file that is not ready yet.
+//!
+//! edge triggered entries poll with `PollEvents::EDGE` through a waker of
+//! their own, every wakeup of that waker is a new edge.
+
+use core::{future::Future, pin::Pin, sync::atomic::{AtomicBool, AtomicUsize, Ordering}, task::{Context, Poll, Waker}};
+
+use alloc::{boxed::Box, collections::btree_map::BTreeMap, sync::{Arc, Weak}, task::Wake, vec::Vec};
+use async_trait::async_trait;
+
+use crate::{fs::{vfs::{file::PollEvents, Dentry, File, FileInner}, OpenFlags}, sync::mutex::SpinNoIrqLock, syscall::SysError};
+
+bitflags! {
+    /// events and flags of `struct epoll_event`
+    pub struct EpollEvents: u32 {
+        /// readable
+        const IN = 0x001;
+        /// urgent data
+        const PRI = 0x002;
+        /// writable
+        const OUT = 0x004;
+        /// error condition, always reported
+        const ERR = 0x008;
+        /// hang up, always reported
+        const HUP = 0x010;
+        /// normal data readable
+        const RDNORM = 0x040;
+        /// priority band data readable
+        const RDBAND = 0x080;
+        /// normal data writable
+        const WRNORM = 0x100;
+        /// priority band data writable
+        const WRBAND = 0x200;
+        /// message
+        const MSG = 0x400;
+        /// peer closed its writing half
+        const RDHUP = 0x2000;
+        /// wake up only one of the epoll instances waiting on the file
+        const EXCLUSIVE = 1 << 28;
+        /// keep the system awake
+        const WAKEUP = 1 << 29;
+        /// disable the entry after one event
+        const ONESHOT = 1 << 30;
+        /// edge triggered
+        const ET = 1 << 31;
+    }
+}
+
+impl EpollEvents {
+    /// the poll events to ask the watched file for
+    fn to_poll(self) -> PollEvents {
+        PollEvents::from_bits_truncate((self.bits() & 0x1f) as i16)
+    }
+
+    /// translate the result of a poll back
+    fn from_poll(events: PollEvents) -> Self {
+        EpollEvents::from_bits_truncate(events.bits() as u16 as u32)
+    }
+}
+
+/// `struct epoll_event`, not packed on riscv64 and loongarch64
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EpollEvent {
+    /// epoll events
+    pub events: u32,
+    /// user data
+    pub data: u64,
+}
+
+/// waker handed to the file of an edge triggered entry, remembers that
+/// the file had an event and passes the wakeup on to the waiting task
+struct EdgeWaker {
+    woken: AtomicBool,
+    waker: SpinNoIrqLock<Option<Waker>>,
+}
+
+impl EdgeWaker {
+    fn new() -> Arc<Self> {
+        Arc::new(Self {
+            woken: AtomicBool::new(false),
+            waker: SpinNoIrqLock::new(None),
+        })
+    }
+}
+
+impl Wake for EdgeWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.woken.store(true, Ordering::Release);
+        let waker = self.waker.lock().clone();
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+/// an entry of the interest list
+struct EpollItem {
+    file: Weak<dyn File>,
+    /// requested events with flags
+    events: EpollEvents,
+    data: u64,
+    /// oneshot entry that has fired and waits for EPOLL_CTL_MOD
+    disabled: bool,
+    /// events reported by the last poll, for edge triggering
+    last: EpollEvents,
+    /// waker of an edge triggered entry
+    edge: Arc<EdgeWaker>,
+}
+
+/// an entry is identified by the fd and the file it referred to when added
+type EpollKey = (usize, usize);
+
+fn epoll_key(fd: usize, file: &Arc<dyn File>) -> EpollKey {
+    (fd, Arc::as_ptr(file) as *const () as usize)
+}
+
+/// epoll instance
+pub struct EpollFile {
+    inner: FileInner,
+    /// interest list keyed by fd and file
+    items: SpinNoIrqLock<BTreeMap<EpollKey, EpollItem>>,
+}
+
+impl EpollFile {
+    /// create a new epoll instance
+    pub fn new(flags: OpenFlags) -> Arc<Self> {
+        Arc::new(Self {
+            inner: FileInner {
+                dentry: Arc::<usize>::new_zeroed(),
+                offset: AtomicUsize::new(0),
+                flags: SpinNoIrqLock::new(flags),
+            },
+            items: SpinNoIrqLock::new(BTreeMap::new()),
+        })
+    }
+
+    /// EPOLL_CTL_ADD
+    pub fn add(&self, fd: usize, file: &Arc<dyn File>, event: EpollEvent) -> Result<(), SysError> {
+        let mut items = self.items.lock();
+        let key = epoll_key(fd, file);
+        if items.contains_key(&key) {
+            return Err(SysError::EEXIST);
+        }
+        items.insert(key, EpollItem {
+            file: Arc::downgrade(file),
+            events: EpollEvents::from_bits_truncate(event.events),
+            data: event.data,
+            disabled: false,
+            last: EpollEvents::empty(),
+            edge: EdgeWaker::new(),
+        });
+        Ok(())
+    }
+
+    /// EPOLL_CTL_MOD, also rearms a oneshot entry
+    pub fn modify(&self, fd: usize, file: &Arc<dyn File>, event: EpollEvent) -> Result<(), SysError> {
+        let mut items = self.items.lock();
+        let item = items.get_mut(&epoll_key(fd, file)).ok_or(SysError::ENOENT)?;
+        let events = EpollEvents::from_bits_truncate(event.events);
+        if events.contains(EpollEvents::EXCLUSIVE) || item.events.contains(EpollEvents::EXCLUSIVE) {
+            return Err(SysError::EINVAL);
+        }
+        item.events = events;
+        item.data = event.data;
+        item.disabled = false;
+        item.last = EpollEvents::empty();
+        Ok(())
+    }
+
+    /// EPOLL_CTL_DEL
+    pub fn delete(&self, fd: usize, file: &Arc<dyn File>) -> Result<(), SysError> {
+        self.items.lock().remove(&epoll_key(fd, file)).map(|_| ()).ok_or(SysError::ENOENT)
+    }
+
+    /// poll every armed entry once and collect at most `max` ready events.
+    /// entries that are not ready keep the waker of `cx`
+    fn poll_items(&self, cx: &mut Context<'_>, max: usize, consume: bool) -> Vec<EpollEvent> {
+        let watched: Vec<(EpollKey, Arc<dyn File>, EpollEvents, Arc<EdgeWaker>)> = {
+            let mut items = self.items.lock();
+            items.retain(|_, item| item.file.strong_count() > 0);
+            items.iter()
+                .filter(|(_, item)| !item.disabled)
+                .filter_map(|(key, item)| Some((*key, item.file.upgrade()?, item.events, item.edge.clone())))
+                .collect()
+        };
+        let mut ready = Vec::new();
+        for (key, file, events, edge) in watched {
+            if ready.len() >= max {
+                break;
+            }
+            let et = events.contains(EpollEvents::ET);
+            let poll = if et {
+                // taken before polling, a wakeup after the poll is the next edge
+                let woken = if consume {
+                    edge.woken.swap(false, Ordering::AcqRel)
+                } else {
+                    edge.woken.load(Ordering::Acquire)
+                };
+                *edge.waker.lock() = Some(cx.waker().clone());
+                let waker = Waker::from(edge);
+                let mut edge_cx = Context::from_waker(&waker);
+                let res = unsafe { Pin::new_unchecked(&mut file.poll(events.to_poll() | PollEvents::EDGE)).poll(&mut edge_cx) };
+                res.map(|res| (res, woken))
+            } else {
+                unsafe { Pin::new_unchecked(&mut file.poll(events.to_poll())).poll(cx) }.map(|res| (res, false))
+            };
+            let (res, woken) = match poll {
+                Poll::Ready(res) => res,
+                Poll::Pending => continue,
+            };
+            let mask = events | EpollEvents::ERR | EpollEvents::HUP;
+            let res = EpollEvents::from_poll(res) & mask;
+            let mut items = self.items.lock();
+            let Some(item) = items.get_mut(&key) else {
+                continue;
+            };
+            let report = if !et || woken {
+                res
+            } else {
+                res & !item.last
+            };
+            if !consume {
+                if !report.is_empty() {
+                    ready.push(EpollEvent { events: report.bits(), data: item.data });
+                }
+                continue;
+            }
+            item.last = res;
+            if report.is_empty() {
+                continue;
+            }
+            if item.events.contains(EpollEvents::ONESHOT) {
+                item.disabled = true;
+            }
+            ready.push(EpollEvent { events: report.bits(), data: item.data });
+        }
+        ready
+    }
+}
+
+/// future waiting for events of an epoll instance
+pub struct EpollWaitFuture {
+    epoll: Arc<EpollFile>,
+    max: usize,
+}
+
+impl EpollWaitFuture {
+    /// wait for at most `max` events
+    pub fn new(epoll: Arc<EpollFile>, max: usize) -> Self {
+        Self { epoll, max }
+    }
+}
+
+impl Future for EpollWaitFuture {
+    type Output = Vec<EpollEvent>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let ready = self.epoll.poll_items(cx, self.max, true);
+        if ready.is_empty() {
+            Poll::Pending
+        } else {
+            Poll::Ready(ready)
+        }
+    }
+}
+
+/// helper future returning the context once, to poll the entries
+/// from inside `base_poll`
+struct PeekFuture<'a> {
+    epoll: &'a EpollFile,
+}
+
+impl Future for PeekFuture<'_> {
+    type Output = bool;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Poll::Ready(!self.epoll.poll_items(cx, 1, false).is_empty())
+    }
+}
+
+#[async_trait]
+impl File for EpollFile {
+    fn file_inner(&self) -> &FileInner {
+        &self.inner
+    }
+
+    fn readable(&self) -> bool {
+        false
+    }
+
+    fn writable(&self) -> bool {
+        false
+    }
+
+    async fn read(&self, _buf: &mut [u8]) -> Result<usize, SysError> {
+        Err(SysError::EINVAL)
+    }
+
+    async fn write(&self, _buf: &[u8]) -> Result<usize, SysError> {
+        Err(SysError::EINVAL)
+    }
+
+    /// an epoll instance is readable when some entry has events,
+    /// so it can be nested or polled itself
+    async fn base_poll(&self, events: PollEvents) -> PollEvents {
+        if events.contains(PollEvents::IN) && (PeekFuture { epoll: self }).await {
+            PollEvents::IN
+        } else {
+            PollEvents::empty()
+        }
+    }
+
+    fn dentry(&self) -> Option<Arc<dyn Dentry>> {
+        None
+    }
+}
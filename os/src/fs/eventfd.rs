@@ -0,0 +1,179 @@
+//! eventfd file object
+//!
+//! a 64 bit counter, write adds to it and read takes it out. in
+//! semaphore mode a read only takes one.
+
+use core::{future::Future, pin::Pin, sync::atomic::AtomicUsize, task::{Context, Poll, Waker}};
+
+use alloc::{boxed::Box, collections::vec_deque::VecDeque, sync::Arc};
+use async_trait::async_trait;
+
+use crate::{fs::{vfs::{file::PollEvents, Dentry, File, FileInner}, OpenFlags}, sync::mutex::SpinNoIrqLock, syscall::SysError, utils::get_waker};
+
+/// the counter never goes above this
+const EVENTFD_MAX: u64 = u64::MAX - 1;
+
+bitflags! {
+    /// flags of eventfd2
+    pub struct EventFdFlags: i32 {
+        /// read takes one instead of the whole counter
+        const EFD_SEMAPHORE = 1;
+        const EFD_NONBLOCK = 0o4000;
+        const EFD_CLOEXEC = 0o2000000;
+    }
+}
+
+struct EventFdInner {
+    count: u64,
+    read_wakers: VecDeque<Waker>,
+    write_wakers: VecDeque<Waker>,
+}
+
+/// eventfd instance
+pub struct EventFdFile {
+    inner: FileInner,
+    semaphore: bool,
+    counter: SpinNoIrqLock<EventFdInner>,
+}
+
+impl EventFdFile {
+    /// create an eventfd with `initval` in the counter
+    pub fn new(initval: u64, flags: EventFdFlags) -> Arc<Self> {
+        let mut open_flags = OpenFlags::O_RDWR;
+        if flags.contains(EventFdFlags::EFD_NONBLOCK) {
+            open_flags |= OpenFlags::O_NONBLOCK;
+        }
+        Arc::new(Self {
+            inner: FileInner {
+                dentry: Arc::<usize>::new_zeroed(),
+                offset: AtomicUsize::new(0),
+                flags: SpinNoIrqLock::new(open_flags),
+            },
+            semaphore: flags.contains(EventFdFlags::EFD_SEMAPHORE),
+            counter: SpinNoIrqLock::new(EventFdInner {
+                count: initval,
+                read_wakers: VecDeque::new(),
+                write_wakers: VecDeque::new(),
+            }),
+        })
+    }
+}
+
+/// future for read, waits until the counter is not zero
+struct EventFdReadFuture<'a> {
+    file: &'a EventFdFile,
+    nowait: bool,
+}
+
+impl Future for EventFdReadFuture<'_> {
+    type Output = Result<u64, SysError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut counter = self.file.counter.lock();
+        if counter.count == 0 {
+            if self.nowait {
+                return Poll::Ready(Err(SysError::EAGAIN));
+            }
+            counter.read_wakers.push_back(cx.waker().clone());
+            return Poll::Pending;
+        }
+        let val = if self.file.semaphore { 1 } else { counter.count };
+        counter.count -= val;
+        while let Some(waker) = counter.write_wakers.pop_front() {
+            waker.wake();
+        }
+        Poll::Ready(Ok(val))
+    }
+}
+
+/// future for write, waits until `val` fits into the counter
+struct EventFdWriteFuture<'a> {
+    file: &'a EventFdFile,
+    val: u64,
+    nowait: bool,
+}
+
+impl Future for EventFdWriteFuture<'_> {
+    type Output = Result<(), SysError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut counter = self.file.counter.lock();
+        if EVENTFD_MAX - counter.count < self.val {
+            if self.nowait {
+                return Poll::Ready(Err(SysError::EAGAIN));
+            }
+            counter.write_wakers.push_back(cx.waker().clone());
+            return Poll::Pending;
+        }
+        counter.count += self.val;
+        if counter.count > 0 {
+            while let Some(waker) = counter.read_wakers.pop_front() {
+                waker.wake();
+            }
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[async_trait]
+impl File for EventFdFile {
+    fn file_inner(&self) -> &FileInner {
+        &self.inner
+    }
+
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn writable(&self) -> bool {
+        true
+    }
+
+    async fn read(&self, buf: &mut [u8]) -> Result<usize, SysError> {
+        if buf.len() < size_of::<u64>() {
+            return Err(SysError::EINVAL);
+        }
+        let nowait = self.flags().contains(OpenFlags::O_NONBLOCK);
+        let val = EventFdReadFuture { file: self, nowait }.await?;
+        buf[..size_of::<u64>()].copy_from_slice(&val.to_ne_bytes());
+        Ok(size_of::<u64>())
+    }
+
+    async fn write(&self, buf: &[u8]) -> Result<usize, SysError> {
+        if buf.len() < size_of::<u64>() {
+            return Err(SysError::EINVAL);
+        }
+        let val = u64::from_ne_bytes(buf[..size_of::<u64>()].try_into().unwrap());
+        if val == u64::MAX {
+            return Err(SysError::EINVAL);
+        }
+        let nowait = self.flags().contains(OpenFlags::O_NONBLOCK);
+        EventFdWriteFuture { file: self, val, nowait }.await?;
+        Ok(size_of::<u64>())
+    }
+
+    async fn base_poll(&self, events: PollEvents) -> PollEvents {
+        let waker = get_waker().await;
+        let mut counter = self.counter.lock();
+        let mut res = PollEvents::empty();
+        if events.contains(PollEvents::IN) && counter.count > 0 {
+            res |= PollEvents::IN;
+        }
+        if events.contains(PollEvents::OUT) && counter.count < EVENTFD_MAX {
+            res |= PollEvents::OUT;
+        }
+        if res.is_empty() || events.contains(PollEvents::EDGE) {
+            if events.contains(PollEvents::IN) {
+                counter.read_wakers.push_back(waker.clone());
+            }
+            if events.contains(PollEvents::OUT) {
+                counter.write_wakers.push_back(waker);
+            }
+        }
+        res
+    }
+
+    fn dentry(&self) -> Option<Arc<dyn Dentry>> {
+        None
+    }
+}
@@ -9,8 +9,9 @@ use crate::fs::vfs::{
     inode::{Inode, InodeInner},
 };
 use crate::fs::SuperBlock;
+use crate::sync::mutex::SpinNoIrqLock;
 
-use alloc::string::ToString;
+use alloc::{format, vec::Vec};
 use lwext4_rust::InodeTypes;
 use alloc::sync::Arc;
 
@@ -39,6 +40,42 @@ static DISK_DNAME: &str = "ext4_fs0";
 static SDCARD_MP: &str = "sdcard/";
 /// device name for sdcard fs
 static SDCARD_DNAME: &str = "ext4_fs1";
+/// mount point and device name of each ext4 instance under the root fs,
+/// lwext4 needs its own for each. an entry is free again once the
+/// super block using it is dropped
+static SUB_MOUNTS: SpinNoIrqLock<Vec<(&'static str, &'static str, bool)>> = SpinNoIrqLock::new(Vec::new());
+
+/// a taken entry of `SUB_MOUNTS`
+pub struct SubMountSlot(usize);
+
+impl SubMountSlot {
+    fn alloc() -> Self {
+        let mut slots = SUB_MOUNTS.lock();
+        if let Some(id) = slots.iter().position(|slot| !slot.2) {
+            slots[id].2 = true;
+            return Self(id);
+        }
+        let id = slots.len();
+        // made once per entry, the entries are reused
+        let (mount_point, dev_name): (&'static str, &'static str) = match id {
+            0 => (SDCARD_MP, SDCARD_DNAME),
+            id => (format!("mnt{}/", id).leak(), format!("ext4_fs{}", id + 1).leak()),
+        };
+        slots.push((mount_point, dev_name, true));
+        Self(id)
+    }
+
+    fn names(&self) -> (&'static str, &'static str) {
+        let slot = SUB_MOUNTS.lock()[self.0];
+        (slot.0, slot.1)
+    }
+}
+
+impl Drop for SubMountSlot {
+    fn drop(&mut self) {
+        SUB_MOUNTS.lock()[self.0].2 = false;
+    }
+}
 
 impl FSType for Ext4FSType {
     fn inner(&self) -> &FSTypeInner {
@@ -47,6 +84,9 @@ impl FSType for Ext4FSType {
     fn kill_sb(&self) -> isize {
         todo!()
     }
+    fn requires_dev(&self) -> bool {
+        true
+    }
     fn mount(&'static self, name: &str, parent: Option<Arc<dyn Dentry>>, _flags: MountFlags, dev: Option<Arc<dyn BlockDevice>>) -> Option<Arc<dyn Dentry>> {
         // can be dangerous..
         let fs_type = unsafe {
@@ -54,25 +94,19 @@ impl FSType for Ext4FSType {
             Arc::from_raw(ptr)
         };
 
-        let mount_point_path = if parent.is_none() {
-            DISK_MP
-        } else {
-            SDCARD_MP
+        let slot = parent.is_some().then(SubMountSlot::alloc);
+        let (mount_point_path, dev_name) = match &slot {
+            None => (DISK_MP, DISK_DNAME),
+            Some(slot) => slot.names(),
         };
 
-        let dev_name = if parent.is_none() {
-            DISK_DNAME
-        } else {
-            SDCARD_DNAME
-        };
-        
-        let sb = Ext4SuperBlock::new(SuperBlockInner::new(dev, fs_type.clone()), mount_point_path, dev_name);
-        let root_inode = Arc::new(Ext4Inode::new(Arc::downgrade(&sb), &mount_point_path, InodeTypes::EXT4_DE_DIR)); 
+        let sb = Ext4SuperBlock::new(SuperBlockInner::new(dev, fs_type.clone()), mount_point_path, dev_name, slot)?;
+        let root_inode = Ext4Inode::new(Arc::downgrade(&sb), &mount_point_path, InodeTypes::EXT4_DE_DIR); 
         let root_dentry = Ext4Dentry::new(name, parent.clone());
         root_dentry.set_inode(root_inode);
         root_dentry.set_state(DentryState::USED);
         sb.set_root_dentry(root_dentry.clone());
-        DCACHE.lock().insert(mount_point_path.to_string(), root_dentry.clone());
+        DCACHE.lock().insert(root_dentry.path(), root_dentry.clone());
         self.add_sb(&root_dentry.path(), sb);
         Some(root_dentry)
     }
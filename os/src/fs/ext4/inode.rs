@@ -14,7 +14,7 @@ use alloc::{vec, vec::Vec};
 
 use log::*;
 use crate::fs::page::cache::PageCache;
-use crate::fs::page::page::{Page, PAGE_SIZE};
+use crate::fs::page::page::{dirty_exceeded, Page, PAGE_SIZE};
 use crate::fs::vfs::inode::InodeMode;
 use crate::fs::vfs::{InodeInner, Inode};
 use crate::fs::{Kstat, StatxTimestamp, SuperBlock, Xstat, XstatMask};
@@ -45,19 +45,70 @@ unsafe impl Send for Ext4Inode {}
 unsafe impl Sync for Ext4Inode {}
 
 impl Ext4Inode {
+    /// write the dirty pages in page cache back to the disk, an error is
+    /// left for the next fsync
+    fn write_back_dirty(&self) {
+        if let Err(e) = self.cache.flush(self) {
+            warn!("[Ext4Inode] write back failed: {}", e);
+        }
+    }
+
+    /// read the pages in [start, end) missing from the page cache, a run of
+    /// them takes a single open and seek of the file. the pages go into the
+    /// cache after the file lock is dropped, and only where a write did not
+    /// put one meanwhile
+    fn fill_pages(&self, start: usize, end: usize) -> Result<(), i32> {
+        let mut index = start;
+        while index < end {
+            if self.cache.contains(index * PAGE_SIZE) {
+                index += 1;
+                continue;
+            }
+            let first = index;
+            let mut run = Vec::new();
+            {
+                let mut file = self.file.lock();
+                let path = file.get_path();
+                let path = path.to_str().unwrap();
+                file.file_open(path, O_RDONLY)?;
+                file.file_seek((index * PAGE_SIZE) as i64, SEEK_SET)?;
+                while index < end && !self.cache.contains(index * PAGE_SIZE) {
+                    let mut page = Page::new(index * PAGE_SIZE);
+                    let read_size = file.file_read(Arc::get_mut(&mut page).unwrap().get_slice_mut::<u8>());
+                    let read_size = match read_size {
+                        Ok(size) => size,
+                        Err(e) => {
+                            let _ = file.file_close();
+                            return Err(e);
+                        }
+                    };
+                    run.push((page, read_size));
+                    index += 1;
+                }
+                let _ = file.file_close();
+            }
+            for (i, (page, read_size)) in run.into_iter().enumerate() {
+                let offset = (first + i) * PAGE_SIZE;
+                self.cache.insert_page_if_absent(offset, page);
+                self.cache.update_end(offset + read_size);
+            }
+        }
+        Ok(())
+    }
+
     /// Create a new inode
-    pub fn new(super_block: Weak<dyn SuperBlock>, path: &str, types: InodeTypes) -> Self {
+    pub fn new(super_block: Weak<dyn SuperBlock>, path: &str, types: InodeTypes) -> Arc<Self> {
         //info!("Inode new {:?} {}", types, path);
         let mode = InodeMode::from_inode_type(types.clone());
         let mut file  = Ext4File::new(path, types);
         // (todo) notice that lwext4 mention in file_size(): should open file as RDONLY first 
         // may be a bug in the future
         let size = file.file_size();
-        Self {
+        Arc::new_cyclic(|this: &Weak<Self>| Self {
             inner: InodeInner::new(Some(super_block.clone()), mode, size as usize),
             file: SpinNoIrqLock::new(file),
-            cache: Arc::new(PageCache::new()),
-        }
+            cache: PageCache::with_host(this.clone()),
+        })
     }
 
     #[allow(unused)]
@@ -126,22 +177,22 @@ impl Inode for Ext4Inode {
         log::debug!("try to look up {}", full_path);
         if file.check_inode_exist(full_path.as_str(), InodeTypes::EXT4_DE_REG_FILE) {
             log::debug!("lookup {} success", name);
-            return Some(Arc::new(Ext4Inode::new(
+            return Some(Ext4Inode::new(
                 self.inode_inner().super_block.clone().unwrap(), 
                 full_path.as_str(), 
-                InodeTypes::EXT4_DE_REG_FILE)));
+                InodeTypes::EXT4_DE_REG_FILE));
         } else if file.check_inode_exist(full_path.as_str(), InodeTypes::EXT4_DE_DIR) {
             log::debug!("lookup dir {} success", name);
-            return Some(Arc::new(Ext4Inode::new(
+            return Some(Ext4Inode::new(
                 self.inode_inner().super_block.clone().unwrap(), 
                 full_path.as_str(), 
-                InodeTypes::EXT4_DE_DIR)));
+                InodeTypes::EXT4_DE_DIR));
         } else if file.check_inode_exist(full_path.as_str(), InodeTypes::EXT4_DE_SYMLINK) {
             log::debug!("look up symlink {} success", name);
-            return Some(Arc::new(Ext4Inode::new(
+            return Some(Ext4Inode::new(
                 self.inode_inner().super_block.clone().unwrap(),
                 full_path.as_str(),
-                InodeTypes::EXT4_DE_SYMLINK)));
+                InodeTypes::EXT4_DE_SYMLINK));
         }
         //info!("lookup {} failed", name);
         None
@@ -220,6 +271,15 @@ impl Inode for Ext4Inode {
             file.file_size() as usize
         };
 
+        // the pages of the read and those the window says come next
+        let eof = cmp::max(self.cache.end(), file_size);
+        if offset < eof && !buf.is_empty() {
+            let first = offset / PAGE_SIZE;
+            let nr = (offset + buf.len()).div_ceil(PAGE_SIZE) - first;
+            let end = self.cache.get_ra().lock().on_read(first, nr, eof.div_ceil(PAGE_SIZE));
+            self.fill_pages(first, end)?;
+        }
+
         while buf_offset < buf.len() {
             let cache = self.cache.clone();
             let max_end = cmp::max(cache.end(), file_size);
@@ -241,9 +301,8 @@ impl Inode for Ext4Inode {
                 let mut page = Page::new(page_offset);
                 let read_size = Arc::get_mut(&mut page).unwrap()
                     .read_from(self.clone(), page_offset);
-                cache.insert_page(page_offset, page.clone());
                 cache.update_end(page_offset + read_size);
-                page
+                cache.insert_page_if_absent(page_offset, page)
             };
 
             // now use the page to fill in the buf
@@ -291,13 +350,13 @@ impl Inode for Ext4Inode {
                     // write inside the file bound, should read out the data first
                     let _ = Arc::get_mut(&mut page).unwrap().read_from(self.clone(), page_offset);
                 }
-                cache.insert_page(page_offset, page.clone());
-                page
+                // a read ahead may have put it in meanwhile, write to that one
+                cache.insert_page_if_absent(page_offset, page)
             };
 
             // now use the buf to fill in the page
             let page_write_size = page.write_at(in_page_offset, &buf[buf_offset..]);
-            page.set_dirty();
+            cache.set_page_dirty(page_offset, &page);
             cache.update_end(page_offset + page_write_size + in_page_offset);
 
             total_write_size += page_write_size;
@@ -305,10 +364,31 @@ impl Inode for Ext4Inode {
             current_offset += page_write_size;
         }
 
+        // too much dirty memory, the writer pays for the flush
+        if dirty_exceeded() {
+            self.write_back_dirty();
+        }
         // log::info!("[cache_write_at] buf len {}, offset {:#x}, write size {:#x}", buf.len(), offset, total_write_size);
         Ok(total_write_size)
     }
 
+    fn readahead(self: Arc<Self>, offset: usize, len: usize) -> Result<(), i32> {
+        let file_size = {
+            let mut file = self.file.lock();
+            let cpath = file.get_path();
+            let path = cpath.to_str().unwrap();
+            file.file_open(path, O_RDONLY)?;
+            let size = file.file_size() as usize;
+            let _ = file.file_close();
+            size
+        };
+        let eof = cmp::max(self.cache.end(), file_size).div_ceil(PAGE_SIZE);
+        let first = offset / PAGE_SIZE;
+        let nr = offset.saturating_add(len).div_ceil(PAGE_SIZE) - first;
+        let end = self.cache.get_ra().lock().force(first, nr, eof);
+        self.fill_pages(first, end)
+    }
+
     /// Truncate the inode to the given size
     fn truncate(&self, size: usize) -> Result<usize, SysError> {
         log::info!("truncate file to size {}", size);
@@ -357,9 +437,9 @@ impl Inode for Ext4Inode {
             }
             Ok(_) => {
                 info!("create inode success");
-                Ok(Arc::new(Ext4Inode::new(
+                Ok(Ext4Inode::new(
                     self.inode_inner().super_block.clone().unwrap(),
-                    fpath, types)))
+                    fpath, types))
             }
         }
     }
@@ -485,11 +565,11 @@ impl Inode for Ext4Inode {
         // create symlink
         file.symlink_create(target_path, link_path).map_err(|e| SysError::from_i32(e))?;
         // get the symlink Inode
-        Ok(Arc::new(Ext4Inode::new(
+        Ok(Ext4Inode::new(
             self.inode_inner().super_block.clone().unwrap(),
             link_path,
             InodeTypes::EXT4_DE_SYMLINK
-        )))
+        ))
     }
 
     fn link(&self, target_path: &str) -> Result<usize, SysError> {
@@ -587,11 +667,7 @@ impl Inode for Ext4Inode {
     }
 
     fn clean_cached(&self) {
-        let cache = self.cache.clone();
-        let mut pages = cache.get_pages().lock();
-        for (_, page) in pages.iter_mut() {
-            page.set_clean();
-        }
+        self.cache.clear_dirty();
     }
 }
 
@@ -601,16 +677,7 @@ impl Drop for Ext4Inode {
         info!("Drop struct Inode");
 
         // flush the dirty page in page cache
-        let cache = self.cache.clone();
-        let mut pages = cache.get_pages().lock();
-        for (&offset, page) in pages.iter_mut() {
-            if page.is_dirty() == false {
-                continue;
-            }
-            // info!("flush dirty page at offset {:#x}", offset);
-            let buf_flush_size = cmp::min(cache.end() - offset, PAGE_SIZE);
-            self.write_at(offset, &page.get_slice::<u8>()[..buf_flush_size]).expect("[PageCache]: failed at flush");
-        }
+        self.write_back_dirty();
 
         // file.file_close().expect("failed to close fd");
         // let _ = file; // todo
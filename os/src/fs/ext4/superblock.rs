@@ -2,7 +2,7 @@
 use crate::fs::vfs::{Dentry, DentryInner, DentryState, Inode, SuperBlock, SuperBlockInner, DCACHE};
 use alloc::string::ToString;
 use lwext4_rust::{Ext4BlockWrapper, Ext4File, InodeTypes, KernelDevOp};
-use super::{disk::Disk, Ext4Dentry};
+use super::{disk::Disk, fstype::SubMountSlot, Ext4Dentry};
 use super::inode::Ext4Inode;
 use alloc::sync::{Arc, Weak};
 
@@ -13,6 +13,8 @@ pub struct Ext4SuperBlock {
     inner: SuperBlockInner,
     /// lwext4 object to control file system
     block: Ext4BlockWrapper<Disk>,
+    /// names lent to lwext4, dropped after `block`
+    slot: Option<SubMountSlot>,
 }
 
 unsafe impl Send for Ext4SuperBlock {}
@@ -20,13 +22,19 @@ unsafe impl Sync for Ext4SuperBlock {}
 
 // EXT4 FS super block implement 
 impl Ext4SuperBlock {
-    /// create a new ext4 super block using device
-    pub fn new(inner: SuperBlockInner, mount_point: &'static str, device_name: &'static str) -> Arc<dyn SuperBlock> {
+    /// create a new ext4 super block using device, None if it holds no ext4 fs
+    pub fn new(inner: SuperBlockInner, mount_point: &'static str, device_name: &'static str, slot: Option<SubMountSlot>) -> Option<Arc<dyn SuperBlock>> {
         log::info!("mount a ext fs at {}, device name {}", mount_point, device_name);
         let block_device = inner.device.as_ref().unwrap().clone();
         let disk = Disk::new(block_device);
-        let block = Ext4BlockWrapper::<Disk>::new(disk, mount_point, device_name).expect("failed to create ext4fs");
-        Arc::new(Self {inner, block})
+        let block = match Ext4BlockWrapper::<Disk>::new(disk, mount_point, device_name) {
+            Ok(block) => block,
+            Err(e) => {
+                log::warn!("failed to create ext4fs: {:?}", e);
+                return None;
+            }
+        };
+        Some(Arc::new(Self {inner, block, slot}))
     }
 }
 
@@ -114,6 +114,7 @@ impl fatfs::Write for DiskCursor {
     }
 
     fn flush(&mut self) -> Result<(), Self::Error> {
+        self.blk_dev.sync();
         Ok(())
     }
 }
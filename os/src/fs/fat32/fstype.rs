@@ -27,21 +27,25 @@ impl FSType for Fat32FSType {
     fn kill_sb(&self) -> isize {
         todo!()
     }
+    fn requires_dev(&self) -> bool {
+        true
+    }
     fn mount(&'static self, name: &str, parent: Option<Arc<dyn Dentry>>, _flags: MountFlags, dev: Option<Arc<dyn BlockDevice>>) -> Option<Arc<dyn Dentry>> {
         // can be dangerous..
         let fs_type = unsafe {
             let ptr: *const dyn FSType = self;
             Arc::from_raw(ptr)
         };
-        let sb = FatSuperBlock::new(SuperBlockInner::new(dev, fs_type.clone()));
-        self.add_sb(name, sb);
-        let sb = self.get_static_sb(name);
-        let dir = sb.get_root_inode(name);
+        let sb = FatSuperBlock::new(SuperBlockInner::new(dev, fs_type.clone()))?;
         let root_dentry = FatDentry::new(name, parent.clone());
+        let path = root_dentry.path();
+        self.add_sb(&path, sb);
+        let sb = self.get_static_sb(&path);
+        let dir = sb.get_root_inode(name);
         root_dentry.set_inode(dir);
         root_dentry.set_state(DentryState::USED);
         sb.set_root_dentry(root_dentry.clone());
-        DCACHE.lock().insert("/".to_string(), root_dentry.clone());
+        DCACHE.lock().insert(path, root_dentry.clone());
         Some(root_dentry)
     }
 }
\ No newline at end of file
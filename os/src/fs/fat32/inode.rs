@@ -53,7 +53,7 @@ impl Inode for FatFileInode {
     }
 
     fn cache(&self) -> Option<Arc<PageCache>> {
-        panic!("not support");
+        None
     }
 
     fn read_page_at(self: Arc<Self>, _offset: usize) -> Option<Arc<Page>> {
@@ -111,6 +111,15 @@ impl Inode for FatFileInode {
         Ok(buf.len())
     }
 
+    fn sync_metadata(&self) -> Result<(), i32> {
+        // the size is kept in the dir entry until the file is flushed
+        self.file
+            .exclusive_access()
+            .inner
+            .flush()
+            .map_err(|_| SysError::EIO as i32)
+    }
+
     fn truncate(&self, size: usize) -> Result<usize, SysError> {
         self.file
             .exclusive_access()
@@ -202,7 +211,7 @@ impl Inode for FatDirInode {
         &self.inner
     }
     fn cache(&self) -> Option<Arc<PageCache>> {
-        panic!("fat32 not support for caching")
+        None
     }
     fn read_page_at(self: Arc<Self>, _offset: usize) -> Option<Arc<Page>> {
         panic!("not support");
@@ -20,11 +20,12 @@ unsafe impl Sync for FatSuperBlock {}
 // FAT32 FS super block implement
 impl FatSuperBlock {
     /// create a new fat32 super block using device
-    pub fn new(inner: SuperBlockInner) -> Arc<Self> {
+    /// None if the device holds no fat fs
+    pub fn new(inner: SuperBlockInner) -> Option<Arc<Self>> {
         let block_device = inner.device.as_ref().unwrap().clone();
         let cursor = DiskCursor::new(block_device);
-        let block = Arc::new(fatfs::FileSystem::new(cursor, fatfs::FsOptions::new()).expect("open fs wrong"));
-        Arc::new(Self {inner, block })
+        let block = Arc::new(fatfs::FileSystem::new(cursor, fatfs::FsOptions::new()).ok()?);
+        Some(Arc::new(Self {inner, block }))
     }
 }
 
@@ -0,0 +1,221 @@
+//! initramfs
+//!
+//! with an initrd from the boot loader, the root is a tmpfs the initrd is
+//! unpacked into and its /init is the first process, the disks can be
+//! mounted later. the initrd is a newc cpio archive, gzip compressed or not.
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use alloc::{sync::Arc, vec::Vec};
+use hal::addr::{PhysAddr, PhysAddrHal};
+
+use crate::{devices::initrd_range, mm::allocator::release_frames, syscall::SysError, timer::ffi::TimeSpec};
+
+use super::{get_filesystem, vfs::{fstype::MountFlags, inode::InodeMode, mount::add_mount, Dentry, Inode, DCACHE}};
+
+/// whether the root is the unpacked initrd
+static ROOTFS: AtomicBool = AtomicBool::new(false);
+
+/// whether the root is the unpacked initrd
+pub fn is_rootfs() -> bool {
+    ROOTFS.load(Ordering::Relaxed)
+}
+
+/// mount a tmpfs as the root and unpack the initrd into it, none without
+/// an initrd or if it has no /init to run. the memory of the initrd is
+/// freed afterwards
+pub fn init_rootfs() -> Option<Arc<dyn Dentry>> {
+    let range = initrd_range()?;
+    log::info!("[initramfs] initrd at {:#x}..{:#x}", range.start, range.end);
+    let tmpfs = get_filesystem("tmpfs");
+    let root = tmpfs.mount("/", None, MountFlags::empty(), None).unwrap();
+    root.inode().unwrap().inode_inner().set_mode(InodeMode::DIR | InodeMode::from_bits_truncate(0o755));
+    let data = unsafe {
+        core::slice::from_raw_parts(PhysAddr::from(range.start).get_ptr::<u8>() as *const u8, range.len())
+    };
+    let unpacked = unpack(&root, data);
+    release_frames(PhysAddr::from(range.start)..PhysAddr::from(range.end));
+    match unpacked {
+        Ok(count) => log::info!("[initramfs] unpacked {} entries", count),
+        Err(e) => log::warn!("[initramfs] bad initrd: {:?}", e),
+    }
+    // a partial archive is still fine as long as it has the init
+    let has_init = root.clone().walk("init").map_or(false, |init| !init.is_negative());
+    if !has_init {
+        log::warn!("[initramfs] no /init in the initrd, falling back to the disk");
+        // nothing else is cached yet, the disk root takes over "/"
+        DCACHE.lock().clear();
+        return None;
+    }
+    add_mount("rootfs", "rootfs", tmpfs, root.clone(), MountFlags::empty());
+    ROOTFS.store(true, Ordering::Relaxed);
+    Some(root)
+}
+
+/// unpack the initrd into `root`, returns the number of entries
+pub fn unpack(root: &Arc<dyn Dentry>, data: &[u8]) -> Result<usize, SysError> {
+    if data.starts_with(&GZIP_MAGIC) {
+        let data = gunzip(data)?;
+        return unpack_cpio(root, &data);
+    }
+    unpack_cpio(root, data)
+}
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// the content of a gzip member
+fn gunzip(data: &[u8]) -> Result<Vec<u8>, SysError> {
+    const FHCRC: u8 = 1 << 1;
+    const FEXTRA: u8 = 1 << 2;
+    const FNAME: u8 = 1 << 3;
+    const FCOMMENT: u8 = 1 << 4;
+    const DEFLATE: u8 = 8;
+    if data.len() < 18 || data[2] != DEFLATE {
+        return Err(SysError::EINVAL);
+    }
+    let flags = data[3];
+    let mut pos = 10;
+    if flags & FEXTRA != 0 {
+        let len = data.get(pos..pos + 2).ok_or(SysError::EINVAL)?;
+        pos += 2 + u16::from_le_bytes([len[0], len[1]]) as usize;
+    }
+    for flag in [FNAME, FCOMMENT] {
+        if flags & flag != 0 {
+            let len = data.get(pos..).and_then(|rest| rest.iter().position(|&b| b == 0)).ok_or(SysError::EINVAL)?;
+            pos += len + 1;
+        }
+    }
+    if flags & FHCRC != 0 {
+        pos += 2;
+    }
+    let deflated = data.get(pos..).ok_or(SysError::EINVAL)?;
+    miniz_oxide::inflate::decompress_to_vec(deflated).map_err(|_| SysError::EINVAL)
+}
+
+/// header of a newc cpio entry, the fields are 8 hex digits each
+struct CpioHeader {
+    mode: u32,
+    uid: u32,
+    gid: u32,
+    mtime: u32,
+    filesize: usize,
+    namesize: usize,
+}
+
+const CPIO_HEADER_LEN: usize = 110;
+const CPIO_TRAILER: &str = "TRAILER!!!";
+
+impl CpioHeader {
+    fn parse(data: &[u8]) -> Result<Self, SysError> {
+        let header = data.get(..CPIO_HEADER_LEN).ok_or(SysError::EINVAL)?;
+        // 070702 is the same with a checksum
+        if &header[..6] != b"070701" && &header[..6] != b"070702" {
+            return Err(SysError::EINVAL);
+        }
+        let field = |i: usize| -> Result<u32, SysError> {
+            let hex = core::str::from_utf8(&header[6 + i * 8..14 + i * 8]).map_err(|_| SysError::EINVAL)?;
+            u32::from_str_radix(hex, 16).map_err(|_| SysError::EINVAL)
+        };
+        Ok(Self {
+            mode: field(1)?,
+            uid: field(2)?,
+            gid: field(3)?,
+            mtime: field(5)?,
+            filesize: field(6)? as usize,
+            namesize: field(11)? as usize,
+        })
+    }
+}
+
+fn align4(n: usize) -> usize {
+    (n + 3) & !3
+}
+
+/// unpack the cpio archives, one after another, into `root`
+fn unpack_cpio(root: &Arc<dyn Dentry>, data: &[u8]) -> Result<usize, SysError> {
+    let mut pos = 0;
+    let mut count = 0;
+    loop {
+        // archives may be padded with zeros in between
+        while data.get(pos..pos + 4) == Some(&[0u8; 4][..]) {
+            pos += 4;
+        }
+        if pos >= data.len() {
+            return Ok(count);
+        }
+        let header = CpioHeader::parse(&data[pos..])?;
+        let name_start = pos + CPIO_HEADER_LEN;
+        let name = data.get(name_start..name_start + header.namesize).ok_or(SysError::EINVAL)?;
+        let name = core::str::from_utf8(name).map_err(|_| SysError::EINVAL)?.trim_end_matches('\0');
+        let data_start = align4(name_start + header.namesize);
+        let content = data.get(data_start..data_start + header.filesize).ok_or(SysError::EINVAL)?;
+        pos = align4(data_start + header.filesize);
+        if name == CPIO_TRAILER {
+            continue;
+        }
+        match create_entry(root, name, &header, content) {
+            Ok(()) => count += 1,
+            Err(e) => log::warn!("[initramfs] skip {}: {:?}", name, e),
+        }
+    }
+}
+
+/// create the entry `path` of the archive under `root`
+fn create_entry(root: &Arc<dyn Dentry>, path: &str, header: &CpioHeader, content: &[u8]) -> Result<(), SysError> {
+    let path = path.trim_start_matches("./").trim_matches('/');
+    let mode = InodeMode::from_bits_truncate(header.mode);
+    let dentry = if path.is_empty() || path == "." {
+        root.clone()
+    } else {
+        let (parent, name) = match path.rsplit_once('/') {
+            Some((parent, name)) => (root.clone().walk(parent)?, name),
+            None => (root.clone(), path),
+        };
+        if parent.is_negative() {
+            return Err(SysError::ENOENT);
+        }
+        let dentry = parent.clone().walk(name)?;
+        if dentry.is_negative() {
+            let dir = parent.inode().unwrap();
+            let inode = match mode.get_type() {
+                InodeMode::DIR | InodeMode::FILE => dir.create(name, mode.get_type())?,
+                InodeMode::LINK => {
+                    let target = core::str::from_utf8(content).map_err(|_| SysError::EINVAL)?;
+                    dir.symlink(target, &dentry.path())?
+                }
+                // device nodes are devfs' business
+                _ => return Err(SysError::EOPNOTSUPP),
+            };
+            dentry.set_inode(inode);
+            parent.add_child(dentry.clone());
+        } else if dentry.inode().unwrap().inode_type() != mode.get_type() {
+            return Err(SysError::EEXIST);
+        }
+        dentry
+    };
+    let inode = dentry.inode().unwrap();
+    if mode.get_type() == InodeMode::FILE && !content.is_empty() {
+        write_all(&inode, content)?;
+    }
+    let inner = inode.inode_inner();
+    inner.set_mode(mode);
+    inner.set_uid(header.uid);
+    inner.set_gid(header.gid);
+    let mtime = TimeSpec { tv_sec: header.mtime as usize, tv_nsec: 0 };
+    inner.set_mtime(mtime);
+    inner.set_ctime(mtime);
+    Ok(())
+}
+
+fn write_all(inode: &Arc<dyn Inode>, content: &[u8]) -> Result<(), SysError> {
+    let mut offset = 0;
+    while offset < content.len() {
+        let len = inode.clone().cache_write_at(offset, &content[offset..]).map_err(SysError::from_i32)?;
+        if len == 0 {
+            return Err(SysError::EIO);
+        }
+        offset += len;
+    }
+    Ok(())
+}
+
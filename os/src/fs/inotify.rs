@@ -0,0 +1,493 @@
+//! inotify: file system event notification
+//!
+//! every inotify fd owns a group of watches. a watch is keyed by the
+//! address of the watched inode in a global table, so it follows the
+//! inode across renames. the vfs paths report events through
+//! `fsnotify_dentry` / `fsnotify_parent`, which queue `struct inotify_event`
+//! records on every interested group.
+
+use core::{future::Future, pin::Pin, sync::atomic::{AtomicI32, AtomicU32, AtomicUsize, Ordering}, task::{Context, Poll, Waker}};
+
+use alloc::{boxed::Box, collections::{btree_map::BTreeMap, vec_deque::VecDeque}, string::{String, ToString}, sync::{Arc, Weak}, vec::Vec};
+use async_trait::async_trait;
+
+use crate::{fs::{vfs::{file::PollEvents, inode::InodeMode, Dentry, File, FileInner, Inode}, OpenFlags}, mm::UserPtrRaw, sync::mutex::SpinNoIrqLock, syscall::{SysError, SysResult}, task::current_task, utils::get_waker};
+
+bitflags! {
+    /// inotify event mask
+    pub struct InotifyMask: u32 {
+        /// file was accessed
+        const IN_ACCESS = 0x0000_0001;
+        /// file was modified
+        const IN_MODIFY = 0x0000_0002;
+        /// metadata changed
+        const IN_ATTRIB = 0x0000_0004;
+        /// writable file was closed
+        const IN_CLOSE_WRITE = 0x0000_0008;
+        /// unwritable file closed
+        const IN_CLOSE_NOWRITE = 0x0000_0010;
+        /// file was opened
+        const IN_OPEN = 0x0000_0020;
+        /// file was moved from X
+        const IN_MOVED_FROM = 0x0000_0040;
+        /// file was moved to Y
+        const IN_MOVED_TO = 0x0000_0080;
+        /// subfile was created
+        const IN_CREATE = 0x0000_0100;
+        /// subfile was deleted
+        const IN_DELETE = 0x0000_0200;
+        /// self was deleted
+        const IN_DELETE_SELF = 0x0000_0400;
+        /// self was moved
+        const IN_MOVE_SELF = 0x0000_0800;
+        /// backing fs was unmounted
+        const IN_UNMOUNT = 0x0000_2000;
+        /// event queue overflowed
+        const IN_Q_OVERFLOW = 0x0000_4000;
+        /// watch was removed
+        const IN_IGNORED = 0x0000_8000;
+        /// only watch the path if it is a directory
+        const IN_ONLYDIR = 0x0100_0000;
+        /// don't follow a symlink
+        const IN_DONT_FOLLOW = 0x0200_0000;
+        /// exclude events on unlinked objects
+        const IN_EXCL_UNLINK = 0x0400_0000;
+        /// only create watches
+        const IN_MASK_CREATE = 0x1000_0000;
+        /// add to the mask of an already existing watch
+        const IN_MASK_ADD = 0x2000_0000;
+        /// event occurred against dir
+        const IN_ISDIR = 0x4000_0000;
+        /// only send event once
+        const IN_ONESHOT = 0x8000_0000;
+    }
+}
+
+impl InotifyMask {
+    /// all events a watch can ask for
+    pub const IN_ALL_EVENTS: Self = Self::from_bits_truncate(0xfff);
+}
+
+/// max queued events of a group before IN_Q_OVERFLOW
+const INOTIFY_MAX_QUEUED_EVENTS: usize = 16384;
+/// ioctl: bytes available to read
+const FIONREAD: usize = 0x541b;
+
+/// header of `struct inotify_event`, followed by `len` bytes of name
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct InotifyEventHdr {
+    wd: i32,
+    mask: u32,
+    cookie: u32,
+    len: u32,
+}
+
+/// a queued event
+#[derive(Clone, PartialEq, Eq)]
+struct InotifyEvent {
+    wd: i32,
+    mask: u32,
+    cookie: u32,
+    name: String,
+}
+
+impl InotifyEvent {
+    /// length of the name field: nul terminated and padded to the header size
+    fn name_len(&self) -> usize {
+        if self.name.is_empty() {
+            0
+        } else {
+            let align = size_of::<InotifyEventHdr>();
+            (self.name.len() + 1 + align - 1) / align * align
+        }
+    }
+
+    /// size of the record seen by user
+    fn size(&self) -> usize {
+        size_of::<InotifyEventHdr>() + self.name_len()
+    }
+
+    fn write_to(&self, buf: &mut [u8]) {
+        let hdr = InotifyEventHdr {
+            wd: self.wd,
+            mask: self.mask,
+            cookie: self.cookie,
+            len: self.name_len() as u32,
+        };
+        let hdr_len = size_of::<InotifyEventHdr>();
+        let hdr = unsafe { core::slice::from_raw_parts(&hdr as *const InotifyEventHdr as *const u8, hdr_len) };
+        buf[..hdr_len].copy_from_slice(hdr);
+        let name = &mut buf[hdr_len..self.size()];
+        name.fill(0);
+        name[..self.name.len()].copy_from_slice(self.name.as_bytes());
+    }
+}
+
+/// the inode held by a watch
+struct WatchedInode(Arc<dyn Inode>);
+
+unsafe impl Send for WatchedInode {}
+unsafe impl Sync for WatchedInode {}
+
+struct InotifyWatch {
+    inode: WatchedInode,
+    mask: InotifyMask,
+}
+
+struct InotifyQueue {
+    events: VecDeque<InotifyEvent>,
+    /// bytes of all queued records
+    bytes: usize,
+    read_wakers: VecDeque<Waker>,
+}
+
+/// watches and event queue of an inotify instance
+pub struct InotifyGroup {
+    watches: SpinNoIrqLock<BTreeMap<i32, InotifyWatch>>,
+    next_wd: AtomicI32,
+    queue: SpinNoIrqLock<InotifyQueue>,
+}
+
+/// inode address -> (group, wd) of the watches on it
+static INOTIFY_WATCHES: SpinNoIrqLock<BTreeMap<usize, Vec<(Weak<InotifyGroup>, i32)>>> =
+    SpinNoIrqLock::new(BTreeMap::new());
+
+/// cookie shared by the two halves of a rename
+static INOTIFY_COOKIE: AtomicU32 = AtomicU32::new(1);
+
+fn inode_key(inode: &Arc<dyn Inode>) -> usize {
+    Arc::as_ptr(inode) as *const () as usize
+}
+
+impl InotifyGroup {
+    fn new() -> Arc<Self> {
+        Arc::new(Self {
+            watches: SpinNoIrqLock::new(BTreeMap::new()),
+            next_wd: AtomicI32::new(1),
+            queue: SpinNoIrqLock::new(InotifyQueue {
+                events: VecDeque::new(),
+                bytes: 0,
+                read_wakers: VecDeque::new(),
+            }),
+        })
+    }
+
+    /// queue an event, merging it with an identical last event
+    fn push_event(&self, event: InotifyEvent) {
+        let mut queue = self.queue.lock();
+        if queue.events.back() == Some(&event) {
+            return;
+        }
+        let event = if queue.events.len() >= INOTIFY_MAX_QUEUED_EVENTS {
+            if queue.events.back().is_some_and(|e| e.mask == InotifyMask::IN_Q_OVERFLOW.bits()) {
+                return;
+            }
+            InotifyEvent { wd: -1, mask: InotifyMask::IN_Q_OVERFLOW.bits(), cookie: 0, name: String::new() }
+        } else {
+            event
+        };
+        queue.bytes += event.size();
+        queue.events.push_back(event);
+        while let Some(waker) = queue.read_wakers.pop_front() {
+            waker.wake();
+        }
+    }
+
+    /// add a watch or update the mask of the existing one, return the wd
+    fn add_watch(self: &Arc<Self>, inode: Arc<dyn Inode>, mask: InotifyMask) -> Result<i32, SysError> {
+        let key = inode_key(&inode);
+        let mut watches = self.watches.lock();
+        let existing = watches.iter_mut().find(|(_, w)| inode_key(&w.inode.0) == key);
+        if let Some((wd, watch)) = existing {
+            if mask.contains(InotifyMask::IN_MASK_CREATE) {
+                return Err(SysError::EEXIST);
+            }
+            if mask.contains(InotifyMask::IN_MASK_ADD) {
+                watch.mask |= mask;
+            } else {
+                watch.mask = mask;
+            }
+            return Ok(*wd);
+        }
+        let wd = self.next_wd.fetch_add(1, Ordering::Relaxed);
+        watches.insert(wd, InotifyWatch { inode: WatchedInode(inode), mask });
+        INOTIFY_WATCHES.lock().entry(key).or_default().push((Arc::downgrade(self), wd));
+        Ok(wd)
+    }
+
+    /// drop a watch and queue IN_IGNORED for it
+    fn rm_watch(self: &Arc<Self>, wd: i32) -> Result<(), SysError> {
+        let watch = self.watches.lock().remove(&wd).ok_or(SysError::EINVAL)?;
+        let key = inode_key(&watch.inode.0);
+        let mut table = INOTIFY_WATCHES.lock();
+        if let Some(refs) = table.get_mut(&key) {
+            refs.retain(|(group, w)| !(*w == wd && group.as_ptr() == Arc::as_ptr(self)));
+            if refs.is_empty() {
+                table.remove(&key);
+            }
+        }
+        drop(table);
+        self.push_event(InotifyEvent { wd, mask: InotifyMask::IN_IGNORED.bits(), cookie: 0, name: String::new() });
+        Ok(())
+    }
+
+    /// deliver an event of the watch `wd`
+    fn handle(self: &Arc<Self>, wd: i32, mask: InotifyMask, cookie: u32, name: &str) {
+        let Some(watch_mask) = self.watches.lock().get(&wd).map(|w| w.mask) else {
+            return;
+        };
+        let report = mask & watch_mask & InotifyMask::IN_ALL_EVENTS;
+        if !report.is_empty() {
+            let mask = report | (mask & InotifyMask::IN_ISDIR);
+            self.push_event(InotifyEvent { wd, mask: mask.bits(), cookie, name: name.to_string() });
+        }
+        // a deleted inode takes its watches with it
+        if (!report.is_empty() && watch_mask.contains(InotifyMask::IN_ONESHOT))
+            || mask.contains(InotifyMask::IN_DELETE_SELF)
+        {
+            let _ = self.rm_watch(wd);
+        }
+    }
+}
+
+/// report `mask` to the watches on `inode`, with `name` for events
+/// about a child of a watched directory
+fn fsnotify_inode(inode: &Arc<dyn Inode>, mask: InotifyMask, cookie: u32, name: &str) {
+    let refs = {
+        let table = INOTIFY_WATCHES.lock();
+        if table.is_empty() {
+            return;
+        }
+        match table.get(&inode_key(inode)) {
+            Some(refs) => refs.clone(),
+            None => return,
+        }
+    };
+    for (group, wd) in refs {
+        if let Some(group) = group.upgrade() {
+            group.handle(wd, mask, cookie, name);
+        }
+    }
+}
+
+/// report an event about `child` of the watched directory `parent`
+pub fn fsnotify_parent(parent: &Arc<dyn Dentry>, name: &str, mask: InotifyMask, cookie: u32) {
+    if let Some(inode) = parent.inode() {
+        fsnotify_inode(&inode, mask, cookie, name);
+    }
+}
+
+/// report an event on the inode of `dentry`, both to watches on the inode
+/// itself and to watches on its parent directory
+pub fn fsnotify_dentry<D: Dentry + ?Sized>(dentry: &D, mask: InotifyMask) {
+    if INOTIFY_WATCHES.lock().is_empty() {
+        return;
+    }
+    let Some(inode) = dentry.inode() else {
+        return;
+    };
+    let mask = if inode.inode_type() == InodeMode::DIR {
+        mask | InotifyMask::IN_ISDIR
+    } else {
+        mask
+    };
+    let self_mask = mask & !(InotifyMask::IN_CREATE | InotifyMask::IN_DELETE | InotifyMask::IN_MOVED_FROM | InotifyMask::IN_MOVED_TO);
+    if !(self_mask & !InotifyMask::IN_ISDIR).is_empty() {
+        fsnotify_inode(&inode, self_mask, 0, "");
+    }
+    let parent_mask = mask & !(InotifyMask::IN_DELETE_SELF | InotifyMask::IN_MOVE_SELF);
+    if !(parent_mask & !InotifyMask::IN_ISDIR).is_empty() {
+        if let Some(parent) = dentry.parent() {
+            fsnotify_parent(&parent, dentry.name(), parent_mask, 0);
+        }
+    }
+}
+
+/// report a rename of `inode` from `old_parent/old_name` to `new_parent/new_name`
+pub fn fsnotify_move(inode: &Arc<dyn Inode>, old_parent: &Arc<dyn Dentry>, old_name: &str, new_parent: &Arc<dyn Dentry>, new_name: &str) {
+    if INOTIFY_WATCHES.lock().is_empty() {
+        return;
+    }
+    let cookie = INOTIFY_COOKIE.fetch_add(1, Ordering::Relaxed);
+    let isdir = if inode.inode_type() == InodeMode::DIR {
+        InotifyMask::IN_ISDIR
+    } else {
+        InotifyMask::empty()
+    };
+    fsnotify_parent(old_parent, old_name, InotifyMask::IN_MOVED_FROM | isdir, cookie);
+    fsnotify_parent(new_parent, new_name, InotifyMask::IN_MOVED_TO | isdir, cookie);
+    fsnotify_inode(inode, InotifyMask::IN_MOVE_SELF | isdir, 0, "");
+}
+
+/// report the final close of a file
+pub fn fsnotify_close(file: &Arc<dyn File>) {
+    if let Some(dentry) = file.dentry() {
+        let mask = if file.writable() {
+            InotifyMask::IN_CLOSE_WRITE
+        } else {
+            InotifyMask::IN_CLOSE_NOWRITE
+        };
+        dentry.notify(mask);
+    }
+}
+
+/// report a modification through `file`
+pub fn fsnotify_modify(file: &Arc<dyn File>) {
+    if let Some(dentry) = file.dentry() {
+        dentry.notify(InotifyMask::IN_MODIFY);
+    }
+}
+
+/// inotify instance
+pub struct InotifyFile {
+    inner: FileInner,
+    group: Arc<InotifyGroup>,
+}
+
+impl InotifyFile {
+    /// create a new inotify instance
+    pub fn new(flags: OpenFlags) -> Arc<Self> {
+        Arc::new(Self {
+            inner: FileInner {
+                dentry: Arc::<usize>::new_zeroed(),
+                offset: AtomicUsize::new(0),
+                flags: SpinNoIrqLock::new(flags),
+            },
+            group: InotifyGroup::new(),
+        })
+    }
+
+    /// inotify_add_watch on an already resolved dentry
+    pub fn add_watch(&self, dentry: &Arc<dyn Dentry>, mask: InotifyMask) -> Result<i32, SysError> {
+        if (mask & InotifyMask::IN_ALL_EVENTS).is_empty() {
+            return Err(SysError::EINVAL);
+        }
+        if mask.contains(InotifyMask::IN_MASK_ADD | InotifyMask::IN_MASK_CREATE) {
+            return Err(SysError::EINVAL);
+        }
+        let inode = dentry.inode().ok_or(SysError::ENOENT)?;
+        if mask.contains(InotifyMask::IN_ONLYDIR) && inode.inode_type() != InodeMode::DIR {
+            return Err(SysError::ENOTDIR);
+        }
+        self.group.add_watch(inode, mask)
+    }
+
+    /// inotify_rm_watch
+    pub fn rm_watch(&self, wd: i32) -> Result<(), SysError> {
+        self.group.rm_watch(wd)
+    }
+
+    fn non_block(&self) -> bool {
+        self.flags().contains(OpenFlags::O_NONBLOCK)
+    }
+}
+
+impl Drop for InotifyFile {
+    fn drop(&mut self) {
+        let watches = core::mem::take(&mut *self.group.watches.lock());
+        let mut table = INOTIFY_WATCHES.lock();
+        for (_, watch) in watches {
+            let key = inode_key(&watch.inode.0);
+            if let Some(refs) = table.get_mut(&key) {
+                refs.retain(|(group, _)| group.as_ptr() != Arc::as_ptr(&self.group));
+                if refs.is_empty() {
+                    table.remove(&key);
+                }
+            }
+        }
+    }
+}
+
+/// future that waits until the queue has events
+struct InotifyReadFuture {
+    group: Arc<InotifyGroup>,
+}
+
+impl Future for InotifyReadFuture {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut queue = self.group.queue.lock();
+        if queue.events.is_empty() {
+            queue.read_wakers.push_back(cx.waker().clone());
+            Poll::Pending
+        } else {
+            Poll::Ready(())
+        }
+    }
+}
+
+#[async_trait]
+impl File for InotifyFile {
+    fn file_inner(&self) -> &FileInner {
+        &self.inner
+    }
+
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn writable(&self) -> bool {
+        false
+    }
+
+    /// read as many whole events as fit in `buf`
+    async fn read(&self, buf: &mut [u8]) -> Result<usize, SysError> {
+        if self.group.queue.lock().events.is_empty() {
+            if self.non_block() {
+                return Err(SysError::EAGAIN);
+            }
+            InotifyReadFuture { group: self.group.clone() }.await;
+        }
+        let mut queue = self.group.queue.lock();
+        let mut len = 0;
+        while let Some(event) = queue.events.front() {
+            let size = event.size();
+            if len + size > buf.len() {
+                break;
+            }
+            event.write_to(&mut buf[len..]);
+            len += size;
+            queue.bytes -= size;
+            queue.events.pop_front();
+        }
+        if len == 0 {
+            return Err(SysError::EINVAL);
+        }
+        Ok(len)
+    }
+
+    async fn write(&self, _buf: &[u8]) -> Result<usize, SysError> {
+        Err(SysError::EINVAL)
+    }
+
+    async fn base_poll(&self, events: PollEvents) -> PollEvents {
+        let waker = get_waker().await;
+        let mut queue = self.group.queue.lock();
+        let mut res = PollEvents::empty();
+        if events.contains(PollEvents::IN) && !queue.events.is_empty() {
+            res |= PollEvents::IN;
+        } else {
+            queue.read_wakers.push_back(waker);
+        }
+        res
+    }
+
+    fn ioctl(&self, cmd: usize, arg: usize) -> SysResult {
+        if cmd != FIONREAD {
+            return Err(SysError::ENOTTY);
+        }
+        let task = current_task().unwrap();
+        let bytes = self.group.queue.lock().bytes as i32;
+        UserPtrRaw::new(arg as *mut i32)
+            .ensure_write(&mut task.get_vm_space().lock())
+            .ok_or(SysError::EFAULT)?
+            .write(bytes);
+        Ok(0)
+    }
+
+    fn dentry(&self) -> Option<Arc<dyn Dentry>> {
+        None
+    }
+}
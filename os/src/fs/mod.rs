@@ -8,26 +8,41 @@ pub mod fat32;
 pub mod ext4;
 pub mod vfs;
 pub mod pipefs;
+pub mod epoll;
+pub mod eventfd;
+pub mod pidfd;
+pub mod nsfs;
+pub mod signalfd;
+pub mod timerfd;
+pub mod inotify;
+pub mod initramfs;
+pub mod mqueue;
+pub mod overlayfs;
 pub mod page;
 pub mod devfs;
 pub mod utils;
 pub mod fs;
 // pub mod simplefs;
 pub mod procfs;
+pub mod sysfs;
 pub mod tmpfs;
 
 use devfs::{fstype::DevFsType, init_devfs};
 use ext4::Ext4FSType;
+use initramfs::init_rootfs;
 use fatfs::FatType;
 use log::*;
+use mqueue::{fstype::MqFSType, init_mqueue};
+use overlayfs::fstype::OverlayFSType;
 use procfs::{fstype::ProcFSType, init_procfs};
+use sysfs::{fstype::SysFSType, init_sysfs};
 pub use stdio::{Stdin, Stdout};
 
-use alloc::{boxed::Box, collections::btree_map::BTreeMap, string::{String, ToString}, sync::Arc};
+use alloc::{boxed::Box, collections::btree_map::BTreeMap, format, string::{String, ToString}, sync::Arc, vec::Vec};
 use tmpfs::{fstype::TmpFSType, init_tmpfs};
-use vfs::{fstype::{FSType, MountFlags}, DCACHE};
+use vfs::{fstype::{FSType, MountFlags}, mount::add_mount, Dentry, DCACHE};
 
-use crate::{devices::{DeviceMajor, DEVICE_MANAGER}, drivers::BLOCK_DEVICE, sync::mutex::{SpinNoIrq, SpinNoIrqLock}};
+use crate::{devices::{DeviceMajor, DEVICE_MANAGER}, drivers::BLOCK_DEVICE, sync::mutex::{SpinNoIrq, SpinNoIrqLock}, syscall::SysError};
 pub use ext4::Ext4SuperBlock;
 pub use vfs::{SuperBlock, SuperBlockInner};
 
@@ -69,8 +84,29 @@ fn register_all_fs() {
     let procfs = ProcFSType::new();
     FS_MANAGER.lock().insert(procfs.name().to_string(), procfs);
 
+    let sysfs = SysFSType::new();
+    FS_MANAGER.lock().insert(sysfs.name().to_string(), sysfs);
+
     let tmpfs = TmpFSType::new();
     FS_MANAGER.lock().insert(tmpfs.name().to_string(), tmpfs);
+
+    // for mounting vfat images, the disk fs already is one with the fat32 feature
+    #[cfg(not(feature = "fat32"))]
+    {
+        let fat32 = Fat32FSType::new();
+        FS_MANAGER.lock().insert(fat32.name().to_string(), fat32);
+    }
+
+    let mqueue = MqFSType::new();
+    FS_MANAGER.lock().insert(mqueue.name().to_string(), mqueue);
+
+    let overlay = OverlayFSType::new();
+    FS_MANAGER.lock().insert(overlay.name().to_string(), overlay);
+}
+
+/// tunables of the file systems
+pub fn register_sysctls() {
+    page::register_sysctls();
 }
 
 /// get the file system by name
@@ -79,10 +115,27 @@ pub fn get_filesystem(name: &str) -> &'static Arc<dyn FSType> {
     Box::leak(Box::new(arc))
 }
 
+/// write back the dirty pages of all files, then sync every mounted file
+/// system, the first error if any
+pub fn sync_all() -> Result<(), SysError> {
+    let synced = page::writeback::sync_pages(None).map_err(SysError::from_i32);
+    // collected first, syncing may sleep on the device
+    let fs_types: Vec<_> = FS_MANAGER.lock().values().cloned().collect();
+    let supers: Vec<_> = fs_types.iter()
+        .flat_map(|fs_type| fs_type.inner().supers.lock().values().cloned().collect::<Vec<_>>())
+        .collect();
+    supers.iter().fold(synced, |ret, sb| ret.and(sb.sync_fs()))
+}
 
-/// init the file system
-pub fn init() {
-    register_all_fs();
+/// write back the dirty pages of the files on `sb`, then sync it
+pub fn sync_filesystem(sb: &Arc<dyn vfs::SuperBlock>) -> Result<(), SysError> {
+    let synced = page::writeback::sync_pages(Some(sb)).map_err(SysError::from_i32);
+    synced.and(sb.sync_fs())
+}
+
+
+/// mount the disk fs as the root, and the sdcard under it
+fn init_diskfs() -> Arc<dyn Dentry> {
     let sdcard_dev_name;
     let disk_dev_name;
     #[cfg(target_arch="riscv64")]
@@ -109,34 +162,68 @@ pub fn init() {
     // create the ext4 file system using the block device
     let diskfs = get_filesystem(DISK_FS_NAME);
     let diskfs_root = diskfs.mount("/", None, MountFlags::empty(), Some(disk_device)).unwrap();
+    add_mount(&format!("/dev/{}", disk_dev_name), DISK_FS_NAME, diskfs, diskfs_root.clone(), MountFlags::empty());
 
     let sdcard = get_filesystem(SDCARD_NAME);
     let sdcard_root = sdcard.mount("sdcard", Some(diskfs_root.clone()), MountFlags::empty(), Some(sdcard_device)).unwrap();
     diskfs_root.add_child(sdcard_root.clone());
+    add_mount(&format!("/dev/{}", sdcard_dev_name), DISK_FS_NAME, sdcard, sdcard_root.clone(), MountFlags::empty());
     log::info!("[FS] insert path: {}", sdcard_root.path());
     DCACHE.lock().insert(sdcard_root.path(), sdcard_root);
+    diskfs_root
+}
 
-    // mount the dev file system under diskfs
+/// init the file system
+/// the root is the initrd if the boot loader gave one, else the disk
+pub fn init() {
+    register_all_fs();
+    let root = match init_rootfs() {
+        Some(root) => root,
+        None => init_diskfs(),
+    };
+
+    // mount the dev file system under the root
     let devfs = get_filesystem("devfs");
-    let devfs_root = devfs.mount("dev", Some(diskfs_root.clone()), MountFlags::empty(), None).unwrap();
+    let devfs_root = devfs.mount("dev", Some(root.clone()), MountFlags::empty(), None).unwrap();
     init_devfs(devfs_root.clone());
-    diskfs_root.add_child(devfs_root.clone());
+    root.add_child(devfs_root.clone());
+    add_mount("devtmpfs", "devtmpfs", devfs, devfs_root.clone(), MountFlags::MS_NOSUID);
     log::info!("[FS] insert path: {}", devfs_root.path());
-    DCACHE.lock().insert(devfs_root.path(), devfs_root);
-
-    // mount the proc file system under diskfs
+    DCACHE.lock().insert(devfs_root.path(), devfs_root.clone());
+
+    // mount the message queue file system under devfs
+    let mqueue = get_filesystem("mqueue");
+    let mqueue_root = mqueue.mount("mqueue", Some(devfs_root.clone()), MountFlags::empty(), None).unwrap();
+    init_mqueue(mqueue_root.clone());
+    devfs_root.add_child(mqueue_root.clone());
+    add_mount("mqueue", "mqueue", mqueue, mqueue_root.clone(), MountFlags::MS_NOSUID | MountFlags::MS_NODEV | MountFlags::MS_NOEXEC);
+    log::info!("[FS] insert path: {}", mqueue_root.path());
+    DCACHE.lock().insert(mqueue_root.path(), mqueue_root);
+
+    // mount the proc file system under the root
     let procfs = get_filesystem("procfs");
-    let procfs_root = procfs.mount("proc", Some(diskfs_root.clone()), MountFlags::empty(), None).unwrap();
+    let procfs_root = procfs.mount("proc", Some(root.clone()), MountFlags::empty(), None).unwrap();
     init_procfs(procfs_root.clone());
-    diskfs_root.add_child(procfs_root.clone());
+    root.add_child(procfs_root.clone());
+    add_mount("proc", "proc", procfs, procfs_root.clone(), MountFlags::MS_NOSUID | MountFlags::MS_NODEV | MountFlags::MS_NOEXEC);
     log::info!("[FS] insert path: {}", procfs_root.path());
     DCACHE.lock().insert(procfs_root.path(), procfs_root);
 
-    // mount the tmp file system under diskfs
+    // mount the sys file system under the root
+    let sysfs = get_filesystem("sysfs");
+    let sysfs_root = sysfs.mount("sys", Some(root.clone()), MountFlags::empty(), None).unwrap();
+    init_sysfs(sysfs_root.clone());
+    root.add_child(sysfs_root.clone());
+    add_mount("sysfs", "sysfs", sysfs, sysfs_root.clone(), MountFlags::MS_NOSUID | MountFlags::MS_NODEV | MountFlags::MS_NOEXEC);
+    log::info!("[FS] insert path: {}", sysfs_root.path());
+    DCACHE.lock().insert(sysfs_root.path(), sysfs_root);
+
+    // mount the tmp file system under the root
     let tmpfs = get_filesystem("tmpfs");
-    let tmpfs_root = tmpfs.mount("tmp", Some(diskfs_root.clone()), MountFlags::empty(), None).unwrap();
+    let tmpfs_root = tmpfs.mount("tmp", Some(root.clone()), MountFlags::empty(), None).unwrap();
     init_tmpfs(tmpfs_root.clone());
-    diskfs_root.add_child(tmpfs_root.clone());
+    root.add_child(tmpfs_root.clone());
+    add_mount("tmpfs", "tmpfs", tmpfs, tmpfs_root.clone(), MountFlags::MS_NOSUID | MountFlags::MS_NODEV);
     log::info!("[FS] insert path: {}", tmpfs_root.path());
     DCACHE.lock().insert(tmpfs_root.path(), tmpfs_root);
 
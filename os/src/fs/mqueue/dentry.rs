@@ -0,0 +1,76 @@
+//! mqueue dentry, owns the queue of a name
+
+use alloc::{sync::Arc, vec::Vec};
+
+use crate::{fs::{tmpfs::file::TmpFile, vfs::{inode::InodeMode, Dentry, DentryInner, DentryState, File}, OpenFlags}, sync::mutex::SpinNoIrqLock, syscall::SysError};
+
+use super::{file::MqFile, queue::{MqQueue, DFLT_MSGMAX, DFLT_MSGSIZEMAX}};
+
+pub struct MqDentry {
+    inner: DentryInner,
+    /// the queue of a positive non-dir dentry
+    queue: SpinNoIrqLock<Option<Arc<MqQueue>>>,
+}
+
+unsafe impl Send for MqDentry {}
+unsafe impl Sync for MqDentry {}
+
+impl MqDentry {
+    pub fn new(name: &str, parent: Option<Arc<dyn Dentry>>, queue: Option<Arc<MqQueue>>) -> Arc<Self> {
+        Arc::new(Self {
+            inner: DentryInner::new(name, parent),
+            queue: SpinNoIrqLock::new(queue),
+        })
+    }
+}
+
+impl Dentry for MqDentry {
+    fn dentry_inner(&self) -> &DentryInner {
+        &self.inner
+    }
+
+    fn new(&self, name: &str, parent: Option<Arc<dyn Dentry>>) -> Arc<dyn Dentry> {
+        MqDentry::new(name, parent, None)
+    }
+
+    /// a queue created by open(2) instead of mq_open gets the default attributes
+    fn open(self: Arc<Self>, flags: OpenFlags) -> Option<Arc<dyn File>> {
+        let inode = self.inode()?;
+        if inode.inode_type().contains(InodeMode::DIR) {
+            return Some(Arc::new(TmpFile::new(self)));
+        }
+        let queue = self.queue.lock()
+            .get_or_insert_with(|| MqQueue::new(DFLT_MSGMAX, DFLT_MSGSIZEMAX))
+            .clone();
+        Some(MqFile::new(self, queue, flags))
+    }
+
+    fn load_child_dentry(self: Arc<Self>) -> Result<Vec<Arc<dyn Dentry>>, SysError> {
+        if let Some(inode) = self.inode() {
+            if !inode.inode_type().contains(InodeMode::DIR) {
+                return Err(SysError::ENOTDIR)
+            }
+        }
+        Ok(self.children()
+            .into_values()
+            .filter(|child| child.state() != DentryState::NEGATIVE)
+            .collect())
+    }
+
+    fn new_neg_dentry(self: Arc<Self>, name: &str) -> Result<Arc<dyn Dentry>, SysError> {
+        let inode = self.inode().unwrap();
+        if !inode.inode_type().contains(InodeMode::DIR) {
+            return Err(SysError::ENOTDIR)
+        }
+        let neg_dentry = MqDentry::new(name, Some(self.clone()), None);
+        neg_dentry.set_state(DentryState::NEGATIVE);
+        Ok(neg_dentry)
+    }
+
+    /// unlink drops the name, opened descriptors keep the queue alive
+    fn clear_inode(&self) {
+        *self.dentry_inner().inode.lock() = None;
+        *self.queue.lock() = None;
+        self.set_state(DentryState::NEGATIVE);
+    }
+}
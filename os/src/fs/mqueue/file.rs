@@ -0,0 +1,75 @@
+//! file of an opened message queue
+
+use core::sync::atomic::AtomicUsize;
+
+use alloc::{boxed::Box, sync::Arc};
+use async_trait::async_trait;
+
+use crate::{fs::{vfs::{file::PollEvents, Dentry, File, FileInner}, OpenFlags}, sync::mutex::SpinNoIrqLock, syscall::SysError, utils::get_waker};
+
+use super::queue::MqQueue;
+
+/// message queue descriptor
+pub struct MqFile {
+    inner: FileInner,
+    queue: Arc<MqQueue>,
+}
+
+impl MqFile {
+    pub fn new(dentry: Arc<dyn Dentry>, queue: Arc<MqQueue>, flags: OpenFlags) -> Arc<Self> {
+        Arc::new(Self {
+            inner: FileInner {
+                dentry,
+                offset: AtomicUsize::new(0),
+                flags: SpinNoIrqLock::new(flags),
+            },
+            queue,
+        })
+    }
+
+    /// the queue behind the descriptor
+    pub fn queue(&self) -> &Arc<MqQueue> {
+        &self.queue
+    }
+}
+
+#[async_trait]
+impl File for MqFile {
+    fn file_inner(&self) -> &FileInner {
+        &self.inner
+    }
+
+    fn readable(&self) -> bool {
+        !self.flags().contains(OpenFlags::O_WRONLY)
+    }
+
+    fn writable(&self) -> bool {
+        self.flags().intersects(OpenFlags::O_WRONLY | OpenFlags::O_RDWR)
+    }
+
+    /// reading the file gives the status line of the queue
+    async fn read(&self, buf: &mut [u8]) -> Result<usize, SysError> {
+        let status = self.queue.status();
+        let pos = self.pos();
+        if pos >= status.len() {
+            return Ok(0);
+        }
+        let len = buf.len().min(status.len() - pos);
+        buf[..len].copy_from_slice(&status.as_bytes()[pos..pos + len]);
+        self.set_pos(pos + len);
+        Ok(len)
+    }
+
+    async fn write(&self, _buf: &[u8]) -> Result<usize, SysError> {
+        Err(SysError::EINVAL)
+    }
+
+    fn size(&self) -> usize {
+        self.queue.bytes()
+    }
+
+    async fn base_poll(&self, events: PollEvents) -> PollEvents {
+        let waker = get_waker().await;
+        self.queue.poll(events, waker)
+    }
+}
@@ -0,0 +1,44 @@
+use alloc::sync::Arc;
+
+use crate::{devices::BlockDevice, fs::{vfs::{fstype::{FSType, FSTypeInner, MountFlags}, inode::InodeMode, Dentry, DentryState, DCACHE}, SuperBlock, SuperBlockInner}};
+
+use super::{dentry::MqDentry, inode::MqInode, superblock::MqSuperBlock};
+
+pub struct MqFSType {
+    inner: FSTypeInner,
+}
+
+impl MqFSType {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self {
+            inner: FSTypeInner::new("mqueue"),
+        })
+    }
+}
+
+impl FSType for MqFSType {
+    fn inner(&self) -> &FSTypeInner {
+        &self.inner
+    }
+
+    fn mount(&'static self, name: &str, parent: Option<Arc<dyn Dentry>>, _flags: MountFlags, dev: Option<Arc<dyn BlockDevice>>) -> Option<Arc<dyn Dentry>> {
+        let fs_type = unsafe {
+            let ptr: *const dyn FSType = self;
+            Arc::from_raw(ptr)
+        };
+        let sb = MqSuperBlock::new(SuperBlockInner::new(dev, fs_type.clone()));
+        // sticky like /tmp, everyone can create queues
+        let root_inode = MqInode::new(Arc::downgrade(&sb), InodeMode::DIR | InodeMode::STICKY | InodeMode::from_bits_truncate(0o777));
+        let root_dentry: Arc<dyn Dentry> = MqDentry::new(name, parent.clone(), None);
+        root_dentry.set_inode(root_inode);
+        root_dentry.set_state(DentryState::USED);
+        sb.set_root_dentry(root_dentry.clone());
+        DCACHE.lock().insert(root_dentry.path(), root_dentry.clone());
+        self.add_sb(&root_dentry.path(), sb);
+        Some(root_dentry)
+    }
+
+    fn kill_sb(&self) -> isize {
+        self.inner.kill_supers()
+    }
+}
@@ -0,0 +1,67 @@
+//! mqueue inode, the queue itself hangs on the dentry
+
+use alloc::sync::{Arc, Weak};
+
+use crate::{fs::{vfs::{inode::InodeMode, Inode, InodeInner}, Kstat, SuperBlock}, syscall::SysError};
+
+pub struct MqInode {
+    inner: InodeInner,
+}
+
+unsafe impl Send for MqInode {}
+unsafe impl Sync for MqInode {}
+
+impl MqInode {
+    /// create a queue or root dir inode
+    pub fn new(super_block: Weak<dyn SuperBlock>, mode: InodeMode) -> Arc<Self> {
+        Arc::new(Self {
+            inner: InodeInner::new(Some(super_block), mode, 0),
+        })
+    }
+}
+
+impl Inode for MqInode {
+    fn inode_inner(&self) -> &InodeInner {
+        &self.inner
+    }
+
+    /// only queues can be created, the dentry gets a default queue on open
+    fn create(&self, _name: &str, mode: InodeMode) -> Result<Arc<dyn Inode>, SysError> {
+        if !self.inode_type().contains(InodeMode::DIR) {
+            return Err(SysError::ENOTDIR);
+        }
+        if mode.get_type() != InodeMode::FILE {
+            return Err(SysError::EINVAL);
+        }
+        let sb = self.inode_inner().super_block.clone().unwrap();
+        Ok(MqInode::new(sb, mode))
+    }
+
+    fn remove(&self, _name: &str, _mode: InodeMode) -> Result<usize, i32> {
+        Ok(0)
+    }
+
+    fn getattr(&self) -> Kstat {
+        let inner = self.inode_inner();
+        Kstat {
+            st_dev: 0,
+            st_ino: inner.ino as u64,
+            st_mode: inner.mode().bits() as _,
+            st_nlink: inner.nlink() as u32,
+            st_uid: inner.uid(),
+            st_gid: inner.gid(),
+            st_rdev: 0,
+            _pad0: 0,
+            st_size: inner.size() as _,
+            _pad1: 0,
+            st_blksize: 0,
+            st_blocks: 0,
+            st_atime_sec: inner.atime().tv_sec as _,
+            st_atime_nsec: inner.atime().tv_nsec as _,
+            st_mtime_sec: inner.mtime().tv_sec as _,
+            st_mtime_nsec: inner.mtime().tv_nsec as _,
+            st_ctime_sec: inner.ctime().tv_sec as _,
+            st_ctime_nsec: inner.ctime().tv_nsec as _,
+        }
+    }
+}
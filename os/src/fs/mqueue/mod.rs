@@ -0,0 +1,131 @@
+//! POSIX message queue file system
+//!
+//! every queue is a regular file in the root of the fs. the instance
+//! mounted at /dev/mqueue is the one mq_open and mq_unlink work on.
+
+use alloc::sync::Arc;
+
+use crate::{fs::{vfs::{inode::InodeMode, Dentry, File, DCACHE}, OpenFlags}, sync::mutex::SpinNoIrqLock, syscall::SysError};
+
+use dentry::MqDentry;
+use inode::MqInode;
+use queue::{MqAttr, MqQueue, DFLT_MSGMAX, DFLT_MSGSIZEMAX, HARD_MSGMAX, HARD_MSGSIZEMAX};
+
+pub mod fstype;
+pub mod superblock;
+pub mod inode;
+pub mod dentry;
+pub mod file;
+pub mod queue;
+
+/// max length of a queue name
+const NAME_MAX: usize = 255;
+
+/// root of the mqueue instance used by the mq_* syscalls
+static MQUEUE_ROOT: SpinNoIrqLock<Option<Arc<dyn Dentry>>> = SpinNoIrqLock::new(None);
+
+/// init /dev/mqueue
+pub fn init_mqueue(root_dentry: Arc<dyn Dentry>) {
+    *MQUEUE_ROOT.lock() = Some(root_dentry);
+}
+
+fn mqueue_root() -> Result<Arc<dyn Dentry>, SysError> {
+    MQUEUE_ROOT.lock().clone().ok_or(SysError::ENOSYS)
+}
+
+fn check_name(name: &str) -> Result<(), SysError> {
+    if name.is_empty() {
+        return Err(SysError::ENOENT);
+    }
+    if name.len() > NAME_MAX {
+        return Err(SysError::ENAMETOOLONG);
+    }
+    if name.contains('/') || name == "." || name == ".." {
+        return Err(SysError::EACCES);
+    }
+    Ok(())
+}
+
+/// permission bits of the queue that apply to `uid`
+fn may_open(dentry: &Arc<dyn Dentry>, flags: OpenFlags, uid: u32) -> Result<(), SysError> {
+    if uid == 0 {
+        return Ok(());
+    }
+    let inode = dentry.inode().ok_or(SysError::ENOENT)?;
+    let mode = inode.inode_inner().mode().bits();
+    let granted = if inode.inode_inner().uid() == uid {
+        (mode >> 6) & 0o7
+    } else {
+        mode & 0o7
+    };
+    let want = if flags.contains(OpenFlags::O_RDWR) {
+        0o6
+    } else if flags.contains(OpenFlags::O_WRONLY) {
+        0o2
+    } else {
+        0o4
+    };
+    if granted & want == want {
+        Ok(())
+    } else {
+        Err(SysError::EACCES)
+    }
+}
+
+/// open or create the queue `name`, `attr` is only used on creation
+pub fn mq_open(name: &str, flags: OpenFlags, mode: u32, attr: Option<MqAttr>, uid: u32) -> Result<Arc<dyn File>, SysError> {
+    check_name(name)?;
+    let root = mqueue_root()?;
+    if let Some(dentry) = root.get_child(name).filter(|d| !d.is_negative()) {
+        if flags.contains(OpenFlags::O_CREAT | OpenFlags::O_EXCL) {
+            return Err(SysError::EEXIST);
+        }
+        may_open(&dentry, flags, uid)?;
+        return dentry.open(flags).ok_or(SysError::ENOENT);
+    }
+    if !flags.contains(OpenFlags::O_CREAT) {
+        return Err(SysError::ENOENT);
+    }
+    let (maxmsg, msgsize) = match attr {
+        Some(attr) => {
+            if attr.mq_maxmsg <= 0 || attr.mq_msgsize <= 0 {
+                return Err(SysError::EINVAL);
+            }
+            let (maxmsg, msgsize) = (attr.mq_maxmsg as usize, attr.mq_msgsize as usize);
+            if maxmsg > HARD_MSGMAX || msgsize > HARD_MSGSIZEMAX {
+                return Err(SysError::EINVAL);
+            }
+            // unprivileged users are bound to the default limits
+            if uid != 0 && (maxmsg > DFLT_MSGMAX || msgsize > DFLT_MSGSIZEMAX) {
+                return Err(SysError::EINVAL);
+            }
+            (maxmsg, msgsize)
+        }
+        None => (DFLT_MSGMAX, DFLT_MSGSIZEMAX),
+    };
+    let sb = root.inode().unwrap().inode_inner().super_block.clone().unwrap();
+    let inode = MqInode::new(sb, InodeMode::FILE | InodeMode::from_bits_truncate(mode & 0o777));
+    inode.inode_inner().set_uid(uid);
+    let dentry: Arc<dyn Dentry> = MqDentry::new(name, Some(root.clone()), Some(MqQueue::new(maxmsg, msgsize)));
+    dentry.set_inode(inode);
+    root.add_child(dentry.clone());
+    DCACHE.lock().insert(dentry.path(), dentry.clone());
+    dentry.open(flags).ok_or(SysError::ENOENT)
+}
+
+/// remove the queue `name`, it is destroyed once the last descriptor is closed
+pub fn mq_unlink(name: &str, uid: u32) -> Result<(), SysError> {
+    check_name(name)?;
+    let root = mqueue_root()?;
+    let dentry = root.get_child(name)
+        .filter(|d| !d.is_negative())
+        .ok_or(SysError::ENOENT)?;
+    let owner = dentry.inode().unwrap().inode_inner().uid();
+    if uid != 0 && uid != owner {
+        return Err(SysError::EACCES);
+    }
+    dentry.clear_inode();
+    root.remove_child(name);
+    DCACHE.lock().remove(&dentry.path());
+    Ok(())
+}
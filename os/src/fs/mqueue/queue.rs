@@ -0,0 +1,292 @@
+//! POSIX message queue object
+
+use core::{future::Future, pin::Pin, task::{Context, Poll, Waker}};
+
+use alloc::{collections::{btree_map::BTreeMap, vec_deque::VecDeque}, format, string::String, sync::Arc, vec::Vec};
+
+use crate::{fs::vfs::file::PollEvents, signal::{SigInfo, SIGRTMAX}, sync::mutex::SpinNoIrqLock, syscall::SysError, task::manager::TASK_MANAGER};
+
+/// priorities are in [0, MQ_PRIO_MAX)
+pub const MQ_PRIO_MAX: u32 = 32768;
+/// default max number of messages
+pub const DFLT_MSGMAX: usize = 10;
+/// default max message size
+pub const DFLT_MSGSIZEMAX: usize = 8192;
+/// limit of max number of messages
+pub const HARD_MSGMAX: usize = 65536;
+/// limit of max message size
+pub const HARD_MSGSIZEMAX: usize = 16 * 1024 * 1024;
+
+/// notify by sending `sigev_signo`
+pub const SIGEV_SIGNAL: i32 = 0;
+/// register, but do not notify
+pub const SIGEV_NONE: i32 = 1;
+/// notify by starting a thread, done by libc through netlink
+pub const SIGEV_THREAD: i32 = 2;
+
+/// `struct mq_attr`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MqAttr {
+    /// 0 or O_NONBLOCK
+    pub mq_flags: isize,
+    /// max number of messages
+    pub mq_maxmsg: isize,
+    /// max message size
+    pub mq_msgsize: isize,
+    /// number of messages in the queue
+    pub mq_curmsgs: isize,
+    _reserved: [isize; 4],
+}
+
+/// `struct sigevent`
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct SigEvent {
+    /// data passed with the notification
+    pub sigev_value: usize,
+    /// notification signal
+    pub sigev_signo: i32,
+    /// notification method
+    pub sigev_notify: i32,
+    _pad: [i32; 12],
+}
+
+/// the process registered by mq_notify
+#[derive(Debug, Clone, Copy)]
+struct MqNotify {
+    pid: usize,
+    notify: i32,
+    signo: i32,
+}
+
+struct MqQueueInner {
+    maxmsg: usize,
+    msgsize: usize,
+    /// queued messages grouped by priority
+    msgs: BTreeMap<u32, VecDeque<Vec<u8>>>,
+    curmsgs: usize,
+    bytes: usize,
+    send_wakers: VecDeque<Waker>,
+    recv_wakers: VecDeque<Waker>,
+    /// tasks blocked in mq_receive, notification is only sent without them
+    receivers: usize,
+    notify: Option<MqNotify>,
+}
+
+/// POSIX message queue
+pub struct MqQueue {
+    inner: SpinNoIrqLock<MqQueueInner>,
+}
+
+impl MqQueue {
+    /// create an empty queue
+    pub fn new(maxmsg: usize, msgsize: usize) -> Arc<Self> {
+        Arc::new(Self {
+            inner: SpinNoIrqLock::new(MqQueueInner {
+                maxmsg,
+                msgsize,
+                msgs: BTreeMap::new(),
+                curmsgs: 0,
+                bytes: 0,
+                send_wakers: VecDeque::new(),
+                recv_wakers: VecDeque::new(),
+                receivers: 0,
+                notify: None,
+            }),
+        })
+    }
+
+    /// attributes of the queue, mq_flags is up to the open file
+    pub fn attr(&self) -> MqAttr {
+        let inner = self.inner.lock();
+        MqAttr {
+            mq_maxmsg: inner.maxmsg as isize,
+            mq_msgsize: inner.msgsize as isize,
+            mq_curmsgs: inner.curmsgs as isize,
+            ..Default::default()
+        }
+    }
+
+    /// bytes of all queued messages
+    pub fn bytes(&self) -> usize {
+        self.inner.lock().bytes
+    }
+
+    /// the text read from a queue file
+    pub fn status(&self) -> String {
+        let inner = self.inner.lock();
+        let (notify, signo, pid) = match inner.notify {
+            Some(n) => (n.notify, if n.notify == SIGEV_SIGNAL { n.signo } else { 0 }, n.pid),
+            None => (0, 0, 0),
+        };
+        format!("QSIZE:{:<10} NOTIFY:{:<5} SIGNO:{:<5} NOTIFY_PID:{:<6}\n", inner.bytes, notify, signo, pid)
+    }
+
+    /// mq_notify: register `pid`, or unregister it when `event` is None
+    pub fn set_notify(&self, pid: usize, event: Option<&SigEvent>) -> Result<(), SysError> {
+        let mut inner = self.inner.lock();
+        let Some(event) = event else {
+            if inner.notify.is_some_and(|n| n.pid == pid) {
+                inner.notify = None;
+            }
+            return Ok(());
+        };
+        match event.sigev_notify {
+            SIGEV_NONE => {}
+            SIGEV_SIGNAL => {
+                if event.sigev_signo <= 0 || event.sigev_signo as usize > SIGRTMAX {
+                    return Err(SysError::EINVAL);
+                }
+            }
+            _ => return Err(SysError::EINVAL),
+        }
+        if inner.notify.is_some() {
+            return Err(SysError::EBUSY);
+        }
+        inner.notify = Some(MqNotify { pid, notify: event.sigev_notify, signo: event.sigev_signo });
+        Ok(())
+    }
+
+    /// poll for a message or for room
+    pub fn poll(&self, events: PollEvents, waker: Waker) -> PollEvents {
+        let mut inner = self.inner.lock();
+        let mut res = PollEvents::empty();
+        if events.contains(PollEvents::IN) && inner.curmsgs > 0 {
+            res |= PollEvents::IN;
+        }
+        if events.contains(PollEvents::OUT) && inner.curmsgs < inner.maxmsg {
+            res |= PollEvents::OUT;
+        }
+        if res.is_empty() {
+            if events.contains(PollEvents::IN) {
+                inner.recv_wakers.push_back(waker.clone());
+            }
+            if events.contains(PollEvents::OUT) {
+                inner.send_wakers.push_back(waker);
+            }
+        }
+        res
+    }
+}
+
+/// send the registered notification, it is a one shot
+fn deliver(notify: MqNotify, sender: usize) {
+    if notify.notify != SIGEV_SIGNAL {
+        return;
+    }
+    if let Some(task) = TASK_MANAGER.get_task(notify.pid) {
+        task.recv_sigs_process_level(SigInfo {
+            si_signo: notify.signo as usize,
+            si_code: SigInfo::MESGQ,
+            si_pid: Some(sender),
+        });
+    }
+}
+
+/// future for mq_timedsend, waits until the queue has room
+pub struct MqSendFuture {
+    queue: Arc<MqQueue>,
+    msg: Option<(u32, Vec<u8>)>,
+    pid: usize,
+    nowait: bool,
+}
+
+impl MqSendFuture {
+    pub fn new(queue: Arc<MqQueue>, prio: u32, msg: Vec<u8>, pid: usize, nowait: bool) -> Self {
+        Self { queue, msg: Some((prio, msg)), pid, nowait }
+    }
+}
+
+impl Future for MqSendFuture {
+    type Output = Result<(), SysError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = unsafe { self.get_unchecked_mut() };
+        let mut inner = this.queue.inner.lock();
+        if inner.curmsgs >= inner.maxmsg {
+            if this.nowait {
+                return Poll::Ready(Err(SysError::EAGAIN));
+            }
+            inner.send_wakers.push_back(cx.waker().clone());
+            return Poll::Pending;
+        }
+        let (prio, msg) = this.msg.take().unwrap();
+        let was_empty = inner.curmsgs == 0;
+        inner.bytes += msg.len();
+        inner.curmsgs += 1;
+        inner.msgs.entry(prio).or_default().push_back(msg);
+        // like linux, only notify when nobody is waiting in mq_receive
+        let notify = if was_empty && inner.receivers == 0 {
+            inner.notify.take()
+        } else {
+            None
+        };
+        while let Some(waker) = inner.recv_wakers.pop_front() {
+            waker.wake();
+        }
+        drop(inner);
+        if let Some(notify) = notify {
+            deliver(notify, this.pid);
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// future for mq_timedreceive, takes the oldest message of the highest priority
+pub struct MqRecvFuture {
+    queue: Arc<MqQueue>,
+    nowait: bool,
+    /// counted in `receivers`
+    waiting: bool,
+}
+
+impl MqRecvFuture {
+    pub fn new(queue: Arc<MqQueue>, nowait: bool) -> Self {
+        Self { queue, nowait, waiting: false }
+    }
+}
+
+impl Future for MqRecvFuture {
+    /// (priority, message)
+    type Output = Result<(u32, Vec<u8>), SysError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = unsafe { self.get_unchecked_mut() };
+        let mut inner = this.queue.inner.lock();
+        let Some(mut entry) = inner.msgs.last_entry() else {
+            if this.nowait {
+                return Poll::Ready(Err(SysError::EAGAIN));
+            }
+            if !this.waiting {
+                this.waiting = true;
+                inner.receivers += 1;
+            }
+            inner.recv_wakers.push_back(cx.waker().clone());
+            return Poll::Pending;
+        };
+        let prio = *entry.key();
+        let msg = entry.get_mut().pop_front().unwrap();
+        if entry.get().is_empty() {
+            entry.remove();
+        }
+        inner.bytes -= msg.len();
+        inner.curmsgs -= 1;
+        if this.waiting {
+            this.waiting = false;
+            inner.receivers -= 1;
+        }
+        while let Some(waker) = inner.send_wakers.pop_front() {
+            waker.wake();
+        }
+        Poll::Ready(Ok((prio, msg)))
+    }
+}
+
+impl Drop for MqRecvFuture {
+    fn drop(&mut self) {
+        if self.waiting {
+            self.queue.inner.lock().receivers -= 1;
+        }
+    }
+}
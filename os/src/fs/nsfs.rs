@@ -0,0 +1,133 @@
+//! namespace files
+//!
+//! the files under /proc/<pid>/ns, opening one gives a handle on the
+//! namespace the task lived in at that time, which setns accepts.
+
+use core::sync::atomic::AtomicUsize;
+
+use alloc::{boxed::Box, format, string::String, sync::{Arc, Weak}};
+use async_trait::async_trait;
+
+use crate::{fs::{tmpfs::{dentry::TmpDentry, inode::{InodeContent, TmpSysInode}}, vfs::{inode::InodeMode, Dentry, DentryInner, File, FileInner}, OpenFlags}, sync::mutex::SpinNoIrqLock, syscall::{process::CloneFlags, SysError}, task::{ns::NsHandle, task::TaskControlBlock}};
+
+/// an open namespace file
+pub struct NsFile {
+    inner: FileInner,
+    handle: NsHandle,
+}
+
+impl NsFile {
+    pub fn new(handle: NsHandle, flags: OpenFlags) -> Arc<Self> {
+        Arc::new(Self {
+            inner: FileInner {
+                dentry: Arc::<usize>::new_zeroed(),
+                offset: AtomicUsize::new(0),
+                flags: SpinNoIrqLock::new(flags),
+            },
+            handle,
+        })
+    }
+
+    /// the namespace behind the file
+    pub fn handle(&self) -> &NsHandle {
+        &self.handle
+    }
+
+    /// name like "uts:[4]", what /proc/<pid>/fd shows
+    pub fn name(&self) -> String {
+        ns_name(&self.handle)
+    }
+}
+
+#[async_trait]
+impl File for NsFile {
+    fn file_inner(&self) -> &FileInner {
+        &self.inner
+    }
+
+    fn readable(&self) -> bool {
+        false
+    }
+
+    fn writable(&self) -> bool {
+        false
+    }
+
+    async fn read(&self, _buf: &mut [u8]) -> Result<usize, SysError> {
+        Err(SysError::EINVAL)
+    }
+
+    async fn write(&self, _buf: &[u8]) -> Result<usize, SysError> {
+        Err(SysError::EINVAL)
+    }
+
+    fn dentry(&self) -> Option<Arc<dyn Dentry>> {
+        None
+    }
+}
+
+fn ns_name(handle: &NsHandle) -> String {
+    format!("{}:[{}]", handle.kind(), handle.id())
+}
+
+/// content of /proc/<pid>/ns/<kind>, the name of the namespace
+struct NsContent {
+    task: Weak<TaskControlBlock>,
+    flag: CloneFlags,
+}
+
+impl InodeContent for NsContent {
+    fn serialize(&self) -> String {
+        self.task.upgrade()
+            .and_then(|task| NsHandle::of(&task, self.flag))
+            .map_or(String::new(), |handle| ns_name(&handle))
+    }
+}
+
+/// /proc/<pid>/ns/<kind>, opens to the namespace of kind `flag` the
+/// task is in at the time of the open
+pub struct NsDentry {
+    inner: DentryInner,
+    task: Weak<TaskControlBlock>,
+    flag: CloneFlags,
+}
+
+impl NsDentry {
+    pub fn new(name: &str, parent: Arc<dyn Dentry>, task: Weak<TaskControlBlock>, flag: CloneFlags) -> Arc<Self> {
+        let dentry = Arc::new(Self {
+            inner: DentryInner::new(name, Some(parent)),
+            task: task.clone(),
+            flag,
+        });
+        dentry.set_inode(TmpSysInode::new(InodeMode::FILE, Arc::new(NsContent { task, flag })));
+        dentry
+    }
+}
+
+impl Dentry for NsDentry {
+    fn dentry_inner(&self) -> &DentryInner {
+        &self.inner
+    }
+
+    fn new(&self, name: &str, parent: Option<Arc<dyn Dentry>>) -> Arc<dyn Dentry> {
+        TmpDentry::new(name, parent)
+    }
+
+    fn open(self: Arc<Self>, flags: OpenFlags) -> Option<Arc<dyn File>> {
+        let task = self.task.upgrade()?;
+        let handle = NsHandle::of(&task, self.flag)?;
+        Some(NsFile::new(handle, flags))
+    }
+}
+
+/// create /proc/<pid>/ns under `parent`
+pub fn create_ns_dir(task: Weak<TaskControlBlock>, dir: Arc<dyn Dentry>) {
+    for (flag, name) in [
+        (CloneFlags::NEWNS, "mnt"),
+        (CloneFlags::NEWUTS, "uts"),
+        (CloneFlags::NEWIPC, "ipc"),
+        (CloneFlags::NEWPID, "pid"),
+    ] {
+        dir.add_child(NsDentry::new(name, dir.clone(), task.clone(), flag));
+    }
+}
@@ -0,0 +1,457 @@
+//! overlay file system dentry
+//!
+//! an overlay dentry stands for one name across the layers: the real dentry
+//! in the upper dir if there is one, then the ones in the lower dirs showing
+//! through it. a `.wh.<name>` in a layer hides `name` in the layers below,
+//! a dir holding `.wh..wh..opq` hides the dirs below it.
+
+use alloc::{collections::{btree_map::BTreeMap, btree_set::BTreeSet}, format, string::{String, ToString}, sync::{Arc, Weak}, vec, vec::Vec};
+
+use crate::{config::PAGE_SIZE, fs::{vfs::{inode::InodeMode, mount::same_dentry, Dentry, DentryInner, DentryState, File, Inode}, OpenFlags}, sync::mutex::SpinNoIrqLock, syscall::SysError};
+
+use super::{file::OverlayFile, inode::OverlayInode};
+
+/// prefix of a whiteout, `.wh.<name>` hides `name` in the layers below
+pub const WHITEOUT_PREFIX: &str = ".wh.";
+/// marker of an opaque dir, nothing below it shows through
+pub const OPAQUE_MARKER: &str = ".wh..wh..opq";
+
+fn whiteout_name(name: &str) -> String {
+    format!("{}{}", WHITEOUT_PREFIX, name)
+}
+
+fn is_dir(dentry: &Arc<dyn Dentry>) -> bool {
+    dentry.inode().map_or(false, |inode| inode.inode_type() == InodeMode::DIR)
+}
+
+/// whether `child` belongs to the same file system as the layer dir `dir`,
+/// what is mounted inside a layer is not part of it
+fn in_layer(dir: &Arc<dyn Dentry>, child: &Arc<dyn Dentry>) -> bool {
+    let dir_sb = dir.inode().and_then(|inode| inode.inode_inner().super_block.clone());
+    let child_sb = child.inode().and_then(|inode| inode.inode_inner().super_block.clone());
+    match (dir_sb, child_sb) {
+        (Some(a), Some(b)) => Weak::ptr_eq(&a, &b),
+        _ => true,
+    }
+}
+
+/// the real child `name` of the layer dir `dir`
+fn real_child(dir: &Arc<dyn Dentry>, name: &str) -> Option<Arc<dyn Dentry>> {
+    let child = dir.clone().walk(name).ok()?;
+    if child.is_negative() || child.inode().is_none() || !in_layer(dir, &child) {
+        return None;
+    }
+    Some(child)
+}
+
+fn is_opaque(dir: &Arc<dyn Dentry>) -> bool {
+    is_dir(dir) && real_child(dir, OPAQUE_MARKER).is_some()
+}
+
+/// create `name` in the real dir `dir`, the way the syscalls do
+fn real_create(dir: &Arc<dyn Dentry>, name: &str, mode: InodeMode) -> Result<Arc<dyn Dentry>, SysError> {
+    let child = dir.clone().walk(name)?;
+    if !child.is_negative() {
+        return Err(SysError::EEXIST);
+    }
+    let inode = dir.inode().ok_or(SysError::ENOENT)?.create(name, mode)?;
+    child.set_inode(inode);
+    dir.add_child(child.clone());
+    Ok(child)
+}
+
+/// create the symlink `name` to `target` in the real dir `dir`
+fn real_symlink(dir: &Arc<dyn Dentry>, name: &str, target: &str) -> Result<Arc<dyn Dentry>, SysError> {
+    let child = dir.clone().walk(name)?;
+    if !child.is_negative() {
+        return Err(SysError::EEXIST);
+    }
+    let inode = dir.inode().ok_or(SysError::ENOENT)?.symlink(target, &child.path())?;
+    child.set_inode(inode);
+    dir.add_child(child.clone());
+    Ok(child)
+}
+
+/// remove the real `child` of the real dir `dir`
+fn real_remove(dir: &Arc<dyn Dentry>, child: &Arc<dyn Dentry>) -> Result<(), SysError> {
+    let inode = child.inode().ok_or(SysError::ENOENT)?;
+    let mode = inode.inode_inner().mode();
+    child.clear_inode();
+    inode.clean_cached();
+    drop(inode);
+    dir.inode().ok_or(SysError::ENOENT)?
+        .remove(child.name(), mode)
+        .map_err(SysError::from_i32)?;
+    dir.remove_child(child.name());
+    Ok(())
+}
+
+fn copy_data(from: &Arc<dyn Inode>, to: &Arc<dyn Inode>) -> Result<(), SysError> {
+    let mut buf = vec![0u8; PAGE_SIZE];
+    let mut offset = 0;
+    loop {
+        let len = if from.cache().is_some() {
+            from.clone().cache_read_at(offset, &mut buf)
+        } else {
+            from.read_at(offset, &mut buf)
+        }.map_err(SysError::from_i32)?;
+        if len == 0 {
+            return Ok(());
+        }
+        if to.cache().is_some() {
+            to.clone().cache_write_at(offset, &buf[..len])
+        } else {
+            to.write_at(offset, &buf[..len])
+        }.map_err(SysError::from_i32)?;
+        offset += len;
+    }
+}
+
+fn copy_attrs(from: &Arc<dyn Inode>, to: &Arc<dyn Inode>) {
+    let (from, to) = (from.inode_inner(), to.inode_inner());
+    to.set_mode(from.mode());
+    to.set_uid(from.uid());
+    to.set_gid(from.gid());
+    to.set_atime(from.atime());
+    to.set_mtime(from.mtime());
+    to.set_ctime(from.ctime());
+}
+
+/// dentry of the overlay file system
+pub struct OverlayDentry {
+    /// own name, parent and the children mounted on top
+    inner: DentryInner,
+    this: Weak<OverlayDentry>,
+    /// the overlay dir it is in, none for the root
+    parent_node: Option<Weak<OverlayDentry>>,
+    /// the real dentry in the upper layer
+    upper: SpinNoIrqLock<Option<Arc<dyn Dentry>>>,
+    /// the real dentries in the lower layers showing through, top first
+    lowers: SpinNoIrqLock<Vec<Arc<dyn Dentry>>>,
+    /// the children looked up so far, negative ones included
+    nodes: SpinNoIrqLock<BTreeMap<String, Arc<OverlayDentry>>>,
+}
+
+unsafe impl Send for OverlayDentry {}
+unsafe impl Sync for OverlayDentry {}
+
+impl OverlayDentry {
+    /// the root of an overlay of `upper` on `lowers`, read only without `upper`
+    pub fn new_root(name: &str, parent: Option<Arc<dyn Dentry>>, upper: Option<Arc<dyn Dentry>>, lowers: Vec<Arc<dyn Dentry>>) -> Arc<Self> {
+        let lowers = if upper.as_ref().map_or(false, is_opaque) { Vec::new() } else { lowers };
+        Self::new_node(name, parent, None, upper, lowers)
+    }
+
+    fn new_node(
+        name: &str,
+        parent: Option<Arc<dyn Dentry>>,
+        parent_node: Option<Weak<OverlayDentry>>,
+        upper: Option<Arc<dyn Dentry>>,
+        lowers: Vec<Arc<dyn Dentry>>,
+    ) -> Arc<Self> {
+        let node = Arc::new_cyclic(|this| Self {
+            inner: DentryInner::new(name, parent),
+            this: this.clone(),
+            parent_node,
+            upper: SpinNoIrqLock::new(upper),
+            lowers: SpinNoIrqLock::new(lowers),
+            nodes: SpinNoIrqLock::new(BTreeMap::new()),
+        });
+        node.refresh();
+        node
+    }
+
+    fn upper(&self) -> Option<Arc<dyn Dentry>> {
+        self.upper.lock().clone()
+    }
+
+    fn lowers(&self) -> Vec<Arc<dyn Dentry>> {
+        self.lowers.lock().clone()
+    }
+
+    /// the real dentry on top, the one the overlay shows
+    pub fn real(&self) -> Option<Arc<dyn Dentry>> {
+        self.upper().or_else(|| self.lowers.lock().first().cloned())
+    }
+
+    fn is_dir(&self) -> bool {
+        self.real().map_or(false, |real| is_dir(&real))
+    }
+
+    /// point the inode at the real one on top
+    fn refresh(&self) {
+        let inode = self.real()
+            .and_then(|real| real.inode())
+            .map(|real| OverlayInode::new(self.this.clone(), real) as Arc<dyn Inode>);
+        *self.inner.inode.lock() = inode;
+    }
+
+    /// show only `upper` from now on, after the name is created or removed
+    fn reset(&self, upper: Option<Arc<dyn Dentry>>) {
+        *self.upper.lock() = upper;
+        self.lowers.lock().clear();
+        self.nodes.lock().clear();
+        self.refresh();
+    }
+
+    /// the real dentries named `name` in the layers of this dir,
+    /// the upper one and the lower ones showing through
+    fn lookup(&self, name: &str) -> (Option<Arc<dyn Dentry>>, Vec<Arc<dyn Dentry>>) {
+        let mut upper = None;
+        let mut lowers = Vec::new();
+        if name.starts_with(WHITEOUT_PREFIX) {
+            return (upper, lowers);
+        }
+        if let Some(dir) = self.upper() {
+            if real_child(&dir, &whiteout_name(name)).is_some() {
+                return (upper, lowers);
+            }
+            if let Some(child) = real_child(&dir, name) {
+                if !is_dir(&child) || is_opaque(&child) {
+                    return (Some(child), lowers);
+                }
+                upper = Some(child);
+            }
+        }
+        for dir in self.lowers() {
+            if real_child(&dir, &whiteout_name(name)).is_some() {
+                break;
+            }
+            let Some(child) = real_child(&dir, name) else {
+                continue;
+            };
+            // what was found above is a dir, a file below it is hidden
+            let found = upper.is_some() || !lowers.is_empty();
+            if found && !is_dir(&child) {
+                break;
+            }
+            let last = !is_dir(&child) || is_opaque(&child);
+            lowers.push(child);
+            if last {
+                break;
+            }
+        }
+        (upper, lowers)
+    }
+
+    /// the child `name`, looked up in the layers the first time
+    fn child(&self, name: &str) -> Arc<OverlayDentry> {
+        if let Some(node) = self.nodes.lock().get(name) {
+            return node.clone();
+        }
+        let (upper, lowers) = self.lookup(name);
+        let this: Arc<dyn Dentry> = self.this.upgrade().unwrap();
+        let node = Self::new_node(name, Some(this), Some(self.this.clone()), upper, lowers);
+        self.nodes.lock().entry(name.to_string()).or_insert(node).clone()
+    }
+
+    /// copy the dentry up to the upper layer unless it is there,
+    /// the dirs above it first, returns the real upper dentry
+    pub fn copy_up(&self) -> Result<Arc<dyn Dentry>, SysError> {
+        if let Some(upper) = self.upper() {
+            return Ok(upper);
+        }
+        let lower = self.real().ok_or(SysError::ENOENT)?;
+        // only the root has no parent, without an upper dir it is read only
+        let parent = self.parent_node.as_ref()
+            .and_then(|parent| parent.upgrade())
+            .ok_or(SysError::EROFS)?;
+        let upper_dir = parent.copy_up()?;
+        let lower_inode = lower.inode().ok_or(SysError::ENOENT)?;
+        let upper = match lower_inode.inode_type() {
+            InodeMode::DIR => real_create(&upper_dir, self.name(), InodeMode::DIR)?,
+            InodeMode::LINK => real_symlink(&upper_dir, self.name(), &lower_inode.readlink()?)?,
+            InodeMode::FILE => {
+                let upper = real_create(&upper_dir, self.name(), InodeMode::FILE)?;
+                copy_data(&lower_inode, &upper.inode().unwrap())?;
+                upper
+            }
+            _ => return Err(SysError::EOPNOTSUPP),
+        };
+        copy_attrs(&lower_inode, &upper.inode().unwrap());
+        log::info!("[overlayfs] copy up {} to {}", lower.path(), upper.path());
+        *self.upper.lock() = Some(upper.clone());
+        self.refresh();
+        Ok(upper)
+    }
+
+    /// create the file `name` of type `mode` in this dir
+    pub fn create_child(&self, name: &str, mode: InodeMode) -> Result<Arc<dyn Inode>, SysError> {
+        self.make_child(name, |dir| real_create(dir, name, mode.get_type()))
+    }
+
+    /// create the symlink `name` to `target` in this dir
+    pub fn symlink_child(&self, name: &str, target: &str) -> Result<Arc<dyn Inode>, SysError> {
+        self.make_child(name, |dir| real_symlink(dir, name, target))
+    }
+
+    fn make_child(
+        &self,
+        name: &str,
+        make: impl FnOnce(&Arc<dyn Dentry>) -> Result<Arc<dyn Dentry>, SysError>,
+    ) -> Result<Arc<dyn Inode>, SysError> {
+        if name.starts_with(WHITEOUT_PREFIX) {
+            return Err(SysError::EINVAL);
+        }
+        let node = self.child(name);
+        if !node.is_negative() {
+            return Err(SysError::EEXIST);
+        }
+        let upper_dir = self.copy_up()?;
+        let whiteout = real_child(&upper_dir, &whiteout_name(name));
+        if let Some(whiteout) = whiteout.as_ref() {
+            real_remove(&upper_dir, whiteout)?;
+        }
+        let upper = make(&upper_dir)?;
+        // a new dir in place of a removed one does not show the old content
+        if whiteout.is_some() && is_dir(&upper) {
+            real_create(&upper, OPAQUE_MARKER, InodeMode::FILE)?;
+        }
+        node.reset(Some(upper));
+        Ok(node.inode().unwrap())
+    }
+
+    /// remove `name` from this dir, leaving a whiteout if a lower layer has it
+    pub fn remove_node(&self, name: &str) -> Result<(), SysError> {
+        let node = self.child(name);
+        if node.is_negative() {
+            return Err(SysError::ENOENT);
+        }
+        if node.is_dir() && !node.clone().load_child_dentry()?.is_empty() {
+            return Err(SysError::ENOTEMPTY);
+        }
+        let upper_dir = self.copy_up()?;
+        if let Some(upper) = node.upper() {
+            if is_dir(&upper) {
+                // only whiteouts and the opaque marker are left in it
+                for child in upper.clone().load_child_dentry()? {
+                    real_remove(&upper, &child)?;
+                }
+            }
+            real_remove(&upper_dir, &upper)?;
+        }
+        if !node.lowers.lock().is_empty() {
+            real_create(&upper_dir, &whiteout_name(name), InodeMode::FILE)?;
+        }
+        node.reset(None);
+        Ok(())
+    }
+}
+
+impl Dentry for OverlayDentry {
+    fn dentry_inner(&self) -> &DentryInner {
+        &self.inner
+    }
+
+    /// an overlay dentry only comes from its dir, this is the child `name`
+    fn new(&self, name: &str, _parent: Option<Arc<dyn Dentry>>) -> Arc<dyn Dentry> {
+        self.child(name)
+    }
+
+    fn prepare_write(&self) -> Result<(), SysError> {
+        self.copy_up().map(|_| ())
+    }
+
+    /// the copy up errors were reported by `prepare_write` already
+    fn open(self: Arc<Self>, flags: OpenFlags) -> Option<Arc<dyn File>> {
+        if flags.writable() || flags.contains(OpenFlags::O_TRUNC) {
+            self.copy_up().ok()?;
+        }
+        let real = self.real()?.open(flags)?;
+        Some(OverlayFile::new(self, real))
+    }
+
+    /// the inode follows the layers, see `refresh`
+    fn set_inode(&self, _inode: Arc<dyn Inode>) {
+        self.refresh();
+    }
+
+    fn clear_inode(&self) {
+        self.refresh();
+    }
+
+    fn children(&self) -> BTreeMap<String, Arc<dyn Dentry>> {
+        let mut children: BTreeMap<String, Arc<dyn Dentry>> = self.nodes.lock().iter()
+            .map(|(name, node)| (name.clone(), node.clone() as Arc<dyn Dentry>))
+            .collect();
+        // the mounts on top hide what is below
+        children.extend(self.inner.children.lock().clone());
+        children
+    }
+
+    fn get_child(&self, name: &str) -> Option<Arc<dyn Dentry>> {
+        if let Some(child) = self.inner.children.lock().get(name) {
+            return Some(child.clone());
+        }
+        if !self.is_dir() {
+            return None;
+        }
+        Some(self.child(name))
+    }
+
+    fn add_child(&self, child: Arc<dyn Dentry>) {
+        let own = self.nodes.lock().get(child.name())
+            .map_or(false, |node| same_dentry(&(node.clone() as Arc<dyn Dentry>), &child));
+        if own {
+            // back from under a mount, or created by open with O_CREAT
+            self.inner.children.lock().remove(child.name());
+        } else {
+            self.inner.children.lock().insert(child.name().to_string(), child);
+        }
+    }
+
+    /// a child stays until the name is created again, negative
+    fn remove_child(&self, name: &str) {
+        self.inner.children.lock().remove(name);
+    }
+
+    fn state(&self) -> DentryState {
+        if self.real().is_some() {
+            DentryState::USED
+        } else {
+            DentryState::NEGATIVE
+        }
+    }
+
+    fn set_state(&self, _state: DentryState) {}
+
+    fn is_negative(&self) -> bool {
+        self.real().is_none()
+    }
+
+    /// the merged listing of the layers
+    fn load_child_dentry(self: Arc<Self>) -> Result<Vec<Arc<dyn Dentry>>, SysError> {
+        if !self.is_dir() {
+            return Err(SysError::ENOTDIR);
+        }
+        let mut names = BTreeSet::new();
+        let mut hidden = BTreeSet::new();
+        for dir in self.upper().into_iter().chain(self.lowers()) {
+            let mut whiteouts = Vec::new();
+            for child in dir.clone().load_child_dentry()? {
+                if !in_layer(&dir, &child) || child.name() == OPAQUE_MARKER {
+                    continue;
+                }
+                match child.name().strip_prefix(WHITEOUT_PREFIX) {
+                    Some(name) => whiteouts.push(name.to_string()),
+                    None if !hidden.contains(child.name()) => {
+                        names.insert(child.name().to_string());
+                    }
+                    None => {}
+                }
+            }
+            hidden.extend(whiteouts);
+        }
+        for name in names {
+            self.child(&name);
+        }
+        Ok(self.children().into_values().filter(|child| !child.is_negative()).collect())
+    }
+
+    fn new_neg_dentry(self: Arc<Self>, name: &str) -> Result<Arc<dyn Dentry>, SysError> {
+        if !self.is_dir() {
+            return Err(SysError::ENOTDIR);
+        }
+        Ok(self.child(name))
+    }
+}
@@ -0,0 +1,94 @@
+//! overlay file system file
+//!
+//! an open real file, shown as the overlay dentry
+
+use core::sync::atomic::AtomicUsize;
+
+use alloc::{boxed::Box, sync::Arc};
+use async_trait::async_trait;
+
+use crate::{fs::{vfs::{file::{PollEvents, SeekFrom}, Dentry, File, FileInner}, OpenFlags}, sync::mutex::SpinNoIrqLock, syscall::{SysError, SysResult}};
+
+use super::dentry::OverlayDentry;
+
+pub struct OverlayFile {
+    /// the overlay dentry, the offset and flags are the real file's
+    inner: FileInner,
+    real: Arc<dyn File>,
+}
+
+impl OverlayFile {
+    pub fn new(dentry: Arc<OverlayDentry>, real: Arc<dyn File>) -> Arc<Self> {
+        Arc::new(Self {
+            inner: FileInner {
+                dentry,
+                offset: AtomicUsize::new(0),
+                flags: SpinNoIrqLock::new(real.flags()),
+            },
+            real,
+        })
+    }
+}
+
+#[async_trait]
+impl File for OverlayFile {
+    fn file_inner(&self) -> &FileInner {
+        &self.inner
+    }
+
+    fn readable(&self) -> bool {
+        self.real.readable()
+    }
+
+    fn writable(&self) -> bool {
+        self.real.writable()
+    }
+
+    async fn read(&self, buf: &mut [u8]) -> Result<usize, SysError> {
+        self.real.read(buf).await
+    }
+
+    async fn write(&self, buf: &[u8]) -> Result<usize, SysError> {
+        self.real.write(buf).await
+    }
+
+    async fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize, SysError> {
+        self.real.read_at(offset, buf).await
+    }
+
+    async fn write_at(&self, offset: usize, buf: &[u8]) -> Result<usize, SysError> {
+        self.real.write_at(offset, buf).await
+    }
+
+    fn ioctl(&self, cmd: usize, arg: usize) -> SysResult {
+        self.real.ioctl(cmd, arg)
+    }
+
+    async fn base_poll(&self, events: PollEvents) -> PollEvents {
+        self.real.base_poll(events).await
+    }
+
+    fn flags(&self) -> OpenFlags {
+        self.real.flags()
+    }
+
+    fn set_flags(&self, flags: OpenFlags) {
+        self.real.set_flags(flags)
+    }
+
+    fn size(&self) -> usize {
+        self.real.size()
+    }
+
+    fn pos(&self) -> usize {
+        self.real.pos()
+    }
+
+    fn set_pos(&self, pos: usize) {
+        self.real.set_pos(pos)
+    }
+
+    fn seek(&self, offset: SeekFrom) -> Result<usize, SysError> {
+        self.real.seek(offset)
+    }
+}
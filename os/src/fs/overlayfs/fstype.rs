@@ -0,0 +1,101 @@
+use alloc::{string::String, sync::Arc, vec::Vec};
+
+use crate::{devices::BlockDevice, fs::{vfs::{fstype::{FSType, FSTypeInner, MountFlags}, inode::InodeMode, Dentry, DCACHE}, AtFlags, SuperBlockInner}, processor::current_task, syscall::{at_helper1, SysError}};
+
+use super::{dentry::OverlayDentry, superblock::OverlaySuperBlock};
+
+pub struct OverlayFSType {
+    inner: FSTypeInner,
+}
+
+impl OverlayFSType {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self {
+            inner: FSTypeInner::new("overlay"),
+        })
+    }
+}
+
+/// the layers given by the mount options
+struct OverlayOptions {
+    /// top first
+    lowers: Vec<String>,
+    upper: Option<String>,
+}
+
+impl OverlayOptions {
+    /// parse `lowerdir=a:b,upperdir=c,workdir=d`, the work dir is not needed
+    fn parse(options: &str) -> Result<Self, SysError> {
+        let mut lowers = Vec::new();
+        let mut upper = None;
+        for option in options.split(',').filter(|o| !o.is_empty()) {
+            match option.split_once('=') {
+                Some(("lowerdir", dirs)) => {
+                    lowers = dirs.split(':').filter(|d| !d.is_empty()).map(String::from).collect();
+                }
+                Some(("upperdir", dir)) => upper = Some(String::from(dir)),
+                Some(("workdir", _)) => {}
+                _ => return Err(SysError::EINVAL),
+            }
+        }
+        if lowers.is_empty() {
+            return Err(SysError::EINVAL);
+        }
+        Ok(Self { lowers, upper })
+    }
+}
+
+/// the dir at `path` as seen by the mounting task
+fn lookup_dir(path: &str) -> Result<Arc<dyn Dentry>, SysError> {
+    let task = current_task().ok_or(SysError::ENOENT)?.clone();
+    let dentry = at_helper1(task, AtFlags::AT_FDCWD.bits() as isize, path, AtFlags::empty())?;
+    if dentry.is_negative() {
+        return Err(SysError::ENOENT);
+    }
+    if dentry.inode().unwrap().inode_type() != InodeMode::DIR {
+        return Err(SysError::ENOTDIR);
+    }
+    Ok(dentry)
+}
+
+impl FSType for OverlayFSType {
+    fn inner(&self) -> &FSTypeInner {
+        &self.inner
+    }
+
+    /// the layers come with the options, see `mount_with_options`
+    fn mount(&'static self, _name: &str, _parent: Option<Arc<dyn Dentry>>, _flags: MountFlags, _dev: Option<Arc<dyn BlockDevice>>) -> Option<Arc<dyn Dentry>> {
+        None
+    }
+
+    /// without an upper dir nothing can be written
+    fn mount_flags(&self, flags: MountFlags, options: &str) -> MountFlags {
+        match OverlayOptions::parse(options) {
+            Ok(options) if options.upper.is_none() => flags | MountFlags::MS_RDONLY,
+            _ => flags,
+        }
+    }
+
+    fn mount_with_options(&'static self, name: &str, parent: Option<Arc<dyn Dentry>>, _flags: MountFlags, dev: Option<Arc<dyn BlockDevice>>, options: &str) -> Result<Arc<dyn Dentry>, SysError> {
+        // first, the count is given back on the error paths too
+        let fs_type = unsafe {
+            let ptr: *const dyn FSType = self;
+            Arc::from_raw(ptr)
+        };
+        let options = OverlayOptions::parse(options)?;
+        let upper = options.upper.as_deref().map(lookup_dir).transpose()?;
+        let lowers = options.lowers.iter()
+            .map(|dir| lookup_dir(dir))
+            .collect::<Result<Vec<_>, _>>()?;
+        let sb = OverlaySuperBlock::new(SuperBlockInner::new(dev, fs_type.clone()));
+        let root_dentry: Arc<dyn Dentry> = OverlayDentry::new_root(name, parent, upper, lowers);
+        sb.set_root_dentry(root_dentry.clone());
+        DCACHE.lock().insert(root_dentry.path(), root_dentry.clone());
+        self.add_sb(&root_dentry.path(), sb);
+        Ok(root_dentry)
+    }
+
+    fn kill_sb(&self) -> isize {
+        self.inner.kill_supers()
+    }
+}
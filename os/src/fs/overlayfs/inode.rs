@@ -0,0 +1,127 @@
+//! overlay file system inode
+//!
+//! the inode of the real file on top, changes to the tree go through the
+//! overlay dentry so they land in the upper layer.
+
+use alloc::{string::String, sync::{Arc, Weak}, vec::Vec};
+
+use crate::{fs::{page::{cache::PageCache, page::Page}, vfs::{inode::InodeMode, Inode, InodeInner}, Kstat, Xstat, XstatMask}, syscall::SysError, utils::abs_path_to_name};
+
+use super::dentry::OverlayDentry;
+
+pub struct OverlayInode {
+    /// the overlay dentry it belongs to
+    dentry: Weak<OverlayDentry>,
+    /// the inode of the real file on top at the time
+    real: Arc<dyn Inode>,
+}
+
+unsafe impl Send for OverlayInode {}
+unsafe impl Sync for OverlayInode {}
+
+impl OverlayInode {
+    pub fn new(dentry: Weak<OverlayDentry>, real: Arc<dyn Inode>) -> Arc<Self> {
+        Arc::new(Self { dentry, real })
+    }
+
+    fn dentry(&self) -> Result<Arc<OverlayDentry>, SysError> {
+        self.dentry.upgrade().ok_or(SysError::ENOENT)
+    }
+}
+
+impl Inode for OverlayInode {
+    fn inode_inner(&self) -> &InodeInner {
+        self.real.inode_inner()
+    }
+
+    fn lookup(&self, name: &str) -> Option<Arc<dyn Inode>> {
+        self.real.lookup(name)
+    }
+
+    fn ls(&self) -> Vec<String> {
+        self.real.ls()
+    }
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize, i32> {
+        self.real.read_at(offset, buf)
+    }
+
+    fn write_at(&self, offset: usize, buf: &[u8]) -> Result<usize, i32> {
+        self.real.write_at(offset, buf)
+    }
+
+    fn cache(&self) -> Option<Arc<PageCache>> {
+        self.real.cache()
+    }
+
+    fn read_page_at(self: Arc<Self>, offset: usize) -> Option<Arc<Page>> {
+        self.real.clone().read_page_at(offset)
+    }
+
+    fn cache_read_at(self: Arc<Self>, offset: usize, buf: &mut [u8]) -> Result<usize, i32> {
+        self.real.clone().cache_read_at(offset, buf)
+    }
+
+    fn cache_write_at(self: Arc<Self>, offset: usize, buf: &[u8]) -> Result<usize, i32> {
+        self.real.clone().cache_write_at(offset, buf)
+    }
+
+    fn readahead(self: Arc<Self>, offset: usize, len: usize) -> Result<(), i32> {
+        self.real.clone().readahead(offset, len)
+    }
+
+    fn fsync(&self, datasync: bool) -> Result<(), SysError> {
+        self.real.fsync(datasync)
+    }
+
+    fn create(&self, name: &str, mode: InodeMode) -> Result<Arc<dyn Inode>, SysError> {
+        self.dentry()?.create_child(name, mode)
+    }
+
+    fn truncate(&self, size: usize) -> Result<usize, SysError> {
+        let upper = self.dentry()?.copy_up()?;
+        upper.inode().ok_or(SysError::ENOENT)?.truncate(size)
+    }
+
+    fn getattr(&self) -> Kstat {
+        self.real.getattr()
+    }
+
+    fn getxattr(&self, mask: XstatMask) -> Xstat {
+        self.real.getxattr(mask)
+    }
+
+    fn symlink(&self, target_path: &str, link_path: &str) -> Result<Arc<dyn Inode>, SysError> {
+        let name = abs_path_to_name(link_path).ok_or(SysError::EINVAL)?;
+        self.dentry()?.symlink_child(&name, target_path)
+    }
+
+    /// hard links would need the lower file copied up under both names
+    fn link(&self, _target: &str) -> Result<usize, SysError> {
+        Err(SysError::EXDEV)
+    }
+
+    fn readlink(&self) -> Result<String, SysError> {
+        self.real.readlink()
+    }
+
+    fn unlink(&self) -> Result<usize, i32> {
+        Err(SysError::EPERM as i32)
+    }
+
+    fn remove(&self, name: &str, _mode: InodeMode) -> Result<usize, i32> {
+        self.dentry()
+            .and_then(|dentry| dentry.remove_node(name))
+            .map(|_| 0)
+            .map_err(|e| e as i32)
+    }
+
+    /// like across mounts, user space falls back to copy and remove
+    fn rename(&self, _target: &str, _new_inode: Option<Arc<dyn Inode>>) -> Result<(), SysError> {
+        Err(SysError::EXDEV)
+    }
+
+    fn clean_cached(&self) {
+        self.real.clean_cached()
+    }
+}
@@ -0,0 +1,11 @@
+//! Overlay file system
+//!
+//! stacks a writable upper dir on read only lower dirs, e.g. a tmpfs on the
+//! disk root, so a read only image can be used and the changes thrown away:
+//! `mount -t overlay overlay -o lowerdir=/lower2:/lower1,upperdir=/upper /merged`
+
+pub mod superblock;
+pub mod fstype;
+pub mod inode;
+pub mod dentry;
+pub mod file;
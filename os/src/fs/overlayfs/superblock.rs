@@ -0,0 +1,24 @@
+//! overlay file system super block
+
+use alloc::sync::Arc;
+
+use crate::fs::{vfs::Inode, SuperBlock, SuperBlockInner};
+
+pub struct OverlaySuperBlock {
+    inner: SuperBlockInner,
+}
+
+impl OverlaySuperBlock {
+    pub fn new(inner: SuperBlockInner) -> Arc<dyn SuperBlock> {
+        Arc::new(Self { inner })
+    }
+}
+
+impl SuperBlock for OverlaySuperBlock {
+    fn inner(&self) -> &SuperBlockInner {
+        &self.inner
+    }
+    fn get_root_inode(&'static self, _name: &str) -> Arc<dyn Inode> {
+        self.inner().root.get().unwrap().clone().inode().unwrap()
+    }
+}
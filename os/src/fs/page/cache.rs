@@ -1,52 +1,176 @@
 //! Page Cache
 //! each inode will hold a page cache
-//! (todos): 1. radix tree to manage the offset to page
-//! 2. ahead read 
+//! pages are kept in an xarray by page index, tagged dirty, under
+//! writeback or uptodate, and read in ahead of sequential readers
 
-use core::{cmp, sync::atomic::{AtomicUsize, Ordering}};
+use core::{cmp, sync::atomic::{AtomicI32, AtomicUsize, Ordering}};
 
-use crate::{fs::vfs::Inode, sync::mutex::SpinNoIrqLock};
-use alloc::{collections::btree_map::BTreeMap, sync::Arc};
+use crate::{fs::vfs::Inode, mm::vm::write_protect_page, sync::mutex::SpinNoIrqLock};
+use alloc::{sync::{Arc, Weak}, vec::Vec};
 // use hashbrown::HashMap;
 use log::info;
 
-use super::page::{Page, PAGE_SIZE};
+use super::{lru, page::{Page, PAGE_SIZE}, readahead::FileRaState, xarray::{XArray, XaMark}};
+
+/// the page is newer than the file
+pub const PAGECACHE_TAG_DIRTY: XaMark = XaMark::new(0);
+/// the page is being written to the file
+pub const PAGECACHE_TAG_WRITEBACK: XaMark = XaMark::new(1);
+/// the page holds the data of the file
+pub const PAGECACHE_TAG_UPTODATE: XaMark = XaMark::new(2);
 
 pub struct PageCache {
-    /// from file offset(should be page aligned)
+    /// from page index (file offset / PAGE_SIZE)
     /// to the cached page
-    /// TODO: now use BTreeMap to flush data from low off to high off
-    /// to avoid the 'Seek Beyond file size' problem
-    /// should fs support this?
-    pages: SpinNoIrqLock<BTreeMap<usize, Arc<Page>>>,
+    pages: SpinNoIrqLock<XArray<Arc<Page>>>,
     /// the postion of EOF
     /// save it to prevent endless read
-    /// notice that it may need to update when 
+    /// notice that it may need to update when
     /// cache write, as it may lead to expand the file
     end: AtomicUsize,
+    /// the cache itself if its pages are on the lru
+    this: Weak<PageCache>,
+    /// the inode dirty pages are written back to, if the file has a backing store
+    host: Option<Weak<dyn Inode>>,
+    /// the first write back error since the last fsync, 0 if none
+    wb_err: AtomicI32,
+    /// read ahead window of the file
+    ra: SpinNoIrqLock<FileRaState>,
 }
 
 impl PageCache {
-    /// create a new Page Cache
+    /// create a new Page Cache, its pages are the only copy of the data
     pub fn new() -> Self {
         Self {
-            pages: SpinNoIrqLock::new(BTreeMap::new()),
+            pages: SpinNoIrqLock::new(XArray::new()),
             end: AtomicUsize::new(0usize),
+            this: Weak::new(),
+            host: None,
+            wb_err: AtomicI32::new(0),
+            ra: SpinNoIrqLock::new(FileRaState::new()),
         }
     }
+    /// create the page cache of `host` which has a backing store, its pages
+    /// are written back by the flusher and dropped under memory pressure
+    pub fn with_host(host: Weak<dyn Inode>) -> Arc<Self> {
+        Arc::new_cyclic(|this| Self {
+            this: this.clone(),
+            host: Some(host),
+            ..Self::new()
+        })
+    }
     /// get the cache inner
-    pub fn get_pages(&self) -> &SpinNoIrqLock<BTreeMap<usize, Arc<Page>>> {
+    pub fn get_pages(&self) -> &SpinNoIrqLock<XArray<Arc<Page>>> {
         &self.pages
     }
+    /// get the read ahead window
+    pub fn get_ra(&self) -> &SpinNoIrqLock<FileRaState> {
+        &self.ra
+    }
     /// get the page at file offset
     pub fn get_page(&self, offset: usize) -> Option<Arc<Page>> {
         assert!(offset % PAGE_SIZE == 0);
-        self.pages.lock().get(&offset).cloned()
+        let page = self.pages.lock().load(offset / PAGE_SIZE).cloned()?;
+        page.mark_accessed();
+        Some(page)
     }
-    /// insert the page at file offset
+    /// whether there is a page at file offset, without counting as a use
+    pub fn contains(&self, offset: usize) -> bool {
+        self.pages.lock().load(offset / PAGE_SIZE).is_some()
+    }
+    /// insert the page at file offset, it must hold the data of the file
     pub fn insert_page(&self, offset: usize, page: Arc<Page>) {
         assert!(offset % PAGE_SIZE == 0);
-        self.pages.lock().insert(offset, page);
+        if self.host.is_some() {
+            page.set_backed();
+        }
+        {
+            let mut pages = self.pages.lock();
+            pages.store(offset / PAGE_SIZE, page.clone());
+            pages.set_mark(offset / PAGE_SIZE, PAGECACHE_TAG_UPTODATE);
+        }
+        if self.host.is_some() {
+            lru::add(self.this.clone(), offset, &page);
+        }
+    }
+    /// insert the page at file offset unless one is there already, returns
+    /// the page that is cached
+    pub fn insert_page_if_absent(&self, offset: usize, page: Arc<Page>) -> Arc<Page> {
+        assert!(offset % PAGE_SIZE == 0);
+        if self.host.is_some() {
+            page.set_backed();
+        }
+        {
+            let mut pages = self.pages.lock();
+            if let Some(cached) = pages.load(offset / PAGE_SIZE) {
+                return cached.clone();
+            }
+            pages.store(offset / PAGE_SIZE, page.clone());
+            pages.set_mark(offset / PAGE_SIZE, PAGECACHE_TAG_UPTODATE);
+        }
+        if self.host.is_some() {
+            lru::add(self.this.clone(), offset, &page);
+        }
+        page
+    }
+    /// set the page at file offset dirty
+    pub fn set_page_dirty(&self, offset: usize, page: &Page) {
+        page.set_dirty();
+        self.pages.lock().set_mark(offset / PAGE_SIZE, PAGECACHE_TAG_DIRTY);
+    }
+    /// forget that the pages are dirty, they are never written back
+    pub fn clear_dirty(&self) {
+        let mut pages = self.pages.lock();
+        let dirty: Vec<_> = pages.iter_marked(PAGECACHE_TAG_DIRTY)
+            .map(|(index, page)| {
+                page.set_clean();
+                index
+            })
+            .collect();
+        for index in dirty {
+            pages.clear_mark(index, PAGECACHE_TAG_DIRTY);
+        }
+    }
+    /// whether `page` at `index` may be dropped, `refs` being the references
+    /// to it the caller knows of
+    fn evictable(pages: &XArray<Arc<Page>>, index: usize, page: &Arc<Page>, refs: usize) -> bool {
+        pages.load(index).is_some_and(|cached| Arc::ptr_eq(cached, page))
+            && Arc::strong_count(page) == refs
+            && page.frame.get_owners() == 1
+            && !page.is_dirty()
+            && !pages.get_mark(index, PAGECACHE_TAG_DIRTY)
+            && !pages.get_mark(index, PAGECACHE_TAG_WRITEBACK)
+    }
+    /// drop the page at `offset` if it is still `page` and no one else
+    /// holds or maps it, whether it was dropped
+    pub(super) fn evict(&self, offset: usize, page: &Arc<Page>) -> bool {
+        let Some(mut pages) = self.pages.try_lock() else {
+            return false;
+        };
+        // the cache, the lru and nothing else
+        if !Self::evictable(&pages, offset / PAGE_SIZE, page, 2) {
+            return false;
+        }
+        pages.erase(offset / PAGE_SIZE);
+        true
+    }
+    /// drop the clean pages in [start, end) no one else holds or maps,
+    /// returns the number dropped
+    pub fn invalidate_range(&self, start: usize, end: usize) -> usize {
+        // without a backing store the pages are the data itself
+        if self.host.is_none() {
+            return 0;
+        }
+        let mut pages = self.pages.lock();
+        let (first, last) = (start / PAGE_SIZE, end.div_ceil(PAGE_SIZE));
+        let unused: Vec<_> = pages.iter()
+            .filter(|&(index, page)| (first..last).contains(&index) && Self::evictable(&pages, index, page, 1))
+            .map(|(index, _)| index)
+            .collect();
+        for &index in unused.iter() {
+            pages.erase(index);
+        }
+        unused.len()
     }
     pub fn update_end(&self, offset: usize) {
         let end = self.end.load(Ordering::Acquire);
@@ -56,15 +180,66 @@ impl PageCache {
     pub fn end(&self) -> usize {
         self.end.load(Ordering::Acquire)
     }
-    /// flush all dirty pages
-    pub fn flush(&self, inode: Arc<dyn Inode>) {
+    /// the inode pages are written back to
+    pub fn host(&self) -> Option<Arc<dyn Inode>> {
+        self.host.as_ref().and_then(|host| host.upgrade())
+    }
+    /// write `page` at `offset` back to `inode` if it is dirty, an error is
+    /// kept for the next fsync
+    pub fn write_page(&self, inode: &dyn Inode, offset: usize, page: &Page) -> Result<(), i32> {
+        let index = offset / PAGE_SIZE;
+        if !page.is_dirty() {
+            self.pages.lock().clear_mark(index, PAGECACHE_TAG_DIRTY);
+            return Ok(());
+        }
+        // stores through a writable shared mapping would not dirty the page
+        // again, those mappings are write protected before it is cleaned. a
+        // mapping that cannot be keeps the page dirty
+        let protected = !page.take_mapped_writable() || write_protect_page(page);
+        {
+            let mut pages = self.pages.lock();
+            if !page.is_dirty() {
+                pages.clear_mark(index, PAGECACHE_TAG_DIRTY);
+                return Ok(());
+            }
+            // cleaned first, a write meanwhile dirties it again
+            page.set_clean();
+            pages.clear_mark(index, PAGECACHE_TAG_DIRTY);
+            pages.set_mark(index, PAGECACHE_TAG_WRITEBACK);
+        }
+        let len = cmp::min(self.end().saturating_sub(offset), PAGE_SIZE);
+        let ret = inode.write_at(offset, &page.get_slice::<u8>()[..len]);
+        self.pages.lock().clear_mark(index, PAGECACHE_TAG_WRITEBACK);
+        if !protected {
+            page.set_mapped_writable();
+            self.set_page_dirty(offset, page);
+        }
+        if let Err(e) = ret {
+            log::warn!("[PageCache] write back at {:#x} failed: {}", offset, e);
+            let _ = self.wb_err.compare_exchange(0, e, Ordering::AcqRel, Ordering::Relaxed);
+            return Err(e);
+        }
+        Ok(())
+    }
+    /// flush all dirty pages, the first error if any
+    pub fn flush(&self, inode: &dyn Inode) -> Result<(), i32> {
         info!("start to flush all pages");
-        let mut pages = self.pages.lock();
-        for (&offset, page) in pages.iter_mut() {
-            if page.is_dirty() == false {
-                continue;
+        let dirty: Vec<_> = self.pages.lock().iter_marked(PAGECACHE_TAG_DIRTY)
+            .map(|(index, page)| (index * PAGE_SIZE, page.clone()))
+            .collect();
+        let mut ret = Ok(());
+        for (offset, page) in dirty {
+            if let Err(e) = self.write_page(inode, offset, &page) {
+                ret = ret.and(Err(e));
             }
-            inode.write_at(offset, page.get_slice::<u8>()).expect("[PageCache]: failed at flush");
+        }
+        ret
+    }
+    /// the write back error since the last call, to be returned by fsync
+    pub fn take_error(&self) -> Result<(), i32> {
+        match self.wb_err.swap(0, Ordering::AcqRel) {
+            0 => Ok(()),
+            e => Err(e),
         }
     }
-}
\ No newline at end of file
+}
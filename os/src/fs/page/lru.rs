@@ -0,0 +1,106 @@
+//! lru of the page cache pages of all files with a backing store
+//!
+//! a page starts on the inactive list and goes to the active one if it is
+//! looked up again before reclaim reaches it. reclaim drops clean pages
+//! from the tail of the inactive list, and ages the active list into it to
+//! keep it no longer than the inactive one. entries are not removed when a
+//! page leaves its cache, reclaim and the flusher drop them instead.
+
+use alloc::{collections::VecDeque, sync::{Arc, Weak}, vec::Vec};
+
+use crate::sync::mutex::SpinNoIrqLock;
+
+use super::{cache::PageCache, page::{Page, PAGE_SIZE}};
+
+struct LruEntry {
+    cache: Weak<PageCache>,
+    offset: usize,
+    page: Weak<Page>,
+}
+
+impl LruEntry {
+    /// the cache and the page, None if either is gone
+    fn get(&self) -> Option<(Arc<PageCache>, Arc<Page>)> {
+        Some((self.cache.upgrade()?, self.page.upgrade()?))
+    }
+}
+
+struct Lru {
+    active: VecDeque<LruEntry>,
+    inactive: VecDeque<LruEntry>,
+}
+
+static LRU: SpinNoIrqLock<Lru> = SpinNoIrqLock::new(Lru {
+    active: VecDeque::new(),
+    inactive: VecDeque::new(),
+});
+
+/// put a page just inserted at `offset` of `cache` on the lru
+pub fn add(cache: Weak<PageCache>, offset: usize, page: &Arc<Page>) {
+    LRU.lock().inactive.push_front(LruEntry { cache, offset, page: Arc::downgrade(page) });
+}
+
+/// the pages on the lru that are dirty, oldest first. entries of pages
+/// gone are dropped on the way
+pub fn dirty_pages() -> Vec<(Arc<PageCache>, usize, Arc<Page>)> {
+    let mut pages: Vec<_> = {
+        let mut lru = LRU.lock();
+        lru.active.retain(|entry| entry.page.strong_count() > 0);
+        lru.inactive.retain(|entry| entry.page.strong_count() > 0);
+        lru.active.iter().chain(lru.inactive.iter())
+            .filter_map(|entry| entry.get().map(|(cache, page)| (cache, entry.offset, page)))
+            .filter(|(_, _, page)| page.is_dirty())
+            .collect()
+    };
+    pages.sort_by_key(|(_, _, page)| page.dirtied_at());
+    pages
+}
+
+/// move entries from the active tail to the inactive head until the
+/// inactive list is at least as long, those looked up meanwhile stay
+fn age_active(lru: &mut Lru) {
+    let mut budget = lru.active.len();
+    while lru.active.len() > lru.inactive.len() && budget > 0 {
+        budget -= 1;
+        let entry = lru.active.pop_back().unwrap();
+        let Some((_, page)) = entry.get() else {
+            continue;
+        };
+        if page.test_and_clear_referenced() {
+            lru.active.push_front(entry);
+        } else {
+            lru.inactive.push_front(entry);
+        }
+    }
+}
+
+/// drop up to `nr` clean pages no one maps, returns the number dropped
+pub fn reclaim_pages(nr: usize) -> usize {
+    // a page may be added with the lru locked when frames run out
+    let Some(mut lru) = LRU.try_lock() else {
+        return 0;
+    };
+    let mut freed = 0;
+    age_active(&mut lru);
+    let mut budget = lru.inactive.len();
+    while freed < nr && budget > 0 {
+        budget -= 1;
+        let Some(entry) = lru.inactive.pop_back() else {
+            break;
+        };
+        let Some((cache, page)) = entry.get() else {
+            continue;
+        };
+        if page.test_and_clear_referenced() {
+            lru.active.push_front(entry);
+        } else if cache.evict(entry.offset, &page) {
+            freed += 1;
+        } else if cache.get_pages().try_lock().map_or(true, |pages| {
+            pages.load(entry.offset / PAGE_SIZE).is_some_and(|cached| Arc::ptr_eq(cached, &page))
+        }) {
+            // dirty, mapped or in use, try again on the next round
+            lru.inactive.push_front(entry);
+        }
+    }
+    freed
+}
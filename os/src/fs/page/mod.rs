@@ -1,3 +1,17 @@
 //! Page and Page cache for the file system
 pub mod page;
-pub mod cache;
\ No newline at end of file
+pub mod cache;
+pub mod lru;
+pub mod readahead;
+pub mod writeback;
+pub mod xarray;
+
+use crate::sysctl::{register_sysctl, IntSysctl};
+
+/// vm.dirty_ratio and the knobs of the flusher
+pub fn register_sysctls() {
+    register_sysctl("vm.dirty_ratio", IntSysctl::new(&page::DIRTY_RATIO, 0, 100));
+    register_sysctl("vm.dirty_background_ratio", IntSysctl::new(&page::DIRTY_BACKGROUND_RATIO, 0, 100));
+    register_sysctl("vm.dirty_expire_centisecs", IntSysctl::new(&page::DIRTY_EXPIRE_CENTISECS, 0, usize::MAX));
+    register_sysctl("vm.dirty_writeback_centisecs", IntSysctl::new(&page::DIRTY_WRITEBACK_CENTISECS, 0, usize::MAX));
+}
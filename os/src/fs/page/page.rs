@@ -5,11 +5,29 @@ use core::{cmp, sync::atomic::{AtomicBool, AtomicUsize, Ordering}};
 use alloc::{alloc::Global, sync::{Arc, Weak}};
 use hal::{addr::{PhysPageNum, RangePPNHal}, allocator::{FrameAllocatorHal, FrameAllocatorTrackerExt}, constant::{Constant, ConstantsHal}, util::smart_point::StrongArc};
 
-use crate::{fs::vfs::Inode, mm::{allocator::{frames_alloc, FrameAllocator}, FrameTracker}, sync::mutex::SpinNoIrqLock};
+use crate::{fs::vfs::Inode, mm::{allocator::{frames_alloc, total_frames, FrameAllocator}, FrameTracker}, sync::mutex::SpinNoIrqLock, timer::get_current_time_ms};
 
 pub struct Page {
     /// page frame state or attribute
     pub is_dirty: AtomicBool,
+    /// looked up since the lru last passed it
+    referenced: AtomicBool,
+    /// note that a shared file mapping made the page writable
+    pub fn set_mapped_writable(&self) {
+        self.mapped_writable.store(true, Ordering::Release);
+    }
+    /// whether a shared file mapping made the page writable since the last call
+    pub fn take_mapped_writable(&self) -> bool {
+        self.mapped_writable.swap(false, Ordering::AcqRel)
+    }
+    /// when the page became dirty, in ms
+    dirtied_at: AtomicUsize,
+    /// in the cache of a file with a backing store, only such pages count
+    /// as dirty memory
+    backed: AtomicBool,
+    /// writable in some shared file mapping, stores there do not fault to
+    /// dirty it again
+    mapped_writable: AtomicBool,
     /// offset in a file (if is owned by file)
     pub index: usize, 
     /// the physical frame it owns
@@ -20,6 +38,39 @@ unsafe impl Send for Page {}
 unsafe impl Sync for Page {}
 
 pub const PAGE_SIZE: usize = Constant::PAGE_SIZE;
+
+/// dirty pages waiting to be written back
+static NR_DIRTY: AtomicUsize = AtomicUsize::new(0);
+
+/// percentage of memory that may be dirty before writers flush, tunable as vm.dirty_ratio
+pub static DIRTY_RATIO: AtomicUsize = AtomicUsize::new(20);
+
+/// percentage of memory dirty before the flusher writes back all it can,
+/// tunable as vm.dirty_background_ratio
+pub static DIRTY_BACKGROUND_RATIO: AtomicUsize = AtomicUsize::new(10);
+
+/// age in centiseconds of dirty pages the flusher writes back, tunable as
+/// vm.dirty_expire_centisecs
+pub static DIRTY_EXPIRE_CENTISECS: AtomicUsize = AtomicUsize::new(3000);
+
+/// period in centiseconds of the flusher, tunable as vm.dirty_writeback_centisecs
+pub static DIRTY_WRITEBACK_CENTISECS: AtomicUsize = AtomicUsize::new(500);
+
+/// number of dirty pages
+pub fn nr_dirty() -> usize {
+    NR_DIRTY.load(Ordering::Relaxed)
+}
+
+/// whether dirty pages take more than vm.dirty_ratio of the memory
+pub fn dirty_exceeded() -> bool {
+    nr_dirty() * 100 > total_frames() * DIRTY_RATIO.load(Ordering::Relaxed)
+}
+
+/// whether dirty pages take more than vm.dirty_background_ratio of the memory
+pub fn dirty_background_exceeded() -> bool {
+    nr_dirty() * 100 > total_frames() * DIRTY_BACKGROUND_RATIO.load(Ordering::Relaxed)
+}
+
 impl Page {
     /// create a Page by allocating a frame
     pub fn new(index: usize) -> Arc<Self> {
@@ -28,6 +79,10 @@ impl Page {
         frame.range_ppn.get_slice_mut::<u8>().fill(0);
         Arc::new(Self {
             is_dirty: AtomicBool::new(false), // need more flags
+            referenced: AtomicBool::new(false),
+            dirtied_at: AtomicUsize::new(0),
+            backed: AtomicBool::new(false),
+            mapped_writable: AtomicBool::new(false),
             index,
             frame: StrongArc::new(frame),
         })
@@ -87,16 +142,46 @@ impl Page {
         // no need to care about the EOF, write_at will handle this
         write_size
     }
+    /// note that the page belongs to a file with a backing store, done
+    /// before it is dirtied
+    pub fn set_backed(&self) {
+        self.backed.store(true, Ordering::Release);
+    }
     /// set the page dirty
     pub fn set_dirty(&self) {
-        self.is_dirty.store(true, Ordering::Release);
+        if !self.is_dirty.swap(true, Ordering::AcqRel) {
+            self.dirtied_at.store(get_current_time_ms(), Ordering::Relaxed);
+            if self.backed.load(Ordering::Acquire) {
+                NR_DIRTY.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+    /// when the page became dirty, in ms
+    pub fn dirtied_at(&self) -> usize {
+        self.dirtied_at.load(Ordering::Relaxed)
+    }
+    /// note a lookup of the page for the lru
+    pub fn mark_accessed(&self) {
+        self.referenced.store(true, Ordering::Relaxed);
+    }
+    /// whether the page was looked up since the last call
+    pub fn test_and_clear_referenced(&self) -> bool {
+        self.referenced.swap(false, Ordering::Relaxed)
     }
     /// set the page clean
     pub fn set_clean(&self) {
-        self.is_dirty.store(false, Ordering::Release);
+        if self.is_dirty.swap(false, Ordering::AcqRel) && self.backed.load(Ordering::Acquire) {
+            NR_DIRTY.fetch_sub(1, Ordering::Relaxed);
+        }
     }
     /// is the page dirty
     pub fn is_dirty(&self) -> bool {
         self.is_dirty.load(Ordering::Acquire)
     }
-}
\ No newline at end of file
+}
+
+impl Drop for Page {
+    fn drop(&mut self) {
+        self.set_clean();
+    }
+}
@@ -0,0 +1,123 @@
+//! adaptive read ahead of the page cache
+//!
+//! a read at the start of a file or right after the previous one opens a
+//! window of pages read in along with it. once a read gets into the last
+//! `async_size` pages of the window the next window is opened behind it,
+//! larger each time up to the limit, so a sequential reader finds its pages
+//! in the cache. any other read takes only the pages it asks for.
+//! readahead(2) and fadvise(2) move the same window, no larger than the
+//! limit however long the range they ask for.
+
+use core::cmp;
+
+/// smallest window, in pages
+const RA_MIN_PAGES: usize = 4;
+/// largest window, in pages (128 KiB)
+const RA_MAX_PAGES: usize = 32;
+
+/// the access pattern fadvise(2) announced
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RaAdvice {
+    Normal,
+    /// the window may grow twice as large
+    Sequential,
+    /// no read ahead
+    Random,
+}
+
+/// read ahead state of a file
+pub struct FileRaState {
+    /// first page of the window
+    start: usize,
+    /// pages in the window, 0 if there is none
+    size: usize,
+    /// pages at the end of the window whose read opens the next one
+    async_size: usize,
+    /// the page after the last one read
+    next: usize,
+    advice: RaAdvice,
+}
+
+impl FileRaState {
+    pub const fn new() -> Self {
+        Self { start: 0, size: 0, async_size: 0, next: 0, advice: RaAdvice::Normal }
+    }
+
+    fn max_pages(&self) -> usize {
+        match self.advice {
+            RaAdvice::Sequential => RA_MAX_PAGES * 2,
+            _ => RA_MAX_PAGES,
+        }
+    }
+
+    /// size of the first window for a read of `nr` pages
+    fn init_size(&self, nr: usize) -> usize {
+        let max = self.max_pages();
+        let size = nr.next_power_of_two();
+        let size = if size <= max / 32 {
+            size * 4
+        } else if size <= max / 4 {
+            size * 2
+        } else {
+            max
+        };
+        size.clamp(RA_MIN_PAGES, max)
+    }
+
+    /// size of the window after the current one
+    fn next_size(&self) -> usize {
+        let max = self.max_pages();
+        let size = if self.size < max / 16 { self.size * 4 } else { self.size * 2 };
+        cmp::min(size, max)
+    }
+
+    /// a read of pages [index, index + nr) of a file of `eof` pages, returns
+    /// the end of the pages that should be in the cache for it
+    pub fn on_read(&mut self, index: usize, nr: usize, eof: usize) -> usize {
+        let end = index + nr;
+        let ra_end = if self.advice == RaAdvice::Random {
+            end
+        } else if self.size > 0 && (index == self.next || (self.start..self.start + self.size).contains(&index)) {
+            // going on sequentially, open the next window when close to the end
+            if end > self.start + self.size - self.async_size {
+                self.start += self.size;
+                self.size = self.next_size();
+                self.async_size = self.size;
+            }
+            self.start + self.size
+        } else if index == 0 || index == self.next {
+            // sequential from here on, presumably
+            self.start = index;
+            self.size = self.init_size(nr);
+            self.async_size = if self.size > nr { self.size - nr } else { self.size };
+            self.start + self.size
+        } else {
+            self.size = 0;
+            end
+        };
+        self.next = end;
+        cmp::min(cmp::max(ra_end, end), eof)
+    }
+
+    /// readahead(2) or POSIX_FADV_WILLNEED of pages [index, index + nr),
+    /// at most the largest window of them, reads going on from there keep
+    /// growing the window. returns the end of the pages that should be in
+    /// the cache
+    pub fn force(&mut self, index: usize, nr: usize, eof: usize) -> usize {
+        let end = cmp::min(index.saturating_add(cmp::min(nr, self.max_pages())), eof);
+        let nr = end.saturating_sub(index);
+        if self.advice != RaAdvice::Random && nr > 0 {
+            self.start = index;
+            self.size = nr;
+            self.async_size = nr;
+        }
+        end
+    }
+
+    pub fn set_advice(&mut self, advice: RaAdvice) {
+        self.advice = advice;
+        if advice == RaAdvice::Random {
+            self.size = 0;
+        }
+    }
+}
@@ -0,0 +1,92 @@
+//! background write back of dirty page cache pages
+//!
+//! the flusher wakes up every dirty_writeback_centisecs and writes back the
+//! pages dirty for longer than dirty_expire_centisecs, or all of them while
+//! more than dirty_background_ratio percent of memory is dirty. errors are
+//! kept in the page cache and returned by the next fsync. each pass then
+//! writes the buffered blocks of the mounted devices, so they do not wait
+//! for a sync.
+//!
+//! clean pages of shared file mappings are mapped read only, so a store
+//! through the mapping faults and dirties the page here too. fsync and sync
+//! see those pages without looking at the page table D bits.
+
+use core::{sync::atomic::Ordering, time::Duration};
+
+use alloc::{sync::Arc, vec::Vec};
+
+use crate::{fs::{vfs::SuperBlock, FS_MANAGER}, timer::{get_current_time_ms, timed_task::ksleep}};
+
+use super::{lru, page::{dirty_background_exceeded, DIRTY_EXPIRE_CENTISECS, DIRTY_WRITEBACK_CENTISECS}};
+
+/// write back the dirty pages older than `expire` ms, or all of them while
+/// over the background ratio. returns the number written
+pub fn writeback(expire: usize) -> usize {
+    let now = get_current_time_ms();
+    let mut written = 0;
+    for (cache, offset, page) in lru::dirty_pages() {
+        if now.saturating_sub(page.dirtied_at()) < expire && !dirty_background_exceeded() {
+            // oldest first, the rest are younger
+            break;
+        }
+        let Some(inode) = cache.host() else {
+            continue;
+        };
+        if cache.write_page(&*inode, offset, &page).is_ok() {
+            written += 1;
+        }
+    }
+    written
+}
+
+/// write back all dirty pages, only those of files on `sb` if given.
+/// returns the first error
+pub fn sync_pages(sb: Option<&Arc<dyn SuperBlock>>) -> Result<(), i32> {
+    let mut ret = Ok(());
+    for (cache, offset, page) in lru::dirty_pages() {
+        let Some(inode) = cache.host() else {
+            continue;
+        };
+        let on_sb = |sb: &Arc<dyn SuperBlock>| inode.inode_inner().super_block.as_ref()
+            .is_some_and(|owner| owner.as_ptr() as *const () == Arc::as_ptr(sb) as *const ());
+        if sb.is_some_and(|sb| !on_sb(sb)) {
+            continue;
+        }
+        if let Err(e) = cache.write_page(&*inode, offset, &page) {
+            ret = ret.and(Err(e));
+        }
+    }
+    ret
+}
+
+/// write the buffered blocks of every device a file system is mounted from
+fn sync_devices() {
+    // collected first, syncing may sleep on the device
+    let fs_types: Vec<_> = FS_MANAGER.lock().values().cloned().collect();
+    let devices: Vec<_> = fs_types.iter()
+        .flat_map(|fs_type| fs_type.inner().supers.lock().values()
+            .filter_map(|sb| sb.inner().device.clone())
+            .collect::<Vec<_>>())
+        .collect();
+    for device in devices {
+        device.sync();
+    }
+}
+
+/// the flusher kernel task
+pub async fn flusher() {
+    loop {
+        let interval = DIRTY_WRITEBACK_CENTISECS.load(Ordering::Relaxed);
+        // 0 turns periodic write back off, look again later
+        ksleep(Duration::from_millis(if interval == 0 { 5000 } else { interval as u64 * 10 })).await;
+        if interval == 0 && !dirty_background_exceeded() {
+            continue;
+        }
+        let expire = DIRTY_EXPIRE_CENTISECS.load(Ordering::Relaxed) * 10;
+        let written = writeback(expire);
+        if written > 0 {
+            log::debug!("[flusher] wrote back {} pages", written);
+        }
+        sync_devices();
+    }
+}
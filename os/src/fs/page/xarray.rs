@@ -0,0 +1,294 @@
+//! radix tree from page index to entry, in the manner of the xarray
+//!
+//! a node has 64 slots and takes 6 bits of the index, the tree grows a
+//! level when an index does not fit. each node keeps for every mark a bitmap
+//! of the slots with a marked entry below, so that marked entries are found
+//! without walking the others.
+
+use alloc::{boxed::Box, vec::Vec};
+
+const XA_CHUNK_SHIFT: usize = 6;
+const XA_CHUNK_SIZE: usize = 1 << XA_CHUNK_SHIFT;
+const XA_CHUNK_MASK: usize = XA_CHUNK_SIZE - 1;
+
+/// number of marks an entry may carry
+pub const XA_MARKS: usize = 3;
+
+/// a mark of the entries, below XA_MARKS
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct XaMark(usize);
+
+impl XaMark {
+    pub const fn new(mark: usize) -> Self {
+        assert!(mark < XA_MARKS);
+        Self(mark)
+    }
+}
+
+enum Slot<T> {
+    Node(Box<Node<T>>),
+    Entry(T),
+}
+
+struct Node<T> {
+    /// bits of the index below this node, 0 if its slots hold entries
+    shift: usize,
+    /// number of slots used
+    count: usize,
+    slots: [Option<Slot<T>>; XA_CHUNK_SIZE],
+    /// slots with a marked entry below, one bitmap per mark
+    marks: [u64; XA_MARKS],
+}
+
+impl<T> Node<T> {
+    fn new(shift: usize) -> Box<Self> {
+        Box::new(Self {
+            shift,
+            count: 0,
+            slots: [const { None }; XA_CHUNK_SIZE],
+            marks: [0; XA_MARKS],
+        })
+    }
+    fn offset(&self, index: usize) -> usize {
+        (index >> self.shift) & XA_CHUNK_MASK
+    }
+    /// largest index under this node as a root
+    fn max_index(&self) -> usize {
+        match self.shift + XA_CHUNK_SHIFT {
+            bits if bits >= usize::BITS as usize => usize::MAX,
+            bits => (1 << bits) - 1,
+        }
+    }
+    fn marked(&self, mark: XaMark) -> bool {
+        self.marks[mark.0] != 0
+    }
+    /// mark the entry at `index`, whether there is one
+    fn set_mark(&mut self, index: usize, mark: XaMark) -> bool {
+        let offset = self.offset(index);
+        let found = match &mut self.slots[offset] {
+            None => false,
+            Some(Slot::Entry(_)) => true,
+            Some(Slot::Node(child)) => child.set_mark(index, mark),
+        };
+        if found {
+            self.marks[mark.0] |= 1 << offset;
+        }
+        found
+    }
+    fn clear_mark(&mut self, index: usize, mark: XaMark) {
+        let offset = self.offset(index);
+        let clear = match &mut self.slots[offset] {
+            Some(Slot::Node(child)) => {
+                child.clear_mark(index, mark);
+                !child.marked(mark)
+            }
+            _ => true,
+        };
+        if clear {
+            self.marks[mark.0] &= !(1 << offset);
+        }
+    }
+    fn get_mark(&self, index: usize, mark: XaMark) -> bool {
+        let offset = self.offset(index);
+        if self.marks[mark.0] & (1 << offset) == 0 {
+            return false;
+        }
+        match &self.slots[offset] {
+            Some(Slot::Node(child)) => child.get_mark(index, mark),
+            _ => true,
+        }
+    }
+    /// remove the entry at `index`, along with the nodes it leaves empty
+    fn erase(&mut self, index: usize) -> Option<T> {
+        let offset = self.offset(index);
+        let old = match self.slots[offset].as_mut()? {
+            Slot::Node(child) => {
+                let old = child.erase(index)?;
+                if child.count > 0 {
+                    for mark in 0..XA_MARKS {
+                        if !child.marked(XaMark(mark)) {
+                            self.marks[mark] &= !(1 << offset);
+                        }
+                    }
+                    return Some(old);
+                }
+                self.slots[offset] = None;
+                old
+            }
+            Slot::Entry(_) => match self.slots[offset].take() {
+                Some(Slot::Entry(entry)) => entry,
+                _ => unreachable!(),
+            },
+        };
+        self.count -= 1;
+        for bits in self.marks.iter_mut() {
+            *bits &= !(1 << offset);
+        }
+        Some(old)
+    }
+}
+
+/// map from index to `T`
+pub struct XArray<T> {
+    root: Option<Box<Node<T>>>,
+    len: usize,
+}
+
+impl<T> XArray<T> {
+    pub const fn new() -> Self {
+        Self { root: None, len: 0 }
+    }
+    /// number of entries
+    pub fn len(&self) -> usize {
+        self.len
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+    /// the entry at `index`
+    pub fn load(&self, index: usize) -> Option<&T> {
+        let mut node = self.root.as_deref()?;
+        if index > node.max_index() {
+            return None;
+        }
+        loop {
+            match node.slots[node.offset(index)].as_ref()? {
+                Slot::Node(child) => node = child,
+                Slot::Entry(entry) => return Some(entry),
+            }
+        }
+    }
+    /// add levels on top until `index` fits
+    fn expand(&mut self, index: usize) {
+        let root = self.root.get_or_insert_with(|| Node::new(0));
+        while index > root.max_index() {
+            let marks = root.marks;
+            let old = core::mem::replace(root, Node::new(root.shift + XA_CHUNK_SHIFT));
+            if old.count == 0 {
+                continue;
+            }
+            root.slots[0] = Some(Slot::Node(old));
+            root.count = 1;
+            for (bits, old_bits) in root.marks.iter_mut().zip(marks) {
+                *bits = (old_bits != 0) as u64;
+            }
+        }
+    }
+    /// put `entry` at `index` without marks, returns the one replaced
+    pub fn store(&mut self, index: usize, entry: T) -> Option<T> {
+        for mark in 0..XA_MARKS {
+            self.clear_mark(index, XaMark(mark));
+        }
+        self.expand(index);
+        let mut node = self.root.as_deref_mut().unwrap();
+        while node.shift > 0 {
+            let offset = node.offset(index);
+            if node.slots[offset].is_none() {
+                node.slots[offset] = Some(Slot::Node(Node::new(node.shift - XA_CHUNK_SHIFT)));
+                node.count += 1;
+            }
+            node = match node.slots[offset].as_mut() {
+                Some(Slot::Node(child)) => child,
+                _ => unreachable!(),
+            };
+        }
+        let offset = node.offset(index);
+        match node.slots[offset].replace(Slot::Entry(entry)) {
+            Some(Slot::Entry(old)) => Some(old),
+            _ => {
+                node.count += 1;
+                self.len += 1;
+                None
+            }
+        }
+    }
+    /// remove the entry at `index`
+    pub fn erase(&mut self, index: usize) -> Option<T> {
+        let root = self.root.as_mut()?;
+        if index > root.max_index() {
+            return None;
+        }
+        let old = root.erase(index)?;
+        self.len -= 1;
+        if root.count == 0 {
+            self.root = None;
+        }
+        Some(old)
+    }
+    /// mark the entry at `index`, whether there is one
+    pub fn set_mark(&mut self, index: usize, mark: XaMark) -> bool {
+        match self.root.as_mut() {
+            Some(root) if index <= root.max_index() => root.set_mark(index, mark),
+            _ => false,
+        }
+    }
+    pub fn clear_mark(&mut self, index: usize, mark: XaMark) {
+        match self.root.as_mut() {
+            Some(root) if index <= root.max_index() => root.clear_mark(index, mark),
+            _ => {}
+        }
+    }
+    /// whether the entry at `index` is marked
+    pub fn get_mark(&self, index: usize, mark: XaMark) -> bool {
+        match self.root.as_ref() {
+            Some(root) if index <= root.max_index() => root.get_mark(index, mark),
+            _ => false,
+        }
+    }
+    /// whether any entry is marked
+    pub fn any_marked(&self, mark: XaMark) -> bool {
+        self.root.as_ref().is_some_and(|root| root.marked(mark))
+    }
+    /// the entries in index order
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter::new(self, None)
+    }
+    /// the entries with `mark` in index order
+    pub fn iter_marked(&self, mark: XaMark) -> Iter<'_, T> {
+        Iter::new(self, Some(mark))
+    }
+}
+
+pub struct Iter<'a, T> {
+    /// nodes on the way down, with the index of their first slot and the
+    /// next slot to look at
+    stack: Vec<(&'a Node<T>, usize, usize)>,
+    mark: Option<XaMark>,
+}
+
+impl<'a, T> Iter<'a, T> {
+    fn new(xa: &'a XArray<T>, mark: Option<XaMark>) -> Self {
+        let mut stack = Vec::new();
+        if let Some(root) = xa.root.as_deref() {
+            stack.push((root, 0, 0));
+        }
+        Self { stack, mark }
+    }
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = (usize, &'a T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((node, base, next)) = self.stack.last_mut() {
+            let node: &'a Node<T> = node;
+            let base = *base;
+            let found = (*next..XA_CHUNK_SIZE).find(|&offset| match self.mark {
+                Some(mark) => node.marks[mark.0] & (1 << offset) != 0,
+                None => node.slots[offset].is_some(),
+            });
+            let Some(offset) = found else {
+                self.stack.pop();
+                continue;
+            };
+            *next = offset + 1;
+            let index = base + (offset << node.shift);
+            match node.slots[offset].as_ref() {
+                Some(Slot::Node(child)) => self.stack.push((child, index, 0)),
+                Some(Slot::Entry(entry)) => return Some((index, entry)),
+                None => {}
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,122 @@
+//! pidfd file object
+//!
+//! a pidfd refers to a process by a weak reference, so a reused pid
+//! never points an old pidfd to a new process.
+
+use core::{sync::atomic::{AtomicUsize, Ordering}, task::Waker};
+
+use alloc::{boxed::Box, collections::btree_map::BTreeMap, sync::{Arc, Weak}, vec::Vec};
+use async_trait::async_trait;
+
+use crate::{fs::{vfs::{file::PollEvents, Dentry, File, FileInner}, OpenFlags}, sync::mutex::SpinNoIrqLock, syscall::SysError, task::task::TaskControlBlock, utils::get_waker};
+
+bitflags! {
+    /// flags of pidfd_open
+    pub struct PidFdFlags: u32 {
+        const PIDFD_NONBLOCK = 0o4000;
+    }
+}
+
+/// wakers of the pollers on each pid, woken when the process exits
+static PIDFD_WAKERS: SpinNoIrqLock<BTreeMap<usize, Vec<Waker>>> = SpinNoIrqLock::new(BTreeMap::new());
+
+/// wake up the pollers of `pid`, called when its last thread exits
+pub fn pidfd_notify_exit(pid: usize) {
+    if let Some(wakers) = PIDFD_WAKERS.lock().remove(&pid) {
+        wakers.into_iter().for_each(|w| w.wake());
+    }
+}
+
+/// pidfd instance
+pub struct PidFdFile {
+    inner: FileInner,
+    pid: AtomicUsize,
+    task: SpinNoIrqLock<Weak<TaskControlBlock>>,
+}
+
+impl PidFdFile {
+    /// create a pidfd for the process led by `task`
+    pub fn new(task: &Arc<TaskControlBlock>, flags: OpenFlags) -> Arc<Self> {
+        let file = Self::new_detached(flags);
+        file.attach(task);
+        file
+    }
+
+    /// create a pidfd that refers to no process yet, for clone to
+    /// install before the child exists
+    pub fn new_detached(flags: OpenFlags) -> Arc<Self> {
+        Arc::new(Self {
+            inner: FileInner {
+                dentry: Arc::<usize>::new_zeroed(),
+                offset: AtomicUsize::new(0),
+                flags: SpinNoIrqLock::new(flags),
+            },
+            pid: AtomicUsize::new(0),
+            task: SpinNoIrqLock::new(Weak::new()),
+        })
+    }
+
+    /// point the pidfd to the process led by `task`
+    pub fn attach(&self, task: &Arc<TaskControlBlock>) {
+        *self.task.lock() = Arc::downgrade(task);
+        self.pid.store(task.pid(), Ordering::Release);
+    }
+
+    /// pid the pidfd was opened on
+    pub fn pid(&self) -> usize {
+        self.pid.load(Ordering::Acquire)
+    }
+
+    /// the process, or None if it has been reaped
+    pub fn task(&self) -> Option<Arc<TaskControlBlock>> {
+        self.task.lock().upgrade()
+    }
+
+    /// whether all threads of the process have exited
+    pub fn exited(&self) -> bool {
+        match self.task() {
+            Some(task) => task.thread_group.lock().get_alive() == 0,
+            None => true,
+        }
+    }
+}
+
+#[async_trait]
+impl File for PidFdFile {
+    fn file_inner(&self) -> &FileInner {
+        &self.inner
+    }
+
+    fn readable(&self) -> bool {
+        false
+    }
+
+    fn writable(&self) -> bool {
+        false
+    }
+
+    async fn read(&self, _buf: &mut [u8]) -> Result<usize, SysError> {
+        Err(SysError::EINVAL)
+    }
+
+    async fn write(&self, _buf: &[u8]) -> Result<usize, SysError> {
+        Err(SysError::EINVAL)
+    }
+
+    async fn base_poll(&self, events: PollEvents) -> PollEvents {
+        let waker = get_waker().await;
+        // hold the lock so an exit between the check and the push is not missed
+        let mut wakers = PIDFD_WAKERS.lock();
+        if self.exited() {
+            return events & PollEvents::IN;
+        }
+        if events.contains(PollEvents::IN) {
+            wakers.entry(self.pid()).or_default().push(waker);
+        }
+        PollEvents::empty()
+    }
+
+    fn dentry(&self) -> Option<Arc<dyn Dentry>> {
+        None
+    }
+}
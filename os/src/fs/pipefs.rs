@@ -283,6 +283,9 @@ impl File for PipeFile {
             }
             if events.contains(PollEvents::OUT) && !meta.ring_buffer.is_full() {
                 res |= PollEvents::OUT;
+                if events.contains(PollEvents::EDGE) {
+                    meta.write_waker.push_back(waker);
+                }
             } else {
                 meta.write_waker.push_back(waker);
             }
@@ -298,6 +301,9 @@ impl File for PipeFile {
             }
             if events.contains(PollEvents::IN) && !meta.ring_buffer.is_empty() {
                 res |= PollEvents::IN;
+                if events.contains(PollEvents::EDGE) {
+                    meta.read_waker.push_back(waker);
+                }
             } else {
                 meta.read_waker.push_back(waker);
             }
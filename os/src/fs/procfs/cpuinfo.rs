@@ -0,0 +1,34 @@
+//! /proc/cpuinfo
+
+use alloc::{format, string::String};
+
+use crate::{devices::DEVICE_MANAGER, fs::tmpfs::inode::InodeContent};
+
+pub struct CpuInfo;
+
+impl CpuInfo {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl InodeContent for CpuInfo {
+    fn serialize(&self) -> String {
+        let mut res = String::new();
+        for (i, cpu) in DEVICE_MANAGER.lock().cpus.iter().enumerate() {
+            res += &format!("processor\t: {}\nhart\t\t: {}\n", i, cpu.hart_id);
+            if !cpu.isa.is_empty() {
+                res += &format!("isa\t\t: {}\n", cpu.isa);
+            }
+            if !cpu.mmu.is_empty() {
+                // "riscv,sv39" is shown as "sv39"
+                res += &format!("mmu\t\t: {}\n", cpu.mmu.rsplit(',').next().unwrap());
+            }
+            if cpu.freq != 0 {
+                res += &format!("cpu MHz\t\t: {}\n", cpu.freq / 1_000_000);
+            }
+            res += "\n";
+        }
+        res
+    }
+}
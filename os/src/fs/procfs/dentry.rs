@@ -0,0 +1,174 @@
+//! directories of procfs whose children follow the running tasks
+//!
+//! the children named by an id (pid, tid or fd) are checked on every
+//! lookup, so they are never kept in the dcache.
+
+use alloc::{collections::btree_map::BTreeMap, string::{String, ToString}, sync::{Arc, Weak}, vec::Vec};
+
+use crate::{fs::{tmpfs::{dentry::TmpDentry, file::TmpFile, inode::TmpInode}, vfs::{inode::InodeMode, Dentry, DentryInner, DentryState, File}, OpenFlags, SuperBlock}, sync::mutex::SpinNoIrqLock, syscall::SysError, task::{manager::TASK_MANAGER, task::TaskControlBlock}};
+
+use super::pid::{all_pids, create_link, create_pid_dir, file_path, FdInfoContent};
+use crate::fs::fs::CNXFS;
+
+/// what the id children of a directory stand for
+pub enum ProcDir {
+    /// /proc, one child per process
+    Root,
+    /// /proc/<pid>/task, one child per thread
+    Task(Weak<TaskControlBlock>),
+    /// /proc/<pid>/fd, one symlink per fd
+    Fd(Weak<TaskControlBlock>),
+    /// /proc/<pid>/fdinfo, one file per fd
+    FdInfo(Weak<TaskControlBlock>),
+}
+
+/// procfs dentry with children generated from the tasks
+pub struct ProcDynDentry {
+    inner: DentryInner,
+    this: Weak<ProcDynDentry>,
+    kind: ProcDir,
+    /// the task each id child was built for, a reused id gets a new child
+    owners: SpinNoIrqLock<BTreeMap<usize, Weak<TaskControlBlock>>>,
+}
+
+unsafe impl Send for ProcDynDentry {}
+unsafe impl Sync for ProcDynDentry {}
+
+impl ProcDynDentry {
+    pub fn new(name: &str, parent: Option<Arc<dyn Dentry>>, kind: ProcDir) -> Arc<Self> {
+        Arc::new_cyclic(|this| Self {
+            inner: DentryInner::new(name, parent),
+            this: this.clone(),
+            kind,
+            owners: SpinNoIrqLock::new(BTreeMap::new()),
+        })
+    }
+
+    /// create a dynamic directory under `parent`
+    pub fn new_dir(name: &str, sb: Weak<dyn SuperBlock>, parent: Arc<dyn Dentry>, kind: ProcDir) -> Arc<dyn Dentry> {
+        let dentry = Self::new(name, Some(parent), kind);
+        dentry.set_inode(TmpInode::new(sb, InodeMode::DIR));
+        dentry
+    }
+
+    fn super_block(&self) -> Weak<dyn SuperBlock> {
+        self.inode().unwrap().inode_inner().super_block.clone().unwrap()
+    }
+
+    /// ids which have a child now
+    fn live_ids(&self) -> Vec<usize> {
+        match &self.kind {
+            ProcDir::Root => all_pids(),
+            ProcDir::Task(task) => task.upgrade()
+                .map_or(Vec::new(), |t| t.thread_group.lock().iter_tid().cloned().collect()),
+            ProcDir::Fd(task) | ProcDir::FdInfo(task) => task.upgrade()
+                .map_or(Vec::new(), |t| t.with_fd_table(|table| {
+                    (0..table.fd_table.len()).filter(|fd| table.fd_table[*fd].is_some()).collect()
+                })),
+        }
+    }
+
+    /// the task the child `id` is built for, None if `id` is gone
+    fn owner(&self, id: usize) -> Option<Weak<TaskControlBlock>> {
+        match &self.kind {
+            ProcDir::Root => TASK_MANAGER.get_task(id)
+                .filter(|t| t.is_leader())
+                .map(|t| Arc::downgrade(&t)),
+            ProcDir::Task(_) => self.live_ids().contains(&id)
+                .then(|| TASK_MANAGER.get_task(id))
+                .flatten()
+                .map(|t| Arc::downgrade(&t)),
+            ProcDir::Fd(task) | ProcDir::FdInfo(task) => self.live_ids().contains(&id)
+                .then(|| task.clone()),
+        }
+    }
+
+    fn build_child(&self, id: usize, owner: Weak<TaskControlBlock>) -> Option<Arc<dyn Dentry>> {
+        let this: Arc<dyn Dentry> = self.this.upgrade()?;
+        let name = id.to_string();
+        let sb = self.super_block();
+        let child = match &self.kind {
+            ProcDir::Root => create_pid_dir(&name, &owner.upgrade()?, sb, this, true),
+            ProcDir::Task(_) => create_pid_dir(&name, &owner.upgrade()?, sb, this, false),
+            ProcDir::Fd(_) => create_link(&name, sb, this, move || {
+                let task = owner.upgrade().ok_or(SysError::ENOENT)?;
+                let file = task.with_fd_table(|table| table.get_file(id))?;
+                Ok(file_path(&file))
+            }),
+            ProcDir::FdInfo(_) => CNXFS::create_sys_file(Arc::new(FdInfoContent::new(owner, id)), &name, this),
+        };
+        Some(child)
+    }
+
+    /// look up an id child, None if `name` is not an id of this dir
+    fn get_id_child(&self, name: &str) -> Option<Option<Arc<dyn Dentry>>> {
+        let id = match name.parse::<usize>() {
+            Ok(id) => id,
+            Err(_) if matches!(self.kind, ProcDir::Root) => return None,
+            Err(_) => return Some(None),
+        };
+        let Some(owner) = self.owner(id) else {
+            self.remove_child(name);
+            self.owners.lock().remove(&id);
+            return Some(None);
+        };
+        let mut owners = self.owners.lock();
+        if owners.get(&id).map_or(false, |old| old.ptr_eq(&owner)) {
+            if let Some(child) = self.dentry_inner().children.lock().get(name) {
+                return Some(Some(child.clone()));
+            }
+        }
+        // build_child adds it to the children
+        let child = self.build_child(id, owner.clone());
+        if child.is_some() {
+            owners.insert(id, owner);
+        }
+        Some(child)
+    }
+}
+
+impl Dentry for ProcDynDentry {
+    fn dentry_inner(&self) -> &DentryInner {
+        &self.inner
+    }
+
+    fn new(&self, name: &str, parent: Option<Arc<dyn Dentry>>) -> Arc<dyn Dentry> {
+        TmpDentry::new(name, parent)
+    }
+
+    fn open(self: Arc<Self>, _flags: OpenFlags) -> Option<Arc<dyn File>> {
+        Some(Arc::new(TmpFile::new(self.clone())))
+    }
+
+    fn get_child(&self, name: &str) -> Option<Arc<dyn Dentry>> {
+        match self.get_id_child(name) {
+            Some(child) => child,
+            None => self.dentry_inner().children.lock().get(name).cloned(),
+        }
+    }
+
+    fn load_child_dentry(self: Arc<Self>) -> Result<Vec<Arc<dyn Dentry>>, SysError> {
+        let ids = self.live_ids();
+        let stale: Vec<String> = self.children().keys()
+            .filter(|name| name.parse::<usize>().map_or(false, |id| !ids.contains(&id)))
+            .cloned()
+            .collect();
+        for name in stale {
+            self.get_id_child(&name);
+        }
+        for id in ids {
+            self.get_id_child(&id.to_string());
+        }
+        Ok(self.children().into_values().filter(|c| c.state() != DentryState::NEGATIVE).collect())
+    }
+
+    fn new_neg_dentry(self: Arc<Self>, name: &str) -> Result<Arc<dyn Dentry>, SysError> {
+        if name.parse::<usize>().is_ok() || !matches!(self.kind, ProcDir::Root) {
+            // ids come and go, a cached negative dentry would hide a new one
+            return Err(SysError::ENOENT);
+        }
+        let neg_dentry = TmpDentry::new(name, Some(self.clone()));
+        neg_dentry.set_state(DentryState::NEGATIVE);
+        Ok(neg_dentry)
+    }
+}
@@ -0,0 +1,24 @@
+//! /proc/filesystems
+
+use alloc::{format, string::String};
+
+use crate::fs::{tmpfs::inode::InodeContent, FS_MANAGER};
+
+pub struct FileSystems;
+
+impl FileSystems {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl InodeContent for FileSystems {
+    fn serialize(&self) -> String {
+        let mut res = String::new();
+        for (name, fs) in FS_MANAGER.lock().iter() {
+            let nodev = if fs.requires_dev() { "" } else { "nodev" };
+            res += &format!("{}\t{}\n", nodev, name);
+        }
+        res
+    }
+}
@@ -1,8 +1,8 @@
 use alloc::sync::Arc;
 
-use crate::{devices::BlockDevice, fs::{tmpfs::{dentry::TmpDentry, inode::TmpInode}, vfs::{fstype::{FSType, FSTypeInner, MountFlags}, inode::InodeMode, Dentry, DentryState, DCACHE}, SuperBlock, SuperBlockInner}};
+use crate::{devices::BlockDevice, fs::{tmpfs::inode::TmpInode, vfs::{fstype::{FSType, FSTypeInner, MountFlags}, inode::InodeMode, Dentry, DentryState, DCACHE}, SuperBlock, SuperBlockInner}};
 
-use super::superblock::ProcSuperBlock;
+use super::{dentry::{ProcDir, ProcDynDentry}, superblock::ProcSuperBlock};
 
 
 pub struct ProcFSType {
@@ -29,7 +29,8 @@ impl FSType for ProcFSType {
         };
         let sb = ProcSuperBlock::new(SuperBlockInner::new(dev, fs_type.clone()));
         let root_inode = TmpInode::new(Arc::downgrade(&sb), InodeMode::DIR);
-        let root_dentry = TmpDentry::new(name, parent.clone());
+        // the pid directories under /proc come from the task manager
+        let root_dentry: Arc<dyn Dentry> = ProcDynDentry::new(name, parent.clone(), ProcDir::Root);
         root_dentry.set_inode(root_inode);
         root_dentry.set_state(DentryState::USED);
         sb.set_root_dentry(root_dentry.clone());
@@ -21,6 +21,11 @@ impl IrqCounter {
         }
     }
 
+    /// number of interrupts of all irqs
+    pub fn total(&self) -> usize {
+        self.counter.values().sum()
+    }
+
     pub fn add_irq(&mut self, irq_no: usize) {
         if let Some(count) = self.counter.get_mut(&irq_no) {
             *count = (*count).saturating_add(1);
@@ -1,23 +1,26 @@
-//! /proc/self
+//! symlinks in /proc, like self, exe and fd/<n>
 
-use alloc::{string::String, sync::{Arc, Weak}};
+use alloc::{boxed::Box, string::String, sync::{Arc, Weak}};
 
-use crate::{fs::{vfs::{inode::InodeMode, Dentry, DentryInner, File, Inode, InodeInner}, Kstat, OpenFlags, StatxTimestamp, SuperBlock, Xstat, XstatMask}, syscall::SysError, task::current_task};
+use crate::{fs::{vfs::{inode::InodeMode, Inode, InodeInner}, Kstat, StatxTimestamp, SuperBlock, Xstat, XstatMask}, syscall::SysError};
 
-
-/// exe inode
-pub struct ExeInode {
+/// symlink whose target is computed on every readlink
+pub struct ProcLinkInode {
     inner: InodeInner,
+    target: Box<dyn Fn() -> Result<String, SysError> + Send + Sync>,
 }
 
-impl ExeInode {
-    pub fn new(super_block: Weak<dyn SuperBlock>) -> Arc<Self> {
+impl ProcLinkInode {
+    pub fn new(
+        super_block: Weak<dyn SuperBlock>,
+        target: impl Fn() -> Result<String, SysError> + Send + Sync + 'static,
+    ) -> Arc<Self> {
         let inner = InodeInner::new(Some(super_block), InodeMode::LINK, 0);
-        Arc::new(Self { inner })
+        Arc::new(Self { inner, target: Box::new(target) })
     }
 }
 
-impl Inode for ExeInode {
+impl Inode for ProcLinkInode {
     fn inode_inner(&self) -> &InodeInner {
         &self.inner
     }
@@ -103,6 +106,6 @@ impl Inode for ExeInode {
     }
 
     fn readlink(&self) -> Result<String, SysError> {
-        return Ok(current_task().unwrap().elf.lock().clone().ok_or(SysError::ENFILE)?.dentry().ok_or(SysError::ENOENT)?.path());
+        (self.target)()
     }
 }
\ No newline at end of file
@@ -0,0 +1,33 @@
+//! /proc/loadavg
+
+use alloc::{format, string::String};
+
+use crate::{fs::tmpfs::inode::InodeContent, processor::loadavg::{loadavg, nr_running, FIXED_1, FSHIFT}, task::manager::TASK_MANAGER};
+
+pub struct LoadAvg;
+
+impl LoadAvg {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+/// fixed point load as "x.xx"
+fn load_str(load: u64) -> String {
+    // round to two decimals
+    let load = load + FIXED_1 / 200;
+    format!("{}.{:02}", load >> FSHIFT, ((load & (FIXED_1 - 1)) * 100) >> FSHIFT)
+}
+
+impl InodeContent for LoadAvg {
+    fn serialize(&self) -> String {
+        let [avg1, avg5, avg15] = loadavg();
+        let tasks = TASK_MANAGER.tasks_group();
+        let last_pid = tasks.iter().map(|t| t.tid()).max().unwrap_or(0);
+        format!(
+            "{} {} {} {}/{} {}\n",
+            load_str(avg1), load_str(avg5), load_str(avg15),
+            nr_running(), tasks.len(), last_pid,
+        )
+    }
+}
@@ -7,7 +7,7 @@ use alloc::sync::{Arc, Weak};
 use async_trait::async_trait;
 use alloc::boxed::Box;
 
-use crate::{config::BLOCK_SIZE, fs::{tmpfs::inode::InodeContent, vfs::{inode::InodeMode, Dentry, DentryInner, File, FileInner, Inode, InodeInner}, Kstat, OpenFlags, StatxTimestamp, SuperBlock, Xstat, XstatMask}, syscall::SysError};
+use crate::{config::{BLOCK_SIZE, PAGE_SIZE}, mm::swap, fs::{tmpfs::inode::InodeContent, vfs::{inode::InodeMode, Dentry, DentryInner, File, FileInner, Inode, InodeInner}, Kstat, OpenFlags, StatxTimestamp, SuperBlock, Xstat, XstatMask}, syscall::SysError};
 
 use alloc::string::{String, ToString};
 
@@ -19,7 +19,6 @@ const TOTAL_MEM: usize = 16251136;
 const FREE_MEM: usize = 327680;
 const BUFFER: usize = 373336;
 const CACHED: usize = 10391984;
-const TOTAL_SWAP: usize = 4194300;
 
 /// Mapping to free output: https://access.redhat.com/solutions/406773.
 pub struct MemInfo {
@@ -30,9 +29,6 @@ pub struct MemInfo {
     /// Buffer and cache
     pub buffers: usize,
     pub cached: usize,
-    /// Swap space
-    pub total_swap: usize,
-    pub free_swap: usize,
     /// Share memory
     pub shmem: usize,
     pub slab: usize,
@@ -46,8 +42,6 @@ impl MemInfo {
             avail_mem: TOTAL_MEM - FREE_MEM,
             buffers: BUFFER,
             cached: CACHED,
-            total_swap: TOTAL_SWAP,
-            free_swap: TOTAL_SWAP,
             shmem: 0,
             slab: 0,
         }
@@ -63,9 +57,11 @@ impl InodeContent for MemInfo {
         let avail_mem = "MemAvailable:\t".to_string() + self.avail_mem.to_string().as_str() + end;
         let buffers = "Buffers:\t".to_string() + self.buffers.to_string().as_str() + end;
         let cached = "Cached:\t".to_string() + self.cached.to_string().as_str() + end;
-        let cached_swap = "SwapCached:\t".to_string() + 0.to_string().as_str() + end;
-        let total_swap = "SwapTotal:\t".to_string() + self.total_swap.to_string().as_str() + end;
-        let free_swap = "SwapFree:\t".to_string() + self.free_swap.to_string().as_str() + end;
+        // swap is counted in pages
+        let kb = |pages: usize| (pages * PAGE_SIZE / 1024).to_string();
+        let cached_swap = "SwapCached:\t".to_string() + kb(swap::cached_pages()).as_str() + end;
+        let total_swap = "SwapTotal:\t".to_string() + kb(swap::total_pages()).as_str() + end;
+        let free_swap = "SwapFree:\t".to_string() + kb(swap::free_pages()).as_str() + end;
         let shmem = "Shmem:\t".to_string() + self.shmem.to_string().as_str() + end;
         let slab = "Slab:\t".to_string() + self.slab.to_string().as_str() + end;
         res += total_mem.as_str();
@@ -1,33 +1,43 @@
 //! proc file system
 
-use alloc::sync::{Arc, Weak};
-use self_::ExeInode;
+use alloc::{format, sync::{Arc, Weak}};
 
-use crate::fs::{fs::CNXFS, procfs::{interrupt::Interrupts, meminfo::{MemInfo, MEM_INFO}, mounts::{list_mounts, MountInfo}, sys::kernel::PidMax}, tmpfs::{dentry::TmpDentry, inode::{InodeContent, TmpInode, TmpSysInode}}, vfs::{inode::InodeMode, Inode}, SuperBlock};
+use crate::{fs::{fs::CNXFS, procfs::{cpuinfo::CpuInfo, filesystems::FileSystems, interrupt::Interrupts, loadavg::LoadAvg, meminfo::{MemInfo, MEM_INFO}, mounts::MountInfo, partitions::Partitions, pid::create_link, stat::Stat, swaps::Swaps, sys::init_sysctl_dir, uptime::Uptime, version::Version}, tmpfs::inode::{InodeContent, TmpInode, TmpSysInode}, vfs::{inode::InodeMode, Inode}, SuperBlock}, syscall::SysError, task::current_task};
 
-use super::vfs::{Dentry, DCACHE};
+use super::vfs::Dentry;
 
 pub mod fstype;
 pub mod superblock;
-pub mod self_;
+pub mod dentry;
+pub mod link;
+pub mod pid;
 pub mod mounts;
 pub mod meminfo;
 pub mod sys;
 pub mod interrupt;
+pub mod cpuinfo;
+pub mod stat;
+pub mod loadavg;
+pub mod uptime;
+pub mod version;
+pub mod filesystems;
+pub mod partitions;
+pub mod swaps;
 
 /// init the whole /proc
 pub fn init_procfs(root_dentry: Arc<dyn Dentry>) {
     let sb = root_dentry.inode().unwrap().inode_inner().super_block.clone();
 
-    // mkdir /proc/self
-    let self_dentry = CNXFS::create_sys_dir("self", sb.clone().unwrap(), root_dentry.clone());
-
-    // touch /proc/self/exe
-    let exe_dentry = TmpDentry::new("exe", Some(root_dentry.clone()));
-    let exe_inode = ExeInode::new(sb.clone().unwrap());
-    exe_dentry.set_inode(exe_inode);
-    self_dentry.add_child(exe_dentry.clone());
-    DCACHE.lock().insert(exe_dentry.path(), exe_dentry.clone());
+    // ln -s /proc/<pid> /proc/self
+    create_link("self", sb.clone().unwrap(), root_dentry.clone(), || {
+        let task = current_task().ok_or(SysError::ESRCH)?;
+        Ok(format!("/proc/{}", task.pid()))
+    });
+    // ln -s /proc/<pid>/task/<tid> /proc/thread-self
+    create_link("thread-self", sb.clone().unwrap(), root_dentry.clone(), || {
+        let task = current_task().ok_or(SysError::ESRCH)?;
+        Ok(format!("/proc/{}/task/{}", task.pid(), task.tid()))
+    });
 
     // touch /proc/meminfo
     CNXFS::create_sys_file(Arc::new(MemInfo::new()), "meminfo", root_dentry.clone());
@@ -35,8 +45,18 @@ pub fn init_procfs(root_dentry: Arc<dyn Dentry>) {
     CNXFS::create_sys_file(Arc::new(MountInfo::new()),"mounts", root_dentry.clone());
     // touch /proc/interrupt
     CNXFS::create_sys_file(Arc::new(Interrupts::new()), "interrupts", root_dentry.clone());
-    // touch /proc/sys/kernel/pid_max
+    // touch /proc/cpuinfo /proc/stat /proc/loadavg /proc/uptime /proc/version
+    CNXFS::create_sys_file(Arc::new(CpuInfo::new()), "cpuinfo", root_dentry.clone());
+    CNXFS::create_sys_file(Arc::new(Stat::new()), "stat", root_dentry.clone());
+    CNXFS::create_sys_file(Arc::new(LoadAvg::new()), "loadavg", root_dentry.clone());
+    CNXFS::create_sys_file(Arc::new(Uptime::new()), "uptime", root_dentry.clone());
+    CNXFS::create_sys_file(Arc::new(Version::new()), "version", root_dentry.clone());
+    // touch /proc/filesystems /proc/partitions
+    CNXFS::create_sys_file(Arc::new(FileSystems::new()), "filesystems", root_dentry.clone());
+    CNXFS::create_sys_file(Arc::new(Partitions::new()), "partitions", root_dentry.clone());
+    // touch /proc/swaps
+    CNXFS::create_sys_file(Arc::new(Swaps::new()), "swaps", root_dentry.clone());
+    // mkdir /proc/sys and fill it with the sysctls
     let sys_dentry = CNXFS::create_sys_dir("sys", sb.clone().unwrap(), root_dentry.clone());
-    let kernel_dentry = CNXFS::create_sys_dir("kernel", sb.clone().unwrap(), sys_dentry);
-    CNXFS::create_sys_file(Arc::new(PidMax::new()), "pid_max", kernel_dentry);
+    init_sysctl_dir(sys_dentry, sb.clone().unwrap());
 }
\ No newline at end of file
@@ -0,0 +1,33 @@
+//! /proc/partitions
+
+use alloc::{format, string::String};
+
+use crate::{devices::{DeviceMajor, DEVICE_MANAGER}, fs::tmpfs::inode::InodeContent};
+
+pub struct Partitions;
+
+impl Partitions {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl InodeContent for Partitions {
+    fn serialize(&self) -> String {
+        let mut res = String::from("major minor  #blocks  name\n\n");
+        for (dev_id, dev) in DEVICE_MANAGER.lock().devices.iter() {
+            if dev_id.major != DeviceMajor::Block {
+                continue;
+            }
+            let Some(blk) = dev.clone().as_blk() else {
+                continue;
+            };
+            // #blocks counts 1K blocks
+            res += &format!(
+                "{:4} {:7} {:10} {}\n",
+                dev_id.major as usize, dev_id.minor, blk.size() / 1024, dev.name(),
+            );
+        }
+        res
+    }
+}
@@ -0,0 +1,315 @@
+//! contents of /proc/<pid> and /proc/<pid>/task/<tid>
+
+use core::sync::atomic::Ordering;
+
+use alloc::{format, string::{String, ToString}, sync::{Arc, Weak}, vec::Vec};
+use hal::{addr::VirtAddr, pagetable::MapPerm};
+
+use crate::{config::PAGE_SIZE, fs::{fs::CNXFS, nsfs::{create_ns_dir, NsFile}, tmpfs::{dentry::TmpDentry, inode::InodeContent}, vfs::{mount::list_mountinfo, Dentry, File}, SuperBlock}, mm::{oom::{oom_score, OOM_SCORE_ADJ_MAX, OOM_SCORE_ADJ_MIN}, vm::{MapFlags, UserVmAreaType, UserVmFile}}, signal::SIGRTMAX, syscall::SysError, task::{current_task, manager::TASK_MANAGER, task::{TaskControlBlock, TaskStatus}, INITPROC_PID}};
+
+use super::{dentry::{ProcDir, ProcDynDentry}, link::ProcLinkInode, stat::ticks};
+
+/// files under a pid directory
+#[derive(Clone, Copy)]
+pub enum TaskFile {
+    Stat,
+    Statm,
+    Status,
+    Cmdline,
+    Environ,
+    Maps,
+    MountInfo,
+    OomScore,
+    OomScoreAdj,
+}
+
+/// content of a file under a pid directory
+pub struct TaskContent {
+    task: Weak<TaskControlBlock>,
+    file: TaskFile,
+}
+
+impl TaskContent {
+    pub fn new(task: Weak<TaskControlBlock>, file: TaskFile) -> Self {
+        Self { task, file }
+    }
+}
+
+impl InodeContent for TaskContent {
+    fn serialize(&self) -> String {
+        let Some(task) = self.task.upgrade() else {
+            return String::new();
+        };
+        match self.file {
+            TaskFile::Stat => stat(&task),
+            TaskFile::Statm => statm(&task),
+            TaskFile::Status => status(&task),
+            TaskFile::Cmdline => nul_joined(&task.exec_args.lock().0),
+            TaskFile::Environ => nul_joined(&task.exec_args.lock().1),
+            TaskFile::Maps => maps(&task),
+            TaskFile::MountInfo => list_mountinfo(),
+            TaskFile::OomScore => format!("{}\n", oom_score(&task)),
+            TaskFile::OomScoreAdj => format!("{}\n", task.oom_score_adj()),
+        }
+    }
+
+    fn deserialize(&self, _offset: usize, buf: &[u8]) -> Result<usize, SysError> {
+        let TaskFile::OomScoreAdj = self.file else {
+            return Err(SysError::EACCES);
+        };
+        let task = self.task.upgrade().ok_or(SysError::ESRCH)?;
+        let adj: i32 = core::str::from_utf8(buf)
+            .map_err(|_| SysError::EINVAL)?
+            .trim()
+            .parse()
+            .map_err(|_| SysError::EINVAL)?;
+        if !(OOM_SCORE_ADJ_MIN..=OOM_SCORE_ADJ_MAX).contains(&adj) {
+            return Err(SysError::EINVAL);
+        }
+        // only root makes a process less likely to be killed
+        if adj < task.oom_score_adj() && current_task().unwrap().uid() != 0 {
+            return Err(SysError::EACCES);
+        }
+        task.set_oom_score_adj(adj);
+        Ok(buf.len())
+    }
+}
+
+/// content of /proc/<pid>/fdinfo/<fd>
+pub struct FdInfoContent {
+    task: Weak<TaskControlBlock>,
+    fd: usize,
+}
+
+impl FdInfoContent {
+    pub fn new(task: Weak<TaskControlBlock>, fd: usize) -> Self {
+        Self { task, fd }
+    }
+}
+
+impl InodeContent for FdInfoContent {
+    fn serialize(&self) -> String {
+        let Some(task) = self.task.upgrade() else {
+            return String::new();
+        };
+        let Ok(fd_info) = task.with_fd_table(|table| table.get_fd_info(self.fd)) else {
+            return String::new();
+        };
+        let file = fd_info.file;
+        let mut flags = file.flags().bits() as u32;
+        if !fd_info.flags.is_empty() {
+            flags |= 0o2000000;
+        }
+        let ino = file.dentry().and_then(|d| d.inode()).map_or(0, |i| i.inode_inner().ino);
+        format!("pos:\t{}\nflags:\t0{:o}\nmnt_id:\t0\nino:\t{}\n", file.pos(), flags, ino)
+    }
+}
+
+/// path of the file behind an fd, as shown by /proc/<pid>/fd/<fd>
+pub fn file_path(file: &Arc<dyn File>) -> String {
+    if let Ok(ns) = file.clone().downcast_arc::<NsFile>() {
+        return ns.name();
+    }
+    match file.dentry() {
+        Some(dentry) => dentry.path(),
+        None => "anon_inode:[unknown]".to_string(),
+    }
+}
+
+fn nul_joined(strs: &[String]) -> String {
+    let mut res = String::new();
+    for s in strs {
+        res += s;
+        res.push('\0');
+    }
+    res
+}
+
+/// short name of the task, at most 15 bytes like linux
+fn comm(task: &Arc<TaskControlBlock>) -> String {
+    let name = match task.exec_args.lock().0.first() {
+        Some(arg0) => arg0.rsplit('/').next().unwrap_or("").to_string(),
+        None => task.elf.lock().as_ref()
+            .and_then(|f| f.dentry())
+            .map_or(String::new(), |d| d.name().to_string()),
+    };
+    name.chars().take(15).collect()
+}
+
+fn state(task: &Arc<TaskControlBlock>) -> (char, &'static str) {
+    match task.get_status() {
+        TaskStatus::Ready | TaskStatus::Running => ('R', "running"),
+        TaskStatus::Interruptable => ('S', "sleeping"),
+        TaskStatus::UnInterruptable => ('D', "disk sleep"),
+        TaskStatus::Stopped => ('T', "stopped"),
+        TaskStatus::Zombie => ('Z', "zombie"),
+    }
+}
+
+fn ppid(task: &Arc<TaskControlBlock>) -> usize {
+    if task.pid() == INITPROC_PID {
+        return 0;
+    }
+    task.get_leader().parent()
+        .and_then(|p| p.upgrade())
+        .map_or(INITPROC_PID, |p| p.pid())
+}
+
+/// (virtual size, resident pages) of the address space
+fn mem_usage(task: &Arc<TaskControlBlock>) -> (usize, usize) {
+    let vm = task.get_vm_space().lock();
+    let vsize = vm.area_views().iter().map(|a| a.range_va.end.0 - a.range_va.start.0).sum();
+    (vsize, vm.resident_pages())
+}
+
+fn stat(task: &Arc<TaskControlBlock>) -> String {
+    let (utime, stime) = task.time_recorder_ref().time_pair();
+    let (cutime, cstime) = task.time_recorder_ref().child_time_pair();
+    let num_threads = task.thread_group.lock().len();
+    let (vsize, rss) = mem_usage(task);
+    let start_time = ticks(task.time_recorder_ref().start_time());
+    let priority = task.priority().load(Ordering::Relaxed);
+    format!(
+        "{} ({}) {} {} {} {} 0 -1 0 0 0 0 0 {} {} {} {} {} {} {} 0 {} {} {} {} 0 0 0 0 0 0 0 0 0 0 0 0 0 {} 0 0 0 0 0 0 0 0 0 0 0 0 0\n",
+        task.tid(), comm(task), state(task).0, ppid(task), task.pgid(), task.pgid(),
+        ticks(utime), ticks(stime), ticks(cutime), ticks(cstime),
+        priority, priority - 20, num_threads, start_time, vsize, rss, usize::MAX,
+        task.processor_id.load(Ordering::Relaxed),
+    )
+}
+
+fn statm(task: &Arc<TaskControlBlock>) -> String {
+    let (vsize, rss) = mem_usage(task);
+    format!("{} {} 0 0 0 0 0\n", vsize / PAGE_SIZE, rss)
+}
+
+fn status(task: &Arc<TaskControlBlock>) -> String {
+    let (state, state_name) = state(task);
+    let uid = task.uid();
+    let (vsize, rss) = mem_usage(task);
+    let swapped = task.get_vm_space().lock().swapped_pages();
+    let threads = task.thread_group.lock().len();
+    let sig = task.sig_manager.lock();
+    format!(
+        "Name:\t{}\nUmask:\t0022\nState:\t{} ({})\nTgid:\t{}\nPid:\t{}\nPPid:\t{}\nTracerPid:\t0\n\
+        Uid:\t{uid}\t{uid}\t{uid}\t{uid}\nGid:\t0\t0\t0\t0\nFDSize:\t{}\n\
+        VmSize:\t{} kB\nVmRSS:\t{} kB\nVmSwap:\t{} kB\nThreads:\t{}\nSigQ:\t{}/{}\nSigPnd:\t{:016x}\nShdPnd:\t{:016x}\nSigBlk:\t{:016x}\n",
+        comm(task), state, state_name, task.pid(), task.tid(), ppid(task),
+        task.with_fd_table(|t| t.fd_table.len()),
+        vsize / 1024, rss * PAGE_SIZE / 1024, swapped * PAGE_SIZE / 1024, threads,
+        sig.pending_sigs.len(), SIGRTMAX,
+        sig.bitmap.bits(), sig.bitmap.bits(), sig.blocked_sigs.bits(),
+    )
+}
+
+fn perm_str(perm: MapPerm, shared: bool) -> String {
+    let mut res = String::new();
+    res.push(if perm.contains(MapPerm::R) { 'r' } else { '-' });
+    res.push(if perm.contains(MapPerm::W) { 'w' } else { '-' });
+    res.push(if perm.contains(MapPerm::X) { 'x' } else { '-' });
+    res.push(if shared { 's' } else { 'p' });
+    res
+}
+
+fn maps(task: &Arc<TaskControlBlock>) -> String {
+    let areas = task.get_vm_space().lock().area_views();
+    let mut res = String::new();
+    for area in areas {
+        let VirtAddr(start) = area.range_va.start;
+        let VirtAddr(end) = area.range_va.end;
+        let shared = area.map_flags.contains(MapFlags::SHARED);
+        let (ino, name) = match &area.file {
+            UserVmFile::File(file) => (
+                file.dentry().and_then(|d| d.inode()).map_or(0, |i| i.inode_inner().ino),
+                file_path(file),
+            ),
+            UserVmFile::Shm(shm) => (shm.get_id(), "/SYSV00000000 (deleted)".to_string()),
+            UserVmFile::None => (0, match area.vma_type {
+                UserVmAreaType::Heap => "[heap]".to_string(),
+                UserVmAreaType::Stack => "[stack]".to_string(),
+                _ => String::new(),
+            }),
+        };
+        res += &format!(
+            "{:08x}-{:08x} {} {:08x} 00:00 {:<10} {}\n",
+            start, end, perm_str(area.map_perm, shared), area.offset, ino, name,
+        );
+    }
+    res
+}
+
+/// create /proc/<id> for a process, or /proc/<pid>/task/<id> for a thread
+pub fn create_pid_dir(
+    name: &str,
+    task: &Arc<TaskControlBlock>,
+    sb: Weak<dyn SuperBlock>,
+    parent: Arc<dyn Dentry>,
+    with_task_dir: bool,
+) -> Arc<dyn Dentry> {
+    let dir = CNXFS::create_sys_dir(name, sb.clone(), parent);
+    let weak = Arc::downgrade(task);
+    for (file, name) in [
+        (TaskFile::Stat, "stat"),
+        (TaskFile::Statm, "statm"),
+        (TaskFile::Status, "status"),
+        (TaskFile::Cmdline, "cmdline"),
+        (TaskFile::Environ, "environ"),
+        (TaskFile::Maps, "maps"),
+        (TaskFile::MountInfo, "mountinfo"),
+        (TaskFile::OomScore, "oom_score"),
+        (TaskFile::OomScoreAdj, "oom_score_adj"),
+    ] {
+        CNXFS::create_sys_file(Arc::new(TaskContent::new(weak.clone(), file)), name, dir.clone());
+    }
+
+    let w = weak.clone();
+    create_link("cwd", sb.clone(), dir.clone(), move || {
+        Ok(w.upgrade().ok_or(SysError::ESRCH)?.cwd().path())
+    });
+    let w = weak.clone();
+    create_link("root", sb.clone(), dir.clone(), move || {
+        Ok(w.upgrade().ok_or(SysError::ESRCH)?.root().path())
+    });
+    let w = weak.clone();
+    create_link("exe", sb.clone(), dir.clone(), move || {
+        let task = w.upgrade().ok_or(SysError::ESRCH)?;
+        let elf = task.elf.lock().clone().ok_or(SysError::ENOENT)?;
+        Ok(elf.dentry().ok_or(SysError::ENOENT)?.path())
+    });
+
+    let ns_dir = CNXFS::create_sys_dir("ns", sb.clone(), dir.clone());
+    create_ns_dir(weak.clone(), ns_dir);
+
+    let mut sub_dirs: Vec<(&str, ProcDir)> = Vec::new();
+    sub_dirs.push(("fd", ProcDir::Fd(weak.clone())));
+    sub_dirs.push(("fdinfo", ProcDir::FdInfo(weak.clone())));
+    if with_task_dir {
+        sub_dirs.push(("task", ProcDir::Task(weak.clone())));
+    }
+    for (name, kind) in sub_dirs {
+        let sub_dir = ProcDynDentry::new_dir(name, sb.clone(), dir.clone(), kind);
+        dir.add_child(sub_dir);
+    }
+    dir
+}
+
+/// create a symlink under `parent` whose target comes from `target`
+pub fn create_link(
+    name: &str,
+    sb: Weak<dyn SuperBlock>,
+    parent: Arc<dyn Dentry>,
+    target: impl Fn() -> Result<String, SysError> + Send + Sync + 'static,
+) -> Arc<dyn Dentry> {
+    let dentry = TmpDentry::new(name, Some(parent.clone()));
+    dentry.set_inode(ProcLinkInode::new(sb, target));
+    parent.add_child(dentry.clone());
+    dentry
+}
+
+/// pids of all processes
+pub fn all_pids() -> Vec<usize> {
+    TASK_MANAGER.tasks_group().iter()
+        .filter(|t| t.is_leader())
+        .map(|t| t.tid())
+        .collect()
+}
@@ -0,0 +1,72 @@
+//! /proc/stat
+
+use core::{sync::atomic::Ordering, time::Duration};
+
+use alloc::{format, string::String};
+use hal::board::MAX_PROCESSORS;
+
+use crate::{devices::DEVICE_MANAGER, fs::tmpfs::inode::InodeContent, processor::{loadavg::nr_running, processor::get_processor}, task::{manager::TASK_MANAGER, task::{TaskStatus, TOTAL_FORKS}}, timer::{clock::{CLOCK_DEVIATION, CLOCK_REALTIME}, get_current_time_duration}};
+
+use super::interrupt::IRQ_COUNTER;
+
+/// clock ticks per second seen by user, see sysconf(_SC_CLK_TCK)
+pub const USER_HZ: u128 = 100;
+
+/// time in clock ticks
+pub fn ticks(time: Duration) -> u128 {
+    time.as_millis() * USER_HZ / 1000
+}
+
+/// number of processors running tasks
+pub fn online_cpus() -> usize {
+    if cfg!(feature = "smp") {
+        DEVICE_MANAGER.lock().cpus.len().clamp(1, MAX_PROCESSORS)
+    } else {
+        1
+    }
+}
+
+/// (user, system, idle) time of each online processor
+pub fn cpu_times() -> impl Iterator<Item = (Duration, Duration, Duration)> {
+    let uptime = get_current_time_duration();
+    (0..online_cpus()).map(move |id| {
+        let (user, sys) = get_processor(id).cpu_time();
+        (user, sys, uptime.saturating_sub(user + sys))
+    })
+}
+
+pub struct Stat;
+
+impl Stat {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl InodeContent for Stat {
+    fn serialize(&self) -> String {
+        let line = |name: &str, (user, sys, idle): (Duration, Duration, Duration)| format!(
+            "{} {} 0 {} {} 0 0 0 0 0 0\n",
+            name, ticks(user), ticks(sys), ticks(idle),
+        );
+        let total = cpu_times().fold((Duration::ZERO, Duration::ZERO, Duration::ZERO), |acc, t| {
+            (acc.0 + t.0, acc.1 + t.1, acc.2 + t.2)
+        });
+        let mut res = line("cpu ", total);
+        for (id, times) in cpu_times().enumerate() {
+            res += &line(&format!("cpu{}", id), times);
+        }
+        let blocked = TASK_MANAGER.tasks_group().iter()
+            .filter(|t| t.get_status() == TaskStatus::UnInterruptable)
+            .count();
+        res += &format!(
+            "intr {}\nctxt 0\nbtime {}\nprocesses {}\nprocs_running {}\nprocs_blocked {}\n",
+            IRQ_COUNTER.lock().total(),
+            unsafe { CLOCK_DEVIATION[CLOCK_REALTIME] }.as_secs(),
+            TOTAL_FORKS.load(Ordering::Relaxed),
+            nr_running(),
+            blocked,
+        );
+        res
+    }
+}
@@ -0,0 +1,34 @@
+//! /proc/swaps
+
+use alloc::{format, string::String};
+
+use crate::{config::PAGE_SIZE, fs::tmpfs::inode::InodeContent, mm::swap::swap_areas};
+
+pub struct Swaps;
+
+impl Swaps {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl InodeContent for Swaps {
+    fn serialize(&self) -> String {
+        let mut res = String::from("Filename\t\t\t\tType\t\tSize\t\tUsed\t\tPriority\n");
+        for area in swap_areas() {
+            // sizes in kB, padded like the tab stops of the header
+            let size = area.pages * PAGE_SIZE / 1024;
+            let used = area.used * PAGE_SIZE / 1024;
+            let pad = 40usize.saturating_sub(area.path.len()).max(1);
+            res += &format!(
+                "{}{:pad$}{}\t{}\t{}{}\t{}{}\n",
+                area.path, "",
+                if area.is_file { "file\t" } else { "partition" },
+                size, if size < 10000000 { "\t" } else { "" },
+                used, if used < 10000000 { "\t" } else { "" },
+                area.priority,
+            );
+        }
+        res
+    }
+}
@@ -1,2 +1,51 @@
+//! /proc/sys, one file per registered sysctl
 
-pub mod kernel;
\ No newline at end of file
+use alloc::{string::String, sync::{Arc, Weak}, vec::Vec};
+
+use crate::{fs::{fs::CNXFS, tmpfs::inode::InodeContent, vfs::Dentry, SuperBlock}, syscall::SysError, sysctl::{sysctls, Sysctl}};
+
+/// content of a sysctl file
+pub struct SysctlContent {
+    sysctl: Arc<dyn Sysctl>,
+}
+
+impl SysctlContent {
+    pub fn new(sysctl: Arc<dyn Sysctl>) -> Self {
+        Self { sysctl }
+    }
+}
+
+impl InodeContent for SysctlContent {
+    fn serialize(&self) -> String {
+        let mut res = self.sysctl.show();
+        res.push('\n');
+        res
+    }
+
+    fn deserialize(&self, offset: usize, buf: &[u8]) -> Result<usize, SysError> {
+        if offset != 0 {
+            // like linux, a write not at the start is ignored
+            return Ok(buf.len());
+        }
+        let val = core::str::from_utf8(buf).map_err(|_| SysError::EINVAL)?;
+        self.sysctl.store(val.strip_suffix('\n').unwrap_or(val))?;
+        Ok(buf.len())
+    }
+}
+
+/// create the files of all registered sysctls under /proc/sys
+pub fn init_sysctl_dir(sys_dentry: Arc<dyn Dentry>, sb: Weak<dyn SuperBlock>) {
+    for (name, sysctl) in sysctls() {
+        // net.core.somaxconn is /proc/sys/net/core/somaxconn
+        let mut parts: Vec<&str> = name.split('.').collect();
+        let file_name = parts.pop().unwrap();
+        let mut dir = sys_dentry.clone();
+        for part in parts {
+            dir = match dir.get_child(part) {
+                Some(child) => child,
+                None => CNXFS::create_sys_dir(part, sb.clone(), dir),
+            };
+        }
+        CNXFS::create_sys_file(Arc::new(SysctlContent::new(sysctl)), file_name, dir);
+    }
+}
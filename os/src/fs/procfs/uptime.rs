@@ -0,0 +1,29 @@
+//! /proc/uptime
+
+use core::time::Duration;
+
+use alloc::{format, string::String};
+
+use crate::{fs::tmpfs::inode::InodeContent, timer::get_current_time_duration};
+
+use super::stat::cpu_times;
+
+pub struct Uptime;
+
+impl Uptime {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl InodeContent for Uptime {
+    fn serialize(&self) -> String {
+        let uptime = get_current_time_duration();
+        let idle: Duration = cpu_times().map(|(_, _, idle)| idle).sum();
+        format!(
+            "{}.{:02} {}.{:02}\n",
+            uptime.as_secs(), uptime.subsec_millis() / 10,
+            idle.as_secs(), idle.subsec_millis() / 10,
+        )
+    }
+}
@@ -0,0 +1,21 @@
+//! /proc/version
+
+use alloc::{format, string::String};
+
+use crate::{fs::tmpfs::inode::InodeContent, syscall::misc::current_uts_ns};
+
+pub struct Version;
+
+impl Version {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl InodeContent for Version {
+    fn serialize(&self) -> String {
+        let ns = current_uts_ns();
+        let uts = ns.uts.lock();
+        format!("{} version {} (chronix@{}) {}\n", uts.sysname, uts.release, uts.nodename, uts.version)
+    }
+}
@@ -0,0 +1,195 @@
+//! signalfd file object
+//!
+//! reading takes pending signals in the mask out of the `SigManager`
+//! of the reader, so they are never delivered to a handler.
+
+use core::{future::Future, pin::Pin, sync::atomic::AtomicUsize, task::{Context, Poll}};
+
+use alloc::{boxed::Box, sync::Arc};
+use async_trait::async_trait;
+
+use crate::{fs::{vfs::{file::PollEvents, Dentry, File, FileInner}, OpenFlags}, signal::{SigInfo, SigSet}, sync::mutex::SpinNoIrqLock, syscall::SysError, task::{current_task, task::TaskControlBlock}, utils::get_waker};
+
+bitflags! {
+    /// flags of signalfd4
+    pub struct SignalFdFlags: i32 {
+        const SFD_NONBLOCK = 0o4000;
+        const SFD_CLOEXEC = 0o2000000;
+    }
+}
+
+/// `struct signalfd_siginfo`
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct SignalFdSigInfo {
+    pub ssi_signo: u32,
+    pub ssi_errno: i32,
+    pub ssi_code: i32,
+    pub ssi_pid: u32,
+    pub ssi_uid: u32,
+    pub ssi_fd: i32,
+    pub ssi_tid: u32,
+    pub ssi_band: u32,
+    pub ssi_overrun: u32,
+    pub ssi_trapno: u32,
+    pub ssi_status: i32,
+    pub ssi_int: i32,
+    pub ssi_ptr: u64,
+    pub ssi_utime: u64,
+    pub ssi_stime: u64,
+    pub ssi_addr: u64,
+    pub ssi_addr_lsb: u16,
+    _pad2: u16,
+    pub ssi_syscall: i32,
+    pub ssi_call_addr: u64,
+    pub ssi_arch: u32,
+    _pad: [u8; 28],
+}
+
+impl From<SigInfo> for SignalFdSigInfo {
+    fn from(info: SigInfo) -> Self {
+        Self {
+            ssi_signo: info.si_signo as u32,
+            ssi_errno: 0,
+            ssi_code: info.si_code,
+            ssi_pid: info.si_pid.unwrap_or(0) as u32,
+            ssi_uid: 0,
+            ssi_fd: 0,
+            ssi_tid: 0,
+            ssi_band: 0,
+            ssi_overrun: 0,
+            ssi_trapno: 0,
+            ssi_status: 0,
+            ssi_int: 0,
+            ssi_ptr: 0,
+            ssi_utime: 0,
+            ssi_stime: 0,
+            ssi_addr: 0,
+            ssi_addr_lsb: 0,
+            _pad2: 0,
+            ssi_syscall: 0,
+            ssi_call_addr: 0,
+            ssi_arch: 0,
+            _pad: [0; 28],
+        }
+    }
+}
+
+/// signalfd instance
+pub struct SignalFdFile {
+    inner: FileInner,
+    mask: SpinNoIrqLock<SigSet>,
+}
+
+impl SignalFdFile {
+    /// create a signalfd accepting the signals in `mask`
+    pub fn new(mask: SigSet, flags: SignalFdFlags) -> Arc<Self> {
+        let mut open_flags = OpenFlags::O_RDWR;
+        if flags.contains(SignalFdFlags::SFD_NONBLOCK) {
+            open_flags |= OpenFlags::O_NONBLOCK;
+        }
+        Arc::new(Self {
+            inner: FileInner {
+                dentry: Arc::<usize>::new_zeroed(),
+                offset: AtomicUsize::new(0),
+                flags: SpinNoIrqLock::new(open_flags),
+            },
+            mask: SpinNoIrqLock::new(Self::sanitize(mask)),
+        })
+    }
+
+    /// SIGKILL and SIGSTOP cannot be read through a signalfd
+    fn sanitize(mask: SigSet) -> SigSet {
+        mask - SigSet::SIGKILL - SigSet::SIGSTOP
+    }
+
+    /// replace the mask, signalfd4 on an existing fd
+    pub fn set_mask(&self, mask: SigSet) {
+        *self.mask.lock() = Self::sanitize(mask);
+    }
+}
+
+/// future for read, waits until a signal in the mask is pending
+struct SignalFdReadFuture {
+    task: Arc<TaskControlBlock>,
+    mask: SigSet,
+}
+
+impl Future for SignalFdReadFuture {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut manager = self.task.sig_manager.lock();
+        if manager.check_pending_flag(self.mask) {
+            return Poll::Ready(());
+        }
+        manager.signalfd_wakers.push_back(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+#[async_trait]
+impl File for SignalFdFile {
+    fn file_inner(&self) -> &FileInner {
+        &self.inner
+    }
+
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn writable(&self) -> bool {
+        false
+    }
+
+    async fn read(&self, buf: &mut [u8]) -> Result<usize, SysError> {
+        const SIZE: usize = size_of::<SignalFdSigInfo>();
+        if buf.len() < SIZE {
+            return Err(SysError::EINVAL);
+        }
+        let task = current_task().unwrap().clone();
+        let mask = *self.mask.lock();
+        if !task.sig_manager.lock().check_pending_flag(mask) {
+            if self.flags().contains(OpenFlags::O_NONBLOCK) {
+                return Err(SysError::EAGAIN);
+            }
+            SignalFdReadFuture { task: task.clone(), mask }.await;
+        }
+        let mut len = 0;
+        let mut manager = task.sig_manager.lock();
+        while len + SIZE <= buf.len() && manager.check_pending_flag(mask) {
+            let Some(info) = manager.dequeue_expected_one(mask) else {
+                break;
+            };
+            let info = SignalFdSigInfo::from(info.seen_from(task.pid_ns()));
+            let bytes = unsafe { core::slice::from_raw_parts(&info as *const SignalFdSigInfo as *const u8, SIZE) };
+            buf[len..len + SIZE].copy_from_slice(bytes);
+            len += SIZE;
+        }
+        Ok(len)
+    }
+
+    async fn write(&self, _buf: &[u8]) -> Result<usize, SysError> {
+        Err(SysError::EINVAL)
+    }
+
+    async fn base_poll(&self, events: PollEvents) -> PollEvents {
+        let waker = get_waker().await;
+        let task = current_task().unwrap().clone();
+        let mask = *self.mask.lock();
+        let mut manager = task.sig_manager.lock();
+        if events.contains(PollEvents::IN) && manager.check_pending_flag(mask) {
+            if events.contains(PollEvents::EDGE) {
+                manager.signalfd_wakers.push_back(waker);
+            }
+            PollEvents::IN
+        } else {
+            manager.signalfd_wakers.push_back(waker);
+            PollEvents::empty()
+        }
+    }
+
+    fn dentry(&self) -> Option<Arc<dyn Dentry>> {
+        None
+    }
+}
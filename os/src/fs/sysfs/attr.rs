@@ -0,0 +1,33 @@
+//! sysfs attribute files, one value per file
+
+use alloc::{boxed::Box, string::String, sync::Arc};
+
+use crate::fs::{fs::CNXFS, tmpfs::inode::InodeContent, vfs::Dentry};
+
+/// an attribute whose value is computed on every read
+pub struct SysAttr {
+    show: Box<dyn Fn() -> String + Send + Sync>,
+}
+
+impl SysAttr {
+    pub fn new(show: impl Fn() -> String + Send + Sync + 'static) -> Self {
+        Self { show: Box::new(show) }
+    }
+}
+
+impl InodeContent for SysAttr {
+    fn serialize(&self) -> String {
+        let mut res = (self.show)();
+        res.push('\n');
+        res
+    }
+}
+
+/// create the attribute `name` under `parent`
+pub fn create_attr(
+    name: &str,
+    parent: Arc<dyn Dentry>,
+    show: impl Fn() -> String + Send + Sync + 'static,
+) -> Arc<dyn Dentry> {
+    CNXFS::create_sys_file(Arc::new(SysAttr::new(show)), name, parent)
+}
@@ -0,0 +1,142 @@
+//! sys file system, a view of the device model
+//!
+//! block devices, cpus and ttys are published when /sys is mounted, the
+//! net interfaces are added when the network is up.
+
+use alloc::{format, string::{String, ToString}, sync::{Arc, Weak}};
+use core::sync::atomic::Ordering;
+use hal::board::MAX_PROCESSORS;
+use spin::Once;
+
+use crate::{devices::{DeviceMajor, DEVICE_MANAGER}, fs::{fs::CNXFS, procfs::{pid::create_link, stat::online_cpus}, vfs::Dentry, SuperBlock}, net::InterfaceWrapper};
+
+use attr::create_attr;
+
+pub mod fstype;
+pub mod superblock;
+pub mod attr;
+
+/// root of the mounted sysfs
+static SYSFS_ROOT: Once<Arc<dyn Dentry>> = Once::new();
+
+fn super_block(dentry: &Arc<dyn Dentry>) -> Weak<dyn SuperBlock> {
+    dentry.inode().unwrap().inode_inner().super_block.clone().unwrap()
+}
+
+/// mkdir -p `path` under `root`
+fn create_dirs(root: &Arc<dyn Dentry>, path: &str) -> Arc<dyn Dentry> {
+    let sb = super_block(root);
+    let mut dir = root.clone();
+    for name in path.split('/').filter(|s| !s.is_empty()) {
+        dir = match dir.get_child(name) {
+            Some(child) => child,
+            None => CNXFS::create_sys_dir(name, sb.clone(), dir),
+        };
+    }
+    dir
+}
+
+/// "0" for a single id, "0-n" for a range of them
+fn id_range(n: usize) -> String {
+    match n {
+        1 => "0".to_string(),
+        n => format!("0-{}", n - 1),
+    }
+}
+
+/// init the whole /sys
+pub fn init_sysfs(root_dentry: Arc<dyn Dentry>) {
+    let sb = super_block(&root_dentry);
+    for dir in ["class/net", "class/tty", "dev/block", "dev/char", "kernel", "fs", "bus"] {
+        create_dirs(&root_dentry, dir);
+    }
+
+    // /sys/block/<dev>
+    let block_dir = create_dirs(&root_dentry, "block");
+    let dev_block_dir = create_dirs(&root_dentry, "dev/block");
+    for (dev_id, dev) in DEVICE_MANAGER.lock().devices.iter() {
+        if dev_id.major != DeviceMajor::Block {
+            continue;
+        }
+        let Some(blk) = dev.clone().as_blk() else {
+            continue;
+        };
+        let name = dev.name().to_string();
+        let dev_str = format!("{}:{}", dev_id.major as usize, dev_id.minor);
+        let dir = CNXFS::create_sys_dir(&name, sb.clone(), block_dir.clone());
+        let size_blk = blk.clone();
+        // size is counted in 512 bytes sectors
+        create_attr("size", dir.clone(), move || (size_blk.size() / 512).to_string());
+        let d = dev_str.clone();
+        create_attr("dev", dir.clone(), move || d.clone());
+        create_attr("removable", dir.clone(), || "0".to_string());
+        create_attr("ro", dir.clone(), || "0".to_string());
+        let queue = CNXFS::create_sys_dir("queue", sb.clone(), dir.clone());
+        let block_size = blk.block_size();
+        create_attr("logical_block_size", queue.clone(), move || block_size.to_string());
+        create_attr("physical_block_size", queue.clone(), move || block_size.to_string());
+        create_attr("rotational", queue, || "0".to_string());
+        // ln -s ../../block/<dev> /sys/dev/block/<major:minor>
+        let target = format!("../../block/{}", name);
+        create_link(&dev_str, sb.clone(), dev_block_dir.clone(), move || Ok(target.clone()));
+    }
+
+    // /sys/devices/system/cpu
+    let cpu_dir = create_dirs(&root_dentry, "devices/system/cpu");
+    create_attr("online", cpu_dir.clone(), || id_range(online_cpus()));
+    create_attr("present", cpu_dir.clone(), || id_range(online_cpus()));
+    create_attr("possible", cpu_dir.clone(), || id_range(MAX_PROCESSORS));
+    create_attr("kernel_max", cpu_dir.clone(), || (MAX_PROCESSORS - 1).to_string());
+    for id in 0..MAX_PROCESSORS {
+        let dir = CNXFS::create_sys_dir(&format!("cpu{}", id), sb.clone(), cpu_dir.clone());
+        create_attr("online", dir, move || ((id < online_cpus()) as usize).to_string());
+    }
+
+    // /sys/class/tty/<tty>
+    let tty_dir = create_dirs(&root_dentry, "class/tty");
+    let mut ttys = alloc::vec![("tty".to_string(), "5:0".to_string())];
+    for (dev_id, _) in DEVICE_MANAGER.lock().devices.iter() {
+        if dev_id.major == DeviceMajor::Serial {
+            // serial lines are ttyS<n>, minor 64 + n like linux
+            ttys.push((format!("ttyS{}", dev_id.minor), format!("{}:{}", dev_id.major as usize, 64 + dev_id.minor)));
+        }
+    }
+    for (name, dev_str) in ttys {
+        let dir = CNXFS::create_sys_dir(&name, sb.clone(), tty_dir.clone());
+        create_attr("dev", dir, move || dev_str.clone());
+    }
+
+    // /sys/class/net/lo, the loopback is always there
+    let lo = CNXFS::create_sys_dir("lo", sb.clone(), create_dirs(&root_dentry, "class/net"));
+    create_attr("address", lo.clone(), || "00:00:00:00:00:00".to_string());
+    create_attr("mtu", lo.clone(), || "65536".to_string());
+    create_attr("operstate", lo.clone(), || "unknown".to_string());
+    // ARPHRD_LOOPBACK
+    create_attr("type", lo.clone(), || "772".to_string());
+    // IFF_UP | IFF_LOOPBACK | IFF_RUNNING
+    create_attr("flags", lo, || "0x49".to_string());
+
+    SYSFS_ROOT.call_once(|| root_dentry);
+}
+
+/// publish /sys/class/net/<iface>, called when the interface is up
+pub fn add_net_iface(iface: &'static InterfaceWrapper) {
+    let Some(root) = SYSFS_ROOT.get() else {
+        return;
+    };
+    let sb = super_block(root);
+    let net_dir = create_dirs(root, "class/net");
+    let dir = CNXFS::create_sys_dir(iface.name(), sb.clone(), net_dir);
+    create_attr("address", dir.clone(), move || {
+        let mac = iface.ethernet_address().0;
+        format!("{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}", mac[0], mac[1], mac[2], mac[3], mac[4], mac[5])
+    });
+    create_attr("mtu", dir.clone(), move || iface.mtu().to_string());
+    create_attr("operstate", dir.clone(), || "up".to_string());
+    create_attr("type", dir.clone(), || "1".to_string());
+    let stats = CNXFS::create_sys_dir("statistics", sb, dir);
+    create_attr("rx_packets", stats.clone(), move || iface.with_stats(|s| s.rx_packets.load(Ordering::Relaxed)).to_string());
+    create_attr("rx_bytes", stats.clone(), move || iface.with_stats(|s| s.rx_bytes.load(Ordering::Relaxed)).to_string());
+    create_attr("tx_packets", stats.clone(), move || iface.with_stats(|s| s.tx_packets.load(Ordering::Relaxed)).to_string());
+    create_attr("tx_bytes", stats, move || iface.with_stats(|s| s.tx_bytes.load(Ordering::Relaxed)).to_string());
+}
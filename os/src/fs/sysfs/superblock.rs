@@ -0,0 +1,24 @@
+//! sysfs super block
+
+use alloc::sync::Arc;
+
+use crate::{devices::BlockDevice, fs::{vfs::Inode, SuperBlock, SuperBlockInner}};
+
+pub struct SysSuperBlock {
+    inner: SuperBlockInner,
+}
+
+impl SysSuperBlock {
+    pub fn new(inner: SuperBlockInner) -> Arc<dyn SuperBlock> {
+        Arc::new(Self { inner })
+    }
+}
+
+impl SuperBlock for SysSuperBlock {
+    fn inner(&self) -> &SuperBlockInner {
+        &self.inner
+    }
+    fn get_root_inode(&'static self, _name: &str) -> Arc<dyn Inode> {
+        self.inner().root.get().unwrap().clone().inode().unwrap()
+    }
+}
\ No newline at end of file
@@ -0,0 +1,231 @@
+//! timerfd file object
+//!
+//! the timer is a `Timer` in the `TIMER_MANAGER`. rearming bumps the id,
+//! so a stale timer finds a different id in the callback and goes away.
+
+use core::{future::Future, pin::Pin, sync::atomic::AtomicUsize, task::{Context, Poll, Waker}, time::Duration};
+
+use alloc::{boxed::Box, collections::vec_deque::VecDeque, sync::{Arc, Weak}};
+use async_trait::async_trait;
+
+use crate::{fs::{vfs::{file::PollEvents, Dentry, File, FileInner}, OpenFlags}, sync::mutex::SpinNoIrqLock, syscall::SysError, timer::{clock::{CLOCK_DEVIATION, CLOCK_MONOTONIC, CLOCK_REALTIME}, get_current_time_duration, timer::{alloc_timer_id, ITimerSpec, Timer, TimerEvent, TIMER_MANAGER}}, utils::get_waker};
+
+bitflags! {
+    /// flags of timerfd_create
+    pub struct TimerFdFlags: i32 {
+        const TFD_NONBLOCK = 0o4000;
+        const TFD_CLOEXEC = 0o2000000;
+    }
+}
+
+bitflags! {
+    /// flags of timerfd_settime
+    pub struct TimerFdSetFlags: i32 {
+        /// it_value is an absolute time of the clock
+        const TFD_TIMER_ABSTIME = 1;
+        /// cancel when the realtime clock is set, accepted and ignored
+        const TFD_TIMER_CANCEL_ON_SET = 2;
+    }
+}
+
+struct TimerFdState {
+    interval: Duration,
+    /// next expiration in kernel time, zero when disarmed
+    next_expire: Duration,
+    /// expirations since the last read
+    ticks: u64,
+    /// id of the armed timer
+    id: usize,
+    wakers: VecDeque<Waker>,
+}
+
+/// timer event of a timerfd
+struct TimerFdEvent {
+    state: Weak<SpinNoIrqLock<TimerFdState>>,
+    id: usize,
+}
+
+impl TimerEvent for TimerFdEvent {
+    fn callback(self: Box<Self>) -> Option<Timer> {
+        let state = self.state.upgrade()?;
+        let mut state = state.lock();
+        if state.id != self.id {
+            return None;
+        }
+        let now = get_current_time_duration();
+        // count the periods we were late for as well
+        let mut ticks = 1u128;
+        if !state.interval.is_zero() && now > state.next_expire {
+            ticks += (now - state.next_expire).as_nanos() / state.interval.as_nanos();
+        }
+        state.ticks = state.ticks.saturating_add(u64::try_from(ticks).unwrap_or(u64::MAX));
+        while let Some(waker) = state.wakers.pop_front() {
+            waker.wake();
+        }
+        if state.interval.is_zero() {
+            state.next_expire = Duration::ZERO;
+            return None;
+        }
+        // in ns, the count may not fit the u32 a Duration is multiplied by
+        let next_expire = state.next_expire.as_nanos() + state.interval.as_nanos() * ticks;
+        let next_expire = Duration::new(
+            u64::try_from(next_expire / 1_000_000_000).unwrap_or(u64::MAX),
+            (next_expire % 1_000_000_000) as u32,
+        );
+        state.next_expire = next_expire;
+        drop(state);
+        Some(Timer::new(next_expire, self))
+    }
+}
+
+/// timerfd instance
+pub struct TimerFdFile {
+    inner: FileInner,
+    clockid: usize,
+    state: Arc<SpinNoIrqLock<TimerFdState>>,
+}
+
+impl TimerFdFile {
+    /// create a disarmed timer on `clockid`
+    pub fn new(clockid: usize, flags: TimerFdFlags) -> Result<Arc<Self>, SysError> {
+        if clockid != CLOCK_REALTIME && clockid != CLOCK_MONOTONIC {
+            return Err(SysError::EINVAL);
+        }
+        let mut open_flags = OpenFlags::O_RDWR;
+        if flags.contains(TimerFdFlags::TFD_NONBLOCK) {
+            open_flags |= OpenFlags::O_NONBLOCK;
+        }
+        Ok(Arc::new(Self {
+            inner: FileInner {
+                dentry: Arc::<usize>::new_zeroed(),
+                offset: AtomicUsize::new(0),
+                flags: SpinNoIrqLock::new(open_flags),
+            },
+            clockid,
+            state: Arc::new(SpinNoIrqLock::new(TimerFdState {
+                interval: Duration::ZERO,
+                next_expire: Duration::ZERO,
+                ticks: 0,
+                id: 0,
+                wakers: VecDeque::new(),
+            })),
+        }))
+    }
+
+    fn current(state: &TimerFdState) -> ITimerSpec {
+        ITimerSpec {
+            it_interval: state.interval.into(),
+            it_value: if state.next_expire.is_zero() {
+                Duration::ZERO.into()
+            } else {
+                // an expired timer still reports a tiny value while armed
+                state.next_expire.saturating_sub(get_current_time_duration()).max(Duration::from_nanos(1)).into()
+            },
+        }
+    }
+
+    /// timerfd_gettime
+    pub fn get_time(&self) -> ITimerSpec {
+        Self::current(&self.state.lock())
+    }
+
+    /// timerfd_settime, returns the old setting
+    pub fn set_time(&self, new: &ITimerSpec, flags: TimerFdSetFlags) -> ITimerSpec {
+        let id = alloc_timer_id();
+        let now = get_current_time_duration();
+        let mut state = self.state.lock();
+        let old = Self::current(&state);
+        state.id = id;
+        state.ticks = 0;
+        state.interval = new.it_interval.into();
+        let value: Duration = new.it_value.into();
+        if value.is_zero() {
+            state.next_expire = Duration::ZERO;
+            return old;
+        }
+        let expire = if flags.contains(TimerFdSetFlags::TFD_TIMER_ABSTIME) {
+            // an absolute time in the past fires right away
+            value.saturating_sub(unsafe { CLOCK_DEVIATION[self.clockid] }).max(now)
+        } else {
+            now + value
+        };
+        state.next_expire = expire;
+        drop(state);
+        TIMER_MANAGER.add_timer(Timer::new(expire, Box::new(TimerFdEvent {
+            state: Arc::downgrade(&self.state),
+            id,
+        })));
+        old
+    }
+}
+
+/// future for read, waits until the timer has expired
+struct TimerFdReadFuture<'a> {
+    file: &'a TimerFdFile,
+    nowait: bool,
+}
+
+impl Future for TimerFdReadFuture<'_> {
+    type Output = Result<u64, SysError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.file.state.lock();
+        if state.ticks == 0 {
+            if self.nowait {
+                return Poll::Ready(Err(SysError::EAGAIN));
+            }
+            state.wakers.push_back(cx.waker().clone());
+            return Poll::Pending;
+        }
+        let ticks = state.ticks;
+        state.ticks = 0;
+        Poll::Ready(Ok(ticks))
+    }
+}
+
+#[async_trait]
+impl File for TimerFdFile {
+    fn file_inner(&self) -> &FileInner {
+        &self.inner
+    }
+
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn writable(&self) -> bool {
+        false
+    }
+
+    async fn read(&self, buf: &mut [u8]) -> Result<usize, SysError> {
+        if buf.len() < size_of::<u64>() {
+            return Err(SysError::EINVAL);
+        }
+        let nowait = self.flags().contains(OpenFlags::O_NONBLOCK);
+        let ticks = TimerFdReadFuture { file: self, nowait }.await?;
+        buf[..size_of::<u64>()].copy_from_slice(&ticks.to_ne_bytes());
+        Ok(size_of::<u64>())
+    }
+
+    async fn write(&self, _buf: &[u8]) -> Result<usize, SysError> {
+        Err(SysError::EINVAL)
+    }
+
+    async fn base_poll(&self, events: PollEvents) -> PollEvents {
+        let waker = get_waker().await;
+        let mut state = self.state.lock();
+        if events.contains(PollEvents::IN) && state.ticks > 0 {
+            if events.contains(PollEvents::EDGE) {
+                state.wakers.push_back(waker);
+            }
+            PollEvents::IN
+        } else {
+            state.wakers.push_back(waker);
+            PollEvents::empty()
+        }
+    }
+
+    fn dentry(&self) -> Option<Arc<dyn Dentry>> {
+        None
+    }
+}
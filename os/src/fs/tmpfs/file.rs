@@ -62,7 +62,7 @@ impl File for TmpFile {
         let size = if inode.cache().is_some() {
             inode.cache_write_at(offset, buf).unwrap()
         } else {
-            inode.write_at(offset, buf).unwrap()
+            inode.write_at(offset, buf).map_err(SysError::from_i32)?
         };
         Ok(size)
     }
@@ -88,7 +88,7 @@ impl File for TmpFile {
         let size = if inode.cache().is_some() {
             inode.cache_write_at(pos, buf).unwrap()
         } else {
-            inode.write_at(pos, buf).unwrap()
+            inode.write_at(pos, buf).map_err(SysError::from_i32)?
         };
         log::debug!("[Tmp file] set pos at {}", pos + size);
         self.set_pos(pos + size);
@@ -263,6 +263,10 @@ impl Inode for TmpInode {
 
 pub trait InodeContent {
     fn serialize(&self) -> String;
+    /// handle a write at `offset`, the file is read only by default
+    fn deserialize(&self, _offset: usize, _buf: &[u8]) -> Result<usize, SysError> {
+        Err(SysError::EACCES)
+    }
 }
 
 pub struct TmpSysInode {
@@ -298,8 +302,8 @@ impl Inode for TmpSysInode {
         Ok(read_size)
     }
 
-    fn write_at(&self, _offset: usize, _buf: &[u8]) -> Result<usize, i32> {
-        Ok(0)
+    fn write_at(&self, offset: usize, buf: &[u8]) -> Result<usize, i32> {
+        self.content.deserialize(offset, buf).map_err(|e| e as i32)
     }
 
     fn getattr(&self) -> Kstat {
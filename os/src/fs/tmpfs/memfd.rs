@@ -0,0 +1,226 @@
+//! memfd: an unnamed tmp file with seals
+//!
+//! the file lives in a private tmpfs super block like an O_TMPFILE, so
+//! mmap goes through the page cache of its `TmpInode`.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use alloc::{boxed::Box, format, sync::Arc};
+use spin::Lazy;
+use async_trait::async_trait;
+
+use hal::pagetable::MapPerm;
+
+use crate::{fs::{vfs::{file::SeekFrom, inode::InodeMode, Dentry, DentryState, File, FileInner}, OpenFlags, SuperBlock, SuperBlockInner, FS_MANAGER}, mm::vm::{MapFlags, UserVmFile}, sync::mutex::SpinNoIrqLock, syscall::SysError, task::manager::TASK_MANAGER};
+
+use super::{dentry::TmpDentry, inode::TmpInode, superblock::TmpSuperBlock};
+
+bitflags! {
+    /// flags of memfd_create
+    pub struct MemFdFlags: u32 {
+        const MFD_CLOEXEC = 0x1;
+        const MFD_ALLOW_SEALING = 0x2;
+        const MFD_HUGETLB = 0x4;
+        const MFD_NOEXEC_SEAL = 0x8;
+        const MFD_EXEC = 0x10;
+    }
+}
+
+bitflags! {
+    /// seals of F_ADD_SEALS / F_GET_SEALS
+    pub struct Seals: u32 {
+        /// no more seals can be added
+        const F_SEAL_SEAL = 0x1;
+        /// the file cannot shrink
+        const F_SEAL_SHRINK = 0x2;
+        /// the file cannot grow
+        const F_SEAL_GROW = 0x4;
+        /// the content cannot be written
+        const F_SEAL_WRITE = 0x8;
+        /// like F_SEAL_WRITE, but existing shared mappings stay writable
+        const F_SEAL_FUTURE_WRITE = 0x10;
+        /// the exec bits cannot be changed
+        const F_SEAL_EXEC = 0x20;
+    }
+}
+
+/// max length of the name passed to memfd_create
+pub const MFD_NAME_MAX: usize = 249;
+
+/// the tmpfs super block of all memfds, reachable from no mount namespace
+static MEMFD_SB: Lazy<Arc<dyn SuperBlock>> = Lazy::new(|| {
+    let fs_type = FS_MANAGER.lock().get("tmpfs").unwrap().clone();
+    let sb = TmpSuperBlock::new(SuperBlockInner::new(None, fs_type));
+    let root = TmpDentry::new("memfd:", None);
+    root.set_inode(TmpInode::new(Arc::downgrade(&sb), InodeMode::DIR));
+    root.set_state(DentryState::USED);
+    sb.set_root_dentry(root);
+    sb
+});
+
+/// file returned by memfd_create
+pub struct MemFdFile {
+    inner: FileInner,
+    seals: SpinNoIrqLock<Seals>,
+    /// bumped under the seals lock each time a writable shared mapping is
+    /// allowed, F_ADD_SEALS looks at the mappings again if it moved
+    write_grants: AtomicUsize,
+}
+
+impl MemFdFile {
+    /// create an empty memfd named `memfd:<name>`
+    pub fn new(name: &str, flags: MemFdFlags) -> Result<Arc<Self>, SysError> {
+        let sb = Arc::downgrade(&*MEMFD_SB);
+        let mode = if flags.contains(MemFdFlags::MFD_NOEXEC_SEAL) { 0o666 } else { 0o777 };
+        let inode = TmpInode::new(sb, InodeMode::FILE | InodeMode::from_bits_truncate(mode));
+        let dentry = TmpDentry::new(&format!("memfd:{}", name), None);
+        dentry.set_inode(inode);
+        // only the fd table holds it, like O_TMPFILE
+        dentry.set_state(DentryState::NEGATIVE);
+        let mut seals = if flags.contains(MemFdFlags::MFD_ALLOW_SEALING) {
+            Seals::empty()
+        } else {
+            Seals::F_SEAL_SEAL
+        };
+        if flags.contains(MemFdFlags::MFD_NOEXEC_SEAL) {
+            seals.remove(Seals::F_SEAL_SEAL);
+            seals.insert(Seals::F_SEAL_EXEC);
+        }
+        Ok(Arc::new(Self {
+            inner: FileInner {
+                dentry,
+                offset: AtomicUsize::new(0),
+                flags: SpinNoIrqLock::new(OpenFlags::O_RDWR),
+            },
+            seals: SpinNoIrqLock::new(seals),
+            write_grants: AtomicUsize::new(0),
+        }))
+    }
+
+    /// F_GET_SEALS
+    pub fn seals(&self) -> Seals {
+        *self.seals.lock()
+    }
+
+    /// F_ADD_SEALS
+    pub fn add_seals(&self, new: Seals) -> Result<(), SysError> {
+        if !self.writable() {
+            return Err(SysError::EPERM);
+        }
+        loop {
+            // the mappings are looked at without the seals lock, mprotect
+            // and mmap take it under the vm space lock
+            let grants = self.write_grants.load(Ordering::Acquire);
+            let mapped = new.contains(Seals::F_SEAL_WRITE) && self.mapped_writable();
+            let mut seals = self.seals.lock();
+            if seals.contains(Seals::F_SEAL_SEAL) {
+                return Err(SysError::EPERM);
+            }
+            if self.write_grants.load(Ordering::Acquire) != grants {
+                // a writable mapping may have come in since
+                continue;
+            }
+            if mapped && !seals.contains(Seals::F_SEAL_WRITE) {
+                return Err(SysError::EBUSY);
+            }
+            seals.insert(new);
+            return Ok(());
+        }
+    }
+
+    /// whether some address space maps the file shared and writable
+    fn mapped_writable(&self) -> bool {
+        let inode = self.dentry().unwrap().inode().unwrap();
+        let mut found = false;
+        TASK_MANAGER.for_each_task(|task| {
+            if found {
+                return;
+            }
+            found = task.with_vm_space(|vm| vm.area_views().iter().any(|view| {
+                let UserVmFile::File(file) = &view.file else {
+                    return false;
+                };
+                view.map_flags.contains(MapFlags::SHARED)
+                    && view.map_perm.contains(MapPerm::W)
+                    && file.dentry().and_then(|d| d.inode()).is_some_and(|i| Arc::ptr_eq(&i, &inode))
+            }));
+        });
+        found
+    }
+
+    /// whether a MAP_SHARED mapping may be writable, called with the vm
+    /// space lock held so that F_ADD_SEALS finds the mapping once it is let in
+    pub fn can_map_write(&self) -> bool {
+        let seals = self.seals.lock();
+        if seals.intersects(Seals::F_SEAL_WRITE | Seals::F_SEAL_FUTURE_WRITE) {
+            return false;
+        }
+        self.write_grants.fetch_add(1, Ordering::AcqRel);
+        true
+    }
+
+    /// check a resize from the current size to `size`
+    pub fn check_truncate(&self, size: usize) -> Result<(), SysError> {
+        let seals = self.seals();
+        let old = self.size();
+        if (size < old && seals.contains(Seals::F_SEAL_SHRINK)) || (size > old && seals.contains(Seals::F_SEAL_GROW)) {
+            return Err(SysError::EPERM);
+        }
+        Ok(())
+    }
+
+    fn check_write(&self, offset: usize, len: usize) -> Result<(), SysError> {
+        let seals = self.seals();
+        if seals.intersects(Seals::F_SEAL_WRITE | Seals::F_SEAL_FUTURE_WRITE) {
+            return Err(SysError::EPERM);
+        }
+        if seals.contains(Seals::F_SEAL_GROW) && offset + len > self.size() {
+            return Err(SysError::EPERM);
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl File for MemFdFile {
+    fn file_inner(&self) -> &FileInner {
+        &self.inner
+    }
+
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn writable(&self) -> bool {
+        true
+    }
+
+    async fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize, SysError> {
+        let inode = self.dentry().unwrap().inode().unwrap();
+        // the page cache may extend past the file size
+        let len = buf.len().min(self.size().saturating_sub(offset));
+        inode.cache_read_at(offset, &mut buf[..len]).map_err(SysError::from_i32)
+    }
+
+    async fn write_at(&self, offset: usize, buf: &[u8]) -> Result<usize, SysError> {
+        self.check_write(offset, buf.len())?;
+        let inode = self.dentry().unwrap().inode().unwrap();
+        inode.cache_write_at(offset, buf).map_err(SysError::from_i32)
+    }
+
+    async fn read(&self, buf: &mut [u8]) -> Result<usize, SysError> {
+        let size = self.read_at(self.pos(), buf).await?;
+        self.seek(SeekFrom::Current(size as i64))?;
+        Ok(size)
+    }
+
+    async fn write(&self, buf: &[u8]) -> Result<usize, SysError> {
+        if self.flags().contains(OpenFlags::O_APPEND) {
+            self.set_pos(self.size());
+        }
+        let pos = self.pos();
+        let size = self.write_at(pos, buf).await?;
+        self.set_pos(pos + size);
+        Ok(size)
+    }
+}
@@ -10,6 +10,7 @@ pub mod fstype;
 pub mod inode;
 pub mod dentry;
 pub mod file;
+pub mod memfd;
 
 /// init the /tmp
 pub fn init_tmpfs(_root_dentry: Arc<dyn Dentry>) {
@@ -0,0 +1,156 @@
+//! vfs bind dentry
+//!
+//! a bind mount shows a subtree at another place. the bind dentry mirrors
+//! a source dentry: the inode and state are the source's, only the name and
+//! the parent are its own, so paths under the bind mount read right and
+//! changes made on either side are seen on both.
+
+use alloc::{collections::btree_map::BTreeMap, string::{String, ToString}, sync::{Arc, Weak}, vec::Vec};
+
+use crate::{fs::{inotify::InotifyMask, OpenFlags}, sync::mutex::SpinNoIrqLock, syscall::SysError};
+
+use super::{Dentry, DentryInner, DentryState, File, Inode};
+
+/// dentry mirroring `source` under another name and parent
+pub struct BindDentry {
+    /// own name, parent and the children mounted on top of the mirror
+    inner: DentryInner,
+    this: Weak<BindDentry>,
+    /// the dentry mirrored
+    source: Arc<dyn Dentry>,
+    /// mirrors of the children of the source, by name
+    binds: SpinNoIrqLock<BTreeMap<String, Arc<BindDentry>>>,
+}
+
+unsafe impl Send for BindDentry {}
+unsafe impl Sync for BindDentry {}
+
+fn same_dentry(a: &Arc<dyn Dentry>, b: &Arc<dyn Dentry>) -> bool {
+    Arc::as_ptr(a) as *const () == Arc::as_ptr(b) as *const ()
+}
+
+impl BindDentry {
+    pub fn new(name: &str, parent: Option<Arc<dyn Dentry>>, source: Arc<dyn Dentry>) -> Arc<Self> {
+        Arc::new_cyclic(|this| Self {
+            inner: DentryInner::new(name, parent),
+            this: this.clone(),
+            source,
+            binds: SpinNoIrqLock::new(BTreeMap::new()),
+        })
+    }
+
+    /// the mirror of the source child `child`
+    fn wrap(&self, child: Arc<dyn Dentry>) -> Arc<dyn Dentry> {
+        let mut binds = self.binds.lock();
+        if let Some(bind) = binds.get(child.name()) {
+            if same_dentry(&bind.source, &child) {
+                return bind.clone();
+            }
+        }
+        let this: Arc<dyn Dentry> = self.this.upgrade().unwrap();
+        let bind = BindDentry::new(child.name(), Some(this), child);
+        binds.insert(bind.name().to_string(), bind.clone());
+        bind
+    }
+
+    /// the mirror named `name` if it is `child`
+    fn find_bind(&self, name: &str, child: &Arc<dyn Dentry>) -> Option<Arc<BindDentry>> {
+        self.binds.lock().get(name)
+            .filter(|bind| same_dentry(&(Arc::clone(bind) as Arc<dyn Dentry>), child))
+            .cloned()
+    }
+}
+
+impl Dentry for BindDentry {
+    fn dentry_inner(&self) -> &DentryInner {
+        &self.inner
+    }
+
+    fn new(&self, name: &str, parent: Option<Arc<dyn Dentry>>) -> Arc<dyn Dentry> {
+        self.source.new(name, parent)
+    }
+
+    fn open(self: Arc<Self>, flags: OpenFlags) -> Option<Arc<dyn File>> {
+        self.source.clone().open(flags)
+    }
+
+    fn prepare_write(&self) -> Result<(), SysError> {
+        self.source.prepare_write()
+    }
+
+    fn inode(&self) -> Option<Arc<dyn Inode>> {
+        self.source.inode()
+    }
+
+    fn set_inode(&self, inode: Arc<dyn Inode>) {
+        self.source.set_inode(inode)
+    }
+
+    fn clear_inode(&self) {
+        self.source.clear_inode()
+    }
+
+    fn children(&self) -> BTreeMap<String, Arc<dyn Dentry>> {
+        let mut children: BTreeMap<String, Arc<dyn Dentry>> = self.source.children().into_values()
+            .map(|child| (child.name().to_string(), self.wrap(child)))
+            .collect();
+        // the mounts on top hide what is below
+        children.extend(self.inner.children.lock().clone());
+        children
+    }
+
+    fn get_child(&self, name: &str) -> Option<Arc<dyn Dentry>> {
+        if let Some(child) = self.inner.children.lock().get(name) {
+            return Some(child.clone());
+        }
+        self.source.get_child(name).map(|child| self.wrap(child))
+    }
+
+    fn add_child(&self, child: Arc<dyn Dentry>) {
+        match self.find_bind(child.name(), &child) {
+            // a mirror created on this side, e.g. by open with O_CREAT
+            Some(bind) => {
+                self.inner.children.lock().remove(child.name());
+                self.source.add_child(bind.source.clone());
+            }
+            // something mounted on top
+            None => {
+                self.inner.children.lock().insert(child.name().to_string(), child);
+            }
+        }
+    }
+
+    fn remove_child(&self, name: &str) {
+        if self.inner.children.lock().remove(name).is_some() {
+            return;
+        }
+        self.binds.lock().remove(name);
+        self.source.remove_child(name);
+    }
+
+    fn state(&self) -> DentryState {
+        self.source.state()
+    }
+
+    fn set_state(&self, state: DentryState) {
+        self.source.set_state(state)
+    }
+
+    fn notify(&self, mask: InotifyMask) {
+        self.source.notify(mask)
+    }
+
+    fn is_negative(&self) -> bool {
+        self.source.is_negative()
+    }
+
+    fn load_child_dentry(self: Arc<Self>) -> Result<Vec<Arc<dyn Dentry>>, SysError> {
+        self.source.clone().load_child_dentry()?;
+        Ok(self.children().into_values().filter(|c| !c.is_negative()).collect())
+    }
+
+    fn new_neg_dentry(self: Arc<Self>, name: &str) -> Result<Arc<dyn Dentry>, SysError> {
+        let neg_dentry = self.source.clone().new_neg_dentry(name)?;
+        Ok(self.wrap(neg_dentry))
+    }
+}
@@ -1,8 +1,8 @@
 //! virtual file system dentry
 
-use core::{default, mem::MaybeUninit};
+use core::{default, mem::MaybeUninit, ops::{Deref, DerefMut}};
 
-use crate::{fs::{vfs::{dentry, inode::InodeMode}, AtFlags, OpenFlags}, sync::mutex::SpinNoIrqLock, syscall::{at_helper1, SysError}, task::task::TaskControlBlock};
+use crate::{fs::{inotify::{fsnotify_dentry, InotifyMask}, vfs::{dentry, inode::InodeMode, mount::{current_mnt_ns, same_dentry, MntNamespace}}, AtFlags, OpenFlags}, processor::current_task, sync::mutex::{spin_mutex::MutexGuard, SpinNoIrq, SpinNoIrqLock}, syscall::{at_helper1, SysError}, task::task::TaskControlBlock};
 
 use super::{superblock, File, Inode, SuperBlock};
 
@@ -60,6 +60,10 @@ pub trait Dentry: Send + Sync {
     fn open(self: Arc<Self>, _flags: OpenFlags) -> Option<Arc<dyn File>> {
         todo!()
     }
+    /// get ready to be opened for writing, an overlay copies up here
+    fn prepare_write(&self) -> Result<(), SysError> {
+        Ok(())
+    }
     /// get the inode it points to
     fn inode(&self) -> Option<Arc<dyn Inode>> {
        self.dentry_inner().inode.lock().as_ref().map(Arc::clone)
@@ -111,6 +115,11 @@ pub trait Dentry: Send + Sync {
     fn set_state(&self, state: DentryState) {
         *self.dentry_inner().state.lock() = state;
     }
+    /// report an inotify event on the inode it points to
+    /// and to the watches on its parent
+    fn notify(&self, mask: InotifyMask) {
+        fsnotify_dentry(self, mask)
+    }
     /// determine if negative dentry
     fn is_negative(&self) -> bool {
         *self.dentry_inner().state.lock() == DentryState::NEGATIVE
@@ -158,13 +167,17 @@ impl dyn Dentry {
             return Ok(Some(current))
         }
         log::info!("path {}", path);
+        let root = current_task().map(|task| task.root());
         let normalize_path = {
             let mut compoents = Vec::new();
             for compoent in path.split("/") {
                 match compoent {
                     "" | "." => continue,
                     ".." => {
-                        current = current.parent().ok_or(SysError::ENOENT)?;
+                        // the task root has no parent for the task
+                        if !root.as_ref().map_or(false, |root| same_dentry(root, &current)) {
+                            current = current.parent().ok_or(SysError::ENOENT)?;
+                        }
                     }
                     name => {
                         compoents.push(name);
@@ -216,7 +229,11 @@ impl dyn Dentry {
         // use the vec to walk, loop
         // if the element exist, keeping walking
         // if not exist, stop.
-        for name in name_vec.iter() {
+        for (i, name) in name_vec.iter().enumerate() {
+            if i > 0 {
+                // a symlink in the middle of the path, like /proc/self/exe
+                current_dentry = current_dentry.follow_dir_link()?;
+            }
             if let Some(child_dentry) = current_dentry.get_child(name) {
                 // first look into self children field
                 // if find, just keep walking
@@ -249,6 +266,33 @@ impl dyn Dentry {
         return Ok(current_dentry.clone());
     }
 
+    /// follow a symlink which stands for a directory during the walk
+    /// relative targets are resolved from the dir holding the link
+    fn follow_dir_link(self: Arc<Self>) -> Result<Arc<dyn Dentry>, SysError> {
+        const MAX_LINK_DEPTH: usize = 40;
+        let mut current = self;
+        for _ in 0..MAX_LINK_DEPTH {
+            let inode = match current.inode() {
+                Some(inode) if inode.inode_type() == InodeMode::LINK => inode,
+                _ => return Ok(current),
+            };
+            let target = inode.readlink()?;
+            let base = if target.starts_with("/") {
+                match current_task() {
+                    Some(task) => task.root(),
+                    None => Arc::clone(DCACHE.lock().get("/").unwrap()),
+                }
+            } else {
+                current.parent().ok_or(SysError::ENOENT)?
+            };
+            current = base.walk(&target)?;
+            if current.is_negative() {
+                return Err(SysError::ENOENT);
+            }
+        }
+        Err(SysError::ELOOP)
+    }
+
     /// follow the link and jump until reach the first NOT link Inode or reach the max depth
     /// need to translate runtime
     pub fn follow(self: Arc<Self>, task: Arc<TaskControlBlock>, dirfd: isize, flags: AtFlags) -> Result<Arc<dyn Dentry>, SysError> {
@@ -301,8 +345,57 @@ pub enum DentryState {
 /// every used or negative dentry should be in cache
 /// the key is the absolute path of the dentry
 /// the value is the dentry
-pub static DCACHE: SpinNoIrqLock<BTreeMap<String, Arc<dyn Dentry>>> = 
-    SpinNoIrqLock::new(BTreeMap::new());
+/// each mount namespace has its own, this locks the one of the current task
+pub static DCACHE: DCache = DCache;
+
+/// the dcache of the current mount namespace
+pub struct DCache;
+
+impl DCache {
+    /// lock the dcache of the current mount namespace
+    pub fn lock(&self) -> DCacheGuard {
+        let ns = current_mnt_ns();
+        // the guard borrows from the namespace it keeps alive
+        let guard = unsafe { &*Arc::as_ptr(&ns) }.dcache.lock();
+        DCacheGuard { guard, _ns: ns }
+    }
+}
+
+/// a locked dcache, fields drop in order so the guard goes first
+pub struct DCacheGuard {
+    guard: MutexGuard<'static, BTreeMap<String, Arc<dyn Dentry>>, SpinNoIrq>,
+    _ns: Arc<MntNamespace>,
+}
+
+impl DCacheGuard {
+    /// insert a dentry, unless it is not in the tree of this namespace:
+    /// a file system shared by two namespaces loads its dentries for both
+    pub fn insert(&mut self, path: String, dentry: Arc<dyn Dentry>) -> Option<Arc<dyn Dentry>> {
+        if let Some(root) = self.guard.get("/") {
+            let mut top = dentry.clone();
+            while let Some(parent) = top.parent() {
+                top = parent;
+            }
+            if !same_dentry(&top, root) {
+                return None;
+            }
+        }
+        self.guard.insert(path, dentry)
+    }
+}
+
+impl Deref for DCacheGuard {
+    type Target = BTreeMap<String, Arc<dyn Dentry>>;
+    fn deref(&self) -> &Self::Target {
+        &self.guard
+    }
+}
+
+impl DerefMut for DCacheGuard {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.guard
+    }
+}
 
 
 /// helper function: Search from root using absolute path,
@@ -47,6 +47,9 @@ bitflags! {
         const HUP = 0x010;
         /// Invalid poll request.
         const INVAL = 0x020;
+        /// kernel only: keep the waker even when the file is ready,
+        /// so edge triggered epoll hears about the next event
+        const EDGE = 0x4000;
     }
 }
 
@@ -4,9 +4,9 @@ use alloc::{
     boxed::Box, collections::btree_map::BTreeMap, string::{String, ToString}, sync::Arc
 };
 
-use crate::{devices::BlockDevice, sync::mutex::SpinNoIrqLock};
+use crate::{devices::BlockDevice, sync::mutex::SpinNoIrqLock, syscall::SysError};
 
-use super::{Dentry, SuperBlock};
+use super::{mount::path_under, Dentry, SuperBlock, DCACHE};
 
 pub struct FSTypeInner {
     /// name of the file system type
@@ -22,6 +22,16 @@ impl FSTypeInner {
             supers: SpinNoIrqLock::new(BTreeMap::new()),
         }
     }
+    /// drop every super block with the dentries cached under it,
+    /// returns how many there were
+    pub fn kill_supers(&self) -> isize {
+        let supers = core::mem::take(&mut *self.supers.lock());
+        let mut dcache = DCACHE.lock();
+        for path in supers.keys() {
+            dcache.retain(|key, _| !path_under(key, path));
+        }
+        supers.len() as isize
+    }
 }
 
 pub trait FSType: Send + Sync {
@@ -29,12 +39,24 @@ pub trait FSType: Send + Sync {
     fn inner(&self) -> &FSTypeInner;
     /// mount a new instance of this file system
     fn mount(&'static self, name: &str, parent: Option<Arc<dyn Dentry>>, flags: MountFlags, dev: Option<Arc<dyn BlockDevice>>) -> Option<Arc<dyn Dentry>>;
+    /// mount with the option string given to mount(2), most take none
+    fn mount_with_options(&'static self, name: &str, parent: Option<Arc<dyn Dentry>>, flags: MountFlags, dev: Option<Arc<dyn BlockDevice>>, _options: &str) -> Result<Arc<dyn Dentry>, SysError> {
+        self.mount(name, parent, flags, dev).ok_or(SysError::EINVAL)
+    }
+    /// the flags of a new mount with `options`, a file system may force some
+    fn mount_flags(&self, flags: MountFlags, _options: &str) -> MountFlags {
+        flags
+    }
     /// shutdown a instance of this file system
     fn kill_sb(&self) -> isize;
     /// get the file system name
     fn name(&self) -> &str {
         &self.inner().name
     }
+    /// whether mounting needs a block device
+    fn requires_dev(&self) -> bool {
+        false
+    }
     /// use the mount path to get the super block
     fn get_sb(&self, abs_mount_path: &str) -> Option<Arc<dyn SuperBlock>> {
         self.inner()
@@ -110,6 +110,11 @@ pub trait Inode {
     fn cache_write_at(self: Arc<Self>, _offset: usize, _buf: &[u8]) -> Result<usize, i32> {
         todo!()
     }
+    /// bring the pages of [offset, offset + len) into the page cache ahead
+    /// of use, nothing to do if they are never read from elsewhere
+    fn readahead(self: Arc<Self>, _offset: usize, _len: usize) -> Result<(), i32> {
+        Ok(())
+    }
     /// create inode under current inode
     fn create(&self, _name: &str, _mode: InodeMode) -> Result<Arc<dyn Inode>, SysError> {
         todo!()
@@ -156,6 +161,31 @@ pub trait Inode {
     fn clean_cached(&self) {
         // do nothing
     }
+    /// write what the file system keeps of the inode in memory, like the
+    /// size, to the disk
+    fn sync_metadata(&self) -> Result<(), i32> {
+        Ok(())
+    }
+    /// write back the dirty pages, then the metadata unless `datasync`, then
+    /// the buffered blocks of the device. a write back error since the last
+    /// call is returned even if this one went well
+    fn fsync(&self, datasync: bool) -> Result<(), SysError> {
+        if let Some(cache) = self.cache() {
+            // only files with a backing store have something to write back
+            if let Some(host) = cache.host() {
+                let flushed = cache.flush(&*host);
+                cache.take_error().and(flushed).map_err(SysError::from_i32)?;
+            }
+        }
+        if !datasync {
+            self.sync_metadata().map_err(SysError::from_i32)?;
+        }
+        let sb = self.inode_inner().super_block.as_ref().and_then(Weak::upgrade);
+        if let Some(device) = sb.as_ref().and_then(|sb| sb.inner().device.as_ref()) {
+            device.sync();
+        }
+        Ok(())
+    }
 }
 
 static INODE_NUMBER: AtomicUsize = AtomicUsize::new(0);
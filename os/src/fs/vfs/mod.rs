@@ -7,6 +7,8 @@ pub mod inode;
 pub mod file;
 pub mod dentry;
 pub mod fstype;
+pub mod mount;
+pub mod bind;
 
 pub use superblock::{SuperBlockInner, SuperBlock};
 pub use inode::{InodeInner, Inode};
@@ -0,0 +1,636 @@
+//! vfs mount table
+//!
+//! a mount grafts the root dentry of a new file system instance onto the
+//! parent of the target dentry, the covered dentry is kept so umount can
+//! put it back.
+//!
+//! every mount namespace has its own mount table and dcache. a new namespace
+//! sees the tree of the old one through bind dentries, so mounts and umounts
+//! made inside it stay inside. like a slave mount, the mounts made later in
+//! the old namespace can still show through.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use alloc::{collections::btree_map::BTreeMap, format, string::{String, ToString}, sync::Arc, vec::Vec};
+use spin::Lazy;
+
+use crate::{devices::{BlockDevice, DeviceMajor, DEVICE_MANAGER}, fs::{sync_filesystem, FS_MANAGER}, processor::current_task, sync::mutex::SpinNoIrqLock, syscall::SysError, task::{manager::TASK_MANAGER, ns::alloc_ns_id}};
+
+use super::{bind::BindDentry, fstype::{FSType, MountFlags}, inode::InodeMode, Dentry, DentryState, File, SuperBlock, DCACHE};
+
+bitflags::bitflags! {
+    /// flags of umount2
+    pub struct UmountFlags: u32 {
+        /// force unmounting even if busy
+        const MNT_FORCE = 1;
+        /// lazy unmount, detach now and clean up when no longer busy
+        const MNT_DETACH = 2;
+        /// mark for expiry
+        const MNT_EXPIRE = 4;
+        /// don't follow symlink on umount
+        const UMOUNT_NOFOLLOW = 8;
+    }
+}
+
+/// the flags a mount can carry, the rest only tell mount(2) what to do
+const PER_MOUNT_FLAGS: MountFlags = MountFlags::MS_RDONLY
+    .union(MountFlags::MS_NOSUID)
+    .union(MountFlags::MS_NODEV)
+    .union(MountFlags::MS_NOEXEC)
+    .union(MountFlags::MS_SYNCHRONOUS)
+    .union(MountFlags::MS_NOATIME)
+    .union(MountFlags::MS_NODEIRATIME)
+    .union(MountFlags::MS_RELATIME);
+
+/// the propagation types of a mount
+const PROPAGATION_FLAGS: MountFlags = MountFlags::MS_SHARED
+    .union(MountFlags::MS_PRIVATE)
+    .union(MountFlags::MS_SLAVE)
+    .union(MountFlags::MS_UNBINDABLE);
+
+/// mount id allocator, ids are shown in /proc/<pid>/mountinfo
+static NEXT_MOUNT_ID: AtomicUsize = AtomicUsize::new(1);
+
+/// a mounted file system instance
+pub struct Mount {
+    /// mount id
+    pub id: usize,
+    /// device or name shown in /proc/mounts
+    pub source: String,
+    /// file system type name shown in /proc/mounts
+    pub fs_name: String,
+    /// file system type the instance comes from, None for a bind mount
+    fs_type: Option<Arc<dyn FSType>>,
+    /// the path mounted from within its file system, "/" unless a bind mount
+    pub bind_root: String,
+    /// root dentry of the instance
+    pub root: Arc<dyn Dentry>,
+    /// the dentry hidden by this mount, None for the ones made by the kernel
+    covered: Option<Arc<dyn Dentry>>,
+    /// keeps the super block alive while mounted
+    sb: Option<Arc<dyn SuperBlock>>,
+    /// mount flags
+    pub flags: SpinNoIrqLock<MountFlags>,
+    /// propagation type, only recorded: every mount acts as private
+    pub propagation: SpinNoIrqLock<MountFlags>,
+}
+
+impl Mount {
+    /// absolute path of the mount point
+    pub fn path(&self) -> String {
+        self.root.path()
+    }
+
+    /// the block device behind the instance
+    pub fn device(&self) -> Option<Arc<dyn BlockDevice>> {
+        self.sb.as_ref().and_then(|sb| sb.inner().device.clone())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        source: &str,
+        fs_name: &str,
+        fs_type: Option<Arc<dyn FSType>>,
+        bind_root: String,
+        root: Arc<dyn Dentry>,
+        covered: Option<Arc<dyn Dentry>>,
+        sb: Option<Arc<dyn SuperBlock>>,
+        flags: MountFlags,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            id: NEXT_MOUNT_ID.fetch_add(1, Ordering::Relaxed),
+            source: source.to_string(),
+            fs_name: fs_name.to_string(),
+            fs_type,
+            bind_root,
+            root,
+            covered,
+            sb,
+            flags: SpinNoIrqLock::new(flags & PER_MOUNT_FLAGS),
+            propagation: SpinNoIrqLock::new(MountFlags::MS_PRIVATE),
+        })
+    }
+
+    /// options column of /proc/mounts
+    pub fn options(&self) -> String {
+        let flags = *self.flags.lock();
+        let mut opts = String::from(if flags.contains(MountFlags::MS_RDONLY) { "ro" } else { "rw" });
+        for (flag, name) in [
+            (MountFlags::MS_NOSUID, "nosuid"),
+            (MountFlags::MS_NODEV, "nodev"),
+            (MountFlags::MS_NOEXEC, "noexec"),
+            (MountFlags::MS_SYNCHRONOUS, "sync"),
+            (MountFlags::MS_NOATIME, "noatime"),
+        ] {
+            if flags.contains(flag) {
+                opts += ",";
+                opts += name;
+            }
+        }
+        opts += ",relatime";
+        opts
+    }
+}
+
+/// a mount namespace: one view of the file system tree
+pub struct MntNamespace {
+    /// namespace id
+    pub id: usize,
+    /// dentry cache of the tree, the key is the absolute path
+    pub dcache: SpinNoIrqLock<BTreeMap<String, Arc<dyn Dentry>>>,
+    /// all mounts, in the order they were made
+    mounts: SpinNoIrqLock<Vec<Arc<Mount>>>,
+}
+
+impl MntNamespace {
+    fn new(root: Option<Arc<dyn Dentry>>, mounts: Vec<Arc<Mount>>) -> Arc<Self> {
+        let mut dcache = BTreeMap::new();
+        if let Some(root) = root {
+            dcache.insert("/".to_string(), root);
+        }
+        Arc::new(Self {
+            id: alloc_ns_id(),
+            dcache: SpinNoIrqLock::new(dcache),
+            mounts: SpinNoIrqLock::new(mounts),
+        })
+    }
+
+    /// root dentry of the tree
+    pub fn root(&self) -> Arc<dyn Dentry> {
+        self.dcache.lock().get("/").unwrap().clone()
+    }
+
+    /// a new namespace with a copy of the mount table
+    pub fn copy(&self) -> Arc<MntNamespace> {
+        let tree: Arc<dyn Dentry> = BindDentry::new("/", None, self.root());
+        let mounts = self.mounts.lock().clone();
+        let mounts = mirror_mounts(&tree, &mounts, |path| path.to_string(), None);
+        MntNamespace::new(Some(tree), mounts)
+    }
+
+    /// the dentry at the path of `dentry` in this tree, the root if it is gone
+    pub fn translate(&self, dentry: &Arc<dyn Dentry>) -> Arc<dyn Dentry> {
+        lookup_in(&self.root(), &dentry.path()).unwrap_or_else(|| self.root())
+    }
+}
+
+/// the namespace the kernel and init live in
+pub static INIT_MNT_NS: Lazy<Arc<MntNamespace>> = Lazy::new(|| MntNamespace::new(None, Vec::new()));
+
+/// the mount namespace of the current task, the init one in kernel context
+pub fn current_mnt_ns() -> Arc<MntNamespace> {
+    match current_task() {
+        Some(task) => task.mnt_ns(),
+        None => INIT_MNT_NS.clone(),
+    }
+}
+
+/// the live dentry at `path` under `tree`
+fn lookup_in(tree: &Arc<dyn Dentry>, path: &str) -> Option<Arc<dyn Dentry>> {
+    tree.clone().walk(path).ok().filter(|d| !d.is_negative())
+}
+
+/// the mounts of `mounts` as seen in `tree`, their paths moved by `map`,
+/// `old_root_covered` is what the old root mount hides when it moved
+fn mirror_mounts(
+    tree: &Arc<dyn Dentry>,
+    mounts: &[Arc<Mount>],
+    map: impl Fn(&str) -> String,
+    old_root_covered: Option<Arc<dyn Dentry>>,
+) -> Vec<Arc<Mount>> {
+    mounts.iter().filter_map(|m| {
+        // not reachable from the tree any more
+        let root = lookup_in(tree, &map(&m.path()))?;
+        let covered = match m.root.parent() {
+            Some(_) => m.covered.clone(),
+            None => old_root_covered.clone(),
+        };
+        let covered = root.parent().map(|parent| match covered {
+            Some(covered) => BindDentry::new(root.name(), Some(parent), covered) as Arc<dyn Dentry>,
+            // mounted by the kernel, umount leaves nothing there
+            None => {
+                let neg = parent.new(root.name(), Some(parent.clone()));
+                neg.set_state(DentryState::NEGATIVE);
+                neg
+            }
+        });
+        let mount = Mount::new(&m.source, &m.fs_name, None, m.bind_root.clone(), root, covered, m.sb.clone(), *m.flags.lock());
+        *mount.propagation.lock() = *m.propagation.lock();
+        Some(mount)
+    }).collect()
+}
+
+/// lazily detached mounts still in use, their super blocks must outlive the open files
+static DETACHED: SpinNoIrqLock<Vec<Arc<Mount>>> = SpinNoIrqLock::new(Vec::new());
+
+/// all mounts, in the order they were made
+pub fn mounts() -> Vec<Arc<Mount>> {
+    current_mnt_ns().mounts.lock().clone()
+}
+
+/// the topmost mount whose root is `dentry`
+pub fn find_mount(dentry: &Arc<dyn Dentry>) -> Option<Arc<Mount>> {
+    current_mnt_ns().mounts.lock().iter().rev()
+        .find(|m| same_dentry(&m.root, dentry))
+        .cloned()
+}
+
+pub fn same_dentry(a: &Arc<dyn Dentry>, b: &Arc<dyn Dentry>) -> bool {
+    Arc::as_ptr(a) as *const () == Arc::as_ptr(b) as *const ()
+}
+
+/// whether `path` is `dir` or lies under it
+pub fn path_under(path: &str, dir: &str) -> bool {
+    dir == "/" || path == dir || path.strip_prefix(dir).map_or(false, |rest| rest.starts_with('/'))
+}
+
+/// drop `path` and everything under it from the dcache
+fn purge_dcache(path: &str) {
+    DCACHE.lock().retain(|key, _| !path_under(key, path));
+}
+
+/// record a mount made by the kernel itself, e.g. the ones in `fs::init`
+pub fn add_mount(source: &str, fs_name: &str, fs_type: &'static Arc<dyn FSType>, root: Arc<dyn Dentry>, flags: MountFlags) {
+    let sb = fs_type.get_sb(&root.path());
+    current_mnt_ns().mounts.lock().push(Mount::new(source, fs_name, Some(fs_type.clone()), "/".to_string(), root, None, sb, flags));
+}
+
+/// the registered file system type named `name`, with the usual aliases
+fn lookup_fs(name: &str) -> Option<Arc<dyn FSType>> {
+    let name = match name {
+        "ext2" | "ext3" => "ext4",
+        "vfat" | "msdos" | "fat" => "fat32",
+        name => name,
+    };
+    FS_MANAGER.lock().get(name).cloned()
+}
+
+/// mount a new instance of `fs_type`
+fn mount_fs(fs_type: &Arc<dyn FSType>, name: &str, parent: Arc<dyn Dentry>, flags: MountFlags, dev: Option<Arc<dyn BlockDevice>>, options: &str) -> Result<Arc<dyn Dentry>, SysError> {
+    // registered types stay in FS_MANAGER for good
+    let this: &'static dyn FSType = unsafe { &*Arc::as_ptr(fs_type) };
+    // `FSType::mount` takes this count over with `Arc::from_raw` and drops
+    // it on the way out, the super block keeps a clone of its own
+    core::mem::forget(fs_type.clone());
+    this.mount_with_options(name, Some(parent), flags, dev, options)
+}
+
+/// the block device named by `source`, e.g. /dev/sda2
+pub fn lookup_blk(source: &str) -> Result<Arc<dyn BlockDevice>, SysError> {
+    if source.is_empty() {
+        return Err(SysError::ENOENT);
+    }
+    let name = source.strip_prefix("/dev/").unwrap_or(source);
+    let dev = DEVICE_MANAGER.lock().devices.iter()
+        .find(|(id, dev)| id.major == DeviceMajor::Block && dev.meta().name == name)
+        .map(|(_, dev)| dev.clone())
+        .ok_or(SysError::ENOTBLK)?;
+    dev.as_blk().ok_or(SysError::ENOTBLK)
+}
+
+/// mount a new instance of `fs_name` from `source` on `target`
+pub fn do_mount(source: &str, target: Arc<dyn Dentry>, fs_name: &str, flags: MountFlags, options: &str) -> Result<(), SysError> {
+    if target.is_negative() {
+        return Err(SysError::ENOENT);
+    }
+    if target.inode().unwrap().inode_type() != InodeMode::DIR {
+        return Err(SysError::ENOTDIR);
+    }
+    let parent = target.parent().ok_or(SysError::EBUSY)?;
+    reap_detached();
+    let fs_type = lookup_fs(fs_name).ok_or(SysError::ENODEV)?;
+    let flags = fs_type.mount_flags(flags, options);
+    let dev = if fs_type.requires_dev() {
+        let dev = lookup_blk(source)?;
+        let in_use = current_mnt_ns().mounts.lock().iter()
+            .filter_map(|m| m.device())
+            .any(|d| Arc::as_ptr(&d) as *const () == Arc::as_ptr(&dev) as *const ());
+        if in_use {
+            return Err(SysError::EBUSY);
+        }
+        Some(dev)
+    } else {
+        None
+    };
+
+    let path = target.path();
+    let root = mount_fs(&fs_type, target.name(), parent.clone(), flags, dev, options)?;
+    let sb = fs_type.get_sb(&path);
+    parent.add_child(root.clone());
+    purge_dcache(&path);
+    DCACHE.lock().insert(path, root.clone());
+
+    let source = if source.is_empty() { fs_type.name() } else { source };
+    current_mnt_ns().mounts.lock().push(Mount::new(source, fs_type.name(), Some(fs_type.clone()), "/".to_string(), root, Some(target), sb, flags));
+    Ok(())
+}
+
+/// show the subtree at `source` on `target` as well
+pub fn do_bind(source: Arc<dyn Dentry>, target: Arc<dyn Dentry>, flags: MountFlags) -> Result<(), SysError> {
+    if source.is_negative() || target.is_negative() {
+        return Err(SysError::ENOENT);
+    }
+    let source_is_dir = source.inode().unwrap().inode_type() == InodeMode::DIR;
+    let target_is_dir = target.inode().unwrap().inode_type() == InodeMode::DIR;
+    if source_is_dir != target_is_dir {
+        return Err(SysError::ENOTDIR);
+    }
+    if mount_of(&source).map_or(false, |m| m.propagation.lock().contains(MountFlags::MS_UNBINDABLE)) {
+        return Err(SysError::EINVAL);
+    }
+    let parent = target.parent().ok_or(SysError::EBUSY)?;
+    reap_detached();
+    let from = mount_of(&source);
+    let fs_name = from.as_ref().map_or("none".to_string(), |m| m.fs_name.clone());
+    let mount_source = from.as_ref().map_or("none".to_string(), |m| m.source.clone());
+    // mountinfo shows the root of a bind mount within its file system
+    let bind_root = match from.as_ref().map(|m| m.path()) {
+        Some(from_path) if from_path != "/" => {
+            let rest = source.path()[from_path.len()..].to_string();
+            if rest.is_empty() { "/".to_string() } else { rest }
+        }
+        _ => source.path(),
+    };
+    // keep the super block alive even if the mount bound from goes away
+    let sb = source.inode().unwrap().inode_inner().super_block.as_ref().and_then(|sb| sb.upgrade());
+
+    let path = target.path();
+    let root: Arc<dyn Dentry> = BindDentry::new(target.name(), Some(parent.clone()), source.clone());
+    parent.add_child(root.clone());
+    purge_dcache(&path);
+    DCACHE.lock().insert(path, root.clone());
+    current_mnt_ns().mounts.lock().push(Mount::new(&mount_source, &fs_name, None, bind_root, root, Some(target), sb, flags));
+    Ok(())
+}
+
+/// change the flags of the mount on `target`
+pub fn do_remount(target: &Arc<dyn Dentry>, flags: MountFlags) -> Result<(), SysError> {
+    let mount = find_mount(target).ok_or(SysError::EINVAL)?;
+    let to_ro = flags.contains(MountFlags::MS_RDONLY)
+        && !mount.flags.lock().contains(MountFlags::MS_RDONLY);
+    if to_ro && has_writers(&mount) {
+        return Err(SysError::EBUSY);
+    }
+    *mount.flags.lock() = flags & PER_MOUNT_FLAGS;
+    Ok(())
+}
+
+/// whether some task holds a file on `mount` open for writing
+fn has_writers(mount: &Arc<Mount>) -> bool {
+    TASK_MANAGER.tasks_group().iter().any(|task| {
+        task.with_fd_table(|table| {
+            table.fd_table.iter().flatten().any(|fd| {
+                fd.file.flags().writable()
+                    && fd.file.dentry().and_then(|d| mount_of(&d)).map_or(false, |m| Arc::ptr_eq(&m, mount))
+            })
+        })
+    })
+}
+
+/// change the propagation type of the mount on `target`,
+/// and of the mounts under it with MS_REC
+pub fn set_propagation(target: &Arc<dyn Dentry>, flags: MountFlags) -> Result<(), SysError> {
+    let propagation = flags & PROPAGATION_FLAGS;
+    if propagation.bits().count_ones() != 1 {
+        return Err(SysError::EINVAL);
+    }
+    let mount = find_mount(target).ok_or(SysError::EINVAL)?;
+    let path = mount.path();
+    for m in mounts() {
+        if Arc::ptr_eq(&m, &mount) || (flags.contains(MountFlags::MS_REC) && path_under(&m.path(), &path)) {
+            *m.propagation.lock() = propagation;
+        }
+    }
+    Ok(())
+}
+
+/// the mount `dentry` lies on
+/// anonymous dentries (pipes, sockets, memfds) are not under any mount
+pub fn mount_of(dentry: &Arc<dyn Dentry>) -> Option<Arc<Mount>> {
+    let mut top = dentry.clone();
+    while let Some(parent) = top.parent() {
+        top = parent;
+    }
+    let ns = current_mnt_ns();
+    let mounts = ns.mounts.lock();
+    if !mounts.iter().any(|m| same_dentry(&m.root, &top)) {
+        return None;
+    }
+    let path = dentry.path();
+    // the deepest mount point wins, then the latest one on it
+    mounts.iter()
+        .filter(|m| path_under(&path, &m.path()))
+        .fold(None, |best: Option<&Arc<Mount>>, m| match best {
+            Some(b) if b.path().len() > m.path().len() => Some(b),
+            _ => Some(m),
+        })
+        .cloned()
+}
+
+/// whether some mount carries `flag`, to skip the lookup in the common case
+fn any_mount_has(flag: MountFlags) -> bool {
+    current_mnt_ns().mounts.lock().iter().any(|m| m.flags.lock().contains(flag))
+}
+
+/// EROFS if `dentry` lies on a read-only mount
+pub fn check_writable(dentry: &Arc<dyn Dentry>) -> Result<(), SysError> {
+    if !any_mount_has(MountFlags::MS_RDONLY) {
+        return Ok(());
+    }
+    match mount_of(dentry) {
+        Some(m) if m.flags.lock().contains(MountFlags::MS_RDONLY) => Err(SysError::EROFS),
+        _ => Ok(()),
+    }
+}
+
+/// EROFS if `file` lies on a read-only mount
+pub fn check_file_writable(file: &Arc<dyn File>) -> Result<(), SysError> {
+    match file.dentry() {
+        Some(dentry) => check_writable(&dentry),
+        None => Ok(()),
+    }
+}
+
+/// EACCES if `dentry` lies on a noexec mount
+pub fn check_exec(dentry: &Arc<dyn Dentry>) -> Result<(), SysError> {
+    if !any_mount_has(MountFlags::MS_NOEXEC) {
+        return Ok(());
+    }
+    match mount_of(dentry) {
+        Some(m) if m.flags.lock().contains(MountFlags::MS_NOEXEC) => Err(SysError::EACCES),
+        _ => Ok(()),
+    }
+}
+
+/// whether some task has a file, its cwd or its executable under `path`
+fn is_busy(path: &str) -> bool {
+    TASK_MANAGER.tasks_group().iter().any(|task| {
+        let under = |d: Option<Arc<dyn Dentry>>| d.map_or(false, |d| path_under(&d.path(), path));
+        under(Some(task.cwd()))
+            || under(task.elf.lock().as_ref().and_then(|f| f.dentry()))
+            || task.with_fd_table(|table| {
+                table.fd_table.iter().flatten().any(|fd| under(fd.file.dentry()))
+            })
+    })
+}
+
+/// unmount the file system mounted on `target`
+pub fn do_umount(target: Arc<dyn Dentry>, flags: UmountFlags) -> Result<(), SysError> {
+    if flags.contains(UmountFlags::MNT_EXPIRE)
+        && flags.intersects(UmountFlags::MNT_FORCE | UmountFlags::MNT_DETACH) {
+        return Err(SysError::EINVAL);
+    }
+    reap_detached();
+    let mount = find_mount(&target).ok_or(SysError::EINVAL)?;
+    if mount.root.parent().is_none() {
+        return Err(SysError::EBUSY);
+    }
+    let path = mount.path();
+    // there is nothing in flight to abort, MNT_FORCE is a plain umount
+    let lazy = flags.contains(UmountFlags::MNT_DETACH);
+    let children: Vec<Arc<Mount>> = mounts().into_iter()
+        .filter(|m| m.path() != path && path_under(&m.path(), &path))
+        .collect();
+    if !lazy && (!children.is_empty() || is_busy(&path)) {
+        return Err(SysError::EBUSY);
+    }
+    if flags.contains(UmountFlags::MNT_EXPIRE) {
+        // we never expire a mount by ourselves, the caller retries
+        return Err(SysError::EAGAIN);
+    }
+    // what is cached for the tree goes to the disk before it is cut off
+    for m in children.iter().chain(core::iter::once(&mount)) {
+        if let Some(sb) = m.sb.as_ref() {
+            if let Err(e) = sync_filesystem(sb) {
+                log::warn!("[do_umount] sync {} failed: {:?}", m.path(), e);
+            }
+        }
+    }
+    // the open files keep the detached tree alive until they are closed
+    for child in children.iter().rev() {
+        detach(child);
+    }
+    detach(&mount);
+    Ok(())
+}
+
+fn detach(mount: &Arc<Mount>) {
+    let path = mount.path();
+    let parent = mount.root.parent().unwrap();
+    purge_dcache(&path);
+    match mount.covered.clone() {
+        Some(covered) => {
+            parent.add_child(covered.clone());
+            DCACHE.lock().insert(path.clone(), covered);
+        }
+        // mounted by the kernel, the next lookup finds what is on the parent fs
+        None => parent.remove_child(mount.root.name()),
+    }
+    if let (Some(sb), Some(fs_type)) = (mount.sb.as_ref(), mount.fs_type.as_ref()) {
+        let mut supers = fs_type.inner().supers.lock();
+        if supers.get(&path).map_or(false, |s| Arc::ptr_eq(s, sb)) {
+            supers.remove(&path);
+        }
+    }
+    current_mnt_ns().mounts.lock().retain(|m| !Arc::ptr_eq(m, mount));
+    if is_busy(&path) {
+        DETACHED.lock().push(mount.clone());
+    }
+    log::info!("[FS] umount {} from {}", mount.source, path);
+}
+
+/// release the detached mounts nobody uses any more
+fn reap_detached() {
+    let detached: Vec<Arc<Mount>> = core::mem::take(&mut *DETACHED.lock());
+    let still_busy = detached.into_iter().filter(|m| is_busy(&m.path())).collect();
+    *DETACHED.lock() = still_busy;
+}
+
+/// make the mount on `new_root` the root of the namespace and hang the old
+/// root on `put_old`, which lies under `new_root`
+pub fn do_pivot_root(new_root: Arc<dyn Dentry>, put_old: Arc<dyn Dentry>) -> Result<(), SysError> {
+    if new_root.is_negative() || put_old.is_negative() {
+        return Err(SysError::ENOENT);
+    }
+    if new_root.inode().unwrap().inode_type() != InodeMode::DIR
+        || put_old.inode().unwrap().inode_type() != InodeMode::DIR {
+        return Err(SysError::ENOTDIR);
+    }
+    let ns = current_mnt_ns();
+    let old_root = ns.root();
+    if same_dentry(&new_root, &old_root) {
+        return Err(SysError::EBUSY);
+    }
+    find_mount(&new_root).ok_or(SysError::EINVAL)?;
+    let new_path = new_root.path();
+    let old_path = put_old.path();
+    // stacking the old root on the new one is not supported
+    if old_path == new_path || !path_under(&old_path, &new_path) {
+        return Err(SysError::EINVAL);
+    }
+    let put_old_rel = old_path[new_path.len()..].to_string();
+
+    let tree: Arc<dyn Dentry> = BindDentry::new("/", None, new_root.clone());
+    let old_parent = lookup_in(&tree, &put_old_rel[..put_old_rel.rfind('/').unwrap()]).ok_or(SysError::ENOENT)?;
+    old_parent.add_child(BindDentry::new(put_old.name(), Some(old_parent.clone()), old_root));
+
+    let map = |path: &str| -> String {
+        if path_under(path, &new_path) {
+            let rest = &path[new_path.len()..];
+            if rest.is_empty() { "/".to_string() } else { rest.to_string() }
+        } else if path == "/" {
+            put_old_rel.clone()
+        } else {
+            put_old_rel.clone() + path
+        }
+    };
+    let mounts = ns.mounts.lock().clone();
+    let mounts = mirror_mounts(&tree, &mounts, map, Some(put_old.clone()));
+    *ns.mounts.lock() = mounts;
+    {
+        let mut dcache = ns.dcache.lock();
+        dcache.clear();
+        dcache.insert("/".to_string(), tree.clone());
+    }
+    // move the tasks of the namespace over, their paths moved the same way
+    for task in TASK_MANAGER.tasks_group() {
+        if !Arc::ptr_eq(&task.mnt_ns(), &ns) {
+            continue;
+        }
+        let moved = |d: Arc<dyn Dentry>| lookup_in(&tree, &map(&d.path())).unwrap_or_else(|| tree.clone());
+        task.set_root(moved(task.root()));
+        task.set_cwd(moved(task.cwd()));
+    }
+    log::info!("[FS] pivot root to {}, old root on {}", new_path, old_path);
+    Ok(())
+}
+
+/// content of /proc/mounts
+pub fn list_mounts() -> String {
+    mounts().iter()
+        .map(|m| format!("{} {} {} {} 0 0\n", m.source, m.path(), m.fs_name, m.options()))
+        .collect()
+}
+
+/// content of /proc/<pid>/mountinfo
+pub fn list_mountinfo() -> String {
+    let mut res = String::new();
+    for m in mounts() {
+        let parent_id = m.root.parent()
+            .and_then(|p| mount_of(&p))
+            .map_or(m.id, |p| p.id);
+        let propagation = match *m.propagation.lock() {
+            p if p.contains(MountFlags::MS_SHARED) => format!(" shared:{}", m.id),
+            p if p.contains(MountFlags::MS_SLAVE) => format!(" master:{}", m.id),
+            p if p.contains(MountFlags::MS_UNBINDABLE) => " unbindable".to_string(),
+            _ => String::new(),
+        };
+        let options = m.options();
+        res += &format!(
+            "{} {} 0:{} {} {} {}{} - {} {} {}\n",
+            m.id, parent_id, m.id, m.bind_root, m.path(), options, propagation,
+            m.fs_name, m.source, options.split(',').next().unwrap(),
+        );
+    }
+    res
+}
@@ -7,6 +7,7 @@ use spin::Once;
 
 use crate::devices::BlockDevice;
 use crate::fs::vfs::Inode;
+use crate::syscall::SysError;
 
 use super::fstype::FSType;
 use super::Dentry;
@@ -42,6 +43,14 @@ pub trait SuperBlock: Send + Sync {
     }
     /// get root dir inode (will only use construct)
     fn get_root_inode(&'static self, name: &str) -> Arc<dyn Inode>;
+    /// write what the file system keeps in memory to the disk, the dirty
+    /// pages of its files are written back before
+    fn sync_fs(&self) -> Result<(), SysError> {
+        if let Some(device) = self.inner().device.as_ref() {
+            device.sync();
+        }
+        Ok(())
+    }
 }
 
 impl dyn SuperBlock {
@@ -1,10 +1,49 @@
 mod shm;
+mod msg;
+mod sem;
 
-pub use shm::{ShmObj, SHM_MANAGER, ShmIdDs};
+use alloc::sync::Arc;
+use lazy_static::lazy_static;
+
+use crate::task::{current_task, ns::alloc_ns_id};
+
+pub use shm::{ShmObj, ShmManager, ShmIdDs};
+pub use msg::{MsgQueue, MsgQueueManager, MsgRecvFuture, MsgSendFuture, MsqIdDs, MsgInfo, MSGMAX};
+pub use sem::{SemBuf, SemIdDs, SemInfo, SemManager, SemOpFuture, SemSet, SEMMSL, SEMOPM};
+
+/// an ipc namespace, the System V objects seen by the tasks in it
+pub struct IpcNamespace {
+    pub id: usize,
+    pub shm: ShmManager,
+    pub msg: MsgQueueManager,
+    pub sem: SemManager,
+}
+
+impl IpcNamespace {
+    /// a new empty namespace
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self {
+            id: alloc_ns_id(),
+            shm: ShmManager::new(),
+            msg: MsgQueueManager::new(),
+            sem: SemManager::new(),
+        })
+    }
+}
+
+lazy_static! {
+    pub static ref INIT_IPC_NS: Arc<IpcNamespace> = IpcNamespace::new();
+}
+
+/// ipc namespace of the current task
+pub fn current_ipc_ns() -> Arc<IpcNamespace> {
+    current_task().map_or_else(|| INIT_IPC_NS.clone(), |task| task.ipc_ns())
+}
 
 /// ipc private
 pub const IPC_PRIVATE: i32 = 0;
 
+/// `struct ipc64_perm`
 #[repr(C)]
 #[derive(Default, Clone, Copy, Debug)]
 pub struct IpcPerm {
@@ -13,6 +52,24 @@ pub struct IpcPerm {
     gid: u32,
     cuid: u32,
     cgid: u32,
-    mode: u16,
+    mode: u32,
     seq: u16,
+    _pad: u16,
+    _unused1: usize,
+    _unused2: usize,
+}
+
+impl IpcPerm {
+    /// permission of a new ipc object created by `uid`/`gid`
+    pub fn new(key: i32, uid: u32, gid: u32, mode: u32) -> Self {
+        Self {
+            key,
+            uid,
+            gid,
+            cuid: uid,
+            cgid: gid,
+            mode: mode & 0o777,
+            ..Default::default()
+        }
+    }
 }
@@ -0,0 +1,332 @@
+use core::{future::Future, pin::Pin, task::{Context, Poll, Waker}};
+
+use alloc::{collections::{btree_map::BTreeMap, vec_deque::VecDeque}, sync::Arc, vec::Vec};
+
+use crate::{sync::mutex::SpinNoIrqLock, syscall::SysError, timer::get_current_time_sec};
+
+use super::{shm::ShmIdAllocator, IpcPerm, IPC_PRIVATE};
+
+/// max size of a message
+pub const MSGMAX: usize = 8192;
+/// default max bytes in a queue
+pub const MSGMNB: usize = 16384;
+/// max number of queues
+pub const MSGMNI: usize = 32000;
+
+/// `struct msqid64_ds`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MsqIdDs {
+    /// ownership and permissions
+    pub perm: IpcPerm,
+    /// time of last msgsnd
+    pub stime: usize,
+    /// time of last msgrcv
+    pub rtime: usize,
+    /// time of creation or last msgctl IPC_SET
+    pub ctime: usize,
+    /// bytes in queue
+    pub cbytes: usize,
+    /// number of messages in queue
+    pub qnum: usize,
+    /// max bytes allowed in queue
+    pub qbytes: usize,
+    /// pid of last msgsnd
+    pub lspid: i32,
+    /// pid of last msgrcv
+    pub lrpid: i32,
+    _unused4: usize,
+    _unused5: usize,
+}
+
+/// `struct msginfo`, for IPC_INFO and MSG_INFO
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MsgInfo {
+    pub msgpool: i32,
+    pub msgmap: i32,
+    pub msgmax: i32,
+    pub msgmnb: i32,
+    pub msgmni: i32,
+    pub msgssz: i32,
+    pub msgtql: i32,
+    pub msgseg: u16,
+}
+
+struct MsgQueueInner {
+    ds: MsqIdDs,
+    /// queued messages: (mtype, text)
+    msgs: VecDeque<(isize, Vec<u8>)>,
+    send_wakers: VecDeque<Waker>,
+    recv_wakers: VecDeque<Waker>,
+    removed: bool,
+}
+
+impl MsgQueueInner {
+    fn wake_all(&mut self) {
+        while let Some(waker) = self.send_wakers.pop_front() {
+            waker.wake();
+        }
+        while let Some(waker) = self.recv_wakers.pop_front() {
+            waker.wake();
+        }
+    }
+
+    /// index of the message selected by `msgtyp`
+    fn select(&self, msgtyp: isize, except: bool) -> Option<usize> {
+        if msgtyp == 0 {
+            return if self.msgs.is_empty() { None } else { Some(0) };
+        }
+        if msgtyp > 0 {
+            return self.msgs.iter().position(|(mtype, _)| (*mtype == msgtyp) != except);
+        }
+        // the first message with the lowest type not above |msgtyp|
+        let bound = msgtyp.unsigned_abs() as isize;
+        let mut res: Option<(usize, isize)> = None;
+        for (i, (mtype, _)) in self.msgs.iter().enumerate() {
+            if *mtype <= bound && res.map_or(true, |(_, lowest)| *mtype < lowest) {
+                res = Some((i, *mtype));
+            }
+        }
+        res.map(|(i, _)| i)
+    }
+}
+
+/// System V message queue
+pub struct MsgQueue {
+    id: usize,
+    inner: SpinNoIrqLock<MsgQueueInner>,
+}
+
+impl MsgQueue {
+    fn new(id: usize, perm: IpcPerm) -> Self {
+        Self {
+            id,
+            inner: SpinNoIrqLock::new(MsgQueueInner {
+                ds: MsqIdDs {
+                    perm,
+                    ctime: get_current_time_sec(),
+                    qbytes: MSGMNB,
+                    ..Default::default()
+                },
+                msgs: VecDeque::new(),
+                send_wakers: VecDeque::new(),
+                recv_wakers: VecDeque::new(),
+                removed: false,
+            }),
+        }
+    }
+
+    /// get id
+    pub fn get_id(&self) -> usize {
+        self.id
+    }
+
+    /// IPC_STAT
+    pub fn stat(&self) -> MsqIdDs {
+        self.inner.lock().ds
+    }
+
+    /// IPC_SET: update owner, mode and queue size limit
+    pub fn set(&self, new: &MsqIdDs, privileged: bool) -> Result<(), SysError> {
+        let mut inner = self.inner.lock();
+        if new.qbytes > MSGMNB && new.qbytes > inner.ds.qbytes && !privileged {
+            return Err(SysError::EPERM);
+        }
+        inner.ds.perm.uid = new.perm.uid;
+        inner.ds.perm.gid = new.perm.gid;
+        inner.ds.perm.mode = (inner.ds.perm.mode & !0o777) | (new.perm.mode & 0o777);
+        inner.ds.qbytes = new.qbytes;
+        inner.ds.ctime = get_current_time_sec();
+        // a bigger queue may let blocked senders through
+        inner.wake_all();
+        Ok(())
+    }
+
+    /// owner and creator uid, for permission checks
+    pub fn owners(&self) -> (u32, u32) {
+        let inner = self.inner.lock();
+        (inner.ds.perm.uid, inner.ds.perm.cuid)
+    }
+
+    fn mark_removed(&self) {
+        let mut inner = self.inner.lock();
+        inner.removed = true;
+        inner.msgs.clear();
+        inner.ds.cbytes = 0;
+        inner.ds.qnum = 0;
+        inner.wake_all();
+    }
+}
+
+/// future for msgsnd, waits until the queue has room
+pub struct MsgSendFuture {
+    queue: Arc<MsgQueue>,
+    msg: Option<(isize, Vec<u8>)>,
+    pid: i32,
+    nowait: bool,
+}
+
+impl MsgSendFuture {
+    pub fn new(queue: Arc<MsgQueue>, mtype: isize, text: Vec<u8>, pid: i32, nowait: bool) -> Self {
+        Self { queue, msg: Some((mtype, text)), pid, nowait }
+    }
+}
+
+impl Future for MsgSendFuture {
+    type Output = Result<(), SysError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = unsafe { self.get_unchecked_mut() };
+        let mut inner = this.queue.inner.lock();
+        if inner.removed {
+            return Poll::Ready(Err(SysError::EIDRM));
+        }
+        let len = this.msg.as_ref().unwrap().1.len();
+        // like linux, the message count is limited by qbytes as well
+        if inner.ds.cbytes + len > inner.ds.qbytes || inner.ds.qnum + 1 > inner.ds.qbytes {
+            if this.nowait {
+                return Poll::Ready(Err(SysError::EAGAIN));
+            }
+            inner.send_wakers.push_back(cx.waker().clone());
+            return Poll::Pending;
+        }
+        inner.msgs.push_back(this.msg.take().unwrap());
+        inner.ds.cbytes += len;
+        inner.ds.qnum += 1;
+        inner.ds.lspid = this.pid;
+        inner.ds.stime = get_current_time_sec();
+        while let Some(waker) = inner.recv_wakers.pop_front() {
+            waker.wake();
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// future for msgrcv, waits until a matching message arrives
+pub struct MsgRecvFuture {
+    queue: Arc<MsgQueue>,
+    max_len: usize,
+    msgtyp: isize,
+    except: bool,
+    noerror: bool,
+    pid: i32,
+    nowait: bool,
+}
+
+impl MsgRecvFuture {
+    pub fn new(queue: Arc<MsgQueue>, max_len: usize, msgtyp: isize, except: bool, noerror: bool, pid: i32, nowait: bool) -> Self {
+        Self { queue, max_len, msgtyp, except, noerror, pid, nowait }
+    }
+}
+
+impl Future for MsgRecvFuture {
+    /// (mtype, text), the text is already truncated to `max_len`
+    type Output = Result<(isize, Vec<u8>), SysError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = unsafe { self.get_unchecked_mut() };
+        let mut inner = this.queue.inner.lock();
+        if inner.removed {
+            return Poll::Ready(Err(SysError::EIDRM));
+        }
+        let Some(idx) = inner.select(this.msgtyp, this.except) else {
+            if this.nowait {
+                return Poll::Ready(Err(SysError::ENOMSG));
+            }
+            inner.recv_wakers.push_back(cx.waker().clone());
+            return Poll::Pending;
+        };
+        if inner.msgs[idx].1.len() > this.max_len && !this.noerror {
+            return Poll::Ready(Err(SysError::E2BIG));
+        }
+        let (mtype, mut text) = inner.msgs.remove(idx).unwrap();
+        inner.ds.cbytes -= text.len();
+        inner.ds.qnum -= 1;
+        inner.ds.lrpid = this.pid;
+        inner.ds.rtime = get_current_time_sec();
+        while let Some(waker) = inner.send_wakers.pop_front() {
+            waker.wake();
+        }
+        text.truncate(this.max_len);
+        Poll::Ready(Ok((mtype, text)))
+    }
+}
+
+/// message queue manager
+pub struct MsgQueueManager {
+    queues: SpinNoIrqLock<BTreeMap<usize, Arc<MsgQueue>>>,
+    /// key -> id, IPC_PRIVATE queues are not in here
+    keys: SpinNoIrqLock<BTreeMap<i32, usize>>,
+    id_alloc: SpinNoIrqLock<ShmIdAllocator>,
+}
+
+impl MsgQueueManager {
+    pub const fn new() -> Self {
+        Self {
+            queues: SpinNoIrqLock::new(BTreeMap::new()),
+            keys: SpinNoIrqLock::new(BTreeMap::new()),
+            id_alloc: SpinNoIrqLock::new(ShmIdAllocator::new()),
+        }
+    }
+
+    /// find a queue by id
+    pub fn get(&self, id: usize) -> Option<Arc<MsgQueue>> {
+        self.queues.lock().get(&id).cloned()
+    }
+
+    /// find a queue by key
+    pub fn find_key(&self, key: i32) -> Option<Arc<MsgQueue>> {
+        if key == IPC_PRIVATE {
+            return None;
+        }
+        let id = *self.keys.lock().get(&key)?;
+        self.get(id)
+    }
+
+    /// create a new queue for `key`
+    pub fn alloc(&self, key: i32, uid: u32, gid: u32, mode: u32) -> Result<Arc<MsgQueue>, SysError> {
+        if self.queues.lock().len() >= MSGMNI {
+            return Err(SysError::ENOSPC);
+        }
+        let id = self.id_alloc.lock().alloc().ok_or(SysError::ENOSPC)?;
+        let queue = Arc::new(MsgQueue::new(id, IpcPerm::new(key, uid, gid, mode)));
+        self.queues.lock().insert(id, queue.clone());
+        if key != IPC_PRIVATE {
+            self.keys.lock().insert(key, id);
+        }
+        Ok(queue)
+    }
+
+    /// IPC_RMID: remove the queue and fail all of its waiters with EIDRM
+    pub fn remove(&self, id: usize) -> Option<Arc<MsgQueue>> {
+        let queue = self.queues.lock().remove(&id)?;
+        let _ = self.id_alloc.lock().dealloc(id);
+        self.keys.lock().retain(|_, v| *v != id);
+        queue.mark_removed();
+        Some(queue)
+    }
+
+    /// IPC_INFO / MSG_INFO, returns the info and the highest used id
+    pub fn info(&self, usage: bool) -> (MsgInfo, usize) {
+        let queues = self.queues.lock();
+        let mut info = MsgInfo {
+            msgpool: (MSGMNI * MSGMNB / 1024) as i32,
+            msgmap: MSGMNB as i32,
+            msgmax: MSGMAX as i32,
+            msgmnb: MSGMNB as i32,
+            msgmni: MSGMNI as i32,
+            msgssz: 16,
+            msgtql: MSGMNB as i32,
+            msgseg: 0xffff,
+        };
+        if usage {
+            // like linux, MSG_INFO reuses the fields for the current usage
+            info.msgpool = queues.len() as i32;
+            info.msgmap = queues.values().map(|q| q.inner.lock().ds.qnum).sum::<usize>() as i32;
+            info.msgtql = queues.values().map(|q| q.inner.lock().ds.cbytes).sum::<usize>() as i32;
+        }
+        let max_id = queues.keys().next_back().copied().unwrap_or(0);
+        (info, max_id)
+    }
+}
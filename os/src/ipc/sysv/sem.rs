@@ -0,0 +1,439 @@
+use core::{future::Future, pin::Pin, task::{Context, Poll, Waker}};
+
+use alloc::{collections::{btree_map::BTreeMap, vec_deque::VecDeque}, sync::Arc, vec, vec::Vec};
+
+use crate::{sync::mutex::SpinNoIrqLock, syscall::SysError, timer::get_current_time_sec};
+
+use super::{shm::ShmIdAllocator, IpcNamespace, IpcPerm, IPC_PRIVATE};
+
+/// max semaphores per set
+pub const SEMMSL: usize = 32000;
+/// max number of sets
+pub const SEMMNI: usize = 32000;
+/// max operations per semop call
+pub const SEMOPM: usize = 500;
+/// max value of a semaphore
+pub const SEMVMX: i32 = 32767;
+/// max value of an undo adjustment
+pub const SEMAEM: i32 = SEMVMX;
+
+/// sem_flg: do not block
+pub const SEM_IPC_NOWAIT: i16 = 0o4000;
+/// sem_flg: undo the operation on exit
+pub const SEM_UNDO: i16 = 0x1000;
+
+/// `struct semid64_ds`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SemIdDs {
+    /// ownership and permissions
+    pub perm: IpcPerm,
+    /// last semop time
+    pub otime: usize,
+    /// creation time or time of last IPC_SET
+    pub ctime: usize,
+    /// number of semaphores in the set
+    pub nsems: usize,
+    _unused3: usize,
+    _unused4: usize,
+}
+
+/// `struct seminfo`, for IPC_INFO and SEM_INFO
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SemInfo {
+    pub semmap: i32,
+    pub semmni: i32,
+    pub semmns: i32,
+    pub semmnu: i32,
+    pub semmsl: i32,
+    pub semopm: i32,
+    pub semume: i32,
+    pub semusz: i32,
+    pub semvmx: i32,
+    pub semaem: i32,
+}
+
+/// `struct sembuf`
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct SemBuf {
+    /// semaphore index in the set
+    pub sem_num: u16,
+    /// semaphore operation
+    pub sem_op: i16,
+    /// operation flags
+    pub sem_flg: i16,
+}
+
+#[derive(Clone, Copy, Default)]
+struct Sem {
+    val: i32,
+    /// pid of the last operation
+    pid: i32,
+    /// tasks waiting for an increase
+    ncnt: usize,
+    /// tasks waiting for zero
+    zcnt: usize,
+}
+
+struct SemSetInner {
+    ds: SemIdDs,
+    sems: Vec<Sem>,
+    wakers: VecDeque<Waker>,
+    removed: bool,
+}
+
+/// System V semaphore set
+pub struct SemSet {
+    id: usize,
+    inner: SpinNoIrqLock<SemSetInner>,
+}
+
+impl SemSet {
+    fn new(id: usize, perm: IpcPerm, nsems: usize) -> Self {
+        Self {
+            id,
+            inner: SpinNoIrqLock::new(SemSetInner {
+                ds: SemIdDs {
+                    perm,
+                    ctime: get_current_time_sec(),
+                    nsems,
+                    ..Default::default()
+                },
+                sems: vec![Sem::default(); nsems],
+                wakers: VecDeque::new(),
+                removed: false,
+            }),
+        }
+    }
+
+    /// get id
+    pub fn get_id(&self) -> usize {
+        self.id
+    }
+
+    /// number of semaphores
+    pub fn nsems(&self) -> usize {
+        self.inner.lock().ds.nsems
+    }
+
+    /// IPC_STAT
+    pub fn stat(&self) -> SemIdDs {
+        self.inner.lock().ds
+    }
+
+    /// owner and creator uid, for permission checks
+    pub fn owners(&self) -> (u32, u32) {
+        let inner = self.inner.lock();
+        (inner.ds.perm.uid, inner.ds.perm.cuid)
+    }
+
+    /// IPC_SET: update owner and mode
+    pub fn set(&self, new: &SemIdDs) {
+        let mut inner = self.inner.lock();
+        inner.ds.perm.uid = new.perm.uid;
+        inner.ds.perm.gid = new.perm.gid;
+        inner.ds.perm.mode = (inner.ds.perm.mode & !0o777) | (new.perm.mode & 0o777);
+        inner.ds.ctime = get_current_time_sec();
+    }
+
+    /// GETVAL / GETPID / GETNCNT / GETZCNT of one semaphore
+    pub fn get(&self, num: usize) -> Result<(i32, i32, usize, usize), SysError> {
+        let inner = self.inner.lock();
+        let sem = inner.sems.get(num).ok_or(SysError::EINVAL)?;
+        Ok((sem.val, sem.pid, sem.ncnt, sem.zcnt))
+    }
+
+    /// GETALL
+    pub fn get_all(&self) -> Vec<u16> {
+        self.inner.lock().sems.iter().map(|s| s.val as u16).collect()
+    }
+
+    /// SETVAL, also clears the undo adjustments of the semaphore
+    pub fn set_val(&self, manager: &SemManager, num: usize, val: i32, pid: i32) -> Result<(), SysError> {
+        if !(0..=SEMVMX).contains(&val) {
+            return Err(SysError::ERANGE);
+        }
+        let mut inner = self.inner.lock();
+        let sem = inner.sems.get_mut(num).ok_or(SysError::EINVAL)?;
+        sem.val = val;
+        sem.pid = pid;
+        inner.ds.ctime = get_current_time_sec();
+        Self::wake_all(&mut inner);
+        drop(inner);
+        manager.clear_undo(self.id, Some(num));
+        Ok(())
+    }
+
+    /// SETALL, also clears the undo adjustments of the set
+    pub fn set_all(&self, manager: &SemManager, vals: &[u16], pid: i32) -> Result<(), SysError> {
+        if vals.iter().any(|v| *v as i32 > SEMVMX) {
+            return Err(SysError::ERANGE);
+        }
+        let mut inner = self.inner.lock();
+        for (sem, val) in inner.sems.iter_mut().zip(vals) {
+            sem.val = *val as i32;
+            sem.pid = pid;
+        }
+        inner.ds.ctime = get_current_time_sec();
+        Self::wake_all(&mut inner);
+        drop(inner);
+        manager.clear_undo(self.id, None);
+        Ok(())
+    }
+
+    fn wake_all(inner: &mut SemSetInner) {
+        while let Some(waker) = inner.wakers.pop_front() {
+            waker.wake();
+        }
+    }
+
+    fn mark_removed(&self) {
+        let mut inner = self.inner.lock();
+        inner.removed = true;
+        Self::wake_all(&mut inner);
+    }
+
+    /// try to apply all of `ops` atomically.
+    /// returns the op that would block if the whole call has to wait
+    fn try_ops(inner: &mut SemSetInner, ops: &[SemBuf], pid: i32) -> Result<Option<SemBuf>, SysError> {
+        let mut vals: Vec<i32> = inner.sems.iter().map(|s| s.val).collect();
+        for op in ops {
+            let val = &mut vals[op.sem_num as usize];
+            let sem_op = op.sem_op as i32;
+            if sem_op > 0 {
+                if *val + sem_op > SEMVMX {
+                    return Err(SysError::ERANGE);
+                }
+                *val += sem_op;
+            } else if sem_op == 0 {
+                if *val != 0 {
+                    return Ok(Some(*op));
+                }
+            } else if *val >= -sem_op {
+                *val += sem_op;
+            } else {
+                return Ok(Some(*op));
+            }
+        }
+        for (sem, val) in inner.sems.iter_mut().zip(vals) {
+            sem.val = val;
+        }
+        for op in ops {
+            inner.sems[op.sem_num as usize].pid = pid;
+        }
+        inner.ds.otime = get_current_time_sec();
+        Ok(None)
+    }
+}
+
+/// future for semop, completes once all operations could be applied at once
+pub struct SemOpFuture {
+    /// namespace of the set, SEM_UNDO adjustments are recorded there
+    ns: Arc<IpcNamespace>,
+    set: Arc<SemSet>,
+    ops: Vec<SemBuf>,
+    pid: i32,
+    /// the semaphore we are counted as a waiter on: (num, waiting for zero)
+    waiting: Option<(usize, bool)>,
+}
+
+impl SemOpFuture {
+    pub fn new(ns: Arc<IpcNamespace>, set: Arc<SemSet>, ops: Vec<SemBuf>, pid: i32) -> Self {
+        Self { ns, set, ops, pid, waiting: None }
+    }
+
+    fn unwait(&mut self, inner: &mut SemSetInner) {
+        if let Some((num, zero)) = self.waiting.take() {
+            let sem = &mut inner.sems[num];
+            if zero {
+                sem.zcnt -= 1;
+            } else {
+                sem.ncnt -= 1;
+            }
+        }
+    }
+}
+
+impl Future for SemOpFuture {
+    type Output = Result<(), SysError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = unsafe { self.get_unchecked_mut() };
+        let set = this.set.clone();
+        let mut inner = set.inner.lock();
+        if inner.removed {
+            this.waiting = None;
+            return Poll::Ready(Err(SysError::EIDRM));
+        }
+        this.unwait(&mut inner);
+        match SemSet::try_ops(&mut inner, &this.ops, this.pid) {
+            Err(e) => Poll::Ready(Err(e)),
+            Ok(None) => {
+                let undo: Vec<(usize, i32)> = this.ops.iter()
+                    .filter(|op| op.sem_flg & SEM_UNDO != 0)
+                    .map(|op| (op.sem_num as usize, op.sem_op as i32))
+                    .collect();
+                SemSet::wake_all(&mut inner);
+                drop(inner);
+                if !undo.is_empty() {
+                    this.ns.sem.record_undo(this.pid as usize, set.id, &undo);
+                }
+                Poll::Ready(Ok(()))
+            }
+            Ok(Some(op)) => {
+                if op.sem_flg & SEM_IPC_NOWAIT != 0 {
+                    return Poll::Ready(Err(SysError::EAGAIN));
+                }
+                let num = op.sem_num as usize;
+                let zero = op.sem_op == 0;
+                if zero {
+                    inner.sems[num].zcnt += 1;
+                } else {
+                    inner.sems[num].ncnt += 1;
+                }
+                this.waiting = Some((num, zero));
+                inner.wakers.push_back(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl Drop for SemOpFuture {
+    fn drop(&mut self) {
+        if self.waiting.is_some() {
+            let set = self.set.clone();
+            let mut inner = set.inner.lock();
+            if !inner.removed {
+                self.unwait(&mut inner);
+            }
+        }
+    }
+}
+
+/// semaphore manager
+pub struct SemManager {
+    sets: SpinNoIrqLock<BTreeMap<usize, Arc<SemSet>>>,
+    /// key -> id, IPC_PRIVATE sets are not in here
+    keys: SpinNoIrqLock<BTreeMap<i32, usize>>,
+    id_alloc: SpinNoIrqLock<ShmIdAllocator>,
+    /// SEM_UNDO adjustments: pid -> (semid, semnum) -> adjustment
+    undo: SpinNoIrqLock<BTreeMap<usize, BTreeMap<(usize, usize), i32>>>,
+}
+
+impl SemManager {
+    pub const fn new() -> Self {
+        Self {
+            sets: SpinNoIrqLock::new(BTreeMap::new()),
+            keys: SpinNoIrqLock::new(BTreeMap::new()),
+            id_alloc: SpinNoIrqLock::new(ShmIdAllocator::new()),
+            undo: SpinNoIrqLock::new(BTreeMap::new()),
+        }
+    }
+
+    /// find a set by id
+    pub fn get(&self, id: usize) -> Option<Arc<SemSet>> {
+        self.sets.lock().get(&id).cloned()
+    }
+
+    /// find a set by key
+    pub fn find_key(&self, key: i32) -> Option<Arc<SemSet>> {
+        if key == IPC_PRIVATE {
+            return None;
+        }
+        let id = *self.keys.lock().get(&key)?;
+        self.get(id)
+    }
+
+    /// create a new set of `nsems` semaphores for `key`
+    pub fn alloc(&self, key: i32, nsems: usize, uid: u32, gid: u32, mode: u32) -> Result<Arc<SemSet>, SysError> {
+        if self.sets.lock().len() >= SEMMNI {
+            return Err(SysError::ENOSPC);
+        }
+        let id = self.id_alloc.lock().alloc().ok_or(SysError::ENOSPC)?;
+        let set = Arc::new(SemSet::new(id, IpcPerm::new(key, uid, gid, mode), nsems));
+        self.sets.lock().insert(id, set.clone());
+        if key != IPC_PRIVATE {
+            self.keys.lock().insert(key, id);
+        }
+        Ok(set)
+    }
+
+    /// IPC_RMID: remove the set and fail all of its waiters with EIDRM
+    pub fn remove(&self, id: usize) -> Option<Arc<SemSet>> {
+        let set = self.sets.lock().remove(&id)?;
+        let _ = self.id_alloc.lock().dealloc(id);
+        self.keys.lock().retain(|_, v| *v != id);
+        self.clear_undo(id, None);
+        set.mark_removed();
+        Some(set)
+    }
+
+    /// IPC_INFO / SEM_INFO, returns the info and the highest used id
+    pub fn info(&self, usage: bool) -> (SemInfo, usize) {
+        let sets = self.sets.lock();
+        let mut info = SemInfo {
+            semmap: (SEMMNI * SEMMSL) as i32,
+            semmni: SEMMNI as i32,
+            semmns: (SEMMNI * SEMMSL) as i32,
+            semmnu: (SEMMNI * SEMMSL) as i32,
+            semmsl: SEMMSL as i32,
+            semopm: SEMOPM as i32,
+            semume: SEMOPM as i32,
+            semusz: 0,
+            semvmx: SEMVMX,
+            semaem: SEMAEM,
+        };
+        if usage {
+            // like linux, SEM_INFO reuses the fields for the current usage
+            info.semusz = sets.len() as i32;
+            info.semaem = sets.values().map(|s| s.nsems()).sum::<usize>() as i32;
+        }
+        let max_id = sets.keys().next_back().copied().unwrap_or(0);
+        (info, max_id)
+    }
+
+    /// remember how to undo `ops` of process `pid`
+    fn record_undo(&self, pid: usize, id: usize, ops: &[(usize, i32)]) {
+        let mut undo = self.undo.lock();
+        let adj = undo.entry(pid).or_default();
+        for (num, op) in ops {
+            let entry = adj.entry((id, *num)).or_insert(0);
+            *entry = (*entry - op).clamp(-SEMAEM, SEMAEM);
+            if *entry == 0 {
+                adj.remove(&(id, *num));
+            }
+        }
+    }
+
+    /// drop the undo adjustments of a set, or of one semaphore in it
+    fn clear_undo(&self, id: usize, num: Option<usize>) {
+        let mut undo = self.undo.lock();
+        for adj in undo.values_mut() {
+            adj.retain(|(sem_id, sem_num), _| !(*sem_id == id && num.map_or(true, |n| n == *sem_num)));
+        }
+        undo.retain(|_, adj| !adj.is_empty());
+    }
+
+    /// apply the SEM_UNDO adjustments of an exiting process
+    pub fn exit(&self, pid: usize) {
+        let Some(adj) = self.undo.lock().remove(&pid) else {
+            return;
+        };
+        for ((id, num), adj) in adj {
+            let Some(set) = self.get(id) else {
+                continue;
+            };
+            let mut inner = set.inner.lock();
+            if let Some(sem) = inner.sems.get_mut(num) {
+                // linux clamps instead of blocking here
+                sem.val = (sem.val + adj).clamp(0, SEMVMX);
+                sem.pid = pid as i32;
+            }
+            inner.ds.otime = get_current_time_sec();
+            SemSet::wake_all(&mut inner);
+        }
+    }
+}
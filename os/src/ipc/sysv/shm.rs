@@ -5,9 +5,6 @@ use crate::{fs::{page::{cache::PageCache, page::Page}, vfs::{File, FileInner, In
 
 use super::IpcPerm;
 
-/// shared memory manager instance
-pub static SHM_MANAGER: ShmManager = ShmManager::new();
-
 #[repr(C)]
 #[derive(Debug, Clone, Copy)]
 pub struct ShmIdDs {
@@ -120,7 +117,7 @@ pub struct ShmManager {
 
 impl ShmManager {
     /// 
-    const fn new() -> Self {
+    pub const fn new() -> Self {
         Self {
             files: SpinNoIrqLock::new(BTreeMap::new()),
             id_alloc: SpinNoIrqLock::new(ShmIdAllocator::new())
@@ -158,7 +155,7 @@ impl ShmManager {
     }
     ///
     pub fn remove(&self, id: usize) -> Option<Arc<ShmObj>> {
-        let _ = SHM_MANAGER.id_alloc.lock().dealloc(id);
+        let _ = self.id_alloc.lock().dealloc(id);
         self.files.lock().remove(&id)
     }
 }
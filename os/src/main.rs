@@ -58,6 +58,7 @@ pub mod mm;
 //pub mod sbi;
 pub mod sync;
 pub mod syscall;
+pub mod sysctl;
 pub mod signal;
 pub mod task;
 mod processor;
@@ -93,6 +94,7 @@ fn main(id: usize, first: bool) -> bool {
         devices::init();
         processor::processor::init(id);
         hal::trap::init();
+        sysctl::init();
         fs::init();
         // fs::vfs::file::list_apps(); 
         net::init_network();
@@ -104,6 +106,7 @@ fn main(id: usize, first: bool) -> bool {
                 task::add_initproc();
             }
         );
+        task::schedule::spawn_kernel_task(fs::page::writeback::flusher());
 
         #[cfg(feature = "smp")]
         processor_start(id);
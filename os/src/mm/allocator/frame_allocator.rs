@@ -2,6 +2,7 @@
 //! controls all the frames in the operating system.
 use crate::sync::mutex::spin_mutex::SpinMutex;
 use crate::sync::mutex::{Spin, SpinNoIrqLock};
+use crate::mm::oom::out_of_memory;
 use crate::sync::UPSafeCell;
 use alloc::alloc::Allocator;
 use alloc::vec::Vec;
@@ -23,6 +24,8 @@ trait FrameAllocatorTrait {
     fn init(&mut self, range_pa: Range<PhysAddr>);
     fn alloc_contiguous(&mut self, size: usize, align_log2: usize) -> Option<Range<PhysPageNum>>;
     fn dealloc_contiguous(&mut self, range_ppn: Range<PhysPageNum>);
+    fn reserve(&mut self, range_pa: Range<PhysAddr>);
+    fn release(&mut self, range_pa: Range<PhysAddr>);
 }
 
 /// Bitmap Frame Allocator, the supported maximum memory space is 64GiB
@@ -81,7 +84,27 @@ impl FrameAllocatorTrait for BitMapFrameAllocator {
         assert!(self.inner.dealloc_contiguous(start, size));
         self.last += size;
     }
-    
+
+    fn reserve(&mut self, range_pa: Range<PhysAddr>) {
+        let range = self.frames_in(range_pa);
+        self.inner.remove(range.clone());
+        self.last -= range.len();
+    }
+
+    fn release(&mut self, range_pa: Range<PhysAddr>) {
+        let range = self.frames_in(range_pa);
+        self.inner.insert(range.clone());
+        self.last += range.len();
+    }
+}
+
+impl BitMapFrameAllocator {
+    /// the bits of the frames of `range_pa` the allocator manages
+    fn frames_in(&self, range_pa: Range<PhysAddr>) -> Range<usize> {
+        let start = range_pa.start.floor().0.clamp(self.range.start.0, self.range.end.0);
+        let end = range_pa.end.ceil().0.clamp(start, self.range.end.0);
+        start - self.range.start.0..end - self.range.start.0
+    }
 }
 
 /// frame allocator
@@ -100,8 +123,19 @@ impl FrameAllocatorHal for FrameAllocator {
         if cnt == 0 {
             return None
         }
-        let mut alloc_guard = FRAME_ALLOCATOR.lock();
-        alloc_guard.alloc_contiguous(cnt, align_log2)
+        loop {
+            if let Some(range_ppn) = FRAME_ALLOCATOR.lock().alloc_contiguous(cnt, align_log2) {
+                return Some(range_ppn);
+            }
+            // enough frames are free but not in one piece, killing a
+            // process would not help
+            if cnt > 1 && free_frames() >= cnt {
+                return None;
+            }
+            if !out_of_memory(cnt) {
+                return None;
+            }
+        }
     }
 
     fn dealloc(&self, range_ppn: Range<PhysPageNum>) {
@@ -138,6 +172,28 @@ pub fn init_frame_allocator() {
     );
 }
 
+/// keep the frames of `range_pa` from being allocated, they must be free,
+/// e.g. the initrd until it is unpacked
+pub fn reserve_frames(range_pa: Range<PhysAddr>) {
+    FRAME_ALLOCATOR.lock().reserve(range_pa)
+}
+
+/// give back the frames reserved by `reserve_frames`
+pub fn release_frames(range_pa: Range<PhysAddr>) {
+    FRAME_ALLOCATOR.lock().release(range_pa)
+}
+
+/// number of frames managed by the frame allocator
+pub fn total_frames() -> usize {
+    let allocator = FRAME_ALLOCATOR.lock();
+    allocator.range.end.0 - allocator.range.start.0
+}
+
+/// number of free frames
+pub fn free_frames() -> usize {
+    FRAME_ALLOCATOR.lock().last
+}
+
 /// allocate frames
 pub fn frames_alloc(size: usize) -> Option<FrameTracker> {
     FrameAllocator
@@ -52,8 +52,7 @@ unsafe impl GlobalAlloc for SlabAllocator {
                 return ret;
             } else {
                 log::warn!("failed alloc layout: {:?}", layout);
-                SLAB_ALLOCATOR_INNER.shrink();
-                SLAB_BLOCK_SLAB_CACHE.lock().shrink();
+                shrink_slab();
             }
             times -= 1;
             if times == 0 {
@@ -71,6 +70,14 @@ unsafe impl GlobalAlloc for SlabAllocator {
     }
 }
 
+/// give the frames of empty slab blocks back
+pub fn shrink_slab() {
+    SLAB_ALLOCATOR_INNER.shrink();
+    if let Some(mut cache) = SLAB_BLOCK_SLAB_CACHE.try_lock() {
+        cache.shrink();
+    }
+}
+
 /// Slab Allocator's Inner
 pub struct SlabAllocatorInner {
     pub cache8: SpinNoIrqLock<SmallSlabCache<8>>, 
@@ -115,21 +122,47 @@ impl SlabAllocatorInner {
         layout.size() <= 8192 && layout.align() <= layout.size() && layout.align() <= 4096
     }
 
-    /// release useless frames
+    /// release useless frames, caches in use are passed over
     pub fn shrink(&self) {
-        self.cache8.lock().shrink();
-        self.cache16.lock().shrink();
-        self.cache32.lock().shrink();
-        self.cache64.lock().shrink();
-        self.cache96.lock().shrink();
-        self.cache128.lock().shrink();
-        self.cache192.lock().shrink();
-        self.cache256.lock().shrink();
-        self.cache512.lock().shrink();
-        self.cache1024.lock().shrink();
-        self.cache2048.lock().shrink();
-        self.cache4096.lock().shrink();
-        self.cache8192.lock().shrink();
+        if let Some(mut cache) = self.cache8.try_lock() {
+            cache.shrink();
+        }
+        if let Some(mut cache) = self.cache16.try_lock() {
+            cache.shrink();
+        }
+        if let Some(mut cache) = self.cache32.try_lock() {
+            cache.shrink();
+        }
+        if let Some(mut cache) = self.cache64.try_lock() {
+            cache.shrink();
+        }
+        if let Some(mut cache) = self.cache96.try_lock() {
+            cache.shrink();
+        }
+        if let Some(mut cache) = self.cache128.try_lock() {
+            cache.shrink();
+        }
+        if let Some(mut cache) = self.cache192.try_lock() {
+            cache.shrink();
+        }
+        if let Some(mut cache) = self.cache256.try_lock() {
+            cache.shrink();
+        }
+        if let Some(mut cache) = self.cache512.try_lock() {
+            cache.shrink();
+        }
+        if let Some(mut cache) = self.cache1024.try_lock() {
+            cache.shrink();
+        }
+        if let Some(mut cache) = self.cache2048.try_lock() {
+            cache.shrink();
+        }
+        if let Some(mut cache) = self.cache4096.try_lock() {
+            cache.shrink();
+        }
+        if let Some(mut cache) = self.cache8192.try_lock() {
+            cache.shrink();
+        }
     }
 
     pub fn alloc_by_layout(&self, layout: core::alloc::Layout) -> Option<NonNull<u8>> {
@@ -9,7 +9,11 @@
 /// allocator
 pub mod allocator;
 mod page_table;
-use core::ops::Deref;
+/// out of memory
+pub mod oom;
+/// swap
+pub mod swap;
+use core::{ops::Deref, sync::atomic::{AtomicUsize, Ordering}};
 /// virtual memory
 pub mod vm;
 
@@ -58,14 +62,47 @@ impl hal::mapper::MmioMapperHal for MmioMapper {
 }
 
 use super::sync::mutex::SpinNoIrqLock;
+use crate::{syscall::SysError, sysctl::{register_sysctl, IntSysctl}};
 lazy_static::lazy_static! {
     #[allow(missing_docs)]
     pub static ref KVMSPACE: SpinNoIrqLock<KernVmSpace> = SpinNoIrqLock::new(KernVmSpace::new());
 }
 
+/// heuristic overcommit, refuse only a mapping larger than all memory
+pub const OVERCOMMIT_GUESS: usize = 0;
+/// always overcommit
+pub const OVERCOMMIT_ALWAYS: usize = 1;
+/// never overcommit, refuse a mapping larger than the free memory
+pub const OVERCOMMIT_NEVER: usize = 2;
+
+/// overcommit policy, tunable as vm.overcommit_memory
+pub static OVERCOMMIT_MEMORY: AtomicUsize = AtomicUsize::new(OVERCOMMIT_GUESS);
+
+/// check whether a new private mapping of `pages` pages may be made
+pub fn vm_enough_memory(pages: usize) -> Result<(), SysError> {
+    let limit = match OVERCOMMIT_MEMORY.load(Ordering::Relaxed) {
+        OVERCOMMIT_ALWAYS => return Ok(()),
+        OVERCOMMIT_NEVER => allocator::free_frames(),
+        _ => allocator::total_frames(),
+    };
+    if pages > limit {
+        return Err(SysError::ENOMEM);
+    }
+    Ok(())
+}
+
+/// vm.overcommit_memory
+pub fn register_sysctls() {
+    register_sysctl("vm.overcommit_memory", IntSysctl::new(&OVERCOMMIT_MEMORY, OVERCOMMIT_GUESS, OVERCOMMIT_NEVER));
+}
+
 /// initiate heap allocator, frame allocator and kernel space
 pub fn init() {
     allocator::init_heap();
     allocator::init_frame_allocator();
+    // kept until fs::init unpacks it
+    if let Some(initrd) = crate::devices::initrd_range() {
+        allocator::reserve_frames(hal::addr::PhysAddr::from(initrd.start)..hal::addr::PhysAddr::from(initrd.end));
+    }
     vm::KernVmSpaceHal::enable(KVMSPACE.lock().deref());
 }
@@ -0,0 +1,216 @@
+//! out of memory handling
+//!
+//! when the frame allocator runs dry, empty slab blocks, clean page cache
+//! pages and cold anonymous pages are given back first. if that is not
+//! enough the process with the highest badness, the pages it uses plus its
+//! oom_score_adj in thousandths of all memory, is killed and its private
+//! memory freed at once so that the allocation can go on.
+//!
+//! a frame may be allocated with any lock held, so nothing here spins on a
+//! lock. when an address space cannot be looked at, like the one of a task
+//! in the middle of a page fault, the kill is left to the fault handler.
+
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use alloc::sync::{Arc, Weak};
+use hal::instruction::{Instruction, InstructionHal};
+
+use crate::{fs::page::lru::reclaim_pages, processor::processor::space_active_elsewhere, signal::{SigInfo, SIGKILL}, sync::mutex::SpinNoIrqLock, task::{manager::TASK_MANAGER, task::TaskControlBlock, INITPROC_PID}};
+
+use super::{allocator::{free_frames, shrink_slab, total_frames}, swap};
+
+/// oom_score_adj of a process never killed
+pub const OOM_SCORE_ADJ_MIN: i32 = -1000;
+/// oom_score_adj of a process killed first
+pub const OOM_SCORE_ADJ_MAX: i32 = 1000;
+
+/// hart in the oom path, usize::MAX if none
+static OOM_OWNER: AtomicUsize = AtomicUsize::new(usize::MAX);
+
+/// an allocation failed and the kill was left to the page fault handler
+static OOM_PENDING: AtomicBool = AtomicBool::new(false);
+
+/// the victim whose memory could not be freed at once, no other is killed
+/// before it is gone
+static VICTIM: SpinNoIrqLock<Option<Weak<TaskControlBlock>>> = SpinNoIrqLock::new(None);
+
+/// run `f` as the only hart in the oom path. None if this hart already is,
+/// or another one did not leave it in time
+fn exclusive<R>(f: impl FnOnce() -> R) -> Option<R> {
+    let hart = Instruction::get_tp();
+    let mut tries = 0usize;
+    loop {
+        match OOM_OWNER.compare_exchange(usize::MAX, hart, Ordering::Acquire, Ordering::Relaxed) {
+            Ok(_) => break,
+            Err(owner) if owner == hart => return None,
+            Err(_) => {
+                // the other one may wait for a lock this hart holds
+                tries += 1;
+                if tries == 0x100000 {
+                    return None;
+                }
+                core::hint::spin_loop();
+            }
+        }
+    }
+    let ret = f();
+    OOM_OWNER.store(usize::MAX, Ordering::Release);
+    Some(ret)
+}
+
+/// pages there are, frames and swap
+fn total_pages() -> usize {
+    total_frames() + swap::total_pages()
+}
+
+/// badness of a process using `pages` pages, None if it is never killed
+fn badness(pages: usize, adj: i32, total: usize) -> Option<isize> {
+    if adj == OOM_SCORE_ADJ_MIN {
+        return None;
+    }
+    Some(pages as isize + adj as isize * total as isize / 1000)
+}
+
+/// /proc/<pid>/oom_score, the badness in thousandths of all memory shifted
+/// by 1000, 0 for a process never killed
+pub fn oom_score(task: &Arc<TaskControlBlock>) -> usize {
+    let pages = {
+        let vm_space = task.get_vm_space().lock();
+        vm_space.resident_pages() + vm_space.swapped_pages()
+    };
+    let total = total_pages();
+    badness(pages, task.oom_score_adj(), total)
+        .map_or(0, |points| (1000 + points * 1000 / total as isize).max(0) as usize)
+}
+
+/// give back what can be given back without killing, whether that made
+/// `frames` frames free
+fn reclaim(frames: usize) -> bool {
+    let before = free_frames();
+    shrink_slab();
+    if free_frames() < frames {
+        reclaim_pages(frames - free_frames());
+    }
+    if swap::total_pages() > 0 && free_frames() < frames {
+        swap::reclaim(frames - free_frames());
+    }
+    let after = free_frames();
+    after > before && after >= frames
+}
+
+enum Selection {
+    /// the process to kill
+    Victim(Arc<TaskControlBlock>),
+    /// an earlier victim is still exiting
+    Wait,
+    /// some process could not be looked at
+    Busy,
+    /// every process is unkillable
+    None,
+}
+
+/// the process with the highest badness. `skip_busy` passes over one
+/// whose address space is locked instead of giving up
+fn select_victim(skip_busy: bool) -> Selection {
+    {
+        let mut victim = VICTIM.lock();
+        match victim.as_ref().and_then(|v| v.upgrade()) {
+            Some(task) if !task.is_zombie() => return Selection::Wait,
+            _ => *victim = None,
+        }
+    }
+    let total = total_pages();
+    let mut chosen: Option<(Arc<TaskControlBlock>, isize)> = None;
+    let Some(tasks) = TASK_MANAGER.try_tasks_group() else {
+        return if skip_busy { Selection::Wait } else { Selection::Busy };
+    };
+    for task in tasks {
+        if !task.is_leader() || task.pid() == INITPROC_PID || task.is_zombie() {
+            continue;
+        }
+        let killed = task.sig_manager.try_lock()
+            .map_or(false, |manager| manager.bitmap.contain_sig(SIGKILL));
+        if killed {
+            continue;
+        }
+        // the fault handler or clone of this very process may hold these
+        let pages = match (task.get_vm_space().try_lock(), task.thread_group.try_lock()) {
+            (Some(vm_space), Some(_)) => vm_space.resident_pages() + vm_space.swapped_pages(),
+            _ if skip_busy => continue,
+            _ => return Selection::Busy,
+        };
+        let Some(points) = badness(pages, task.oom_score_adj(), total) else {
+            continue;
+        };
+        if chosen.as_ref().map_or(true, |(_, max)| points > *max) {
+            chosen = Some((task, points));
+        }
+    }
+    match chosen {
+        Some((task, _)) => Selection::Victim(task),
+        None => Selection::None,
+    }
+}
+
+/// kill `victim` and free its private memory, returns the number of frames
+/// given back
+fn kill(victim: &Arc<TaskControlBlock>) -> usize {
+    log::warn!(
+        "[oom] out of memory, killing process {} with oom_score_adj {}",
+        victim.pid(), victim.oom_score_adj(),
+    );
+    victim.recv_sigs_process_level(
+        SigInfo { si_signo: SIGKILL, si_code: SigInfo::KERNEL, si_pid: None }
+    );
+    // it may take long to exit, it does not need its memory for that. a
+    // space running on another processor is left to exit, its frames
+    // cannot be freed without a remote tlb flush
+    let vm_space = victim.get_vm_space();
+    let reaped = vm_space.try_lock()
+        .filter(|_| !space_active_elsewhere(vm_space))
+        .map_or(0, |mut vm_space| vm_space.reap());
+    if reaped == 0 {
+        *VICTIM.lock() = Some(Arc::downgrade(victim));
+    }
+    reaped
+}
+
+/// allocating `frames` frames failed, whether it is worth trying again
+pub fn out_of_memory(frames: usize) -> bool {
+    exclusive(|| {
+        if reclaim(frames) {
+            return true;
+        }
+        match select_victim(false) {
+            Selection::Victim(task) => kill(&task) > 0,
+            Selection::Busy => {
+                OOM_PENDING.store(true, Ordering::Relaxed);
+                false
+            }
+            Selection::Wait | Selection::None => false,
+        }
+    }).unwrap_or(false)
+}
+
+/// whether an allocation left the kill to the page fault handler
+pub fn take_pending() -> bool {
+    OOM_PENDING.swap(false, Ordering::Relaxed)
+}
+
+/// a page fault failed for want of frames, kill now that no lock is held.
+/// whether the fault is worth taking again
+pub fn pagefault_out_of_memory() -> bool {
+    exclusive(|| {
+        if reclaim(1) {
+            return true;
+        }
+        match select_victim(true) {
+            Selection::Victim(task) => {
+                kill(&task);
+                true
+            }
+            Selection::Wait => true,
+            Selection::Busy | Selection::None => false,
+        }
+    }).unwrap_or(true)
+}
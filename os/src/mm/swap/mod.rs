@@ -0,0 +1,486 @@
+//! swap
+//!
+//! when frames run low, cold anonymous pages of user space are written out
+//! to a swap area and a swap entry is left in the page table in their place,
+//! the page is read back on the next fault. a swap area is a block device or
+//! a file prepared by mkswap and turned on by swapon.
+
+mod reclaim;
+
+pub use reclaim::{balance, reclaim};
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use alloc::{collections::btree_map::BTreeMap, string::String, sync::Arc, vec, vec::Vec};
+use hal::{addr::{PhysPageNum, RangePPNHal}, util::smart_point::StrongArc};
+
+use crate::{config::PAGE_SIZE, devices::BlockDevice, fs::vfs::File, sync::mutex::SpinNoIrqLock, syscall::SysError, task::manager::TASK_MANAGER};
+
+use super::{allocator::frames_alloc, FrameTracker};
+
+/// at most that many swap areas, the area is kept in the low bits of an entry
+pub const MAX_SWAPFILES: usize = 32;
+const AREA_BITS: usize = 5;
+
+/// swapon flags
+pub const SWAP_FLAG_PREFER: i32 = 0x8000;
+pub const SWAP_FLAG_PRIO_MASK: i32 = 0x7fff;
+pub const SWAP_FLAG_DISCARD: i32 = 0x10000;
+
+/// a page of a swap area
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub struct SwapEntry(usize);
+
+impl SwapEntry {
+    fn new(area: usize, slot: usize) -> Self {
+        Self(slot << AREA_BITS | area)
+    }
+
+    /// the entry as left in a page table
+    pub fn from_bits(bits: usize) -> Self {
+        Self(bits)
+    }
+
+    pub fn bits(self) -> usize {
+        self.0
+    }
+
+    pub fn area(self) -> usize {
+        self.0 & (MAX_SWAPFILES - 1)
+    }
+
+    pub fn slot(self) -> usize {
+        self.0 >> AREA_BITS
+    }
+}
+
+/// a counted reference to a swap entry, the slot is freed with the last one
+pub struct SwapSlot(SwapEntry);
+
+impl SwapSlot {
+    pub fn entry(&self) -> SwapEntry {
+        self.0
+    }
+}
+
+impl Clone for SwapSlot {
+    fn clone(&self) -> Self {
+        SWAP.lock().dup(self.0);
+        Self(self.0)
+    }
+}
+
+impl Drop for SwapSlot {
+    fn drop(&mut self) {
+        let frame = SWAP.lock().put(self.0);
+        // the frame may be freed, not under the lock
+        drop(frame);
+    }
+}
+
+/// where the pages of a swap area go
+#[derive(Clone)]
+enum SwapBackend {
+    Device(Arc<dyn BlockDevice>),
+    File(Arc<dyn File>),
+}
+
+impl SwapBackend {
+    /// identifies the device or the file across opens
+    fn key(&self) -> *const () {
+        match self {
+            Self::Device(dev) => Arc::as_ptr(dev) as *const (),
+            Self::File(file) => file.inode().map_or(core::ptr::null(), |inode| Arc::as_ptr(&inode) as *const ()),
+        }
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            Self::Device(dev) => dev.size() as usize,
+            Self::File(file) => file.size(),
+        }
+    }
+
+    fn read_page(&self, slot: usize, buf: &mut [u8]) -> Result<(), SysError> {
+        match self {
+            Self::Device(dev) => {
+                let block_size = dev.block_size();
+                let first = slot * PAGE_SIZE / block_size;
+                for (i, block) in buf.chunks_mut(block_size).enumerate() {
+                    dev.direct_read_block(first + i, block);
+                }
+                Ok(())
+            }
+            Self::File(file) => {
+                let inode = file.inode().ok_or(SysError::EIO)?;
+                match inode.read_at(slot * PAGE_SIZE, buf) {
+                    Ok(len) if len == buf.len() => Ok(()),
+                    _ => Err(SysError::EIO),
+                }
+            }
+        }
+    }
+
+    fn write_page(&self, slot: usize, buf: &[u8]) -> Result<(), SysError> {
+        match self {
+            Self::Device(dev) => {
+                let block_size = dev.block_size();
+                let first = slot * PAGE_SIZE / block_size;
+                for (i, block) in buf.chunks(block_size).enumerate() {
+                    dev.direct_write_block(first + i, block);
+                }
+                Ok(())
+            }
+            Self::File(file) => {
+                let inode = file.inode().ok_or(SysError::EIO)?;
+                match inode.write_at(slot * PAGE_SIZE, buf) {
+                    Ok(len) if len == buf.len() => Ok(()),
+                    _ => Err(SysError::EIO),
+                }
+            }
+        }
+    }
+}
+
+/// the header mkswap writes in the first page
+const SWAP_MAGIC: &[u8] = b"SWAPSPACE2";
+const SWAP_VERSION: u32 = 1;
+const HEADER_VERSION: usize = 1024;
+const HEADER_LAST_PAGE: usize = 1028;
+const HEADER_NR_BADPAGES: usize = 1032;
+const HEADER_BADPAGES: usize = 1536;
+
+/// a turned on swap area
+struct SwapArea {
+    /// as given to swapon
+    path: String,
+    backend: SwapBackend,
+    priority: isize,
+    /// references of each slot, the header and bad pages are never free
+    counts: Vec<u32>,
+    /// slots that hold pages
+    usable: usize,
+    inuse: usize,
+    /// where to look for a free slot next
+    cursor: usize,
+    /// no new pages while being turned off
+    writable: bool,
+}
+
+const SLOT_BAD: u32 = u32::MAX;
+
+impl SwapArea {
+    /// check the header of `backend` and take the slots it allows
+    fn new(path: String, backend: SwapBackend, priority: isize) -> Result<Self, SysError> {
+        let mut header = vec![0u8; PAGE_SIZE];
+        if backend.size() < 2 * PAGE_SIZE {
+            return Err(SysError::EINVAL);
+        }
+        backend.read_page(0, &mut header)?;
+        if &header[PAGE_SIZE - SWAP_MAGIC.len()..] != SWAP_MAGIC {
+            return Err(SysError::EINVAL);
+        }
+        let field = |offset: usize| u32::from_ne_bytes(header[offset..offset + 4].try_into().unwrap());
+        if field(HEADER_VERSION) != SWAP_VERSION {
+            return Err(SysError::EINVAL);
+        }
+        let pages = (field(HEADER_LAST_PAGE) as usize + 1).min(backend.size() / PAGE_SIZE);
+        let mut counts = vec![0u32; pages];
+        counts[0] = SLOT_BAD;
+        let nr_badpages = field(HEADER_NR_BADPAGES) as usize;
+        if HEADER_BADPAGES + nr_badpages * 4 > PAGE_SIZE - SWAP_MAGIC.len() {
+            return Err(SysError::EINVAL);
+        }
+        for i in 0..nr_badpages {
+            let bad = field(HEADER_BADPAGES + i * 4) as usize;
+            if bad < pages {
+                counts[bad] = SLOT_BAD;
+            }
+        }
+        let usable = counts.iter().filter(|&&c| c == 0).count();
+        if usable == 0 {
+            return Err(SysError::EINVAL);
+        }
+        Ok(Self { path, backend, priority, counts, usable, inuse: 0, cursor: 1, writable: true })
+    }
+
+    fn alloc(&mut self) -> Option<usize> {
+        if !self.writable || self.inuse == self.usable {
+            return None;
+        }
+        let pages = self.counts.len();
+        let slot = (0..pages)
+            .map(|i| (self.cursor + i) % pages)
+            .find(|&slot| self.counts[slot] == 0)?;
+        self.counts[slot] = 1;
+        self.inuse += 1;
+        self.cursor = slot + 1;
+        Some(slot)
+    }
+}
+
+struct SwapInfo {
+    areas: Vec<Option<SwapArea>>,
+    /// frames of entries still in use by others after a swap in or out, so
+    /// that they share one frame again
+    cache: BTreeMap<SwapEntry, StrongArc<FrameTracker>>,
+    cached_ppn: BTreeMap<PhysPageNum, SwapEntry>,
+    /// priority of the next area turned on without one
+    least_priority: isize,
+}
+
+impl SwapInfo {
+    const fn new() -> Self {
+        Self {
+            areas: Vec::new(),
+            cache: BTreeMap::new(),
+            cached_ppn: BTreeMap::new(),
+            least_priority: -1,
+        }
+    }
+
+    fn area(&mut self, entry: SwapEntry) -> &mut SwapArea {
+        self.areas[entry.area()].as_mut().expect("swap entry of no area")
+    }
+
+    /// a free slot of the area with the highest priority
+    fn alloc(&mut self) -> Option<(SwapEntry, SwapBackend)> {
+        let mut order: Vec<usize> = (0..self.areas.len()).filter(|&i| self.areas[i].is_some()).collect();
+        order.sort_by_key(|&i| -self.areas[i].as_ref().unwrap().priority);
+        order.into_iter().find_map(|i| {
+            let area = self.areas[i].as_mut().unwrap();
+            area.alloc().map(|slot| (SwapEntry::new(i, slot), area.backend.clone()))
+        })
+    }
+
+    fn dup(&mut self, entry: SwapEntry) {
+        self.area(entry).counts[entry.slot()] += 1;
+    }
+
+    fn count(&mut self, entry: SwapEntry) -> u32 {
+        self.area(entry).counts[entry.slot()]
+    }
+
+    /// drop a reference, the cached frame goes with the last one
+    fn put(&mut self, entry: SwapEntry) -> Option<StrongArc<FrameTracker>> {
+        let area = self.area(entry);
+        area.counts[entry.slot()] -= 1;
+        if area.counts[entry.slot()] != 0 {
+            return None;
+        }
+        area.inuse -= 1;
+        self.uncache(entry)
+    }
+
+    fn cache(&mut self, entry: SwapEntry, frame: StrongArc<FrameTracker>) {
+        self.cached_ppn.insert(frame.range_ppn.start, entry);
+        self.cache.insert(entry, frame);
+    }
+
+    fn uncache(&mut self, entry: SwapEntry) -> Option<StrongArc<FrameTracker>> {
+        let frame = self.cache.remove(&entry)?;
+        self.cached_ppn.remove(&frame.range_ppn.start);
+        Some(frame)
+    }
+}
+
+static SWAP: SpinNoIrqLock<SwapInfo> = SpinNoIrqLock::new(SwapInfo::new());
+
+/// pages of all swap areas
+static TOTAL_PAGES: AtomicUsize = AtomicUsize::new(0);
+
+/// number of pages swap areas hold
+pub fn total_pages() -> usize {
+    TOTAL_PAGES.load(Ordering::Relaxed)
+}
+
+/// number of free pages of swap areas
+pub fn free_pages() -> usize {
+    let swap = SWAP.lock();
+    swap.areas.iter().flatten().map(|area| area.usable - area.inuse).sum()
+}
+
+/// number of frames kept for swapped out pages
+pub fn cached_pages() -> usize {
+    SWAP.lock().cache.len()
+}
+
+/// a line of /proc/swaps
+pub struct SwapAreaInfo {
+    pub path: String,
+    pub is_file: bool,
+    pub pages: usize,
+    pub used: usize,
+    pub priority: isize,
+}
+
+/// the turned on swap areas
+pub fn swap_areas() -> Vec<SwapAreaInfo> {
+    SWAP.lock().areas.iter().flatten().map(|area| SwapAreaInfo {
+        path: area.path.clone(),
+        is_file: matches!(area.backend, SwapBackend::File(_)),
+        pages: area.usable,
+        used: area.inuse,
+        priority: area.priority,
+    }).collect()
+}
+
+/// write `frame` out, the entry that holds it
+pub fn swap_out(frame: &StrongArc<FrameTracker>) -> Result<SwapSlot, SysError> {
+    {
+        let mut swap = SWAP.lock();
+        // some other sharer wrote it out already
+        if let Some(&entry) = swap.cached_ppn.get(&frame.range_ppn.start) {
+            swap.dup(entry);
+            return Ok(SwapSlot(entry));
+        }
+    }
+    let (entry, backend) = SWAP.lock().alloc().ok_or(SysError::ENOSPC)?;
+    let slot = SwapSlot(entry);
+    backend.write_page(entry.slot(), frame.range_ppn.get_slice::<u8>())?;
+    if frame.get_owners() > 1 {
+        // a page shared on fork, those still mapping it find the entry by the frame
+        SWAP.lock().cache(entry, frame.clone());
+    }
+    Ok(slot)
+}
+
+/// a frame with the content of `slot`
+pub fn swap_in(slot: &SwapSlot) -> Result<StrongArc<FrameTracker>, SysError> {
+    let entry = slot.entry();
+    let backend = {
+        let mut swap = SWAP.lock();
+        if let Some(frame) = swap.cache.get(&entry) {
+            return Ok(frame.clone());
+        }
+        swap.area(entry).backend.clone()
+    };
+    let frame = StrongArc::new(frames_alloc(1).ok_or(SysError::ENOMEM)?);
+    backend.read_page(entry.slot(), frame.range_ppn.get_slice_mut::<u8>())?;
+    let mut swap = SWAP.lock();
+    if swap.count(entry) > 1 {
+        // the others swapping it in share the frame
+        if let Some(cached) = swap.cache.get(&entry) {
+            return Ok(cached.clone());
+        }
+        swap.cache(entry, frame.clone());
+    }
+    Ok(frame)
+}
+
+/// free the cached frames no one maps any more, returns the number freed
+pub fn shrink_cache() -> usize {
+    let frames: Vec<_> = {
+        let mut swap = SWAP.lock();
+        let unused: Vec<_> = swap.cache.iter()
+            .filter(|(_, frame)| frame.get_owners() == 1)
+            .map(|(&entry, _)| entry)
+            .collect();
+        unused.into_iter().filter_map(|entry| swap.uncache(entry)).collect()
+    };
+    frames.len()
+}
+
+/// turn on swapping to the block device `dev`
+pub fn swapon_device(path: String, dev: Arc<dyn BlockDevice>, flags: i32) -> Result<(), SysError> {
+    do_swapon(path, SwapBackend::Device(dev), flags)
+}
+
+/// turn on swapping to the regular file `file`
+pub fn swapon_file(path: String, file: Arc<dyn File>, flags: i32) -> Result<(), SysError> {
+    let inode = file.inode().ok_or(SysError::EINVAL)?;
+    // write back what is cached and never again, the pages go to the file directly
+    if let Some(cache) = inode.cache() {
+        cache.flush(&*inode).map_err(SysError::from_i32)?;
+    }
+    inode.clean_cached();
+    do_swapon(path, SwapBackend::File(file), flags)
+}
+
+fn do_swapon(path: String, backend: SwapBackend, flags: i32) -> Result<(), SysError> {
+    let key = backend.key();
+    if SWAP.lock().areas.iter().flatten().any(|area| area.backend.key() == key) {
+        return Err(SysError::EBUSY);
+    }
+    let priority = if flags & SWAP_FLAG_PREFER != 0 {
+        (flags & SWAP_FLAG_PRIO_MASK) as isize
+    } else {
+        0
+    };
+    let mut area = SwapArea::new(path, backend, priority)?;
+    let mut swap = SWAP.lock();
+    if swap.areas.iter().flatten().any(|area| area.backend.key() == key) {
+        return Err(SysError::EBUSY);
+    }
+    let index = match swap.areas.iter().position(|area| area.is_none()) {
+        Some(index) => index,
+        None if swap.areas.len() < MAX_SWAPFILES => {
+            swap.areas.push(None);
+            swap.areas.len() - 1
+        }
+        None => return Err(SysError::EPERM),
+    };
+    if flags & SWAP_FLAG_PREFER == 0 {
+        swap.least_priority -= 1;
+        area.priority = swap.least_priority;
+    }
+    log::info!("[swap] swapon {} with {} pages, priority {}", area.path, area.usable, area.priority);
+    TOTAL_PAGES.fetch_add(area.usable, Ordering::Relaxed);
+    swap.areas[index] = Some(area);
+    Ok(())
+}
+
+/// turn off swapping to the device or file `key` names, after reading all its
+/// pages back
+fn do_swapoff(key: *const ()) -> Result<(), SysError> {
+    let index = {
+        let mut swap = SWAP.lock();
+        let index = swap.areas.iter()
+            .position(|area| area.as_ref().map_or(false, |area| area.backend.key() == key))
+            .ok_or(SysError::EINVAL)?;
+        swap.areas[index].as_mut().unwrap().writable = false;
+        index
+    };
+    if let Err(e) = try_to_unuse(index) {
+        SWAP.lock().areas[index].as_mut().unwrap().writable = true;
+        return Err(e);
+    }
+    let area = SWAP.lock().areas[index].take().unwrap();
+    TOTAL_PAGES.fetch_sub(area.usable, Ordering::Relaxed);
+    log::info!("[swap] swapoff {}", area.path);
+    Ok(())
+}
+
+/// turn off swapping to the block device `dev`
+pub fn swapoff_device(dev: Arc<dyn BlockDevice>) -> Result<(), SysError> {
+    do_swapoff(SwapBackend::Device(dev).key())
+}
+
+/// turn off swapping to the file `file`
+pub fn swapoff_file(file: Arc<dyn File>) -> Result<(), SysError> {
+    do_swapoff(SwapBackend::File(file).key())
+}
+
+/// read every page in the area `index` back into the address spaces
+fn try_to_unuse(index: usize) -> Result<(), SysError> {
+    // pages may be passed on by fork while at it, so look again
+    for _ in 0..3 {
+        let mut spaces = Vec::new();
+        TASK_MANAGER.for_each_task(|task| {
+            let vm_space = task.get_vm_space().clone();
+            if !spaces.iter().any(|s| Arc::ptr_eq(s, &vm_space)) {
+                spaces.push(vm_space);
+            }
+        });
+        for vm_space in spaces {
+            while !vm_space.lock().swap_in_area(index, reclaim::SWAP_CLUSTER_MAX)? {
+                balance();
+            }
+        }
+        shrink_cache();
+        if SWAP.lock().areas[index].as_ref().unwrap().inuse == 0 {
+            return Ok(());
+        }
+    }
+    Err(SysError::EBUSY)
+}
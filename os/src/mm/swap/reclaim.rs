@@ -0,0 +1,95 @@
+//! reclaim
+//!
+//! a clock over the anonymous pages of all address spaces: a page accessed
+//! since the hand last passed gets its accessed bit cleared and another
+//! round, one that was not is written out, so the least recently used pages
+//! go first.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use alloc::{sync::Arc, vec::Vec};
+
+use crate::{mm::allocator::{free_frames, total_frames}, processor::processor::space_active_elsewhere, task::manager::TASK_MANAGER};
+
+use super::{shrink_cache, total_pages};
+
+/// pages written out at a time
+pub const SWAP_CLUSTER_MAX: usize = 32;
+
+/// pid of the address space the hand is at
+static HAND: AtomicUsize = AtomicUsize::new(0);
+
+/// reclaim once free frames fall below that
+fn low_watermark() -> usize {
+    total_frames() / 64
+}
+
+/// and until they are back above that
+fn high_watermark() -> usize {
+    total_frames() / 32
+}
+
+/// reclaim if frames run low, called before taking a fault so that the
+/// faulting address space can give pages too
+pub fn balance() {
+    if total_pages() == 0 || free_frames() >= low_watermark() {
+        return;
+    }
+    let want = high_watermark().saturating_sub(free_frames());
+    reclaim(want);
+}
+
+/// write cold anonymous pages out until `frames` frames are freed, returns
+/// the number freed
+pub fn reclaim(frames: usize) -> usize {
+    // swap itself may be what is short of frames
+    if super::SWAP.try_lock().is_none() {
+        return 0;
+    }
+    let start = free_frames();
+    let freed = || free_frames().saturating_sub(start);
+    shrink_cache();
+    // a page accessed once is passed over on the first round only
+    for _ in 0..2 {
+        let mut spaces = Vec::new();
+        let mut pids = Vec::new();
+        let Some(tasks) = TASK_MANAGER.try_tasks_group() else {
+            break;
+        };
+        for task in tasks {
+            let vm_space = task.get_vm_space().clone();
+            if !spaces.iter().any(|s| Arc::ptr_eq(s, &vm_space)) {
+                spaces.push(vm_space);
+                pids.push(task.pid());
+            }
+        }
+        // go on after the address space the hand stopped at
+        let hand = HAND.load(Ordering::Relaxed);
+        let first = pids.iter().position(|&pid| pid >= hand).unwrap_or(0);
+        for i in (first..spaces.len()).chain(0..first) {
+            while freed() < frames {
+                let want = SWAP_CLUSTER_MAX.min(frames - freed());
+                // one being changed is passed over, it may be locked by the
+                // caller when called out of the frame allocator
+                let Some(mut vm_space) = spaces[i].try_lock() else {
+                    break;
+                };
+                // its pages cannot be unmapped without a remote tlb flush
+                if space_active_elsewhere(&spaces[i]) {
+                    break;
+                }
+                let (_, wrapped) = vm_space.swap_out_cold(want);
+                drop(vm_space);
+                shrink_cache();
+                if wrapped {
+                    break;
+                }
+            }
+            if freed() >= frames {
+                HAND.store(pids[i], Ordering::Relaxed);
+                return freed();
+            }
+        }
+    }
+    freed()
+}
@@ -7,7 +7,7 @@ use xmas_elf::{reader::Reader, ElfFile};
 
 use crate::{ipc::sysv, fs::vfs::File, sync::mutex::{spin_mutex::SpinMutex, MutexSupport}, syscall::{mm::MmapFlags, SysError, SysResult}, task::utils::AuxHeader};
 
-use super::{allocator::{FrameAllocator, SlabAllocator}, FrameTracker, PageTable};
+use super::{allocator::{FrameAllocator, SlabAllocator}, swap::SwapSlot, FrameTracker, PageTable};
 
 /// Type of Kernel's Virtual Memory Area
 #[derive(Debug, Clone, Copy,  PartialEq, Eq)]
@@ -155,6 +155,8 @@ pub struct UserVmArea {
     pub vma_type: UserVmAreaType,
     pub map_perm: MapPerm,
     frames: BTreeMap<VirtPageNum, StrongArc<FrameTracker>>,
+    /// pages written out to swap
+    swapped: BTreeMap<VirtPageNum, SwapSlot>,
     /// for mmap usage
     pub file: UserVmFile,
     pub map_flags: MapFlags,
@@ -227,6 +229,7 @@ impl UserVmArea {
             vma_type,
             map_perm,
             frames: BTreeMap::new(),
+            swapped: BTreeMap::new(),
             file: UserVmFile::None,
             map_flags: MapFlags::empty(),
             offset: 0,
@@ -247,6 +250,7 @@ impl UserVmArea {
             vma_type: UserVmAreaType::Mmap,
             map_perm,
             frames: BTreeMap::new(),
+            swapped: BTreeMap::new(),
             file,
             map_flags: flags.into(),
             offset,
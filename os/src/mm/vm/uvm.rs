@@ -6,7 +6,7 @@ use log::info;
 use range_map::RangeMap;
 use xmas_elf::reader::Reader;
 
-use crate::{config::PAGE_SIZE, fs::{page, utils::FileReader, vfs::{dentry::global_find_dentry, inode::InodeMode, DentryState, File}, OpenFlags}, ipc::sysv::{self, ShmObj}, mm::{allocator::{frames_alloc, FrameAllocator, SlabAllocator}, vm, FrameTracker, PageTable, KVMSPACE}, sync::mutex::{spin_rw_mutex::SpinRwMutex, MutexSupport, SpinNoIrqLock}, syscall::{mm::MmapFlags, SysError, SysResult}, task::utils::{generate_early_auxv, AuxHeader, AT_BASE, AT_CLKTCK, AT_EGID, AT_ENTRY, AT_EUID, AT_FLAGS, AT_GID, AT_HWCAP, AT_NOTELF, AT_PAGESZ, AT_PHDR, AT_PHENT, AT_PHNUM, AT_PLATFORM, AT_RANDOM, AT_SECURE, AT_UID}, utils::{round_down_to_page, timer::TimerGuard}};
+use crate::{config::PAGE_SIZE, fs::{page::{self, cache::PageCache, page::Page}, utils::FileReader, vfs::{dentry::global_find_dentry, inode::InodeMode, DentryState, File}, OpenFlags}, ipc::sysv::{self, ShmObj}, processor::processor::space_active_elsewhere, mm::{allocator::{frames_alloc, FrameAllocator, SlabAllocator}, swap::{self, SwapSlot}, vm, FrameTracker, PageTable, KVMSPACE}, sync::mutex::{spin_rw_mutex::SpinRwMutex, MutexSupport, SpinNoIrqLock}, syscall::{mm::MmapFlags, SysError, SysResult}, task::{current_task, manager::TASK_MANAGER, task::Shared, utils::{generate_early_auxv, AuxHeader, AT_BASE, AT_CLKTCK, AT_EGID, AT_ENTRY, AT_EUID, AT_FLAGS, AT_GID, AT_HWCAP, AT_NOTELF, AT_PAGESZ, AT_PHDR, AT_PHENT, AT_PHNUM, AT_PLATFORM, AT_RANDOM, AT_SECURE, AT_UID}}, utils::{round_down_to_page, timer::TimerGuard}};
 
 use super::{KernVmArea, KernVmAreaType, KernVmSpaceHal, MapFlags, MaxEndVpn, PageFaultAccessType, StartPoint, UserVmArea, UserVmAreaType, UserVmAreaView, UserVmFile, UserVmSpaceHal};
 
@@ -14,7 +14,9 @@ use super::{KernVmArea, KernVmAreaType, KernVmSpaceHal, MapFlags, MaxEndVpn, Pag
 pub struct UserVmSpace {
     page_table: PageTable,
     areas: RangeMap<VirtPageNum, UserVmArea>,
-    brk: Range<VirtAddr>
+    brk: Range<VirtAddr>,
+    /// where the reclaimer goes on
+    swap_hand: VirtPageNum,
 }
 
 impl UserVmSpace {
@@ -24,6 +26,7 @@ impl UserVmSpace {
             page_table: PageTable::new_in(0, FrameAllocator),
             areas: RangeMap::new(),
             brk: VirtAddr(0)..VirtAddr(0),
+            swap_hand: VirtPageNum(0),
         }
     }
 
@@ -402,6 +405,160 @@ impl UserVmSpace {
         Some(area.into())
     }
 
+    /// views of all areas in address order
+    pub fn area_views(&self) -> Vec<UserVmAreaView> {
+        self.areas.iter().map(|(_, area)| area.into()).collect()
+    }
+
+    /// number of pages backed by frames
+    pub fn resident_pages(&self) -> usize {
+        self.areas.iter().map(|(_, area)| area.frames.len()).sum()
+    }
+
+    /// number of pages written out to swap
+    pub fn swapped_pages(&self) -> usize {
+        self.areas.iter().map(|(_, area)| area.swapped.len()).sum()
+    }
+
+    /// write out up to `max` anonymous pages not accessed since the last
+    /// call, going on from where that stopped. returns the number written
+    /// out and whether there is nothing more to look at this round
+    pub fn swap_out_cold(&mut self, max: usize) -> (usize, bool) {
+        let mut count = 0;
+        let hand = self.swap_hand;
+        for (range, area) in self.areas.iter_mut() {
+            if range.end <= hand {
+                continue;
+            }
+            let vpns: Vec<_> = area.frames.range(hand..).map(|(&vpn, _)| vpn).collect();
+            for vpn in vpns {
+                if count == max {
+                    self.swap_hand = vpn;
+                    return (count, false);
+                }
+                match area.swap_out_page(&mut self.page_table, vpn) {
+                    Ok(true) => count += 1,
+                    Ok(false) => {}
+                    // swap is full
+                    Err(_) => return (count, true),
+                }
+            }
+        }
+        self.swap_hand = VirtPageNum(0);
+        (count, true)
+    }
+
+    /// read back up to `max` pages written out to the swap area `index`,
+    /// whether all of them are back
+    pub fn swap_in_area(&mut self, index: usize, max: usize) -> Result<bool, SysError> {
+        let mut count = 0;
+        for (_, area) in self.areas.iter_mut() {
+            let vpns: Vec<_> = area.swapped.iter()
+                .filter(|(_, slot)| slot.entry().area() == index)
+                .map(|(&vpn, _)| vpn)
+                .collect();
+            for vpn in vpns {
+                if count == max {
+                    return Ok(false);
+                }
+                let slot = area.swapped.remove(&vpn).unwrap();
+                area.swap_in_page(&mut self.page_table, vpn, slot)?;
+                count += 1;
+            }
+        }
+        Ok(true)
+    }
+
+    /// free the private memory of a process being killed, what it touches
+    /// again on its way out is faulted in afresh. returns the number of
+    /// frames given back
+    pub fn reap(&mut self) -> usize {
+        let mut count = 0;
+        for (_, area) in self.areas.iter_mut() {
+            if area.map_flags.contains(MapFlags::SHARED) || area.file.is_shm() {
+                continue;
+            }
+            area.unmap(&mut self.page_table);
+            count += area.frames.values().filter(|frame| frame.get_owners() == 1).count();
+            area.frames.clear();
+            area.swapped.clear();
+        }
+        count
+    }
+
+    /// the dirty pages of shared file mappings in [va, va + len), with their
+    /// cache and file offset. ENOMEM if part of the range is not mapped
+    pub fn sync_shared(&self, va: VirtAddr, len: usize) -> Result<Vec<(Arc<PageCache>, usize, Arc<Page>)>, SysError> {
+        let range = va.floor()..(va + len).ceil();
+        let mut next = range.start;
+        let mut dirty = Vec::new();
+        for (area_range, area) in self.areas.iter() {
+            if area_range.end <= range.start || area_range.start >= range.end {
+                continue;
+            }
+            if area_range.start > next {
+                return Err(SysError::ENOMEM);
+            }
+            next = area_range.end;
+            let UserVmFile::File(file) = &area.file else {
+                continue;
+            };
+            if !area.map_flags.contains(MapFlags::SHARED) {
+                continue;
+            }
+            // only files with a backing store have something to write back
+            let Some(cache) = file.inode().and_then(|inode| inode.cache()).filter(|cache| cache.host().is_some()) else {
+                continue;
+            };
+            let start = area_range.start.max(range.start);
+            let end = area_range.end.min(range.end);
+            for (&vpn, frame) in area.frames.range(start..end) {
+                let offset = area.offset + (vpn.0 - area_range.start.0) * Constant::PAGE_SIZE;
+                let Some(page) = cache.get_pages().lock().load(offset / Constant::PAGE_SIZE).cloned() else {
+                    continue;
+                };
+                if page.ppn() != frame.range_ppn.start {
+                    continue;
+                }
+                // shared file pages are mapped read only while clean, the
+                // first store faults and dirties the page, so the sticky D
+                // bit is not needed to tell
+                if page.is_dirty() {
+                    dirty.push((cache.clone(), offset, page));
+                }
+            }
+        }
+        if next < range.end {
+            return Err(SysError::ENOMEM);
+        }
+        Ok(dirty)
+    }
+
+    /// write protect the shared file mappings of `page`, so the next store
+    /// faults and dirties it again
+    fn write_protect_shared(&mut self, page: &Page) {
+        let offset = page.index * Constant::PAGE_SIZE;
+        for (range, area) in self.areas.iter() {
+            if !area.map_flags.contains(MapFlags::SHARED) || !matches!(area.file, UserVmFile::File(_)) {
+                continue;
+            }
+            if offset < area.offset || offset >= area.offset + (range.end.0 - range.start.0) * Constant::PAGE_SIZE {
+                continue;
+            }
+            let vpn = range.start + (offset - area.offset) / Constant::PAGE_SIZE;
+            if area.frames.get(&vpn).map_or(true, |frame| frame.range_ppn.start != page.ppn()) {
+                continue;
+            }
+            if let Some((pte, _)) = self.page_table.find_pte(vpn) {
+                if pte.is_valid() && pte.is_writable() {
+                    pte.set_writable(false);
+                    pte.set_dirty(false);
+                    unsafe { Instruction::tlb_flush_addr(vpn.start_addr().0); }
+                }
+            }
+        }
+    }
+
     pub fn get_area_mut(&mut self, va: VirtAddr) -> Option<&mut UserVmArea> {
         self.areas.get_mut(va.floor())
     }
@@ -545,6 +702,7 @@ impl UserVmSpace {
     pub fn clear(&mut self) {
         self.areas.iter_mut().for_each(|(_, vma)| {
             vma.frames.clear();
+            vma.swapped.clear();
         });
     }
 }
@@ -586,7 +744,9 @@ impl UserVmSpace {
         log::info!("[load_dl] interp {}", interp);
 
         let interp_file;
-        let dentry = global_find_dentry(&interp).expect("cannot find interp dentry");
+        // the interpreter is looked up under the root of the exec'ing task
+        let in_root = |path: &str| current_task().map_or(path.to_string(), |task| task.path_from_root(path));
+        let dentry = global_find_dentry(&in_root(&interp))?;
         if dentry.is_negative() {
             log::warn!("[load_dl] missing dl {}", interp);
             return Err(SysError::ENOENT);
@@ -596,7 +756,7 @@ impl UserVmSpace {
         let dentry = if dentry.inode().unwrap().inode_type() == InodeMode::LINK {
             let inode = dentry.inode().unwrap();
             let follow_path = inode.readlink()?;
-            global_find_dentry(&follow_path)?
+            global_find_dentry(&in_root(&follow_path))?
         } else {
             dentry
         };
@@ -612,6 +772,30 @@ impl UserVmSpace {
     }
 }
 
+/// write protect the shared file mappings of `page` in all address spaces,
+/// before it is cleaned. whether all of them are, one locked or active on
+/// another processor is left as it is
+pub fn write_protect_page(page: &Page) -> bool {
+    let Some(tasks) = TASK_MANAGER.try_tasks_group() else {
+        return false;
+    };
+    let mut spaces: Vec<Shared<UserVmSpace>> = Vec::new();
+    for task in tasks {
+        let vm_space = task.get_vm_space();
+        if !spaces.iter().any(|s| Arc::ptr_eq(s, vm_space)) {
+            spaces.push(vm_space.clone());
+        }
+    }
+    let mut all = true;
+    for space in spaces.iter() {
+        match space.try_lock() {
+            Some(mut vm_space) if !space_active_elsewhere(space) => vm_space.write_protect_shared(page),
+            _ => all = false,
+        }
+    }
+    all
+}
+
 impl Drop for UserVmSpace {
     fn drop(&mut self) {
         // if this page table is using, switch to KVMSPACE
@@ -660,6 +844,7 @@ impl UserVmArea {
         let ret = Self {
             range_va: p.start_addr()..self.range_va.end,
             frames: self.frames.split_off(&p),
+            swapped: self.swapped.split_off(&p),
             map_perm: self.map_perm,
             vma_type: self.vma_type,
             file: self.file.clone(),
@@ -684,11 +869,18 @@ impl UserVmArea {
             let pte = page_table
                 .map(vpn, frame.range_ppn.start, self.map_perm, PageLevel::Small)
                 .expect(format!("vpn: {:#x} is mapped", vpn.0).as_str());
-            if frame.get_owners() > 1 && !self.map_flags.contains(MapFlags::SHARED) {
+            // a store to a shared file page faults first to dirty it
+            let shared_file = self.map_flags.contains(MapFlags::SHARED) && matches!(self.file, UserVmFile::File(_));
+            if shared_file || (frame.get_owners() > 1 && !self.map_flags.contains(MapFlags::SHARED)) {
                 pte.set_writable(false);
                 pte.set_dirty(false);
             }
         }
+        for (&vpn, slot) in self.swapped.iter() {
+            page_table
+                .map_swap(vpn, slot.entry().bits())
+                .expect(format!("vpn: {:#x} is mapped", vpn.0).as_str());
+        }
     }
 
     fn unmap(&self, page_table: &mut PageTable) {
@@ -696,6 +888,9 @@ impl UserVmArea {
             page_table.unmap(vpn);
             unsafe { Instruction::tlb_flush_addr(vpn.start_addr().0); }
         }
+        for &vpn in self.swapped.keys() {
+            page_table.unmap_swap(vpn);
+        }
     }
 
     fn clone_cow(&mut self, page_table: &mut PageTable) -> Result<Self, ()> {
@@ -711,6 +906,7 @@ impl UserVmArea {
         Ok(Self {
             range_va: self.range_va.clone(), 
             frames: self.frames.clone(), 
+            swapped: self.swapped.clone(),
             map_perm: self.map_perm.clone(), 
             vma_type: self.vma_type.clone(),
             file: self.file.clone(),
@@ -746,6 +942,10 @@ impl UserVmArea {
             other.frames.insert(new_vpn, frame.clone());
         }
         self.frames.clear();
+        for (vpn, slot) in core::mem::take(&mut self.swapped) {
+            let new_vpn = other_start + (vpn.0 - self_start.0);
+            other.swapped.insert(new_vpn, slot);
+        }
     }
 
     pub fn handle_page_fault(&mut self, 
@@ -762,6 +962,15 @@ impl UserVmArea {
         }
         match page_table.find_pte(vpn).map(|(pte, i)| (pte, PageLevel::from(i)) ) {
             Some((pte, _)) if pte.is_valid() => {
+                // reclaim cleared the accessed bit, and the hart does not set
+                // it itself but faults
+                if !pte.is_accessed() {
+                    pte.set_accessed(true);
+                    unsafe { Instruction::tlb_flush_addr(vpn.start_addr().0); }
+                    if !access_type.contains(PageFaultAccessType::WRITE) || pte.is_writable() {
+                        return Ok(());
+                    }
+                }
                 if !access_type.contains(PageFaultAccessType::WRITE) {
                     return Err(());
                 }
@@ -772,11 +981,15 @@ impl UserVmArea {
                     pte.set_writable(true);
                     pte.set_dirty(true);
                     unsafe { Instruction::tlb_flush_addr(vpn.start_addr().0); }
+                    if let Some((cache, offset, page)) = self.shared_file_page(vpn) {
+                        cache.set_page_dirty(offset, &page);
+                        page.set_mapped_writable();
+                    }
                     return Ok(());
                 }
                 let old_frame = self.frames.get_mut(&vpn).unwrap();
                 if old_frame.get_owners() > 1 {
-                    let new_frame = frames_alloc(1).unwrap();
+                    let new_frame = frames_alloc(1).ok_or(())?;
                     new_frame.range_ppn.get_slice_mut::<usize>().copy_from_slice(
                         old_frame.range_ppn.get_slice()
                     );
@@ -789,6 +1002,17 @@ impl UserVmArea {
                 Ok(())
             }
             _ => {
+                if let Some(slot) = self.swapped.remove(&vpn) {
+                    if let Err(e) = self.swap_in_page(page_table, vpn, slot) {
+                        log::warn!("[VmArea::handle_page_fault] swap in failed: {:?}", e);
+                        return Err(());
+                    }
+                    if access_type.contains(PageFaultAccessType::WRITE) {
+                        // a page still shared is copied like on any write
+                        return self.handle_page_fault(page_table, vpn, access_type);
+                    }
+                    return Ok(());
+                }
                 let ret = match self.vma_type {
                     UserVmAreaType::Data =>
                         UserDataHandler::handle_lazy_page_fault(self, page_table, vpn, access_type),
@@ -830,6 +1054,7 @@ impl UserVmArea {
         self.range_va.end = back.range_va.end;
         self.len += back.len;
         self.frames.append(&mut back.frames);
+        self.swapped.append(&mut back.swapped);
     }
 
     pub fn push_back(&mut self, back: Self) -> Result<(), Self> {
@@ -840,6 +1065,110 @@ impl UserVmArea {
         Ok(())
     }
 
+    /// the page cache page of the shared file mapping at `vpn`, with its
+    /// cache and file offset
+    fn shared_file_page(&self, vpn: VirtPageNum) -> Option<(Arc<PageCache>, usize, Arc<Page>)> {
+        let UserVmFile::File(file) = &self.file else {
+            return None;
+        };
+        let cache = file.inode()?.cache()?;
+        let offset = self.offset + (vpn.0 - self.range_vpn().start.0) * Constant::PAGE_SIZE;
+        let page = cache.get_page(offset)?;
+        let frame = self.frames.get(&vpn)?;
+        (page.ppn() == frame.range_ppn.start).then_some((cache, offset, page))
+    }
+
+    /// whether the page at `vpn` belongs to this area alone, neither the
+    /// file page it maps nor the zero page, so it can go to swap
+    fn is_anon_page(&self, vpn: VirtPageNum) -> bool {
+        if self.map_flags.contains(MapFlags::SHARED) || self.file.is_shm() {
+            return false;
+        }
+        let Some(frame) = self.frames.get(&vpn) else {
+            return false;
+        };
+        let ppn = frame.range_ppn.start;
+        if ppn == ZERO_PAGE_ARC.range_ppn.start || frame.range_ppn.clone().count() != 1 {
+            return false;
+        }
+        if let UserVmFile::File(file) = &self.file {
+            let offset = self.offset + (vpn.0 - self.range_vpn().start.0) * Constant::PAGE_SIZE;
+            let page = file.inode()
+                .and_then(|inode| inode.cache())
+                .and_then(|cache| cache.get_page(offset));
+            if page.map_or(false, |page| page.ppn() == ppn) {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// write the page at `vpn` out to swap if it is anonymous and was not
+    /// accessed since the last time, else clear its accessed bit. whether it
+    /// was written out. the space must not be active on another processor,
+    /// see `space_active_elsewhere`
+    fn swap_out_page(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) -> Result<bool, SysError> {
+        if !self.is_anon_page(vpn) {
+            return Ok(false);
+        }
+        let Some((pte, _)) = page_table.find_pte(vpn) else {
+            return Ok(false);
+        };
+        if !pte.is_valid() {
+            return Ok(false);
+        }
+        if pte.is_accessed() {
+            pte.set_accessed(false);
+            unsafe { Instruction::tlb_flush_addr(vpn.start_addr().0); }
+            return Ok(false);
+        }
+        // unmap first so no store lands in the page while it is written,
+        // an anonymous page has no other copy so it is written dirty or not
+        let old = page_table.unmap(vpn).unwrap();
+        unsafe { Instruction::tlb_flush_addr(vpn.start_addr().0); }
+        let slot = match swap::swap_out(self.frames.get(&vpn).unwrap()) {
+            Ok(slot) => slot,
+            Err(e) => {
+                let pte = page_table
+                    .map(vpn, old.ppn(), old.flags(), PageLevel::Small)
+                    .expect(format!("vpn: {:#x} is mapped", vpn.0).as_str());
+                pte.set_dirty(old.is_dirty());
+                pte.set_cow(old.is_cow());
+                return Err(e);
+            }
+        };
+        page_table
+            .map_swap(vpn, slot.entry().bits())
+            .expect(format!("vpn: {:#x} is mapped", vpn.0).as_str());
+        self.frames.remove(&vpn);
+        self.swapped.insert(vpn, slot);
+        Ok(true)
+    }
+
+    /// map the page written out to `slot` at `vpn` again
+    fn swap_in_page(&mut self, page_table: &mut PageTable, vpn: VirtPageNum, slot: SwapSlot) -> Result<(), SysError> {
+        let frame = match swap::swap_in(&slot) {
+            Ok(frame) => frame,
+            Err(e) => {
+                self.swapped.insert(vpn, slot);
+                return Err(e);
+            }
+        };
+        drop(slot);
+        page_table.unmap_swap(vpn);
+        let mut perm = self.map_perm;
+        if frame.get_owners() > 1 {
+            // still shared with those it was shared with on fork
+            perm.remove(MapPerm::W);
+        }
+        page_table
+            .map(vpn, frame.range_ppn.start, perm, PageLevel::Small)
+            .expect(format!("vpn: {:#x} is mapped", vpn.0).as_str());
+        unsafe { Instruction::tlb_flush_addr(vpn.start_addr().0); }
+        self.frames.insert(vpn, frame);
+        Ok(())
+    }
+
     fn access_no_fault(&self, vpn: VirtPageNum, access_type: PageFaultAccessType) -> bool {
         if let Some(frame) = self.frames.get(&vpn) {
             if access_type.contains(PageFaultAccessType::WRITE) && !self.map_flags.contains(MapFlags::SHARED){
@@ -872,6 +1201,7 @@ impl Clone for UserVmArea {
             vma_type: self.vma_type.clone(), 
             map_perm: self.map_perm.clone(), 
             frames,
+            swapped: self.swapped.clone(),
             file: self.file.clone(),
             map_flags: self.map_flags.clone(),
             offset: self.offset,
@@ -1019,20 +1349,26 @@ impl PageFaultProcessor {
     ) -> Result<(), ()> {
         let inode = file.inode().ok_or(())?.clone();
         // share file mapping
-        let page = match inode.read_page_at(offset) {
+        let page = match inode.clone().read_page_at(offset) {
             Some(page) => page,
             None => { 
                 log::error!("[map_shared_file] no page");
                 return Err(());
             }
         };
-        // map a single page
+        // map a single page, read only until the first store dirties it
+        let write = access_type.contains(PageFaultAccessType::WRITE);
+        let map_perm = if write { perm } else { perm - MapPerm::W };
         let pte = page_table
-            .map(vpn, page.ppn(), perm, PageLevel::Small)
+            .map(vpn, page.ppn(), map_perm, PageLevel::Small)
             .expect(format!("vpn: {:#x} is mapped", vpn.0).as_str());
-        if access_type.contains(PageFaultAccessType::WRITE) {
+        if write {
             pte.set_dirty(true);
-            page.set_dirty();
+            match inode.cache() {
+                Some(cache) => cache.set_page_dirty(offset, &page),
+                None => page.set_dirty(),
+            }
+            page.set_mapped_writable();
         }
         frames.insert(vpn, page.frame());
         unsafe { Instruction::tlb_flush_addr(vpn.start_addr().0); }
@@ -249,3 +249,20 @@ pub struct SockAddrUn {
     pub path: [u8; 108],
 }
 
+impl SockAddrUn {
+    /// length of the address as reported to user space:
+    /// family only for unnamed sockets, the name (plus the leading nul)
+    /// for abstract ones, the path with its terminating nul otherwise
+    pub fn len(&self) -> usize {
+        let family_len = size_of::<u16>();
+        if self.path[0] != 0 {
+            let path_len = self.path.iter().position(|&c| c == 0).unwrap_or(self.path.len() - 1);
+            return family_len + path_len + 1;
+        }
+        match self.path.iter().rposition(|&c| c != 0) {
+            Some(last) => family_len + last + 1,
+            None => family_len,
+        }
+    }
+}
+
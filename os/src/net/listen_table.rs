@@ -2,6 +2,7 @@ use alloc::{boxed::Box, collections::VecDeque, vec::Vec};
 use fatfs::{info, warn};
 use core::{
     ops::{Deref, DerefMut},
+    sync::atomic::Ordering,
     task::Waker,
 };
 use smoltcp::{
@@ -31,7 +32,7 @@ impl ListenEntry {
     pub fn new(listen_endpoint: IpListenEndpoint, waker: &Waker) -> Self {
         Self {
             listen_endpoint,
-            syn_queue: VecDeque::with_capacity(LISTEN_QUEUE_SIZE),
+            syn_queue: VecDeque::new(),
             waker: waker.clone(),
         }
     }
@@ -151,7 +152,7 @@ impl ListenTable {
                 log::warn!("[LISTEN_TABLE] not listening on addr {}", dst.addr);
                 return;
             }
-            if entry.syn_queue.len() >= LISTEN_QUEUE_SIZE {
+            if entry.syn_queue.len() >= LISTEN_QUEUE_SIZE.load(Ordering::Relaxed) {
                 log::warn!("[LISTEN_TABLE] syn_queue overflow!");
                 return;
             }
@@ -1,4 +1,4 @@
-use core::{ops::DerefMut, time::Duration};
+use core::{ops::DerefMut, sync::atomic::AtomicUsize, time::Duration};
 
 use alloc::{boxed::Box, collections::btree_map::BTreeMap, vec,vec::Vec};
 use listen_table::ListenTable;
@@ -9,7 +9,7 @@ use socket::SockResult;
 use spin::{Lazy, Once};
 use strum::FromRepr;
 
-use crate::{devices::{net::NetDeviceWrapper, NetDevice}, drivers::net::{init_network_device, loopback::LoopbackDevice}, net::addr::UNSPECIFIED_LISTEN_ENDPOINT, sync::{mutex::{SpinNoIrq, SpinNoIrqLock}, UPSafeCell}, syscall::SysError, timer::{get_current_time_duration, get_current_time_us, timer::{Timer, TimerEvent, TIMER_MANAGER}}};
+use crate::{devices::{net::{NetDeviceWrapper, NetStats}, NetDevice}, drivers::net::{init_network_device, loopback::LoopbackDevice}, net::addr::UNSPECIFIED_LISTEN_ENDPOINT, sync::{mutex::{SpinNoIrq, SpinNoIrqLock}, UPSafeCell}, syscall::SysError, sysctl::{register_sysctl, IntSysctl, RangeSysctl}, timer::{get_current_time_duration, get_current_time_us, timer::{Timer, TimerEvent, TIMER_MANAGER}}};
 /// Network Address Module
 pub mod addr;
 /// alg Module
@@ -24,6 +24,10 @@ pub mod udp;
 pub mod listen_table;
 /// socketpair concerning
 pub mod socketpair;
+/// AF_UNIX sockets
+pub mod unix;
+/// socket control messages
+pub mod scm;
 #[repr(u16)]
 #[derive(Debug, Clone, Copy, FromRepr, PartialEq, Eq, PartialOrd, Ord)]
 /// socket address family, used for syscalls
@@ -164,7 +168,18 @@ const CONFIG_RANDOM_SEED: u64 = 0x3A0C_1495_BC68_9A2C; // for smoltcp random see
 const PORT_START: u16 = 0xc000; // 49152
 const PORT_END: u16 = 0xffff;   // 65535
 
-const LISTEN_QUEUE_SIZE: usize = 512;
+/// ports handed out by get_ephemeral_port, tunable as net.ipv4.ip_local_port_range
+static IP_LOCAL_PORT_RANGE: SpinNoIrqLock<(usize, usize)> =
+    SpinNoIrqLock::new((PORT_START as usize, PORT_END as usize));
+
+/// max pending connections of a listening socket, tunable as net.core.somaxconn
+static LISTEN_QUEUE_SIZE: AtomicUsize = AtomicUsize::new(512);
+
+/// net.ipv4.ip_local_port_range and net.core.somaxconn
+pub fn register_sysctls() {
+    register_sysctl("net.ipv4.ip_local_port_range", RangeSysctl::new(&IP_LOCAL_PORT_RANGE, 1, PORT_END as usize));
+    register_sysctl("net.core.somaxconn", IntSysctl::new(&LISTEN_QUEUE_SIZE, 1, 65535));
+}
 static LISTEN_TABLE: Lazy<ListenTable> = Lazy::new(ListenTable::new);
 
 /// A wrapper for SocketSet in smoltcp
@@ -180,7 +195,7 @@ const UDP_TX_BUF_LEN: usize = 64 * 1024;
 
 static ETH0: Once<InterfaceWrapper> = Once::new();
 /// A wrapper for interface in smoltcp
-struct InterfaceWrapper {
+pub struct InterfaceWrapper {
     /// The name of the network interface.
     name: &'static str,
     /// The Ethernet address of the network interface.
@@ -215,6 +230,18 @@ impl InterfaceWrapper {
     pub fn ethernet_address(&self) -> EthernetAddress {
         self.ether_addr
     }
+    /// max transmission unit, without the ethernet header
+    pub fn mtu(&self) -> usize {
+        let caps = smoltcp::phy::Device::capabilities(&*self.dev.lock());
+        match caps.medium {
+            Medium::Ethernet => caps.max_transmission_unit - 14,
+            _ => caps.max_transmission_unit,
+        }
+    }
+    /// read the packet counters of the device
+    pub fn with_stats<T>(&self, f: impl FnOnce(&NetStats) -> T) -> T {
+        f(self.dev.lock().stats())
+    }
     fn current_time() -> Instant {
         Instant::from_micros_const(get_current_time_us() as i64)
     }
@@ -260,14 +287,17 @@ impl InterfaceWrapper {
 }
 /// random port alloc
 pub fn get_ephemeral_port() -> SockResult<u16> {
-    const PORT_START: u16 = 0xc000;
-    const PORT_END: u16 = 0xffff;
     static CURR: SpinNoIrqLock<u16> = SpinNoIrqLock::new(PORT_START);
+    let (start, end) = *IP_LOCAL_PORT_RANGE.lock();
     let mut curr = CURR.lock();
+    // the range may have been changed since the last port
+    if (*curr as usize) < start || (*curr as usize) > end {
+        *curr = start as u16;
+    }
 
     let port = *curr;
-    if *curr == PORT_END {
-        *curr = PORT_START;
+    if *curr as usize == end {
+        *curr = start as u16;
     } else {
         *curr += 1;
     }
@@ -441,6 +471,7 @@ pub fn init_network() {
     }
     ETH0.call_once(|| eth0);
 
+    crate::fs::sysfs::add_net_iface(ETH0.get().unwrap());
     info!("created net interface {:?}:", ETH0.get().unwrap().name());
     info!("  ether:    {}", ETH0.get().unwrap().ethernet_address());
     info!("  ip:       {}", ip);
@@ -449,8 +480,7 @@ pub fn init_network() {
 }
 
 
-/// Unix Sock
-pub struct UnixSocket {}
+pub use unix::UnixSocket;
 
 /// ip white list
 pub const LOCAL_IPS: &[IpAddress] = &[
@@ -0,0 +1,118 @@
+//! socket control messages (SCM_RIGHTS / SCM_CREDENTIALS)
+//!
+//! ancillary data travels along with the bytes it was sent with,
+//! over socketpairs and AF_UNIX sockets.
+
+use alloc::{collections::vec_deque::VecDeque, sync::Arc, vec::Vec};
+
+use crate::{fs::vfs::File, task::current_task};
+
+/// level of the control messages below
+pub const SOL_SOCKET: i32 = 1;
+/// pass file descriptors
+pub const SCM_RIGHTS: i32 = 1;
+/// pass process credentials
+pub const SCM_CREDENTIALS: i32 = 2;
+/// maximum number of files in one SCM_RIGHTS message
+pub const SCM_MAX_FD: usize = 253;
+
+/// process credentials, `struct ucred`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UCred {
+    /// process id of the sender
+    pub pid: i32,
+    /// user id of the sender
+    pub uid: u32,
+    /// group id of the sender
+    pub gid: u32,
+}
+
+impl UCred {
+    /// credentials of the current task
+    pub fn current() -> Self {
+        let task = current_task().unwrap();
+        Self {
+            pid: task.pid() as i32,
+            uid: task.uid() as u32,
+            gid: task.gid() as u32,
+        }
+    }
+
+    /// what SO_PEERCRED reports for a socket without a peer
+    pub fn invalid() -> Self {
+        Self {
+            pid: 0,
+            uid: u32::MAX,
+            gid: u32::MAX,
+        }
+    }
+}
+
+/// ancillary data attached to a message
+#[derive(Clone, Default)]
+pub struct ScmData {
+    /// files passed with SCM_RIGHTS
+    pub files: Vec<Arc<dyn File>>,
+    /// credentials passed with SCM_CREDENTIALS
+    pub creds: Option<UCred>,
+}
+
+impl ScmData {
+    /// whether there is nothing to deliver
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty() && self.creds.is_none()
+    }
+
+    /// merge the data of a later message into this one
+    fn merge(&mut self, other: ScmData) {
+        self.files.extend(other.files);
+        if self.creds.is_none() {
+            self.creds = other.creds;
+        }
+    }
+}
+
+/// ancillary data queued in a byte stream, tagged with the stream
+/// offset of the first byte it was sent with
+#[derive(Default)]
+pub struct ScmQueue {
+    entries: VecDeque<(usize, ScmData)>,
+    write_pos: usize,
+    read_pos: usize,
+}
+
+impl ScmQueue {
+    /// create an empty queue
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// record `len` bytes written, with optional ancillary data
+    pub fn on_write(&mut self, len: usize, scm: Option<ScmData>) {
+        if let Some(scm) = scm.filter(|scm| !scm.is_empty()) {
+            self.entries.push_back((self.write_pos, scm));
+        }
+        self.write_pos = self.write_pos.wrapping_add(len);
+    }
+
+    /// record `len` bytes consumed, returning the ancillary data whose
+    /// bytes have started to be read
+    pub fn on_read(&mut self, len: usize) -> Option<ScmData> {
+        let start = self.read_pos;
+        self.read_pos = self.read_pos.wrapping_add(len);
+        let mut res: Option<ScmData> = None;
+        while let Some((offset, _)) = self.entries.front() {
+            // a zero-length packet still carries its ancillary data
+            if offset.wrapping_sub(start) >= len.max(1) {
+                break;
+            }
+            let (_, scm) = self.entries.pop_front().unwrap();
+            match res.as_mut() {
+                Some(res) => res.merge(scm),
+                None => res = Some(scm),
+            }
+        }
+        res
+    }
+}
@@ -4,7 +4,7 @@ use alloc::{boxed::Box, string::String, sync::Arc, vec::Vec};
 use async_trait::async_trait;
 use fatfs::info;
 use smoltcp::{socket::udp, wire::{IpAddress, IpEndpoint, IpListenEndpoint}};
-use crate::{fs::{vfs::{file::PollEvents, Dentry, File, FileInner}, OpenFlags}, net::{addr::ZERO_IPV4_ENDPOINT, crypto::{encode_raw, AlgInstance, AlgType, SockAddrAlg}, socketpair::{SocketPairConnection, SocketPairInternal}, LOCAL_IPS}, sync::mutex::{SpinNoIrq, SpinNoIrqLock}, syscall::sys_error::SysError, task::current_task, timer::ffi::TimeSpec};
+use crate::{fs::{vfs::{file::PollEvents, Dentry, File, FileInner}, OpenFlags}, net::{addr::ZERO_IPV4_ENDPOINT, crypto::{encode_raw, AlgInstance, AlgType, SockAddrAlg}, scm::{ScmData, UCred}, socketpair::{SocketPairConnection, SocketPairInternal}, unix::UnixAddr, LOCAL_IPS}, sync::mutex::{SpinNoIrq, SpinNoIrqLock}, syscall::sys_error::SysError, task::current_task, timer::ffi::TimeSpec};
 use crate::syscall::net::SocketType;
 use super::{addr::{SockAddr, SockAddrIn4, ZERO_IPV4_ADDR}, poll_interfaces, tcp::TcpSocket, udp::UdpSocket, SaFamily, UnixSocket};
 pub type SockResult<T> = Result<T, SysError>;
@@ -63,17 +63,17 @@ impl Sock {
                     udp.bind(local_endpoint)
                 }
             }
-            // todo: suit for most cases
-            Sock::Unix(_) => {Ok(())},
+            // unix sockets are bound through `UnixSocket::bind` with the raw address length
             _ => {
                 Err(SysError::EAFNOSUPPORT)
             }
         }
     }
     /// listen method for socket to listen for incoming connections, for server socket
-    pub fn listen(&self) -> SockResult<()>{
+    pub fn listen(&self, backlog: usize) -> SockResult<()>{
         match self {
             Sock::TCP(tcp) => tcp.listen(),
+            Sock::Unix(unix) => unix.listen(backlog),
             Sock::UDP(udp) => Err(SysError::EOPNOTSUPP),
             _ => Err(SysError::EAFNOSUPPORT),
         }
@@ -83,7 +83,8 @@ impl Sock {
         match self {
             Sock::TCP(tcp) => tcp.set_nonblocking(),
             Sock::UDP(udp) => udp.set_nonblocking(),
-            _ => {},
+            Sock::Unix(unix) => unix.set_nonblocking(),
+            Sock::SocketPair(pair) => pair.set_nonblocking(),
         }
     }
     /// get the peer_addr of the socket
@@ -97,6 +98,7 @@ impl Sock {
                 let peer_addr = udp_socket.peer_addr()?;
                 Ok(SockAddr::from_endpoint(peer_addr))
             },
+            Sock::Unix(unix) => unix.peer_addr(),
             _ =>  Err(SysError::EAFNOSUPPORT),
         }
     }
@@ -111,6 +113,7 @@ impl Sock {
                 let local_addr = udp_socket.local_addr()?;
                 Ok(SockAddr::from_endpoint(local_addr))
             },
+            Sock::Unix(unix) => Ok(unix.local_addr()),
             _ =>  Err(SysError::EAFNOSUPPORT),
         }
     }
@@ -124,7 +127,7 @@ impl Sock {
                     None => udp_socket.send(data).await,
                 }
             },
-            Sock::Unix(_) =>  Err(SysError::EAFNOSUPPORT),
+            Sock::Unix(unix) => unix.send_to(data, None, None).await,
             Sock::SocketPair(socket_pair) => socket_pair.send(data).await,
         }
     }
@@ -133,19 +136,75 @@ impl Sock {
         match self {
             Sock::TCP(tcp) => tcp.recv(data).await,
             Sock::UDP(udp_socket) => udp_socket.recv(data).await,
-            Sock::Unix(_) =>  Err(SysError::EAFNOSUPPORT),
+            Sock::Unix(unix) => {
+                let (res, _) = unix.recv_from(data).await?;
+                Ok((res, ZERO_IPV4_ENDPOINT))
+            },
             Sock::SocketPair(pair) => {
                 let res = pair.recv(data).await?;
                 Ok((res, ZERO_IPV4_ENDPOINT))
             },
         }
     }
+    /// recv data from the socket along with the address of the sender
+    pub async fn recv_from(&self, data: &mut [u8]) -> SockResult<(usize, SockAddr)>{
+        match self {
+            Sock::Unix(unix) => {
+                let (res, src) = unix.recv_from(data).await?;
+                Ok((res, src.to_sockaddr()))
+            },
+            Sock::SocketPair(pair) => {
+                let res = pair.recv(data).await?;
+                Ok((res, UnixAddr::Unnamed.to_sockaddr()))
+            },
+            _ => {
+                let (res, remote) = self.recv(data).await?;
+                Ok((res, SockAddr::from_endpoint(remote)))
+            }
+        }
+    }
+    /// send data along with ancillary data, only for unix sockets and socketpairs
+    pub async fn send_msg(&self, data: &[u8], scm: Option<ScmData>) -> SockResult<usize>{
+        match self {
+            Sock::Unix(unix) => unix.send_to(data, None, scm).await,
+            Sock::SocketPair(pair) => pair.send_with_scm(data, scm).await,
+            _ if scm.is_some() => Err(SysError::EOPNOTSUPP),
+            _ => self.send(data, None).await,
+        }
+    }
+    /// recv data along with the sender address and ancillary data
+    pub async fn recv_msg(&self, data: &mut [u8]) -> SockResult<(usize, SockAddr, ScmData)>{
+        match self {
+            Sock::Unix(unix) => {
+                let (res, src, scm) = unix.recv_msg(data).await?;
+                Ok((res, src.to_sockaddr(), scm))
+            },
+            Sock::SocketPair(pair) => {
+                let (res, scm) = pair.recv_with_scm(data).await?;
+                let mut scm = scm.unwrap_or_default();
+                scm.creds.get_or_insert(pair.peer_cred);
+                Ok((res, UnixAddr::Unnamed.to_sockaddr(), scm))
+            },
+            _ => {
+                let (res, src) = self.recv_from(data).await?;
+                Ok((res, src, ScmData::default()))
+            }
+        }
+    }
+    /// credentials of the connected peer
+    pub fn peer_cred(&self) -> SockResult<UCred>{
+        match self {
+            Sock::Unix(unix) => Ok(unix.peer_cred()),
+            Sock::SocketPair(pair) => Ok(pair.peer_cred),
+            _ => Err(SysError::ENOPROTOOPT),
+        }
+    }
     /// shutdown a connection
     pub fn shutdown(&self, how: u8) -> SockResult<()>{
         match self {
             Sock::TCP(tcp) => tcp.shutdown(how),
             Sock::UDP(udp_socket) => udp_socket.shutdown(),
-            Sock::Unix(_) =>  Err(SysError::EAFNOSUPPORT),
+            Sock::Unix(unix) => unix.shutdown(how),
             Sock::SocketPair(pair) => Ok(pair.close()),
         }
     }
@@ -207,6 +266,8 @@ pub struct Socket {
     pub dont_route: bool,
     /// socketopt version
     pub packet_version: AtomicU32,
+    /// socketopt SO_PASSCRED
+    pub pass_cred: AtomicBool,
     // !member concerning af_alg
     /// whether af_alg or not 
     pub is_af_alg: AtomicBool,
@@ -228,7 +289,7 @@ impl Socket {
                     _ => Sock::TCP(TcpSocket::new_v4_without_handle()),
                 }
             },
-            SaFamily::AfUnix => Sock::Unix(UnixSocket {  }),
+            SaFamily::AfUnix => Sock::Unix(UnixSocket::new(sk_type)),
             SaFamily::Alg => Sock::TCP(TcpSocket::new_v4_without_handle()),
             _ => Sock::TCP(TcpSocket::new_v4_without_handle()),
         };
@@ -257,6 +318,7 @@ impl Socket {
             ciphertext: SpinNoIrqLock::new(None),
             alg_instance: SpinNoIrqLock::new(None),
             packet_version: AtomicU32::new(0),
+            pass_cred: AtomicBool::new(false),
         }
     }
     /// new a socket with a given socket 
@@ -279,6 +341,7 @@ impl Socket {
             ciphertext: SpinNoIrqLock::new(None),
             alg_instance: SpinNoIrqLock::new(None),
             packet_version: AtomicU32::new(0),
+            pass_cred: AtomicBool::new(another.pass_cred.load(Ordering::Acquire)),
         }
     }
     /// get send buf size
@@ -397,7 +460,9 @@ impl File for Socket {
     async fn base_poll(&self, events:PollEvents) -> PollEvents {
         if let Sock::SocketPair(socket_pair) = &self.sk {
             return socket_pair.poll(events).await;
-        }else {
+        } else if let Sock::Unix(unix) = &self.sk {
+            return unix.poll(events).await;
+        } else {
              let mut res = PollEvents::empty();
             poll_interfaces();
             let netstate = self.sk.poll().await;
@@ -453,6 +518,7 @@ impl Socket {
             ciphertext: SpinNoIrqLock::new(self.ciphertext.lock().clone()),
             alg_instance: SpinNoIrqLock::new(self.alg_instance.lock().clone()),
             packet_version: AtomicU32::new(0),
+            pass_cred: AtomicBool::new(false),
         })
     }
 }
@@ -1,12 +1,16 @@
-use core::{future::Future, pin::Pin, task::{Context, Poll, Waker}};
-use alloc::collections::vec_deque::VecDeque;
+use core::{future::Future, pin::Pin, sync::atomic::{AtomicBool, Ordering}, task::{Context, Poll, Waker}};
+use alloc::{collections::vec_deque::VecDeque, vec};
 use alloc::sync::Arc;
 use spin::Mutex;
-use crate::{fs::vfs::file::PollEvents, net::{socket::Socket, SaFamily}, sync::mutex::SpinNoIrqLock, syscall::{net::SocketType, SysError}, utils::{get_waker, RingBuffer}};
+use crate::{fs::vfs::file::PollEvents, net::{scm::{ScmData, ScmQueue, UCred}, socket::Socket, SaFamily}, sync::mutex::SpinNoIrqLock, syscall::{net::SocketType, SysError}, utils::{get_waker, RingBuffer}};
 
 
 pub struct BufferEndpoint {
     buffer: Mutex<RingBuffer>,
+    /// lengths of the packets queued in buffer, only used by packet-oriented sockets
+    packets: Mutex<VecDeque<usize>>,
+    /// ancillary data sent along with the bytes in buffer
+    scm: Mutex<ScmQueue>,
     read_wakers: Mutex<VecDeque<Waker>>,
     write_wakers: Mutex<VecDeque<Waker>>,
 }
@@ -15,10 +19,26 @@ impl BufferEndpoint {
     pub fn new(capacity: usize) -> Self {
         Self {
             buffer: Mutex::new(RingBuffer::new(capacity)),
+            packets: Mutex::new(VecDeque::new()),
+            scm: Mutex::new(ScmQueue::new()),
             read_wakers: Mutex::new(VecDeque::new()),
             write_wakers: Mutex::new(VecDeque::new()),
         }
     }
+
+    /// whether there is something to read
+    fn readable(&self, packet: bool) -> bool {
+        if packet {
+            !self.packets.lock().is_empty()
+        } else {
+            !self.buffer.lock().is_empty()
+        }
+    }
+
+    /// whether `need` bytes can be written at once
+    fn writable(&self, need: usize) -> bool {
+        self.buffer.lock().available() >= need.max(1)
+    }
 }
 /// Shared metadata of socketpair,
 ///  managing communication buffers in both directions.
@@ -35,10 +55,13 @@ pub struct SocketPairMeta {
 /// Internal shared object of socketpair, similar to PipeInode.
 pub struct SocketPairInternal {
     meta: SpinNoIrqLock<SocketPairMeta>,
+    /// keep message boundaries (SOCK_SEQPACKET / SOCK_DGRAM)
+    packet: bool,
+    capacity: usize,
 }
 
 impl SocketPairInternal {
-    pub fn new(capacity: usize) -> Arc<Self> {
+    pub fn new(capacity: usize, sk_type: SocketType) -> Arc<Self> {
         let meta = SocketPairMeta {
             end1: BufferEndpoint::new(capacity),
             end2: BufferEndpoint::new(capacity),
@@ -47,6 +70,8 @@ impl SocketPairInternal {
         };
         Arc::new(Self {
             meta: SpinNoIrqLock::new(meta),
+            packet: sk_type != SocketType::STREAM,
+            capacity,
         })
     }
 }
@@ -68,22 +93,21 @@ impl Future for SocketPairReadFuture {
     type Output = PollEvents;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        let mut meta = self.internal.meta.lock();
+        let meta = self.internal.meta.lock();
         let (read_endpoint, other_end_closed_mutex) = if self.is_first_end {
             (&meta.end2, &meta.end2_closed)
         } else {
             (&meta.end1, &meta.end1_closed)
         };
-        let mut read_buffer = read_endpoint.buffer.lock();
 
-        if !read_buffer.is_empty() {
+        if read_endpoint.readable(self.internal.packet) {
             return Poll::Ready(PollEvents::IN);
         }
         if *other_end_closed_mutex {
             // The peer is closed and the buffer is empty, triggering HUP
             return Poll::Ready(PollEvents::HUP);
         }
-        
+
         // The buffer is empty and the peer is not closed, register waker and wait
         read_endpoint.read_wakers.lock().push_back(cx.waker().clone());
         Poll::Pending
@@ -94,11 +118,13 @@ impl Future for SocketPairReadFuture {
 pub struct SocketPairWriteFuture {
     internal: Arc<SocketPairInternal>,
     is_first_end: bool,
+    /// bytes that have to fit into the buffer at once
+    need: usize,
 }
 
 impl SocketPairWriteFuture {
-    pub fn new(internal: Arc<SocketPairInternal>, is_first_end: bool) -> Self {
-        Self { internal, is_first_end }
+    pub fn new(internal: Arc<SocketPairInternal>, is_first_end: bool, need: usize) -> Self {
+        Self { internal, is_first_end, need }
     }
 }
 
@@ -106,23 +132,22 @@ impl Future for SocketPairWriteFuture {
     type Output = PollEvents;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        let mut meta = self.internal.meta.lock();
+        let meta = self.internal.meta.lock();
         let (write_endpoint, other_end_closed_mutex) = if self.is_first_end {
             (&meta.end1, &meta.end2_closed)
         } else {
             (&meta.end2, &meta.end1_closed)
         };
-        let mut write_buffer = write_endpoint.buffer.lock();
 
-        if !write_buffer.is_empty() {
-            return Poll::Ready(PollEvents::IN);
-        }
         if *other_end_closed_mutex {
-            // The peer is closed and the buffer is empty, triggering HUP
-            return Poll::Ready(PollEvents::HUP);
+            // The peer is closed, writing would break the pipe
+            return Poll::Ready(PollEvents::ERR);
         }
-        
-        // The buffer is empty and the peer is not closed, register waker and wait
+        if write_endpoint.writable(self.need) {
+            return Poll::Ready(PollEvents::OUT);
+        }
+
+        // The buffer is full and the peer is not closed, register waker and wait
         write_endpoint.write_wakers.lock().push_back(cx.waker().clone());
         Poll::Pending
     }
@@ -134,84 +159,181 @@ impl Future for SocketPairWriteFuture {
 pub struct SocketPairConnection {
     pub internal: Arc<SocketPairInternal>,
     pub is_first_end: bool, // tell whether socket1 or socket2
+    pub non_block: AtomicBool,
+    /// credentials of the peer when the connection was made (SO_PEERCRED)
+    pub peer_cred: UCred,
 }
 
 impl SocketPairConnection {
+    /// create one end of the connection
+    pub fn new(internal: Arc<SocketPairInternal>, is_first_end: bool, non_block: bool, peer_cred: UCred) -> Self {
+        Self {
+            internal,
+            is_first_end,
+            non_block: AtomicBool::new(non_block),
+            peer_cred,
+        }
+    }
+
+    /// set the endpoint non-blocking
+    pub fn set_nonblocking(&self) {
+        self.non_block.store(true, Ordering::Release);
+    }
+
+    fn nonblock(&self) -> bool {
+        self.non_block.load(Ordering::Acquire)
+    }
+
     pub async fn recv(&self, buf: &mut [u8]) -> Result<usize, SysError> {
-        let revents = SocketPairReadFuture::new(self.internal.clone(), self.is_first_end).await;
+        self.recv_with_scm(buf).await.map(|(len, _)| len)
+    }
 
-        if revents.contains(PollEvents::HUP) {
-            return Ok(0); 
-        }
-        
-        let mut meta = self.internal.meta.lock();
-        let (mut read_buffer, mut writer_waker) = if self.is_first_end {
-            (meta.end2.buffer.lock(), meta.end2.write_wakers.lock())
+    /// Receive data together with the ancillary data sent along with it
+    pub async fn recv_with_scm(&self, buf: &mut [u8]) -> Result<(usize, Option<ScmData>), SysError> {
+        // checked again under the lock, another reader may have emptied the
+        // buffer since the wakeup
+        let meta = loop {
+            if self.nonblock() && !self.poll_now(PollEvents::IN, 0).intersects(PollEvents::IN | PollEvents::HUP) {
+                return Err(SysError::EAGAIN);
+            }
+            let revents = SocketPairReadFuture::new(self.internal.clone(), self.is_first_end).await;
+            if revents.contains(PollEvents::HUP) {
+                return Ok((0, None));
+            }
+            let meta = self.internal.meta.lock();
+            let read_endpoint = if self.is_first_end {
+                &meta.end2
+            } else {
+                &meta.end1
+            };
+            if read_endpoint.readable(self.internal.packet) {
+                break meta;
+            }
+        };
+        let read_endpoint = if self.is_first_end {
+            &meta.end2
         } else {
-            (meta.end1.buffer.lock(), meta.end1.write_wakers.lock())
+            &meta.end1
         };
+        let mut read_buffer = read_endpoint.buffer.lock();
 
-        let len = read_buffer.read(buf);
-        if len > 0 {
-            // Successfully read data and wake up the peer that may be waiting to write
-            if let Some(waker) = writer_waker.pop_front() {
-                waker.wake();
+        let (len, consumed) = if self.internal.packet {
+            // read one whole packet, the part not fitting in buf is discarded
+            let packet_len = read_endpoint.packets.lock().pop_front().unwrap_or(0);
+            let len = read_buffer.read(&mut buf[..packet_len.min(buf.len())]);
+            if packet_len > len {
+                let mut discard = vec![0u8; packet_len - len];
+                read_buffer.read(&mut discard);
             }
+            (len, packet_len)
+        } else {
+            let len = read_buffer.read(buf);
+            (len, len)
+        };
+        let scm = read_endpoint.scm.lock().on_read(consumed);
+        // Successfully read data and wake up the peer that may be waiting to write
+        if let Some(waker) = read_endpoint.write_wakers.lock().pop_front() {
+            waker.wake();
         }
-        Ok(len)
+        Ok((len, scm))
     }
 
     /// Send data to this endpoint (implement send)
     pub async fn send(&self, buf: &[u8]) -> Result<usize, SysError> {
-        let revents = SocketPairWriteFuture::new(self.internal.clone(), self.is_first_end).await;
+        self.send_with_scm(buf, None).await
+    }
 
-        if revents.contains(PollEvents::ERR) {
-            return Err(SysError::EPIPE); // The peer is closed, the pipe is broken
-        }
+    /// Send data along with ancillary data
+    pub async fn send_with_scm(&self, buf: &[u8], scm: Option<ScmData>) -> Result<usize, SysError> {
+        let need = if self.internal.packet {
+            if buf.len() > self.internal.capacity {
+                return Err(SysError::EMSGSIZE);
+            }
+            buf.len()
+        } else {
+            1
+        };
+        // the buffer is checked again under the lock, another sender may
+        // have filled it since the wakeup
+        let meta = loop {
+            if self.nonblock() && !self.poll_now(PollEvents::OUT, need).intersects(PollEvents::OUT | PollEvents::ERR) {
+                return Err(SysError::EAGAIN);
+            }
+            let revents = SocketPairWriteFuture::new(self.internal.clone(), self.is_first_end, need).await;
+            if revents.contains(PollEvents::ERR) {
+                return Err(SysError::EPIPE); // The peer is closed, the pipe is broken
+            }
+            let meta = self.internal.meta.lock();
+            let (write_endpoint, other_end_closed) = if self.is_first_end {
+                (&meta.end1, meta.end2_closed)
+            } else {
+                (&meta.end2, meta.end1_closed)
+            };
+            if other_end_closed {
+                return Err(SysError::EPIPE);
+            }
+            if write_endpoint.writable(need) {
+                break meta;
+            }
+        };
+        let write_endpoint = if self.is_first_end {
+            &meta.end1
+        } else {
+            &meta.end2
+        };
 
-        assert!(revents.contains(PollEvents::OUT));
+        let len = write_endpoint.buffer.lock().write(buf);
+        if self.internal.packet {
+            write_endpoint.packets.lock().push_back(len);
+        }
+        write_endpoint.scm.lock().on_write(len, scm);
+        // Successfully wrote data and wake up the peer that may be waiting to read
+        if let Some(waker) = write_endpoint.read_wakers.lock().pop_front() {
+            waker.wake();
+        }
+        Ok(len)
+    }
 
-        let mut meta = self.internal.meta.lock();
-        let (mut write_buffer, mut reader_waker) = if self.is_first_end {
-            (meta.end1.buffer.lock(), meta.end1.read_wakers.lock())
+    /// events ready right now, without registering any waker
+    fn poll_now(&self, events: PollEvents, need: usize) -> PollEvents {
+        let meta = self.internal.meta.lock();
+        let (read_endpoint, write_endpoint, other_end_closed) = if self.is_first_end {
+            (&meta.end2, &meta.end1, meta.end2_closed)
         } else {
-            (meta.end2.buffer.lock(), meta.end2.read_wakers.lock())
+            (&meta.end1, &meta.end2, meta.end1_closed)
         };
-
-        let len = write_buffer.write(buf);
-        if len > 0 {
-            // Successfully wrote data and wake up the peer that may be waiting to read
-            if let Some(waker) = reader_waker.pop_front() {
-                waker.wake();
+        let mut res = PollEvents::empty();
+        if events.contains(PollEvents::IN) {
+            if read_endpoint.readable(self.internal.packet) {
+                res |= PollEvents::IN;
+            } else if other_end_closed {
+                res |= PollEvents::HUP;
             }
         }
-        Ok(len)
+        if events.contains(PollEvents::OUT) {
+            if other_end_closed {
+                res |= PollEvents::ERR;
+            } else if write_endpoint.writable(need) {
+                res |= PollEvents::OUT;
+            }
+        }
+        res
     }
 
     /// Implementing poll logic
     pub async fn poll(&self, events: PollEvents) -> PollEvents {
         let mut res = PollEvents::empty();
         let waker = get_waker().await;
-        let mut meta = self.internal.meta.lock();
+        let meta = self.internal.meta.lock();
 
-        let (mut read_buffer, mut write_buffer, mut read_waker, mut write_waker, other_end_closed) = if self.is_first_end {
-            (
-                meta.end2.buffer.lock(), meta.end1.buffer.lock(), 
-                meta.end2.read_wakers.lock(), meta.end1.write_wakers.lock(),
-                meta.end2_closed
-            )
+        let (read_endpoint, write_endpoint, other_end_closed) = if self.is_first_end {
+            (&meta.end2, &meta.end1, meta.end2_closed)
         } else {
-            (
-                (
-                meta.end1.buffer.lock(), meta.end2.buffer.lock(), 
-                meta.end1.read_wakers.lock(), meta.end2.write_wakers.lock(),
-                meta.end1_closed
-            )
-            )
+            (&meta.end1, &meta.end2, meta.end1_closed)
         };
-        
+
         // Check for read events
-        if !read_buffer.is_empty() {
+        if read_endpoint.readable(self.internal.packet) {
             res |= PollEvents::IN;
         } else if other_end_closed {
             res |= PollEvents::HUP; // The peer is closed and there is no data to read
@@ -220,16 +342,17 @@ impl SocketPairConnection {
         // Check for write events
         if other_end_closed {
             res |= PollEvents::ERR; // The peer is closed, and the write operation will fail.
-        } else if !write_buffer.is_full() {
+        } else if write_endpoint.writable(1) {
             res |= PollEvents::OUT;
         }
 
         // If the requested event is not currently ready, register the waker
-        if events.contains(PollEvents::IN) && !res.contains(PollEvents::IN | PollEvents::HUP) {
-            read_waker.push_back(waker.clone());
+        let edge = events.contains(PollEvents::EDGE);
+        if events.contains(PollEvents::IN) && (edge || !res.intersects(PollEvents::IN | PollEvents::HUP)) {
+            read_endpoint.read_wakers.lock().push_back(waker.clone());
         }
-        if events.contains(PollEvents::OUT) && !res.contains(PollEvents::OUT | PollEvents::ERR) {
-            write_waker.push_back(waker);
+        if events.contains(PollEvents::OUT) && (edge || !res.intersects(PollEvents::OUT | PollEvents::ERR)) {
+            write_endpoint.write_wakers.lock().push_back(waker);
         }
 
         res
@@ -241,17 +364,17 @@ impl SocketPairConnection {
         if self.is_first_end {
             if meta.end1_closed { return; }
             meta.end1_closed = true;
-            // Wake up all wakers waiting for end1 to be written (they will receive ERR)
-            while let Some(waker) = meta.end1.write_wakers.lock().pop_front() { waker.wake(); }
-            // Wake up all wakers waiting for end1 to be read (they will receive HUP)
-            while let Some(waker) = meta.end2.read_wakers.lock().pop_front() { waker.wake(); }
+            // Wake up the peer waiting to read from end1 (they will receive HUP)
+            while let Some(waker) = meta.end1.read_wakers.lock().pop_front() { waker.wake(); }
+            // Wake up the peer waiting to write into end2 (they will receive ERR)
+            while let Some(waker) = meta.end2.write_wakers.lock().pop_front() { waker.wake(); }
         } else {
             if meta.end2_closed { return; }
             meta.end2_closed = true;
-            // Wake up all wakers waiting for end2 to be written (they will receive ERR)
-            while let Some(waker) = meta.end2.write_wakers.lock().pop_front() { waker.wake(); }
-            // Wake up all wakers waiting for end2 to be read (they will receive HUP)
-            while let Some(waker) = meta.end1.read_wakers.lock().pop_front() { waker.wake(); }
+            // Wake up the peer waiting to read from end2 (they will receive HUP)
+            while let Some(waker) = meta.end2.read_wakers.lock().pop_front() { waker.wake(); }
+            // Wake up the peer waiting to write into end1 (they will receive ERR)
+            while let Some(waker) = meta.end1.write_wakers.lock().pop_front() { waker.wake(); }
         }
     }
 }
@@ -263,12 +386,10 @@ impl Drop for SocketPairConnection {
 }
 
 pub fn make_socketpair(domain: SaFamily, sk_type: SocketType, capacity: usize, non_block: bool) -> (Arc<Socket>, Arc<Socket>) {
-    let internal = SocketPairInternal::new(capacity);
+    let internal = SocketPairInternal::new(capacity, sk_type);
 
-    let conn1 = SocketPairConnection {
-        internal: internal.clone(),
-        is_first_end: true,
-    };
+    let cred = UCred::current();
+    let conn1 = SocketPairConnection::new(internal.clone(), true, non_block, cred);
 
     let mut socket1 = Socket::new(
             domain,
@@ -276,11 +397,8 @@ pub fn make_socketpair(domain: SaFamily, sk_type: SocketType, capacity: usize, n
             non_block,
         );
     socket1.sk = super::socket::Sock::SocketPair(conn1);
-    
-    let conn2 = SocketPairConnection {
-        internal: internal,
-        is_first_end: false,
-    };
+
+    let conn2 = SocketPairConnection::new(internal, false, non_block, cred);
     let mut socket2 = Socket::new(
         domain,
         sk_type,
@@ -289,4 +407,4 @@ pub fn make_socketpair(domain: SaFamily, sk_type: SocketType, capacity: usize, n
     socket2.sk = super::socket::Sock::SocketPair(conn2);
 
     (Arc::new(socket1), Arc::new(socket2))
-}
\ No newline at end of file
+}
@@ -7,7 +7,7 @@ use rand::{rngs::SmallRng, Rng, SeedableRng};
 use smoltcp::{iface::SocketHandle, socket::{dns::GetQueryResultError, udp::{self, BindError, SendError}}, wire::{IpEndpoint, IpListenEndpoint}};
 use spin::{RwLock, Spin};
 
-use crate::{net::{LISTEN_TABLE, PORT_END, PORT_START, SOCK_RAND_SEED}, sync::mutex::SpinNoIrqLock, syscall::{SysError, SysResult}, task::current_task, utils::{get_waker, suspend_now, yield_now}};
+use crate::{net::{get_ephemeral_port, LISTEN_TABLE, PORT_END, PORT_START, SOCK_RAND_SEED}, sync::mutex::SpinNoIrqLock, syscall::{SysError, SysResult}, task::current_task, utils::{get_waker, suspend_now, yield_now}};
 
 use super::{addr::{is_unspecified, to_endpoint, SockAddr, UNSPECIFIED_LISTEN_ENDPOINT}, socket::{PollState, SockResult}, SocketSetWrapper, PORT_MANAGER, SOCKET_SET};
 
@@ -275,18 +275,7 @@ impl UdpSocket {
 
 impl UdpSocket {
     fn get_ephemeral_port(&self) -> SockResult<u16> {
-        const PORT_START: u16 = 0xc000;
-        const PORT_END: u16 = 0xffff;
-        static CURR: SpinNoIrqLock<u16> = SpinNoIrqLock::new(PORT_START);
-        let mut curr = CURR.lock();
-
-        let port = *curr;
-        if *curr == PORT_END {
-            *curr = PORT_START;
-        } else {
-            *curr += 1;
-        }
-        Ok(port)
+        get_ephemeral_port()
     }
 
     async fn block_on<F, R>(&self, mut f: F) -> SockResult<R>
@@ -0,0 +1,633 @@
+//! AF_UNIX sockets
+//!
+//! SOCK_STREAM and SOCK_SEQPACKET connections are carried by the same
+//! ring-buffer endpoints as socketpair, one `SocketPairInternal` per
+//! accepted connection. SOCK_DGRAM sockets own a bounded message queue
+//! which peers push into.
+
+use core::{future::Future, pin::Pin, sync::atomic::{AtomicBool, AtomicUsize, Ordering}, task::{Context, Poll, Waker}};
+
+use alloc::{collections::{btree_map::BTreeMap, vec_deque::VecDeque}, format, string::{String, ToString}, sync::{Arc, Weak}, vec::Vec};
+
+use crate::{fs::{vfs::{file::PollEvents, inode::InodeMode, Dentry, DentryState}, AtFlags}, sync::mutex::SpinNoIrqLock, syscall::{fs::at_helper1, net::SocketType, SysError}, task::current_task, utils::get_waker};
+
+use super::{addr::{SockAddr, SockAddrUn}, scm::{ScmData, UCred}, socket::SockResult, socketpair::{SocketPairConnection, SocketPairInternal}, SaFamily};
+
+/// buffer size of a unix connection or datagram queue
+pub const UNIX_BUF_LEN: usize = 16 * 4096;
+
+/// address of a unix socket
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum UnixAddr {
+    /// not bound
+    Unnamed,
+    /// bound to a filesystem path
+    Path(String),
+    /// bound in the abstract namespace, without the leading nul byte
+    Abstract(Vec<u8>),
+}
+
+impl UnixAddr {
+    /// parse the first `addr_len` bytes of a user `sockaddr_un`
+    pub fn from_sockaddr(addr: &SockAddrUn, addr_len: usize) -> SockResult<Self> {
+        let family_len = size_of::<u16>();
+        if addr_len < family_len || addr_len > size_of::<SockAddrUn>() {
+            return Err(SysError::EINVAL);
+        }
+        if addr.family != SaFamily::AfUnix as u16 {
+            return Err(SysError::EAFNOSUPPORT);
+        }
+        let path = &addr.path[..addr_len - family_len];
+        if path.is_empty() {
+            return Ok(UnixAddr::Unnamed);
+        }
+        if path[0] == 0 {
+            return Ok(UnixAddr::Abstract(path[1..].to_vec()));
+        }
+        let end = path.iter().position(|&c| c == 0).unwrap_or(path.len());
+        let path = core::str::from_utf8(&path[..end]).map_err(|_| SysError::EINVAL)?;
+        Ok(UnixAddr::Path(path.to_string()))
+    }
+
+    /// convert into a user `sockaddr_un`
+    pub fn to_sockaddr(&self) -> SockAddr {
+        let mut unix = SockAddrUn {
+            family: SaFamily::AfUnix as u16,
+            path: [0; 108],
+        };
+        match self {
+            UnixAddr::Unnamed => {}
+            UnixAddr::Path(path) => {
+                let len = path.len().min(unix.path.len() - 1);
+                unix.path[..len].copy_from_slice(&path.as_bytes()[..len]);
+            }
+            UnixAddr::Abstract(name) => {
+                let len = name.len().min(unix.path.len() - 1);
+                unix.path[1..len + 1].copy_from_slice(&name[..len]);
+            }
+        }
+        SockAddr { unix }
+    }
+}
+
+/// bound unix sockets, keyed by absolute path or abstract name
+static UNIX_BIND_TABLE: SpinNoIrqLock<BTreeMap<UnixAddr, Weak<UnixSocketInner>>> =
+    SpinNoIrqLock::new(BTreeMap::new());
+
+/// counter for autobind names
+static UNIX_AUTOBIND_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// a connection waiting in the backlog of a listening socket
+struct UnixPendingConn {
+    /// the server end of the connection
+    conn: SocketPairConnection,
+    /// address of the connecting socket
+    peer_addr: UnixAddr,
+}
+
+/// listening state of a connection-oriented socket
+struct UnixListener {
+    backlog: usize,
+    /// credentials of the listening process, reported to connecting peers
+    cred: UCred,
+    pending: VecDeque<UnixPendingConn>,
+    accept_wakers: VecDeque<Waker>,
+    connect_wakers: VecDeque<Waker>,
+}
+
+enum UnixState {
+    /// freshly created or bound
+    Idle,
+    /// waiting for connections
+    Listening(UnixListener),
+    /// connection-oriented socket with an established connection
+    Connected {
+        conn: Arc<SocketPairConnection>,
+        peer_addr: UnixAddr,
+    },
+    /// datagram socket with a default destination
+    DgramConnected {
+        peer: Weak<UnixSocketInner>,
+        peer_addr: UnixAddr,
+    },
+}
+
+/// a queued datagram
+struct UnixDgram {
+    data: Vec<u8>,
+    src: UnixAddr,
+    /// ancillary data, credentials are always filled in
+    scm: ScmData,
+}
+
+/// receive queue of a datagram socket
+struct DgramQueue {
+    msgs: VecDeque<UnixDgram>,
+    bytes: usize,
+    read_wakers: VecDeque<Waker>,
+    write_wakers: VecDeque<Waker>,
+}
+
+/// shared part of a unix socket, looked up by peers through the bind table
+pub struct UnixSocketInner {
+    sk_type: SocketType,
+    non_block: AtomicBool,
+    /// address as given to bind
+    local: SpinNoIrqLock<UnixAddr>,
+    /// key in the bind table
+    bind_key: SpinNoIrqLock<Option<UnixAddr>>,
+    state: SpinNoIrqLock<UnixState>,
+    dgram: SpinNoIrqLock<DgramQueue>,
+}
+
+/// Unix Sock
+pub struct UnixSocket {
+    inner: Arc<UnixSocketInner>,
+}
+
+impl UnixSocket {
+    /// create an unbound unix socket
+    pub fn new(sk_type: SocketType) -> Self {
+        Self::with_state(sk_type, UnixAddr::Unnamed, UnixState::Idle)
+    }
+
+    fn with_state(sk_type: SocketType, local: UnixAddr, state: UnixState) -> Self {
+        let inner = UnixSocketInner {
+            sk_type,
+            non_block: AtomicBool::new(false),
+            local: SpinNoIrqLock::new(local),
+            bind_key: SpinNoIrqLock::new(None),
+            state: SpinNoIrqLock::new(state),
+            dgram: SpinNoIrqLock::new(DgramQueue {
+                msgs: VecDeque::new(),
+                bytes: 0,
+                read_wakers: VecDeque::new(),
+                write_wakers: VecDeque::new(),
+            }),
+        };
+        Self { inner: Arc::new(inner) }
+    }
+
+    fn is_stream(&self) -> bool {
+        self.inner.sk_type != SocketType::DGRAM
+    }
+
+    fn nonblock(&self) -> bool {
+        self.inner.non_block.load(Ordering::Acquire)
+    }
+
+    /// set socket non-blocking
+    pub fn set_nonblocking(&self) {
+        self.inner.non_block.store(true, Ordering::Release);
+        if let UnixState::Connected { conn, .. } = &*self.inner.state.lock() {
+            conn.set_nonblocking();
+        }
+    }
+
+    /// assign `addr` to the socket
+    pub fn bind(&self, addr: UnixAddr) -> SockResult<()> {
+        if *self.inner.local.lock() != UnixAddr::Unnamed {
+            return Err(SysError::EINVAL);
+        }
+        let (local, key) = match addr {
+            UnixAddr::Unnamed => {
+                // autobind: pick an unused abstract name
+                let mut table = UNIX_BIND_TABLE.lock();
+                let name = loop {
+                    let id = UNIX_AUTOBIND_ID.fetch_add(1, Ordering::Relaxed) & 0xfffff;
+                    let name = UnixAddr::Abstract(format!("{:05x}", id).into_bytes());
+                    if table.get(&name).and_then(|w| w.upgrade()).is_none() {
+                        break name;
+                    }
+                };
+                table.insert(name.clone(), Arc::downgrade(&self.inner));
+                (name.clone(), name)
+            }
+            UnixAddr::Abstract(_) => {
+                let mut table = UNIX_BIND_TABLE.lock();
+                if table.get(&addr).and_then(|w| w.upgrade()).is_some() {
+                    return Err(SysError::EADDRINUSE);
+                }
+                table.insert(addr.clone(), Arc::downgrade(&self.inner));
+                (addr.clone(), addr)
+            }
+            UnixAddr::Path(ref path) => {
+                let abs_path = create_socket_inode(path)?;
+                let key = UnixAddr::Path(abs_path);
+                UNIX_BIND_TABLE.lock().insert(key.clone(), Arc::downgrade(&self.inner));
+                (addr, key)
+            }
+        };
+        log::info!("[UnixSocket::bind] bind to {:?}", local);
+        *self.inner.local.lock() = local;
+        *self.inner.bind_key.lock() = Some(key);
+        Ok(())
+    }
+
+    /// start listening for connections
+    pub fn listen(&self, backlog: usize) -> SockResult<()> {
+        if !self.is_stream() {
+            return Err(SysError::EOPNOTSUPP);
+        }
+        if *self.inner.local.lock() == UnixAddr::Unnamed {
+            return Err(SysError::EINVAL);
+        }
+        let mut state = self.inner.state.lock();
+        match &mut *state {
+            UnixState::Idle => {
+                *state = UnixState::Listening(UnixListener {
+                    backlog: backlog.max(1),
+                    cred: UCred::current(),
+                    pending: VecDeque::new(),
+                    accept_wakers: VecDeque::new(),
+                    connect_wakers: VecDeque::new(),
+                });
+                Ok(())
+            }
+            UnixState::Listening(listener) => {
+                // listen again only adjusts the backlog
+                listener.backlog = backlog.max(1);
+                Ok(())
+            }
+            _ => Err(SysError::EINVAL),
+        }
+    }
+
+    /// connect to the socket bound to `addr`
+    pub async fn connect(&self, addr: UnixAddr) -> SockResult<()> {
+        let target = lookup(&addr)?;
+        if target.sk_type != self.inner.sk_type {
+            return Err(SysError::EPROTOTYPE);
+        }
+        if !self.is_stream() {
+            // datagram sockets only record the default destination
+            *self.inner.state.lock() = UnixState::DgramConnected {
+                peer: Arc::downgrade(&target),
+                peer_addr: target.local.lock().clone(),
+            };
+            return Ok(());
+        }
+        match &*self.inner.state.lock() {
+            UnixState::Idle => {}
+            UnixState::Connected { .. } => return Err(SysError::EISCONN),
+            _ => return Err(SysError::EINVAL),
+        }
+        let internal = SocketPairInternal::new(UNIX_BUF_LEN, self.inner.sk_type);
+        let server = SocketPairConnection::new(internal.clone(), false, false, UCred::current());
+        let pending = UnixPendingConn {
+            conn: server,
+            peer_addr: self.inner.local.lock().clone(),
+        };
+        let listener_cred = UnixConnectFuture::new(target.clone(), pending, self.nonblock()).await?;
+        let client = SocketPairConnection::new(internal, true, self.nonblock(), listener_cred);
+        *self.inner.state.lock() = UnixState::Connected {
+            conn: Arc::new(client),
+            peer_addr: target.local.lock().clone(),
+        };
+        Ok(())
+    }
+
+    /// take a connection from the backlog
+    pub async fn accept(&self) -> SockResult<UnixSocket> {
+        let pending = UnixAcceptFuture::new(self.inner.clone(), self.nonblock()).await?;
+        let local = self.inner.local.lock().clone();
+        let state = UnixState::Connected {
+            conn: Arc::new(pending.conn),
+            peer_addr: pending.peer_addr,
+        };
+        Ok(UnixSocket::with_state(self.inner.sk_type, local, state))
+    }
+
+    /// send data to the connected peer, or to `addr` for datagram sockets
+    pub async fn send_to(&self, data: &[u8], addr: Option<UnixAddr>, scm: Option<ScmData>) -> SockResult<usize> {
+        if self.is_stream() {
+            if addr.is_some() {
+                return Err(SysError::EISCONN);
+            }
+            return self.connection()?.send_with_scm(data, scm).await;
+        }
+        if data.len() > UNIX_BUF_LEN {
+            return Err(SysError::EMSGSIZE);
+        }
+        let target = match addr {
+            Some(addr) => lookup(&addr)?,
+            None => match &*self.inner.state.lock() {
+                UnixState::DgramConnected { peer, .. } => {
+                    peer.upgrade().ok_or(SysError::ECONNREFUSED)?
+                }
+                _ => return Err(SysError::ENOTCONN),
+            },
+        };
+        if target.sk_type != SocketType::DGRAM {
+            return Err(SysError::EPROTOTYPE);
+        }
+        let mut scm = scm.unwrap_or_default();
+        if scm.creds.is_none() {
+            scm.creds = Some(UCred::current());
+        }
+        let msg = UnixDgram {
+            data: data.to_vec(),
+            src: self.inner.local.lock().clone(),
+            scm,
+        };
+        DgramSendFuture::new(target, msg, self.nonblock()).await
+    }
+
+    /// receive data together with the sender address
+    pub async fn recv_from(&self, data: &mut [u8]) -> SockResult<(usize, UnixAddr)> {
+        let (len, src, _) = self.recv_msg(data).await?;
+        Ok((len, src))
+    }
+
+    /// receive data together with the sender address and ancillary data,
+    /// the credentials of the sender are always filled in
+    pub async fn recv_msg(&self, data: &mut [u8]) -> SockResult<(usize, UnixAddr, ScmData)> {
+        if self.is_stream() {
+            let (conn, peer_addr) = match &*self.inner.state.lock() {
+                UnixState::Connected { conn, peer_addr } => (conn.clone(), peer_addr.clone()),
+                _ => return Err(SysError::ENOTCONN),
+            };
+            let (len, scm) = conn.recv_with_scm(data).await?;
+            let mut scm = scm.unwrap_or_default();
+            scm.creds.get_or_insert(conn.peer_cred);
+            return Ok((len, peer_addr, scm));
+        }
+        let msg = DgramRecvFuture::new(self.inner.clone(), self.nonblock()).await?;
+        let len = msg.data.len().min(data.len());
+        data[..len].copy_from_slice(&msg.data[..len]);
+        Ok((len, msg.src, msg.scm))
+    }
+
+    /// credentials of the peer when the connection was made
+    pub fn peer_cred(&self) -> UCred {
+        match &*self.inner.state.lock() {
+            UnixState::Connected { conn, .. } => conn.peer_cred,
+            _ => UCred::invalid(),
+        }
+    }
+
+    fn connection(&self) -> SockResult<Arc<SocketPairConnection>> {
+        match &*self.inner.state.lock() {
+            UnixState::Connected { conn, .. } => Ok(conn.clone()),
+            _ => Err(SysError::ENOTCONN),
+        }
+    }
+
+    /// shutdown the connection
+    pub fn shutdown(&self, how: u8) -> SockResult<()> {
+        if !self.is_stream() {
+            return Ok(());
+        }
+        let conn = self.connection()?;
+        if how != super::SHUTRD {
+            conn.close();
+        }
+        Ok(())
+    }
+
+    /// address the socket is bound to
+    pub fn local_addr(&self) -> SockAddr {
+        self.inner.local.lock().to_sockaddr()
+    }
+
+    /// address of the connected peer
+    pub fn peer_addr(&self) -> SockResult<SockAddr> {
+        match &*self.inner.state.lock() {
+            UnixState::Connected { peer_addr, .. } |
+            UnixState::DgramConnected { peer_addr, .. } => Ok(peer_addr.to_sockaddr()),
+            _ => Err(SysError::ENOTCONN),
+        }
+    }
+
+    /// poll the socket for events
+    pub async fn poll(&self, events: PollEvents) -> PollEvents {
+        let waker = get_waker().await;
+        let conn = {
+            let mut state = self.inner.state.lock();
+            match &mut *state {
+                UnixState::Connected { conn, .. } => conn.clone(),
+                UnixState::Listening(listener) => {
+                    if listener.pending.is_empty() || events.contains(PollEvents::EDGE) {
+                        listener.accept_wakers.push_back(waker);
+                    }
+                    if !listener.pending.is_empty() {
+                        return PollEvents::IN & events;
+                    }
+                    return PollEvents::empty();
+                }
+                UnixState::Idle if self.is_stream() => return PollEvents::HUP,
+                _ => {
+                    let mut res = PollEvents::OUT & events;
+                    let mut dgram = self.inner.dgram.lock();
+                    if !dgram.msgs.is_empty() {
+                        res |= PollEvents::IN;
+                    }
+                    if events.contains(PollEvents::IN) && (dgram.msgs.is_empty() || events.contains(PollEvents::EDGE)) {
+                        dgram.read_wakers.push_back(waker);
+                    }
+                    return res;
+                }
+            }
+        };
+        conn.poll(events).await
+    }
+}
+
+impl Drop for UnixSocket {
+    fn drop(&mut self) {
+        if let Some(key) = self.inner.bind_key.lock().take() {
+            let mut table = UNIX_BIND_TABLE.lock();
+            if table.get(&key).is_some_and(|w| w.as_ptr() == Arc::as_ptr(&self.inner)) {
+                table.remove(&key);
+            }
+        }
+        // refuse the connections still waiting in the backlog
+        if let UnixState::Listening(listener) = &mut *self.inner.state.lock() {
+            listener.pending.clear();
+            while let Some(waker) = listener.connect_wakers.pop_front() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+/// create the socket inode for a path bind, returning its absolute path
+fn create_socket_inode(path: &str) -> SockResult<String> {
+    let task = current_task().unwrap().clone();
+    let dentry = at_helper1(task, AtFlags::AT_FDCWD.bits() as isize, path, AtFlags::AT_SYMLINK_NOFOLLOW)?;
+    if !dentry.is_negative() {
+        return Err(SysError::EADDRINUSE);
+    }
+    let parent = dentry.parent().ok_or(SysError::ENOENT)?;
+    let parent_inode = parent.inode().ok_or(SysError::ENOENT)?;
+    let inode = parent_inode.create(dentry.name(), InodeMode::SOCKET)?;
+    inode.inode_inner().set_mode(InodeMode::SOCKET | InodeMode::from_bits_truncate(0o777));
+    dentry.set_inode(inode);
+    dentry.set_state(DentryState::USED);
+    parent.add_child(dentry.clone());
+    Ok(dentry.path())
+}
+
+/// find the socket bound to `addr`
+fn lookup(addr: &UnixAddr) -> SockResult<Arc<UnixSocketInner>> {
+    let key = match addr {
+        UnixAddr::Unnamed => return Err(SysError::EINVAL),
+        UnixAddr::Abstract(_) => addr.clone(),
+        UnixAddr::Path(path) => {
+            let task = current_task().unwrap().clone();
+            let dentry = at_helper1(task, AtFlags::AT_FDCWD.bits() as isize, path, AtFlags::empty())?;
+            if dentry.is_negative() {
+                return Err(SysError::ENOENT);
+            }
+            if dentry.inode().unwrap().inode_type() != InodeMode::SOCKET {
+                return Err(SysError::ECONNREFUSED);
+            }
+            UnixAddr::Path(dentry.path())
+        }
+    };
+    UNIX_BIND_TABLE
+        .lock()
+        .get(&key)
+        .and_then(|w| w.upgrade())
+        .ok_or(SysError::ECONNREFUSED)
+}
+
+/// queue a connection into the backlog of a listening socket
+struct UnixConnectFuture {
+    target: Arc<UnixSocketInner>,
+    pending: Option<UnixPendingConn>,
+    non_block: bool,
+}
+
+impl UnixConnectFuture {
+    fn new(target: Arc<UnixSocketInner>, pending: UnixPendingConn, non_block: bool) -> Self {
+        Self { target, pending: Some(pending), non_block }
+    }
+}
+
+impl Future for UnixConnectFuture {
+    /// credentials of the listener
+    type Output = SockResult<UCred>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let mut state = this.target.state.lock();
+        let UnixState::Listening(listener) = &mut *state else {
+            return Poll::Ready(Err(SysError::ECONNREFUSED));
+        };
+        if listener.pending.len() >= listener.backlog {
+            if this.non_block {
+                return Poll::Ready(Err(SysError::EAGAIN));
+            }
+            listener.connect_wakers.push_back(cx.waker().clone());
+            return Poll::Pending;
+        }
+        listener.pending.push_back(this.pending.take().unwrap());
+        if let Some(waker) = listener.accept_wakers.pop_front() {
+            waker.wake();
+        }
+        Poll::Ready(Ok(listener.cred))
+    }
+}
+
+/// wait for a connection in the backlog
+struct UnixAcceptFuture {
+    inner: Arc<UnixSocketInner>,
+    non_block: bool,
+}
+
+impl UnixAcceptFuture {
+    fn new(inner: Arc<UnixSocketInner>, non_block: bool) -> Self {
+        Self { inner, non_block }
+    }
+}
+
+impl Future for UnixAcceptFuture {
+    type Output = SockResult<UnixPendingConn>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.inner.state.lock();
+        let UnixState::Listening(listener) = &mut *state else {
+            return Poll::Ready(Err(SysError::EINVAL));
+        };
+        if let Some(pending) = listener.pending.pop_front() {
+            if let Some(waker) = listener.connect_wakers.pop_front() {
+                waker.wake();
+            }
+            return Poll::Ready(Ok(pending));
+        }
+        if self.non_block {
+            return Poll::Ready(Err(SysError::EAGAIN));
+        }
+        listener.accept_wakers.push_back(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+/// push a datagram into the receive queue of the target
+struct DgramSendFuture {
+    target: Arc<UnixSocketInner>,
+    msg: Option<UnixDgram>,
+    non_block: bool,
+}
+
+impl DgramSendFuture {
+    fn new(target: Arc<UnixSocketInner>, msg: UnixDgram, non_block: bool) -> Self {
+        Self { target, msg: Some(msg), non_block }
+    }
+}
+
+impl Future for DgramSendFuture {
+    type Output = SockResult<usize>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let mut dgram = this.target.dgram.lock();
+        let len = this.msg.as_ref().unwrap().data.len();
+        if !dgram.msgs.is_empty() && dgram.bytes + len > UNIX_BUF_LEN {
+            if this.non_block {
+                return Poll::Ready(Err(SysError::EAGAIN));
+            }
+            dgram.write_wakers.push_back(cx.waker().clone());
+            return Poll::Pending;
+        }
+        dgram.bytes += len;
+        dgram.msgs.push_back(this.msg.take().unwrap());
+        if let Some(waker) = dgram.read_wakers.pop_front() {
+            waker.wake();
+        }
+        Poll::Ready(Ok(len))
+    }
+}
+
+/// take a datagram from the receive queue
+struct DgramRecvFuture {
+    inner: Arc<UnixSocketInner>,
+    non_block: bool,
+}
+
+impl DgramRecvFuture {
+    fn new(inner: Arc<UnixSocketInner>, non_block: bool) -> Self {
+        Self { inner, non_block }
+    }
+}
+
+impl Future for DgramRecvFuture {
+    type Output = SockResult<UnixDgram>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut dgram = self.inner.dgram.lock();
+        if let Some(msg) = dgram.msgs.pop_front() {
+            dgram.bytes -= msg.data.len();
+            if let Some(waker) = dgram.write_wakers.pop_front() {
+                waker.wake();
+            }
+            return Poll::Ready(Ok(msg));
+        }
+        if self.non_block {
+            return Poll::Ready(Err(SysError::EAGAIN));
+        }
+        dgram.read_wakers.push_back(cx.waker().clone());
+        Poll::Pending
+    }
+}
@@ -0,0 +1,91 @@
+//! system load average, partial reference to linux's calc_global_load
+//!
+//! every LOAD_FREQ the active load is folded into three exponentially
+//! decaying averages kept in fixed point.
+
+use core::{sync::atomic::{AtomicUsize, Ordering}, time::Duration};
+
+use crate::{sync::mutex::SpinNoIrqLock, task::task::TaskStatus, timer::get_current_time_duration};
+
+/// bits of the fractional part
+pub const FSHIFT: u32 = 11;
+/// 1.0 in fixed point
+pub const FIXED_1: u64 = 1 << FSHIFT;
+/// interval between two samples
+const LOAD_FREQ: Duration = Duration::from_secs(5);
+/// 1/exp(5sec/1min), 1/exp(5sec/5min) and 1/exp(5sec/15min) in fixed point
+const EXP: [u64; 3] = [1884, 2014, 2037];
+
+struct LoadAvg {
+    avenrun: [u64; 3],
+    next_update: Duration,
+}
+
+static LOAD_AVG: SpinNoIrqLock<LoadAvg> = SpinNoIrqLock::new(LoadAvg {
+    avenrun: [0; 3],
+    next_update: LOAD_FREQ,
+});
+
+/// load of all run queues, from the pelt tracker of each processor
+#[cfg(feature = "smp")]
+fn active_load() -> u64 {
+    use hal::board::MAX_PROCESSORS;
+    use super::processor::get_processor;
+    (0..MAX_PROCESSORS)
+        .map(get_processor)
+        .filter(|p| p.sche_entity.is_some())
+        .map(|p| p.unwrap_with_sche_entity(|se| se.runnable_load(FIXED_1)))
+        .sum()
+}
+
+/// load of the only run queue, the number of runnable tasks
+#[cfg(not(feature = "smp"))]
+fn active_load() -> u64 {
+    nr_running() as u64 * FIXED_1
+}
+
+/// tasks running or ready to run, kept up to date on every state change so
+/// that the timer interrupt only has to read it
+static NR_RUNNING: AtomicUsize = AtomicUsize::new(0);
+
+/// account a task going from `old` to `new` state, `None` when it is
+/// created or dropped
+pub fn update_nr_running(old: Option<TaskStatus>, new: Option<TaskStatus>) {
+    let runnable = |status: Option<TaskStatus>| matches!(status, Some(TaskStatus::Running | TaskStatus::Ready));
+    match (runnable(old), runnable(new)) {
+        (false, true) => { NR_RUNNING.fetch_add(1, Ordering::Relaxed); }
+        (true, false) => { NR_RUNNING.fetch_sub(1, Ordering::Relaxed); }
+        _ => {}
+    }
+}
+
+/// number of tasks running or ready to run
+pub fn nr_running() -> usize {
+    NR_RUNNING.load(Ordering::Relaxed)
+}
+
+/// sample the active load if a LOAD_FREQ has passed, called on timer interrupts
+pub fn calc_global_load() {
+    let now = get_current_time_duration();
+    let mut load_avg = LOAD_AVG.lock();
+    if now < load_avg.next_update {
+        return;
+    }
+    let active = active_load();
+    // fold in every missed sample with the same load
+    while load_avg.next_update <= now {
+        for (avg, exp) in load_avg.avenrun.iter_mut().zip(EXP) {
+            let mut new = *avg * exp + active * (FIXED_1 - exp);
+            if active >= *avg {
+                new += FIXED_1 - 1;
+            }
+            *avg = new >> FSHIFT;
+        }
+        load_avg.next_update += LOAD_FREQ;
+    }
+}
+
+/// load averages over 1, 5 and 15 minutes in fixed point
+pub fn loadavg() -> [u64; 3] {
+    LOAD_AVG.lock().avenrun
+}
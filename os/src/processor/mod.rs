@@ -1,4 +1,5 @@
 pub mod processor;
 pub mod context;
+pub mod loadavg;
 #[cfg(feature = "smp")]
 pub mod schedule;
\ No newline at end of file
@@ -1,6 +1,7 @@
 //!Implementation of [`Processor`] and Intersection of control flow
 use core::arch::asm;
-use core::sync::atomic::{AtomicU64, AtomicUsize};
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use core::time::Duration;
 use crate::sync::mutex::SpinNoIrqLock;
 use crate::task::task::{get_cpu_mask, new_shared, turn_cpu_mask_to_id, Shared, TaskControlBlock, TaskStatus};
 use crate::sync::UPSafeCell;
@@ -15,7 +16,7 @@ use hal::trap::{TrapContext, TrapContextHal};
 use crate::mm::vm::KernVmSpaceHal;
 use lazy_static::*;
 use log::*;
-use crate::mm::{self, KVMSPACE};
+use crate::mm::{self, UserVmSpace, KVMSPACE};
 use hal::board::MAX_PROCESSORS;
 pub static mut PROCESSORS: [Processor; MAX_PROCESSORS] = [const { Processor::new() }; MAX_PROCESSORS]; 
 #[cfg(feature = "smp")]
@@ -41,7 +42,15 @@ pub struct Processor {
     /// mark whether there is a task need to be migrate
     pub need_migrate: AtomicUsize,
     /// the cpu timeline
-    pub timeline: AtomicU64
+    pub timeline: AtomicU64,
+    /// user time spent by tasks on this processor, in microseconds
+    user_time: AtomicU64,
+    /// kernel time spent by tasks on this processor, in microseconds
+    sys_time: AtomicU64,
+    /// (user, kernel) time of the current task when it was switched in
+    switch_in_time: (Duration, Duration),
+    /// address of the user vm space this processor runs on, 0 if none
+    active_space: AtomicUsize,
 }
 #[cfg(feature = "smp")]
 #[macro_export]
@@ -83,8 +92,18 @@ impl Processor {
             timeline: AtomicU64::new(0),
             #[cfg(feature = "smp")]
             need_migrate: AtomicUsize::new(0),
+            user_time: AtomicU64::new(0),
+            sys_time: AtomicU64::new(0),
+            switch_in_time: (Duration::ZERO, Duration::ZERO),
+            active_space: AtomicUsize::new(0),
         }
     }
+    /// record the user vm space this processor is about to run on, before
+    /// it is enabled under its lock, see `space_active_elsewhere`
+    pub fn set_active_space(&self, space: Option<&Shared<UserVmSpace>>) {
+        let addr = space.map_or(0, |space| Arc::as_ptr(space) as usize);
+        self.active_space.store(addr, Ordering::SeqCst);
+    }
     /// Get the id of the current processor
     pub fn id(&self) -> usize {
         self.id
@@ -172,6 +191,19 @@ impl Processor {
         let current = self.current().unwrap();
         self.add_current_timeline(current.time_recorder().processor_time().as_micros() as u64);
     }
+    /// (user, kernel) time spent by tasks on this processor
+    pub fn cpu_time(&self) -> (Duration, Duration) {
+        (
+            Duration::from_micros(self.user_time.load(Ordering::Relaxed)),
+            Duration::from_micros(self.sys_time.load(Ordering::Relaxed)),
+        )
+    }
+    /// add the time the current task spent since it was switched in
+    fn account_cpu_time(&self, (user, sys): (Duration, Duration)) {
+        let (user_in, sys_in) = self.switch_in_time;
+        self.user_time.fetch_add(user.saturating_sub(user_in).as_micros() as u64, Ordering::Relaxed);
+        self.sys_time.fetch_add(sys.saturating_sub(sys_in).as_micros() as u64, Ordering::Relaxed);
+    }
     /// get runqueue task clock
     pub fn rq_task_clock(&mut self) -> u64 {
         self.update_current_timeline();
@@ -210,12 +242,14 @@ pub fn switch_to_current_task(processor: &mut Processor, task: &mut Arc<TaskCont
     task.set_processor_id(processor.id());
     //info!("[in switch to current task] processor id: {}, task id: {}", processor.id(),task.tid.0);
     task.time_recorder().record_switch_in();
+    processor.switch_in_time = task.time_recorder_ref().time_pair();
     //info!("[in switch to current task] task id: {}kernel_time:{:?}",task.tid(),task.time_recorder().kernel_time());
     if processor.current().is_none() {
         info!("fail to set current! processor id: {}, task id: {}", processor.id(),task.tid.0);
     }
     core::mem::swap(&mut processor.env, env); 
     //info!("switch page table");
+    processor.set_active_space(Some(task.get_vm_space()));
     unsafe {
         task.switch_page_table();
     }
@@ -228,9 +262,11 @@ pub fn switch_out_current_task(processor: &mut Processor, env: &mut EnvContext){
     unsafe { Instruction::disable_interrupt()};
     unsafe {env.auto_sum()};
     KVMSPACE.lock().enable();
+    processor.set_active_space(None);
     core::mem::swap(processor.env_mut(), env);
     let current = processor.current().unwrap();
     current.time_recorder().record_switch_out();
+    processor.account_cpu_time(current.time_recorder_ref().time_pair());
     processor.add_current_timeline(current.time_recorder().processor_time().as_micros() as u64);
     //info!("task id: {}kernel_time:{:?}",current.tid(),current.time_recorder().kernel_time());
     // float_pointer saved, marked restore is needed
@@ -271,6 +307,18 @@ pub fn current_processor() -> &'static mut Processor {
     get_processor(Instruction::get_tp())
 } 
 
+/// whether another processor may hold tlb entries of `space`. there is no
+/// remote tlb shootdown, so with its lock held a space not active elsewhere
+/// can be changed with a local flush: a processor switching to it takes the
+/// lock and flushes its whole tlb after
+pub fn space_active_elsewhere(space: &Shared<UserVmSpace>) -> bool {
+    let addr = Arc::as_ptr(space) as usize;
+    let id = current_processor().id();
+    (0..MAX_PROCESSORS)
+        .filter(|&i| i != id)
+        .any(|i| get_processor(i).active_space.load(Ordering::SeqCst) == addr)
+}
+
 pub fn init(id: usize){
     info!("init processor {}", id);
     set_processor(id);
@@ -52,6 +52,10 @@ impl TaskLoadTracker {
     pub fn get_pelt_divider(&self) -> u32 {
         self.period_contribute + PELT_MIN_DIVIDER
     }
+    /// runnable load in fixed point, `one` stands for a task always runnable
+    pub fn runnable_load(&self, one: u64) -> u64 {
+        self.load_sum * one / (self.get_pelt_divider() as u64 * 1024)
+    }
 }
 /// return val*y^n
 fn decay_load(mut val: u64, num: u64) -> u64 {
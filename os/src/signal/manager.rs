@@ -2,7 +2,7 @@
 //! every process & thread have a signal manager
 //! it is responsible for receving signal and check and handle them
 
-use core::arch::global_asm;
+use core::{arch::global_asm, task::Waker};
 
 use alloc::collections::{btree_map::BTreeMap, vec_deque::VecDeque};
 use hal::{addr::VirtAddr, signal::*};
@@ -27,6 +27,8 @@ pub struct SigManager {
     pub sig_handler: [KSigAction; SIGRTMAX + 1],
     /// Wake up signals
     pub wake_sigs: SigSet,
+    /// signalfd pollers waiting for a signal to arrive
+    pub signalfd_wakers: VecDeque<Waker>,
 }
 
 impl SigManager {
@@ -39,6 +41,7 @@ impl SigManager {
             blocked_sigs: SigSet::empty(),
             sig_handler: core::array::from_fn(|signo| KSigAction::new(signo, false)),
             wake_sigs: SigSet::empty(),
+            signalfd_wakers: VecDeque::new(),
         }
     }
     pub fn from_another(sig_manager: &SigManager) -> Self {
@@ -51,6 +54,7 @@ impl SigManager {
             blocked_sigs: SigSet::empty(),
             sig_handler: sig_manager.sig_handler,
             wake_sigs: SigSet::empty(),
+            signalfd_wakers: VecDeque::new(),
         }
     }
     /// signal manager receive a new signal
@@ -71,7 +75,9 @@ impl SigManager {
                 .or_insert_with(VecDeque::new)
                 .push_back(signo_info);
         }
-        
+        while let Some(waker) = self.signalfd_wakers.pop_front() {
+            waker.wake();
+        }
     }
 
     /// return all pending signals
@@ -9,7 +9,7 @@ pub use action::*;
 pub use handler::*;
 pub use manager::*;
 
-use crate::task::current_task;
+use crate::task::{current_task, ns::PidNamespace};
 
 // Standard signals
 /// Hangup detected on controlling terminal
@@ -222,6 +222,12 @@ impl SigInfo {
     /// stopped child has continued
     pub const CLD_CONTINUED: i32 = 6;
     pub const NSIGCHLD: i32 = 6;
+
+    /// the info with the sender numbered in `ns`, 0 if it is not visible there
+    pub fn seen_from(mut self, ns: &PidNamespace) -> Self {
+        self.si_pid = self.si_pid.map(|pid| ns.nr_of(pid).unwrap_or(0));
+        self
+    }
 }
 
 #[derive(Default, Copy, Clone)]
@@ -88,6 +88,19 @@ impl<T, S: MutexSupport> SpinMutex<T, S> {
         }
     }
 
+    /// Lock if no one holds it, without spinning
+    #[inline(always)]
+    pub fn try_lock(&self) -> Option<MutexGuard<T, S>> {
+        let support_guard = S::before_lock();
+        self.owner
+            .compare_exchange(usize::MAX, Instruction::get_tp(), Ordering::Release, Ordering::Relaxed)
+            .ok()
+            .map(|_| MutexGuard {
+                mutex: self,
+                support_guard,
+            })
+    }
+
     /// # Safety
     ///
     /// This is highly unsafe.
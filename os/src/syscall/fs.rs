@@ -1,14 +1,14 @@
 //! File and filesystem-related syscalls
 use core::{any::Any, cmp, ops::DerefMut, ptr::copy_nonoverlapping};
 
-use alloc::{string::{String, ToString}, sync::Arc, vec};
+use alloc::{format, string::{String, ToString}, sync::Arc, vec};
 use hal::{addr::{PhysAddrHal, PhysPageNumHal, VirtAddr, VirtAddrHal}, constant::{Constant, ConstantsHal}, instruction::{Instruction, InstructionHal}, pagetable::PageTableHal, println};
 use log::{info, warn};
 use strum::FromRepr;
 use virtio_drivers::PAGE_SIZE;
-use crate::{config::BLOCK_SIZE, drivers::BLOCK_DEVICE, fs::{
-    fs::CNXFS, get_filesystem, pipefs::make_pipe, vfs::{dentry::{self, global_find_dentry, global_update_dentry}, file::{open_file, SeekFrom}, fstype::MountFlags, inode::InodeMode, Dentry, DentryState, File}, AtFlags, Kstat, OpenFlags, RenameFlags, RwfFlags, SpliceFlags, StatFs, Xstat, XstatMask
-}, mm::{translate_uva_checked, vm::{PageFaultAccessType, UserVmSpaceHal}, UserPtrRaw, UserSliceRaw}, processor::context::SumGuard, task::{fs::{FdFlags, FdInfo}, task::TaskControlBlock}, timer::{ffi::TimeSpec, get_current_time_duration}, utils::{block_on, is_page_aligned}};
+use crate::{config::BLOCK_SIZE, devices::BlockDevice, drivers::BLOCK_DEVICE, fs::{
+    fs::CNXFS, get_filesystem, page::readahead::RaAdvice, inotify::{fsnotify_close, fsnotify_modify, fsnotify_move, InotifyFile, InotifyMask}, pipefs::make_pipe, tmpfs::memfd::{MemFdFile, MemFdFlags, Seals, MFD_NAME_MAX}, vfs::{dentry::{self, global_find_dentry, global_update_dentry}, file::{open_file, SeekFrom}, fstype::MountFlags, mount::{check_file_writable, check_writable, do_bind, do_mount, do_pivot_root, do_remount, do_umount, lookup_blk, same_dentry, set_propagation, UmountFlags}, inode::InodeMode, Dentry, DentryState, File}, AtFlags, Kstat, OpenFlags, RenameFlags, RwfFlags, SpliceFlags, StatFs, Xstat, XstatMask
+}, mm::{swap, translate_uva_checked, vm::{PageFaultAccessType, UserVmSpaceHal}, UserPtrRaw, UserSliceRaw}, processor::context::SumGuard, task::{fs::{FdFlags, FdInfo}, task::TaskControlBlock}, timer::{ffi::TimeSpec, get_current_time_duration}, utils::{block_on, is_page_aligned}};
 use crate::utils::{
     path::*,
     string::*,
@@ -21,12 +21,16 @@ pub async fn sys_write(fd: usize, buf: usize, len: usize) -> SysResult {
     let task = current_task().unwrap().clone();
     log::debug!("task {} trying to write fd {}", task.gettid(), fd);
     let file = task.with_fd_table(|table| table.get_file(fd))?;
+    check_file_writable(&file)?;
     let user_buf = 
         UserSliceRaw::new(buf as *mut u8, len)
             .ensure_read(&mut task.get_vm_space().lock())
             .ok_or(SysError::EFAULT)?;
     let buf = user_buf.to_ref();
     let ret = file.write(buf).await?;
+    if ret > 0 {
+        fsnotify_modify(&file);
+    }
 
     // let start = buf & !(Constant::PAGE_SIZE - 1);
     // let end = buf + len;
@@ -83,7 +87,12 @@ pub async fn sys_read(fd: usize, buf: usize, len: usize) -> SysResult {
 pub fn sys_close(fd: usize) -> SysResult {
     log::info!("[sys_close]: close on fd: {}", fd);
     let task = current_task().unwrap();
+    let file = task.with_fd_table(|table| table.get_file(fd))?;
     task.with_mut_fd_table(|table| table.remove(fd))?;
+    // only the last close counts
+    if Arc::strong_count(&file) == 1 {
+        fsnotify_close(&file);
+    }
     Ok(0)
 }
 
@@ -127,7 +136,7 @@ pub fn sys_getcwd(buf: usize, len: usize) -> SysResult {
     let task = current_task().unwrap();
     
     task.with_cwd(|cwd| {
-        let path = cwd.path();
+        let path = task.path_in_root(cwd).unwrap_or_else(|| format!("(unreachable){}", cwd.path()));
         if len < path.len() + 1 {
             info!("[sys_getcwd]: buf len too small to recv path");
             return Err(SysError::ERANGE);
@@ -186,6 +195,10 @@ pub fn sys_openat(dirfd: isize, pathname: *const u8, flags: u32, _mode: u32) ->
     )?;
     log::info!("task {} trying to open {}, oflags: {:?}, atflags: {:?}, dirfd {}", task.tid(), path, open_flags, at_flags, dirfd);
     let dentry = at_helper(task.clone(), dirfd, pathname, at_flags)?;
+    if open_flags.writable() || open_flags.contains(OpenFlags::O_TRUNC)
+        || (open_flags.contains(OpenFlags::O_CREAT) && dentry.is_negative()) {
+        check_writable(&dentry)?;
+    }
     if open_flags.contains(OpenFlags::O_CREAT) {
         // the dir may not exist
         if abs_path_to_name(&path).unwrap() != abs_path_to_name(&dentry.path()).unwrap() {
@@ -195,21 +208,23 @@ pub fn sys_openat(dirfd: isize, pathname: *const u8, flags: u32, _mode: u32) ->
         if open_flags.contains(OpenFlags::O_EXCL) && dentry.state() != DentryState::NEGATIVE {
             return Err(SysError::EEXIST);
         }
-        let parent = dentry.parent().expect("[sys_openat]: can not open root as file!");
-        let name = abs_path_to_name(&path).unwrap();
-        let new_inode = parent.inode().unwrap().create(&name, InodeMode::FILE);
-        match new_inode {
-            Ok(inode) => {
-                dentry.set_inode(inode);
-            }
-            Err(SysError::EEXIST) => {}
-            _ => {
-                panic!("should not reach here")
+        // an existing file is opened as it is, e.g. a sysctl file
+        if dentry.state() == DentryState::NEGATIVE {
+            let parent = dentry.parent().expect("[sys_openat]: can not open root as file!");
+            let name = abs_path_to_name(&path).unwrap();
+            let new_inode = parent.inode().unwrap().create(&name, InodeMode::FILE);
+            match new_inode {
+                Ok(inode) => {
+                    dentry.set_inode(inode);
+                    dentry.notify(InotifyMask::IN_CREATE);
+                }
+                Err(SysError::EEXIST) => {}
+                Err(e) => return Err(e),
             }
+
+            // we shall not add child to parent until child is valid!
+            parent.add_child(dentry.clone());
         }
-        
-        // we shall not add child to parent until child is valid!
-        parent.add_child(dentry.clone());
     }
     if dentry.state() == DentryState::NEGATIVE {
         log::warn!("cannot open {}, not exist", path);
@@ -219,8 +234,12 @@ pub fn sys_openat(dirfd: isize, pathname: *const u8, flags: u32, _mode: u32) ->
     if open_flags.contains(OpenFlags::O_DIRECTORY) && inode.inode_type() != InodeMode::DIR {
         return Err(SysError::ENOTDIR);
     }
-    let file = dentry.open(open_flags).unwrap();
+    if open_flags.writable() || open_flags.contains(OpenFlags::O_TRUNC) {
+        dentry.prepare_write()?;
+    }
+    let file = dentry.open(open_flags).ok_or(SysError::EIO)?;
     file.set_flags(open_flags);
+    dentry.notify(InotifyMask::IN_OPEN);
     let fd = task.with_mut_fd_table(|table| table.alloc_fd())?;
     let fd_info = FdInfo { file, flags: open_flags.into() };
     task.with_mut_fd_table(|t|t.put_file(fd, fd_info))?;
@@ -248,12 +267,14 @@ pub fn sys_mkdirat(dirfd: isize, pathname: *const u8, mode: usize) -> SysResult
     if dentry.state() != DentryState::NEGATIVE {
         return Err(SysError::EEXIST);
     }
+    check_writable(&dentry)?;
     let parent = dentry.parent().unwrap();
     let name = abs_path_to_name(&path).unwrap();
-    let new_inode = parent.inode().unwrap().create(&name, InodeMode::DIR).unwrap();
+    let new_inode = parent.inode().unwrap().create(&name, InodeMode::DIR)?;
     // new_inode.inode_inner().set_mode(mode);
     dentry.set_inode(new_inode);
     dentry.set_state(DentryState::USED);
+    dentry.notify(InotifyMask::IN_CREATE);
     parent.add_child(dentry);
     Ok(0)
 }
@@ -293,7 +314,7 @@ pub fn sys_chdir(path: *const u8) -> SysResult {
     info!("try to switch to path {}", path);
     let old_dentry = task.cwd();
     let new_dentry = if path.starts_with("/") {
-        global_find_dentry(&path)?
+        global_find_dentry(&task.path_from_root(&path))?
     } else {
         old_dentry.find(&path)?.ok_or(SysError::ENOENT)?
     };
@@ -544,6 +565,7 @@ pub fn sys_unlinkat(dirfd: isize, pathname: *const u8, flags: i32) -> SysResult
     if dentry.is_negative() {
         return Err(SysError::ENOENT);
     }
+    check_writable(&dentry)?;
     let inode = dentry.inode().unwrap();
     let inode_mode = inode.inode_inner().mode();
     let is_dir = inode_mode == InodeMode::DIR;
@@ -552,14 +574,25 @@ pub fn sys_unlinkat(dirfd: isize, pathname: *const u8, flags: i32) -> SysResult
     } else if flags != AT_REMOVEDIR && is_dir {
         // return Err(SysError::EPERM);
     }
-    // should clear inode first to drop inode (flush datas to disk)
-    dentry.clear_inode();
-    inode.clean_cached();
-    drop(inode);
     // use parent inode to remove the inode in the fs
     let name = abs_path_to_name(&path).unwrap();
     let parent = dentry.parent().unwrap();
-    parent.inode().unwrap().remove(&name, inode_mode).expect("remove failed");
+    parent.inode().unwrap().remove(&name, inode_mode).map_err(SysError::from_i32)?;
+    // the inode itself is gone only with its last link
+    let nlink = inode.inode_inner().nlink();
+    let last_link = is_dir || nlink <= 1;
+    if last_link {
+        dentry.notify(InotifyMask::IN_DELETE | InotifyMask::IN_DELETE_SELF);
+    } else {
+        inode.inode_inner().set_nlink(nlink - 1);
+        dentry.notify(InotifyMask::IN_DELETE);
+    }
+    // the dirty pages of a removed file are never written back
+    if last_link {
+        inode.clean_cached();
+    }
+    dentry.clear_inode();
+    drop(inode);
     parent.remove_child(&name);
 
     //inode.unlink().expect("inode unlink failed");
@@ -580,6 +613,7 @@ pub fn sys_symlinkat(old_path_ptr: *const u8, new_dirfd: isize, new_path_ptr: *c
     }
     log::info!("[sys_symlinkat] task {}, sym-link old path {} to new path {}, fd {new_dirfd}", task.tid(), old_path, new_path);
     let new_dentry = at_helper(task, new_dirfd, new_path_ptr, AtFlags::AT_SYMLINK_NOFOLLOW)?;
+    check_writable(&new_dentry)?;
     let new_path = new_dentry.path();
     let parent = new_dentry.parent().unwrap().inode().unwrap();
     // let old_path = old_dentry.path();
@@ -610,9 +644,11 @@ pub fn sys_fchmodat(dirfd: isize, pathname: *const u8, mode: u32, flags: i32) ->
     if dentry.is_negative() && dentry.inode().is_none() {
         return Err(SysError::ENOENT);
     }
+    check_writable(&dentry)?;
     let inode = dentry.inode().unwrap();
     let inode_type = inode.inode_type();
     inode.inode_inner().set_mode(mode | inode_type);
+    dentry.notify(InotifyMask::IN_ATTRIB);
     Ok(0)
 }
 
@@ -622,10 +658,14 @@ pub fn sys_fchmodat(dirfd: isize, pathname: *const u8, mode: u32, flags: i32) ->
 pub fn sys_fchmod(fd: isize, mode: u32) -> SysResult {
     let task = current_task().unwrap().clone();
     let file = task.with_fd_table(|t| t.get_file(fd as usize))?;
+    check_file_writable(&file)?;
     let mode = InodeMode::from_bits_truncate(mode);
     let inode = file.inode().unwrap();
     let inode_type = inode.inode_type();
     inode.inode_inner().set_mode(mode | inode_type);
+    if let Some(dentry) = file.dentry() {
+        dentry.notify(InotifyMask::IN_ATTRIB);
+    }
     Ok(0)
 }
 
@@ -646,6 +686,7 @@ pub fn sys_fchownat(dirfd: isize, pathname: *const u8, uid: i32, gid: i32, flags
     if dentry.is_negative() && dentry.inode().is_none() {
         return Err(SysError::ENOENT);
     }
+    check_writable(&dentry)?;
     let inode = dentry.inode().unwrap();
     if gid != -1 {
         inode.inode_inner().set_gid(gid as u32);
@@ -668,6 +709,7 @@ pub fn sys_fchownat(dirfd: isize, pathname: *const u8, uid: i32, gid: i32, flags
 pub fn sys_fchown(fd: isize, uid: i32, gid: i32) -> SysResult {
     let task = current_task().unwrap().clone();
     let file = task.with_fd_table(|t| t.get_file(fd as usize))?;
+    check_file_writable(&file)?;
     let inode = file.inode().unwrap();
     if gid != -1 {
         inode.inode_inner().set_gid(gid as u32);
@@ -739,6 +781,7 @@ pub fn sys_utimensat(dirfd: isize, pathname: *const u8, times: usize, flags: i32
     // file referred to by the file descriptor dirfd
     let inode = if pathname.is_null() {
         let file = task.with_fd_table(|t| t.get_file(dirfd as usize))?;
+        check_file_writable(&file)?;
         file.inode().unwrap()
     } else {
         let dentry = at_helper(task.clone(), dirfd, pathname, at_flags)?;
@@ -746,6 +789,7 @@ pub fn sys_utimensat(dirfd: isize, pathname: *const u8, times: usize, flags: i32
         if dentry.is_negative() {
             return Err(SysError::ENOENT);
         }
+        check_writable(&dentry)?;
         dentry.inode().unwrap()
     };
     
@@ -779,31 +823,166 @@ pub fn sys_utimensat(dirfd: isize, pathname: *const u8, times: usize, flags: i32
 }
 
 /// syscall: mount
-/// (todo)
+/// moving a mount is not supported, propagation types are only recorded
 pub fn sys_mount(
-    _source: *const u8,
-    _target: *const u8,
-    _fstype: *const u8,
-    _flags: u32,
-    _data: usize,
+    source: *const u8,
+    target: *const u8,
+    fstype: *const u8,
+    flags: u32,
+    data: usize,
 ) -> SysResult {
-    /*
-    let _source_path = user_path_to_string(source).unwrap();
-    let target_path = user_path_to_string(target).unwrap();
-    let flags = MountFlags::from_bits(flags).unwrap();
-    let fat32_type = get_filesystem("fat32");
-    let dev = Some(BLOCK_DEVICE.clone());
-    let parent_path = abs_path_to_parent(&target_path).unwrap();
-    let name = abs_path_to_name(&target_path).unwrap();
-    let parent = global_find_dentry(&parent_path);
-
-    fat32_type.mount(&name, Some(parent), flags, dev);
-    */
+    let task = current_task().unwrap().clone();
+    if task.uid() != 0 {
+        return Err(SysError::EPERM);
+    }
+    let flags = MountFlags::from_bits_truncate(flags);
+    let target = at_helper(task.clone(), AtFlags::AT_FDCWD.bits() as isize, target, AtFlags::empty())?;
+    if target.is_negative() {
+        return Err(SysError::ENOENT);
+    }
+    if flags.contains(MountFlags::MS_REMOUNT) {
+        do_remount(&target, flags)?;
+        return Ok(0);
+    }
+    if flags.contains(MountFlags::MS_BIND) {
+        let source = at_helper(task.clone(), AtFlags::AT_FDCWD.bits() as isize, source, AtFlags::empty())?;
+        log::info!("[sys_mount] bind {} on {} flags {:?}", source.path(), target.path(), flags);
+        do_bind(source, target, flags)?;
+        return Ok(0);
+    }
+    if flags.contains(MountFlags::MS_MOVE) {
+        return Err(SysError::EINVAL);
+    }
+    if flags.intersects(MountFlags::MS_SHARED | MountFlags::MS_PRIVATE | MountFlags::MS_SLAVE | MountFlags::MS_UNBINDABLE) {
+        set_propagation(&target, flags)?;
+        return Ok(0);
+    }
+    let (source, fstype, options) = {
+        let _sum_guard = SumGuard::new();
+        let mut vm = task.get_vm_space().lock();
+        let source = if source.is_null() {
+            String::new()
+        } else {
+            user_path_to_string(UserPtrRaw::new(source), &mut vm)?
+        };
+        if fstype.is_null() {
+            return Err(SysError::EINVAL);
+        }
+        let options = if data == 0 {
+            String::new()
+        } else {
+            user_path_to_string(UserPtrRaw::new(data as *const u8), &mut vm)?
+        };
+        (source, user_path_to_string(UserPtrRaw::new(fstype), &mut vm)?, options)
+    };
+    log::info!("[sys_mount] {} on {} type {} flags {:?} options {}", source, target.path(), fstype, flags, options);
+    do_mount(&source, target, &fstype, flags, &options)?;
     Ok(0)
 }
 
-/// fake unmount
-pub fn sys_umount2(_target: *const u8, _flags: u32) -> SysResult {
+/// syscall: umount2
+pub fn sys_umount2(target: *const u8, flags: u32) -> SysResult {
+    let task = current_task().unwrap().clone();
+    if task.uid() != 0 {
+        return Err(SysError::EPERM);
+    }
+    let flags = UmountFlags::from_bits(flags).ok_or(SysError::EINVAL)?;
+    let target = at_helper(task.clone(), AtFlags::AT_FDCWD.bits() as isize, target, AtFlags::empty())?;
+    if target.is_negative() {
+        return Err(SysError::ENOENT);
+    }
+    log::info!("[sys_umount2] {} flags {:?}", target.path(), flags);
+    do_umount(target, flags)?;
+    Ok(0)
+}
+
+/// syscall: chroot
+pub fn sys_chroot(path: *const u8) -> SysResult {
+    let task = current_task().unwrap().clone();
+    if task.uid() != 0 {
+        return Err(SysError::EPERM);
+    }
+    let dentry = at_helper(task.clone(), AtFlags::AT_FDCWD.bits() as isize, path, AtFlags::empty())?;
+    if dentry.is_negative() {
+        return Err(SysError::ENOENT);
+    }
+    if !dentry.inode().unwrap().inode_type().is_dir() {
+        return Err(SysError::ENOTDIR);
+    }
+    // like linux, the cwd stays where it is
+    task.set_root(dentry);
+    Ok(0)
+}
+
+/// syscall: pivot_root
+pub fn sys_pivot_root(new_root: *const u8, put_old: *const u8) -> SysResult {
+    let task = current_task().unwrap().clone();
+    if task.uid() != 0 {
+        return Err(SysError::EPERM);
+    }
+    let new_root = at_helper(task.clone(), AtFlags::AT_FDCWD.bits() as isize, new_root, AtFlags::empty())?;
+    let put_old = at_helper(task.clone(), AtFlags::AT_FDCWD.bits() as isize, put_old, AtFlags::empty())?;
+    // a chrooted task may only pivot its own root
+    if task.is_chrooted() {
+        return Err(SysError::EINVAL);
+    }
+    do_pivot_root(new_root, put_old)?;
+    Ok(0)
+}
+
+/// what swapon and swapoff act on
+enum SwapTarget {
+    Device(Arc<dyn BlockDevice>),
+    File(Arc<dyn File>),
+}
+
+/// resolve the path of swapon and swapoff, a block device or a regular file
+fn swap_target(task: &Arc<TaskControlBlock>, path: *const u8) -> Result<(String, SwapTarget), SysError> {
+    let name = {
+        let _sum_guard = SumGuard::new();
+        user_path_to_string(UserPtrRaw::new(path), &mut task.get_vm_space().lock())?
+    };
+    if let Ok(dev) = lookup_blk(&name) {
+        return Ok((name, SwapTarget::Device(dev)));
+    }
+    let dentry = at_helper(task.clone(), AtFlags::AT_FDCWD.bits() as isize, path, AtFlags::empty())?;
+    if dentry.is_negative() {
+        return Err(SysError::ENOENT);
+    }
+    if dentry.inode().unwrap().inode_type() != InodeMode::FILE {
+        return Err(SysError::EINVAL);
+    }
+    let file = dentry.clone().open(OpenFlags::O_RDWR).ok_or(SysError::EINVAL)?;
+    Ok((dentry.path(), SwapTarget::File(file)))
+}
+
+/// syscall: swapon
+pub fn sys_swapon(path: *const u8, flags: i32) -> SysResult {
+    let task = current_task().unwrap().clone();
+    if task.uid() != 0 {
+        return Err(SysError::EPERM);
+    }
+    let (name, target) = swap_target(&task, path)?;
+    log::info!("[sys_swapon] {} flags {:#x}", name, flags);
+    match target {
+        SwapTarget::Device(dev) => swap::swapon_device(name, dev, flags)?,
+        SwapTarget::File(file) => swap::swapon_file(name, file, flags)?,
+    }
+    Ok(0)
+}
+
+/// syscall: swapoff
+pub fn sys_swapoff(path: *const u8) -> SysResult {
+    let task = current_task().unwrap().clone();
+    if task.uid() != 0 {
+        return Err(SysError::EPERM);
+    }
+    let (name, target) = swap_target(&task, path)?;
+    log::info!("[sys_swapoff] {}", name);
+    match target {
+        SwapTarget::Device(dev) => swap::swapoff_device(dev)?,
+        SwapTarget::File(file) => swap::swapoff_file(file)?,
+    }
     Ok(0)
 }
 
@@ -839,6 +1018,8 @@ pub enum FcntlOp {
     F_SETOWN_EX = 15,
     F_GETOWN_EX	= 16,
     F_GETOWNER_UIDS	= 17,
+    F_ADD_SEALS = 1033,
+    F_GET_SEALS = 1034,
     #[default]
     F_UNIMPL,
 }
@@ -889,6 +1070,20 @@ pub fn sys_fnctl(fd: usize, op: isize, arg: usize) -> SysResult {
             file.set_flags(old_flags.masked_set_flags(flags, mask));
             Ok(0)
         }
+        FcntlOp::F_ADD_SEALS => {
+            let file = task.with_fd_table(|table| table.get_file(fd))?
+                .downcast_arc::<MemFdFile>()
+                .map_err(|_| SysError::EINVAL)?;
+            let seals = Seals::from_bits(arg as u32).ok_or(SysError::EINVAL)?;
+            file.add_seals(seals)?;
+            Ok(0)
+        }
+        FcntlOp::F_GET_SEALS => {
+            let file = task.with_fd_table(|table| table.get_file(fd))?
+                .downcast_arc::<MemFdFile>()
+                .map_err(|_| SysError::EINVAL)?;
+            Ok(file.seals().bits() as isize)
+        }
         _ => {
             log::warn!("fcntl cmd: {op:?} not implemented");
             Ok(0)
@@ -945,6 +1140,7 @@ pub async fn sys_readv(fd: usize, iov: usize, iovcnt: usize) -> SysResult {
 pub async fn sys_writev(fd: usize, iov: usize, iovcnt: usize) -> SysResult {
     let task = current_task().unwrap().clone();
     let file = task.with_fd_table(|t| t.get_file(fd))?;
+    check_file_writable(&file)?;
     let iovs = UserSliceRaw::new(iov as *const IoVec, iovcnt)
         .ensure_read(&mut task.get_vm_space().lock())
         .ok_or(SysError::EINVAL)?;
@@ -966,6 +1162,9 @@ pub async fn sys_writev(fd: usize, iov: usize, iovcnt: usize) -> SysResult {
         let ret = file.write(iov_buf.to_ref()).await?;
         totol_len += ret;
     }
+    if totol_len > 0 {
+        fsnotify_modify(&file);
+    }
     Ok(totol_len as isize)
 }
 
@@ -1000,12 +1199,16 @@ pub async fn sys_pwrite(fd: usize, buf: usize, count: usize, offset: usize) -> S
     let task = current_task().unwrap().clone();
     log::debug!("[sys_pwrite] task {} try to read fd {} to buf {:#x} at offset {}, len {}", task.tid(), fd, buf, offset, count);
     let file = task.with_fd_table(|t| t.get_file(fd))?;
+    check_file_writable(&file)?;
     let user_buf = 
         UserSliceRaw::new(buf as *mut u8, count)
             .ensure_read(&mut task.get_vm_space().lock())
             .ok_or(SysError::EINVAL)?;
     let ret = file.write_at(offset, user_buf.to_ref()).await?;
     log::debug!("finish pwrite return {}", ret);
+    if ret > 0 {
+        fsnotify_modify(&file);
+    }
     Ok(ret as isize)
 }
 
@@ -1082,6 +1285,7 @@ pub async fn sys_preadv2(fd: usize, iov: usize, iovcnt: usize, offset: usize, fl
 pub async fn sys_pwritev2(fd: usize, iov: usize, iovcnt: usize, offset: usize, flags: i32) -> SysResult {
     let task = current_task().unwrap().clone();
     let file = task.with_fd_table(|t| t.get_file(fd))?;
+    check_file_writable(&file)?;
     let flags = RwfFlags::from_bits_truncate(flags);
     info!("pwritev2 using flags {:?}", flags);
     
@@ -1130,6 +1334,9 @@ pub async fn sys_pwritev2(fd: usize, iov: usize, iovcnt: usize, offset: usize, f
         current_offset += ret;
 
     }
+    if total_len > 0 {
+        fsnotify_modify(&file);
+    }
     Ok(total_len as isize)
 }
 
@@ -1200,6 +1407,7 @@ pub async fn sys_sendfile(out_fd: usize, in_fd: usize, offset: usize, count: usi
     let task = current_task().unwrap().clone();
     let in_file = task.with_fd_table(|t| t.get_file(in_fd))?;
     let out_file = task.with_fd_table(|t| t.get_file(out_fd))?;
+    check_file_writable(&out_file)?;
     let mut buf = vec![0u8; count];
     
     let len;
@@ -1238,6 +1446,7 @@ pub async fn sys_splice(in_fd: usize, in_off_ptr: usize, out_fd: usize, out_off_
     if !in_is_pipe && !out_is_pipe {
         return Err(SysError::EINVAL);
     }
+    check_file_writable(&out_file)?;
     let read_size = if in_is_pipe {
         if !in_file.readable() {
             return Err(SysError::EBADF);
@@ -1290,6 +1499,116 @@ pub async fn sys_splice(in_fd: usize, in_off_ptr: usize, out_fd: usize, out_off_
     Ok(write_size as isize)
 }
 
+bitflags! {
+    /// modes of fallocate
+    pub struct FallocMode: i32 {
+        /// do not change the file size
+        const FALLOC_FL_KEEP_SIZE = 0x01;
+    }
+}
+
+/// syscall: fallocate
+/// only reserving space is supported, the file grows to cover the range
+/// unless FALLOC_FL_KEEP_SIZE is given
+pub fn sys_fallocate(fd: usize, mode: i32, offset: isize, len: isize) -> SysResult {
+    let task = current_task().unwrap().clone();
+    let mode = FallocMode::from_bits(mode).ok_or(SysError::EOPNOTSUPP)?;
+    if offset < 0 || len <= 0 {
+        return Err(SysError::EINVAL);
+    }
+    let file = task.with_fd_table(|t| t.get_file(fd))?;
+    if !file.writable() {
+        return Err(SysError::EBADF);
+    }
+    let inode = file.inode().ok_or(SysError::ENODEV)?;
+    match inode.inode_type() {
+        InodeMode::FILE => {}
+        InodeMode::DIR => return Err(SysError::EISDIR),
+        InodeMode::FIFO => return Err(SysError::ESPIPE),
+        _ => return Err(SysError::ENODEV),
+    }
+    check_file_writable(&file)?;
+    let end = offset.checked_add(len).ok_or(SysError::EFBIG)? as usize;
+    if mode.contains(FallocMode::FALLOC_FL_KEEP_SIZE) || end <= file.size() {
+        return Ok(0);
+    }
+    if let Ok(memfd) = file.clone().downcast_arc::<MemFdFile>() {
+        memfd.check_truncate(end)?;
+    }
+    inode.truncate(end)?;
+    Ok(0)
+}
+
+/// syscall: copy_file_range
+/// copies through a kernel buffer, between two regular files
+pub async fn sys_copy_file_range(fd_in: usize, off_in_ptr: usize, fd_out: usize, off_out_ptr: usize, len: usize, flags: u32) -> SysResult {
+    /// bytes copied per round
+    const CHUNK: usize = 64 * 1024;
+    if flags != 0 {
+        return Err(SysError::EINVAL);
+    }
+    let task = current_task().unwrap().clone();
+    let in_file = task.with_fd_table(|t| t.get_file(fd_in))?;
+    let out_file = task.with_fd_table(|t| t.get_file(fd_out))?;
+    if !in_file.readable() || !out_file.writable() || out_file.flags().contains(OpenFlags::O_APPEND) {
+        return Err(SysError::EBADF);
+    }
+    for file in [&in_file, &out_file] {
+        match file.inode().ok_or(SysError::EINVAL)?.inode_type() {
+            InodeMode::FILE => {}
+            InodeMode::DIR => return Err(SysError::EISDIR),
+            _ => return Err(SysError::EINVAL),
+        }
+    }
+    check_file_writable(&out_file)?;
+    let off_ptr = |ptr: usize| -> Result<Option<usize>, SysError> {
+        if ptr == 0 {
+            return Ok(None);
+        }
+        let off = *UserPtrRaw::new(ptr as *const usize)
+            .ensure_read(&mut task.get_vm_space().lock())
+            .ok_or(SysError::EFAULT)?
+            .to_ref();
+        if (off as isize) < 0 {
+            return Err(SysError::EINVAL);
+        }
+        Ok(Some(off))
+    };
+    let mut off_in = off_ptr(off_in_ptr)?;
+    let mut off_out = off_ptr(off_out_ptr)?;
+    let mut buf = vec![0u8; len.min(CHUNK)];
+    let mut copied = 0;
+    while copied < len {
+        let want = (len - copied).min(buf.len());
+        let read = match off_in {
+            Some(off) => in_file.read_at(off, &mut buf[..want]).await?,
+            None => in_file.read(&mut buf[..want]).await?,
+        };
+        if read == 0 {
+            break;
+        }
+        let written = match off_out {
+            Some(off) => out_file.write_at(off, &buf[..read]).await?,
+            None => out_file.write(&buf[..read]).await?,
+        };
+        off_in = off_in.map(|off| off + written);
+        off_out = off_out.map(|off| off + written);
+        copied += written;
+        if written < read {
+            break;
+        }
+    }
+    for (ptr, off) in [(off_in_ptr, off_in), (off_out_ptr, off_out)] {
+        if let Some(off) = off {
+            UserPtrRaw::new(ptr as *mut usize)
+                .ensure_write(&mut task.get_vm_space().lock())
+                .ok_or(SysError::EFAULT)?
+                .write(off);
+        }
+    }
+    Ok(copied as isize)
+}
+
 /// syscall: linkat
 /// link() creates a new link (also known as a hard link) to an existing file.
 /// The linkat() system call operates in exactly the same way as link(2), 
@@ -1298,6 +1617,7 @@ pub fn sys_linkat(old_dirfd: isize, old_pathname: *const u8, new_dirfd: isize, n
     let at_flags = AtFlags::from_bits_truncate(flags);
     let old_dentry = at_helper(task.clone(), old_dirfd, old_pathname, at_flags)?;
     let new_dentry = at_helper(task.clone(), new_dirfd, new_pathname, at_flags)?;
+    check_writable(&new_dentry)?;
     log::debug!("[sys_linkat]: try to create hard link between {} {}", old_dentry.path(), new_dentry.path());
     let old_inode = old_dentry.inode().ok_or(SysError::ENOENT)?;
     old_inode.link(&new_dentry.path())?;
@@ -1370,6 +1690,8 @@ pub fn sys_renameat2(old_dirfd: isize, old_path: *const u8, new_dirfd: isize, ne
 
     let old_dentry = at_helper(task.clone(), old_dirfd, old_path, AtFlags::AT_SYMLINK_NOFOLLOW)?;
     let new_dentry = at_helper(task.clone(), new_dirfd, new_path, AtFlags::AT_SYMLINK_NOFOLLOW)?;
+    check_writable(&old_dentry)?;
+    check_writable(&new_dentry)?;
 
     let old_path = user_path_to_string(UserPtrRaw::new(old_path), &mut task.vm_space.lock())?;
     let new_path = user_path_to_string(UserPtrRaw::new(new_path), &mut task.vm_space.lock())?;
@@ -1398,7 +1720,14 @@ pub fn sys_renameat2(old_dirfd: isize, old_path: *const u8, new_dirfd: isize, ne
 
     let old_inode = old_dentry.inode().ok_or(SysError::ENOENT)?;
     let new_inode = new_dentry.inode();
+    let replaced = new_inode.is_some() && !flags.contains(RenameFlags::RENAME_EXCHANGE);
     old_inode.rename(&new_dentry.path(), new_inode)?;
+    if replaced {
+        new_dentry.notify(InotifyMask::IN_DELETE_SELF);
+    }
+    if let (Some(old_parent), Some(new_parent)) = (old_dentry.parent(), new_dentry.parent()) {
+        fsnotify_move(&old_inode, &old_parent, old_dentry.name(), &new_parent, new_dentry.name());
+    }
     new_dentry.set_inode(old_inode);
     // warning: due to lwext4 unsupport for RENAME_EXCHANGE
     if flags.contains(RenameFlags::RENAME_EXCHANGE) {
@@ -1417,7 +1746,11 @@ pub fn sys_ftruncate(fildes: usize, length: usize) -> SysResult {
     }
     log::info!("[sys_ftruncate] fd {} truncate size to {}", fildes, length);
     let file = task.with_fd_table(|f| f.get_file(fildes))?;
+    if let Ok(memfd) = file.clone().downcast_arc::<MemFdFile>() {
+        memfd.check_truncate(length)?;
+    }
     let dentry = file.dentry().ok_or(SysError::EINVAL)?;
+    check_writable(&dentry)?;
     dentry.inode().unwrap().truncate(length)?;
     Ok(0)
 }
@@ -1438,21 +1771,59 @@ pub fn sys_truncate(pathname: *const u8, length: usize) -> SysResult {
     }
     let inode = dentry.inode().ok_or(SysError::EINVAL)?;
     inode.inode_type().is_dir_err()?;
+    check_writable(&dentry)?;
     inode.truncate(length)?;
     Ok(0)
 }
 
 
-/// fake async
+/// syscall: sync
+/// the dirty pages of all files and the buffered blocks of all devices reach the disk
+pub fn sys_sync() -> SysResult {
+    if let Err(e) = crate::fs::sync_all() {
+        // sync(2) has no way to report it
+        log::warn!("[sys_sync] failed: {:?}", e);
+    }
+    Ok(0)
+}
+
+/// syscall: syncfs
+/// same as sync, only for the file system the fd is on
+pub fn sys_syncfs(fd: usize) -> SysResult {
+    let task = current_task().unwrap().clone();
+    let file = task.with_fd_table(|t| t.get_file(fd))?;
+    let Some(inode) = file.inode() else {
+        return Ok(0);
+    };
+    let sb = inode.inode_inner().super_block.as_ref().and_then(|sb| sb.upgrade());
+    if let Some(sb) = sb {
+        crate::fs::sync_filesystem(&sb)?;
+    }
+    Ok(0)
+}
+
+/// syscall: fsync
+pub fn sys_fsync(fd: usize) -> SysResult {
+    let task = current_task().unwrap().clone();
+    let file = task.with_fd_table(|t| t.get_file(fd))?;
+    let inode = file.inode().ok_or(SysError::EINVAL)?;
+    inode.fsync(false)?;
+    Ok(0)
+}
+
+/// syscall: fdatasync
+/// as fsync, without the metadata not needed to read the data back
 pub fn sys_fdatasync(fd: usize) -> SysResult {
     let task = current_task().unwrap().clone();
-    let _ = task.with_fd_table(|t| t.get_file(fd))?;
+    let file = task.with_fd_table(|t| t.get_file(fd))?;
+    let inode = file.inode().ok_or(SysError::EINVAL)?;
+    inode.fsync(true)?;
     Ok(0)
 }
 
 /// readahead: readahead() initiates readahead on a file so that subsequent reads
 // from that file will be satisfied from the cache
-pub fn sys_readahead(fd: usize, _offset: usize, _count: usize) -> SysResult {
+pub fn sys_readahead(fd: usize, offset: usize, count: usize) -> SysResult {
     let task = current_task().unwrap().clone();
     let file = task.with_fd_table(|f| f.get_file(fd))?;
     let dentry = file.dentry().unwrap();
@@ -1463,10 +1834,104 @@ pub fn sys_readahead(fd: usize, _offset: usize, _count: usize) -> SysResult {
     if inode.inode_type() != InodeMode::FILE {
         return Err(SysError::EINVAL);
     }
+    inode.readahead(offset, count).map_err(SysError::from_i32)?;
+    Ok(0)
+}
+
+pub const POSIX_FADV_NORMAL: usize = 0;
+pub const POSIX_FADV_RANDOM: usize = 1;
+pub const POSIX_FADV_SEQUENTIAL: usize = 2;
+pub const POSIX_FADV_WILLNEED: usize = 3;
+pub const POSIX_FADV_DONTNEED: usize = 4;
+pub const POSIX_FADV_NOREUSE: usize = 5;
+
+/// syscall: fadvise64
+/// the advice goes to the read ahead window of the file, len 0 means up to its end
+pub fn sys_fadvise64(fd: usize, offset: usize, len: usize, advice: usize) -> SysResult {
+    let task = current_task().unwrap().clone();
+    let file = task.with_fd_table(|f| f.get_file(fd))?;
+    let inode = file.inode().ok_or(SysError::ESPIPE)?;
+    inode.inode_type().is_pipe_err()?;
+    if advice > POSIX_FADV_NOREUSE {
+        return Err(SysError::EINVAL);
+    }
+    if inode.inode_type() != InodeMode::FILE {
+        // nothing cached to advise about
+        return Ok(0);
+    }
+    let len = if len == 0 { usize::MAX - offset } else { len };
+    let cache = inode.cache();
+    match advice {
+        POSIX_FADV_NORMAL | POSIX_FADV_RANDOM | POSIX_FADV_SEQUENTIAL => {
+            let advice = match advice {
+                POSIX_FADV_RANDOM => RaAdvice::Random,
+                POSIX_FADV_SEQUENTIAL => RaAdvice::Sequential,
+                _ => RaAdvice::Normal,
+            };
+            if let Some(cache) = cache {
+                cache.get_ra().lock().set_advice(advice);
+            }
+        }
+        POSIX_FADV_WILLNEED => inode.readahead(offset, len).map_err(SysError::from_i32)?,
+        POSIX_FADV_DONTNEED => {
+            if let Some(cache) = cache.filter(|cache| cache.host().is_some()) {
+                // dirty pages cannot go, write them back first
+                let _ = cache.flush(&*inode);
+                cache.invalidate_range(offset, offset.saturating_add(len));
+            }
+        }
+        _ => {}
+    }
     Ok(0)
 }
 
 
+/// syscall: inotify_init1
+pub fn sys_inotify_init1(flags: i32) -> SysResult {
+    let flags = OpenFlags::from_bits(flags).ok_or(SysError::EINVAL)?;
+    if !(flags - (OpenFlags::O_NONBLOCK | OpenFlags::O_CLOEXEC)).is_empty() {
+        return Err(SysError::EINVAL);
+    }
+    let task = current_task().unwrap().clone();
+    let file = InotifyFile::new(flags & OpenFlags::O_NONBLOCK);
+    let fd = task.with_mut_fd_table(|table| table.alloc_fd())?;
+    let fd_info = FdInfo { file, flags: flags.into() };
+    task.with_mut_fd_table(|t| t.put_file(fd, fd_info))?;
+    Ok(fd as isize)
+}
+
+/// syscall: inotify_add_watch
+/// returns the watch descriptor, an existing watch on the same inode is updated
+pub fn sys_inotify_add_watch(fd: usize, pathname: *const u8, mask: u32) -> SysResult {
+    let task = current_task().unwrap().clone();
+    let inotify = task.with_fd_table(|t| t.get_file(fd))?
+        .downcast_arc::<InotifyFile>()
+        .map_err(|_| SysError::EINVAL)?;
+    let mask = InotifyMask::from_bits_truncate(mask);
+    let at_flags = if mask.contains(InotifyMask::IN_DONT_FOLLOW) {
+        AtFlags::AT_SYMLINK_NOFOLLOW
+    } else {
+        AtFlags::empty()
+    };
+    let dentry = at_helper(task, AtFlags::AT_FDCWD.bits() as isize, pathname, at_flags)?;
+    if dentry.is_negative() {
+        return Err(SysError::ENOENT);
+    }
+    log::info!("[sys_inotify_add_watch] watch {} with mask {:?}", dentry.path(), mask);
+    let wd = inotify.add_watch(&dentry, mask)?;
+    Ok(wd as isize)
+}
+
+/// syscall: inotify_rm_watch
+pub fn sys_inotify_rm_watch(fd: usize, wd: i32) -> SysResult {
+    let task = current_task().unwrap().clone();
+    let inotify = task.with_fd_table(|t| t.get_file(fd))?
+        .downcast_arc::<InotifyFile>()
+        .map_err(|_| SysError::EINVAL)?;
+    inotify.rm_watch(wd)?;
+    Ok(0)
+}
+
 /// at helper:
 /// since many "xxxat" type file system syscalls will use the same logic of getting dentry,
 /// we need to write a helper function to reduce code duplication
@@ -1487,7 +1952,7 @@ pub fn at_helper(task: Arc<TaskControlBlock>, dirfd: isize, pathname: *const u8,
 pub fn at_helper1(task: Arc<TaskControlBlock>, dirfd: isize, path: &str, flags: AtFlags) -> Result<Arc<dyn Dentry>, SysError> {
     let dentry = if path != "" {
         if path.starts_with("/") {
-                global_find_dentry(&path)?
+                global_find_dentry(&task.path_from_root(path))?
         } else {
             // getting full path (absolute path)
             let mut rel_path = path.to_string();
@@ -1511,7 +1976,8 @@ pub fn at_helper1(task: Arc<TaskControlBlock>, dirfd: isize, path: &str, flags:
             let mut cnt = 0usize;
             while rel_path.starts_with("../") {
                 info!("before resolve parent {}, rel_path {}", parent_dentry.path(), rel_path);
-                if cnt > 0 {
+                // never above the root dir
+                if cnt > 0 && !same_dentry(&parent_dentry, &task.root()) {
                     parent_dentry = parent_dentry.parent().expect("failed no parent");
                 }
                 rel_path = rel_path.trim_start_matches("..").to_string();
@@ -1523,7 +1989,11 @@ pub fn at_helper1(task: Arc<TaskControlBlock>, dirfd: isize, path: &str, flags:
                 info!("finish resolve parent {}, rel_path {}", parent_dentry.path(), rel_path);
             }
 
-            let fpath = rel_path_to_abs(&parent_dentry.path(), &rel_path).unwrap();
+            let fpath = match task.path_in_root(&parent_dentry) {
+                Some(dir) => task.path_from_root(&rel_path_to_abs(&dir, &rel_path).unwrap()),
+                // a dir outside the root dir, e.g. the cwd after chroot
+                None => rel_path_to_abs(&parent_dentry.path(), &rel_path).unwrap(),
+            };
 
             global_find_dentry(&fpath)?
         }
@@ -1553,4 +2023,25 @@ pub fn at_helper1(task: Arc<TaskControlBlock>, dirfd: isize, path: &str, flags:
 pub fn sys_umask(_mask: i32) -> SysResult {
     // TODO: implement this
     Ok(0x777)
-}
\ No newline at end of file
+}
+/// syscall: memfd_create
+pub fn sys_memfd_create(name: *const u8, flags: u32) -> SysResult {
+    let flags = MemFdFlags::from_bits(flags).ok_or(SysError::EINVAL)?;
+    if flags.contains(MemFdFlags::MFD_EXEC | MemFdFlags::MFD_NOEXEC_SEAL) {
+        return Err(SysError::EINVAL);
+    }
+    let task = current_task().unwrap().clone();
+    let name = user_path_to_string(UserPtrRaw::new(name), &mut task.get_vm_space().lock())?;
+    if name.len() > MFD_NAME_MAX {
+        return Err(SysError::EINVAL);
+    }
+    log::info!("[sys_memfd_create] name {} flags {:?}", name, flags);
+    let file = MemFdFile::new(&name, flags)?;
+    let fd_flags = if flags.contains(MemFdFlags::MFD_CLOEXEC) { OpenFlags::O_CLOEXEC } else { OpenFlags::empty() };
+    let fd = task.with_mut_fd_table(|table| -> Result<usize, SysError> {
+        let fd = table.alloc_fd()?;
+        table.put_file(fd, FdInfo { file, flags: fd_flags.into() })?;
+        Ok(fd)
+    })?;
+    Ok(fd as isize)
+}
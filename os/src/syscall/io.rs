@@ -8,7 +8,7 @@ use log::SetLoggerError;
 use smoltcp::time;
 use virtio_drivers::device::socket::SocketError;
 
-use crate::{fs::vfs::{file::PollEvents, File}, mm::{UserPtrRaw, UserSliceRaw}, signal::SigSet, task::{current_task, signal::IntrBySignalFuture}, timer::{ffi::TimeSpec, timed_task::{PendingFuture, TimedTaskFuture, TimedTaskOutput}}, utils::{Select2Futures, SelectOutput}};
+use crate::{fs::{epoll::{EpollEvent, EpollFile, EpollWaitFuture}, eventfd::{EventFdFile, EventFdFlags}, vfs::{file::PollEvents, File}, OpenFlags}, mm::{UserPtrRaw, UserSliceRaw}, signal::SigSet, task::{current_task, fs::FdInfo, signal::IntrBySignalFuture}, timer::{ffi::TimeSpec, timed_task::{PendingFuture, TimedTaskFuture, TimedTaskOutput}}, utils::{Select2Futures, SelectOutput}};
 
 use super::{SysError, SysResult};
 
@@ -101,7 +101,7 @@ pub async fn sys_ppoll(fds: usize, nfds: usize, timeout_ts: usize, sigmask: usiz
     let mut polls = Vec::<(PollEvents, Arc<dyn File>)>::with_capacity(nfds);
     for (_i, poll_fd) in poll_fds.iter_mut().enumerate() {
         let fd = poll_fd.fd as usize;
-        let events = poll_fd.events;
+        let events = poll_fd.events - PollEvents::EDGE;
         // let file = task.with_fd_table(|t| t.get_file(fd))?;
         match task.with_fd_table(|t| t.get_file(fd)) {
             Ok(file) => {
@@ -458,4 +458,137 @@ impl Future for PSelectFuture {
             Poll::Pending
         }
     }
-}
\ No newline at end of file
+}
+/// epoll_ctl operations
+const EPOLL_CTL_ADD: usize = 1;
+const EPOLL_CTL_DEL: usize = 2;
+const EPOLL_CTL_MOD: usize = 3;
+
+/// syscall: epoll_create1
+pub fn sys_epoll_create1(flags: usize) -> SysResult {
+    let flags = OpenFlags::from_bits(flags as i32).ok_or(SysError::EINVAL)?;
+    if !(flags - OpenFlags::O_CLOEXEC).is_empty() {
+        return Err(SysError::EINVAL);
+    }
+    let task = current_task().unwrap();
+    let epoll = EpollFile::new(OpenFlags::O_RDWR);
+    let fd = task.with_mut_fd_table(|table| -> Result<usize, SysError> {
+        let fd = table.alloc_fd()?;
+        table.put_file(fd, FdInfo { file: epoll, flags: flags.into() })?;
+        Ok(fd)
+    })?;
+    Ok(fd as isize)
+}
+
+/// syscall: epoll_ctl
+pub fn sys_epoll_ctl(epfd: usize, op: usize, fd: usize, event_ptr: usize) -> SysResult {
+    let task = current_task().unwrap();
+    let epoll = task.with_fd_table(|table| table.get_file(epfd))?
+        .downcast_arc::<EpollFile>()
+        .map_err(|_| SysError::EINVAL)?;
+    let file = task.with_fd_table(|table| table.get_file(fd))?;
+    if fd == epfd {
+        return Err(SysError::EINVAL);
+    }
+    let event = if op == EPOLL_CTL_DEL {
+        EpollEvent::default()
+    } else {
+        *UserPtrRaw::new(event_ptr as *const EpollEvent)
+            .ensure_read(&mut task.get_vm_space().lock())
+            .ok_or(SysError::EFAULT)?
+            .to_ref()
+    };
+    log::debug!("[sys_epoll_ctl] epfd {epfd} op {op} fd {fd} events {:#x}", event.events);
+    match op {
+        EPOLL_CTL_ADD => {
+            if file.clone().downcast_arc::<EpollFile>().is_ok_and(|nested| Arc::ptr_eq(&nested, &epoll)) {
+                return Err(SysError::ELOOP);
+            }
+            epoll.add(fd, &file, event)
+        }
+        EPOLL_CTL_DEL => epoll.delete(fd, &file),
+        EPOLL_CTL_MOD => epoll.modify(fd, &file, event),
+        _ => Err(SysError::EINVAL),
+    }?;
+    Ok(0)
+}
+
+/// syscall: epoll_pwait
+/// waits for events on the epoll instance, `timeout` is in milliseconds
+/// and -1 blocks forever
+pub async fn sys_epoll_pwait(epfd: usize, events_ptr: usize, maxevents: usize, timeout: usize, sigmask: usize) -> SysResult {
+    let task = current_task().unwrap().clone();
+    let maxevents = maxevents as i32;
+    if maxevents <= 0 {
+        return Err(SysError::EINVAL);
+    }
+    let epoll = task.with_fd_table(|table| table.get_file(epfd))?
+        .downcast_arc::<EpollFile>()
+        .map_err(|_| SysError::EINVAL)?;
+    let events_w = UserSliceRaw::new(events_ptr as *mut EpollEvent, maxevents as usize)
+        .ensure_write(&mut task.get_vm_space().lock())
+        .ok_or(SysError::EFAULT)?;
+    let timeout = timeout as i32;
+    let timeout = if timeout < 0 {
+        None
+    } else {
+        Some(Duration::from_millis(timeout as u64))
+    };
+    let new_mask = if sigmask == 0 {
+        None
+    } else {
+        Some(
+            *UserPtrRaw::new(sigmask as *const SigSet)
+            .ensure_read(&mut task.get_vm_space().lock())
+            .ok_or(SysError::EFAULT)?
+            .to_ref()
+        )
+    };
+
+    // save the old sig mask
+    let old_mask = task.sig_manager.lock().blocked_sigs;
+    let mut current_mask = old_mask;
+    if let Some(mask) = new_mask {
+        task.sig_manager.lock().blocked_sigs |= mask;
+        current_mask |= mask;
+    }
+    task.set_interruptable();
+    task.set_wake_up_sigs(!current_mask);
+    let wait_future = EpollWaitFuture::new(epoll, maxevents as usize);
+    let intr_future = IntrBySignalFuture {
+        task: task.clone(),
+        mask: current_mask,
+    };
+    let ret = if let Some(timeout) = timeout {
+        match Select2Futures::new(TimedTaskFuture::new(timeout, wait_future), intr_future).await {
+            SelectOutput::Output1(TimedTaskOutput::OK(ret)) => Ok(ret),
+            SelectOutput::Output1(TimedTaskOutput::TimedOut) => Ok(Vec::new()),
+            SelectOutput::Output2(_) => Err(SysError::EINTR),
+        }
+    } else {
+        match Select2Futures::new(wait_future, intr_future).await {
+            SelectOutput::Output1(ret) => Ok(ret),
+            SelectOutput::Output2(_) => Err(SysError::EINTR),
+        }
+    };
+    task.set_running();
+    // restore the sig mask
+    task.sig_manager.lock().blocked_sigs = old_mask;
+    let ret = ret?;
+    events_w.to_mut()[..ret.len()].copy_from_slice(&ret);
+    Ok(ret.len() as isize)
+}
+
+/// syscall: eventfd2
+pub fn sys_eventfd2(initval: u32, flags: i32) -> SysResult {
+    let flags = EventFdFlags::from_bits(flags).ok_or(SysError::EINVAL)?;
+    let task = current_task().unwrap();
+    let eventfd = EventFdFile::new(initval as u64, flags);
+    let fd_flags = if flags.contains(EventFdFlags::EFD_CLOEXEC) { OpenFlags::O_CLOEXEC } else { OpenFlags::empty() };
+    let fd = task.with_mut_fd_table(|table| -> Result<usize, SysError> {
+        let fd = table.alloc_fd()?;
+        table.put_file(fd, FdInfo { file: eventfd, flags: fd_flags.into() })?;
+        Ok(fd)
+    })?;
+    Ok(fd as isize)
+}
@@ -1,5 +1,7 @@
 
 /// System V IPC
 pub mod sysv;
+/// POSIX message queue
+pub mod mqueue;
 
 
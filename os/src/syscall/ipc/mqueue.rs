@@ -0,0 +1,187 @@
+//! POSIX message queue syscalls
+
+use core::{future::Future, time::Duration};
+
+use alloc::{sync::Arc, vec::Vec};
+
+use crate::{fs::{mqueue::{self, file::MqFile, queue::{MqAttr, MqRecvFuture, MqSendFuture, SigEvent, MQ_PRIO_MAX}}, vfs::File, OpenFlags}, mm::{UserPtrRaw, UserSliceRaw}, syscall::{SysError, SysResult}, task::{current_task, fs::FdInfo, signal::IntrBySignalFuture, task::TaskControlBlock}, timer::{clock::{CLOCK_DEVIATION, CLOCK_REALTIME}, ffi::TimeSpec, get_current_time_duration, timed_task::{TimedTaskFuture, TimedTaskOutput}}, utils::{user_path_to_string, Select2Futures, SelectOutput}};
+
+fn get_mq_file(task: &Arc<TaskControlBlock>, mqdes: usize) -> Result<Arc<MqFile>, SysError> {
+    task.with_fd_table(|t| t.get_file(mqdes))?
+        .downcast_arc::<MqFile>()
+        .map_err(|_| SysError::EBADF)
+}
+
+/// time left until the absolute CLOCK_REALTIME `abs_timeout`
+fn mq_timeout(task: &Arc<TaskControlBlock>, abs_timeout: usize) -> Result<Option<Duration>, SysError> {
+    if abs_timeout == 0 {
+        return Ok(None);
+    }
+    let ts = *UserPtrRaw::new(abs_timeout as *const TimeSpec)
+        .ensure_read(&mut task.get_vm_space().lock())
+        .ok_or(SysError::EFAULT)?
+        .to_ref();
+    if !ts.is_valid() {
+        return Err(SysError::EINVAL);
+    }
+    let now = unsafe { CLOCK_DEVIATION[CLOCK_REALTIME] } + get_current_time_duration();
+    let deadline: Duration = ts.into();
+    Ok(Some(deadline.saturating_sub(now)))
+}
+
+/// run a queue future, interruptable by signals and bounded by `timeout`
+async fn mq_wait<F: Future<Output = Result<T, SysError>> + Send + 'static, T>(task: &Arc<TaskControlBlock>, fut: F, timeout: Option<Duration>) -> Result<T, SysError> {
+    task.set_interruptable();
+    task.set_wake_up_sigs(task.with_sig_manager(|m| !m.blocked_sigs));
+    let intr_future = IntrBySignalFuture {
+        task: task.clone(),
+        mask: task.with_sig_manager(|m| m.blocked_sigs),
+    };
+    let ret = if let Some(timeout) = timeout {
+        match Select2Futures::new(TimedTaskFuture::new(timeout, fut), intr_future).await {
+            SelectOutput::Output1(TimedTaskOutput::OK(ret)) => ret,
+            SelectOutput::Output1(TimedTaskOutput::TimedOut) => Err(SysError::ETIMEOUT),
+            SelectOutput::Output2(_) => Err(SysError::EINTR),
+        }
+    } else {
+        match Select2Futures::new(fut, intr_future).await {
+            SelectOutput::Output1(ret) => ret,
+            SelectOutput::Output2(_) => Err(SysError::EINTR),
+        }
+    };
+    task.set_running();
+    ret
+}
+
+/// open a message queue, `name` comes without the leading slash
+pub fn sys_mq_open(name: *const u8, oflag: i32, mode: u32, attr: usize) -> SysResult {
+    let task = current_task().unwrap().clone();
+    let flags = OpenFlags::from_bits_truncate(oflag);
+    let name = user_path_to_string(UserPtrRaw::new(name), &mut task.get_vm_space().lock())?;
+    let attr = if attr != 0 && flags.contains(OpenFlags::O_CREAT) {
+        Some(*UserPtrRaw::new(attr as *const MqAttr)
+            .ensure_read(&mut task.get_vm_space().lock())
+            .ok_or(SysError::EFAULT)?
+            .to_ref())
+    } else {
+        None
+    };
+    log::info!("[sys_mq_open] {} {:?} mode {:o} attr {:?}", name, flags, mode, attr);
+    let file = mqueue::mq_open(&name, flags, mode, attr, task.uid() as u32)?;
+    let fd = task.with_mut_fd_table(|table| -> Result<usize, SysError> {
+        let fd = table.alloc_fd()?;
+        table.put_file(fd, FdInfo { file, flags: flags.into() })?;
+        Ok(fd)
+    })?;
+    Ok(fd as isize)
+}
+
+/// remove a message queue name
+pub fn sys_mq_unlink(name: *const u8) -> SysResult {
+    let task = current_task().unwrap().clone();
+    let name = user_path_to_string(UserPtrRaw::new(name), &mut task.get_vm_space().lock())?;
+    log::info!("[sys_mq_unlink] {}", name);
+    mqueue::mq_unlink(&name, task.uid() as u32)?;
+    Ok(0)
+}
+
+/// send a message with priority `msg_prio`
+pub async fn sys_mq_timedsend(mqdes: usize, msg_ptr: usize, msg_len: usize, msg_prio: u32, abs_timeout: usize) -> SysResult {
+    let task = current_task().unwrap().clone();
+    let file = get_mq_file(&task, mqdes)?;
+    if !file.writable() {
+        return Err(SysError::EBADF);
+    }
+    if msg_prio >= MQ_PRIO_MAX {
+        return Err(SysError::EINVAL);
+    }
+    if msg_len > file.queue().attr().mq_msgsize as usize {
+        return Err(SysError::EMSGSIZE);
+    }
+    let timeout = mq_timeout(&task, abs_timeout)?;
+    let msg = UserSliceRaw::new(msg_ptr as *const u8, msg_len)
+        .ensure_read(&mut task.get_vm_space().lock())
+        .ok_or(SysError::EFAULT)?
+        .to_ref()
+        .to_vec();
+    let nowait = file.flags().contains(OpenFlags::O_NONBLOCK);
+    log::debug!("[sys_mq_timedsend] fd {mqdes} len {msg_len} prio {msg_prio} timeout {:?}", timeout);
+    let send_future = MqSendFuture::new(file.queue().clone(), msg_prio, msg, task.pid(), nowait);
+    mq_wait(&task, send_future, timeout).await?;
+    Ok(0)
+}
+
+/// receive the oldest message of the highest priority
+pub async fn sys_mq_timedreceive(mqdes: usize, msg_ptr: usize, msg_len: usize, msg_prio: usize, abs_timeout: usize) -> SysResult {
+    let task = current_task().unwrap().clone();
+    let file = get_mq_file(&task, mqdes)?;
+    if !file.readable() {
+        return Err(SysError::EBADF);
+    }
+    if msg_len < file.queue().attr().mq_msgsize as usize {
+        return Err(SysError::EMSGSIZE);
+    }
+    let timeout = mq_timeout(&task, abs_timeout)?;
+    let nowait = file.flags().contains(OpenFlags::O_NONBLOCK);
+    let recv_future = MqRecvFuture::new(file.queue().clone(), nowait);
+    let (prio, msg): (u32, Vec<u8>) = mq_wait(&task, recv_future, timeout).await?;
+    log::debug!("[sys_mq_timedreceive] fd {mqdes} got len {} prio {prio}", msg.len());
+    UserSliceRaw::new(msg_ptr as *mut u8, msg.len())
+        .ensure_write(&mut task.get_vm_space().lock())
+        .ok_or(SysError::EFAULT)?
+        .to_mut()
+        .copy_from_slice(&msg);
+    if msg_prio != 0 {
+        UserPtrRaw::new(msg_prio as *mut u32)
+            .ensure_write(&mut task.get_vm_space().lock())
+            .ok_or(SysError::EFAULT)?
+            .write(prio);
+    }
+    Ok(msg.len() as isize)
+}
+
+/// register or unregister for a notification on message arrival
+pub fn sys_mq_notify(mqdes: usize, sevp: usize) -> SysResult {
+    let task = current_task().unwrap().clone();
+    let file = get_mq_file(&task, mqdes)?;
+    let event = if sevp == 0 {
+        None
+    } else {
+        Some(*UserPtrRaw::new(sevp as *const SigEvent)
+            .ensure_read(&mut task.get_vm_space().lock())
+            .ok_or(SysError::EFAULT)?
+            .to_ref())
+    };
+    log::info!("[sys_mq_notify] fd {} {:?}", mqdes, event);
+    file.queue().set_notify(task.pid(), event.as_ref())?;
+    Ok(0)
+}
+
+/// get the attributes and change O_NONBLOCK of the descriptor
+pub fn sys_mq_getsetattr(mqdes: usize, newattr: usize, oldattr: usize) -> SysResult {
+    let task = current_task().unwrap().clone();
+    let file = get_mq_file(&task, mqdes)?;
+    let mut attr = file.queue().attr();
+    if file.flags().contains(OpenFlags::O_NONBLOCK) {
+        attr.mq_flags = OpenFlags::O_NONBLOCK.bits() as isize;
+    }
+    if newattr != 0 {
+        let new = *UserPtrRaw::new(newattr as *const MqAttr)
+            .ensure_read(&mut task.get_vm_space().lock())
+            .ok_or(SysError::EFAULT)?
+            .to_ref();
+        if new.mq_flags & !(OpenFlags::O_NONBLOCK.bits() as isize) != 0 {
+            return Err(SysError::EINVAL);
+        }
+        let mut flags = file.flags();
+        flags.set(OpenFlags::O_NONBLOCK, new.mq_flags != 0);
+        file.set_flags(flags);
+    }
+    if oldattr != 0 {
+        UserPtrRaw::new(oldattr as *mut MqAttr)
+            .ensure_write(&mut task.get_vm_space().lock())
+            .ok_or(SysError::EFAULT)?
+            .write(attr);
+    }
+    Ok(0)
+}
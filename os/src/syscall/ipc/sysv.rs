@@ -1,9 +1,9 @@
-use core::ops::Deref;
+use core::{ops::Deref, time::Duration};
 
 use alloc::task;
 use hal::{addr::{VirtAddr, VirtAddrHal, VirtPageNumHal}, constant::{Constant, ConstantsHal}, pagetable::MapPerm};
 
-use crate::{ipc::sysv::{self, ShmIdDs, IPC_PRIVATE}, mm::{vm::{MapFlags, UserVmFile, UserVmSpaceHal}, UserPtrRaw}, syscall::{mm::MmapFlags, SysError, SysResult}, task::current_task};
+use crate::{ipc::sysv::{self, current_ipc_ns, ShmIdDs, IPC_PRIVATE}, mm::{vm::{MapFlags, UserVmFile, UserVmSpaceHal}, UserPtrRaw, UserSliceRaw}, syscall::{mm::MmapFlags, SysError, SysResult}, task::{current_task, signal::IntrBySignalFuture}, timer::{ffi::TimeSpec, timed_task::{TimedTaskFuture, TimedTaskOutput}}, utils::{Select2Futures, SelectOutput}};
 
 bitflags! {
     struct ShmGetFlags: i32 {
@@ -42,10 +42,10 @@ pub fn sys_shmget(key: i32, size: usize, shmflg: i32) -> SysResult {
     log::info!("[sys_shmget] {key} {size} {:?}", shmflg);
     let rounded_up_sz = (size - 1 + Constant::PAGE_SIZE) & !(Constant::PAGE_SIZE - 1);
     if key == IPC_PRIVATE {
-        let shm = sysv::SHM_MANAGER.alloc(rounded_up_sz, task.pid()).unwrap();
+        let shm = current_ipc_ns().shm.alloc(rounded_up_sz, task.pid()).unwrap();
         return Ok(shm.get_id() as isize);
     }
-    if let Some(shm) = sysv::SHM_MANAGER.get(key as usize) {
+    if let Some(shm) = current_ipc_ns().shm.get(key as usize) {
         if shmflg.contains(ShmGetFlags::IPC_CREAT | ShmGetFlags::IPC_EXCL) {
             return Err(SysError::EEXIST);
         }
@@ -55,7 +55,7 @@ pub fn sys_shmget(key: i32, size: usize, shmflg: i32) -> SysResult {
         return Ok(key as isize);
     }
     if shmflg.contains(ShmGetFlags::IPC_CREAT) {
-        let shm = sysv::SHM_MANAGER.alloc_at(rounded_up_sz, task.pid(), key as usize).unwrap();
+        let shm = current_ipc_ns().shm.alloc_at(rounded_up_sz, task.pid(), key as usize).unwrap();
         return Ok(shm.get_id() as isize);
     } else {
         return Err(SysError::ENOENT);
@@ -77,7 +77,7 @@ pub fn sys_shmat(shmid: i32, mut shmaddr: VirtAddr, shmflg: i32) -> SysResult {
     if shmflg.contains(ShmAtFlags::SHM_RDONLY) {
         perm.remove(MapPerm::W);
     }
-    if let Some(shm) = sysv::SHM_MANAGER.get(shmid as usize) {
+    if let Some(shm) = current_ipc_ns().shm.get(shmid as usize) {
         let task = current_task().unwrap();
         let mut vm = task.get_vm_space().lock();
         let ret = vm.alloc_anon_area(
@@ -121,7 +121,7 @@ pub fn sys_shmctl(shmid: i32, op: i32, shmid_ds: UserPtrRaw<ShmIdDs>) -> SysResu
     match op {
         IPC_STAT => {
             let task = current_task().unwrap();
-            let shm = sysv::SHM_MANAGER.get(shmid as usize).ok_or(SysError::ENOENT)?;
+            let shm = current_ipc_ns().shm.get(shmid as usize).ok_or(SysError::ENOENT)?;
             shmid_ds
                 .ensure_write(&mut task.get_vm_space().lock())
                 .ok_or(SysError::EINVAL)?
@@ -129,7 +129,7 @@ pub fn sys_shmctl(shmid: i32, op: i32, shmid_ds: UserPtrRaw<ShmIdDs>) -> SysResu
             Ok(0)
         }
         IPC_RMID => {
-            sysv::SHM_MANAGER.remove(shmid as usize).ok_or(SysError::ENOENT)?;
+            current_ipc_ns().shm.remove(shmid as usize).ok_or(SysError::ENOENT)?;
             Ok(0)
         }
         IPC_SET => {
@@ -140,3 +140,401 @@ pub fn sys_shmctl(shmid: i32, op: i32, shmid_ds: UserPtrRaw<ShmIdDs>) -> SysResu
         _ => Err(SysError::EINVAL)
     }
 }
+
+bitflags! {
+    struct MsgFlags: i32 {
+        /// return immediately if no message of the requested type is in the queue,
+        /// or the queue is full when sending
+        const IPC_NOWAIT = 0o4000;
+        /// truncate the message text if longer than msgsz bytes
+        const MSG_NOERROR = 0o10000;
+        /// read the first message with a type not equal to msgtyp
+        const MSG_EXCEPT = 0o20000;
+        /// nondestructively fetch a copy of the message at position msgtyp
+        const MSG_COPY = 0o40000;
+    }
+}
+
+/// ipc commands newer glibc may or in
+const IPC_64: i32 = 0x100;
+const IPC_INFO: i32 = 3;
+const MSG_INFO: i32 = 12;
+
+/// permission bits of `mode` that apply to the current user
+fn ipc_access(uid: u32, cuid: u32, mode: u32, want: u32) -> bool {
+    let task = current_task().unwrap();
+    let me = task.uid() as u32;
+    if me == 0 {
+        return true;
+    }
+    let granted = if me == uid || me == cuid {
+        (mode >> 6) & 0o7
+    } else {
+        mode & 0o7
+    };
+    granted & want == want
+}
+
+pub fn sys_msgget(key: i32, msgflg: i32) -> SysResult {
+    let task = current_task().unwrap();
+    let flags = ShmGetFlags::from_bits_truncate(msgflg);
+    log::info!("[sys_msgget] key {key} {:?}", flags);
+    if let Some(queue) = current_ipc_ns().msg.find_key(key) {
+        if flags.contains(ShmGetFlags::IPC_CREAT | ShmGetFlags::IPC_EXCL) {
+            return Err(SysError::EEXIST);
+        }
+        let ds = queue.stat();
+        let want = ((msgflg as u32) >> 6) & 0o7;
+        let (uid, cuid) = queue.owners();
+        if !ipc_access(uid, cuid, ds.perm.mode, want) {
+            return Err(SysError::EACCES);
+        }
+        return Ok(queue.get_id() as isize);
+    }
+    if key != IPC_PRIVATE && !flags.contains(ShmGetFlags::IPC_CREAT) {
+        return Err(SysError::ENOENT);
+    }
+    let uid = task.uid() as u32;
+    let queue = current_ipc_ns().msg.alloc(key, uid, 0, msgflg as u32)?;
+    Ok(queue.get_id() as isize)
+}
+
+pub async fn sys_msgsnd(msqid: i32, msgp: usize, msgsz: usize, msgflg: i32) -> SysResult {
+    let task = current_task().unwrap().clone();
+    let flags = MsgFlags::from_bits_truncate(msgflg);
+    if msqid < 0 || msgsz > sysv::MSGMAX {
+        return Err(SysError::EINVAL);
+    }
+    let queue = current_ipc_ns().msg.get(msqid as usize).ok_or(SysError::EINVAL)?;
+    let (uid, cuid) = queue.owners();
+    if !ipc_access(uid, cuid, queue.stat().perm.mode, 0o2) {
+        return Err(SysError::EACCES);
+    }
+    // struct msgbuf { long mtype; char mtext[]; }
+    let msg = UserSliceRaw::new(msgp as *const u8, size_of::<isize>() + msgsz)
+        .ensure_read(&mut task.get_vm_space().lock())
+        .ok_or(SysError::EFAULT)?;
+    let msg = msg.to_ref();
+    let mtype = isize::from_ne_bytes(msg[..size_of::<isize>()].try_into().unwrap());
+    if mtype < 1 {
+        return Err(SysError::EINVAL);
+    }
+    let text = msg[size_of::<isize>()..].to_vec();
+    log::debug!("[sys_msgsnd] id {msqid} type {mtype} len {msgsz} {:?}", flags);
+    let send_future = sysv::MsgSendFuture::new(queue, mtype, text, task.pid() as i32, flags.contains(MsgFlags::IPC_NOWAIT));
+    task.set_interruptable();
+    task.set_wake_up_sigs(task.with_sig_manager(|m| !m.blocked_sigs));
+    let intr_future = IntrBySignalFuture {
+        task: task.clone(),
+        mask: task.with_sig_manager(|m| m.blocked_sigs),
+    };
+    let ret = Select2Futures::new(send_future, intr_future).await;
+    task.set_running();
+    match ret {
+        SelectOutput::Output1(ret) => ret.map(|_| 0),
+        SelectOutput::Output2(_) => Err(SysError::EINTR),
+    }
+}
+
+pub async fn sys_msgrcv(msqid: i32, msgp: usize, msgsz: usize, msgtyp: isize, msgflg: i32) -> SysResult {
+    let task = current_task().unwrap().clone();
+    let flags = MsgFlags::from_bits_truncate(msgflg);
+    if msqid < 0 || (msgsz as isize) < 0 {
+        return Err(SysError::EINVAL);
+    }
+    if flags.contains(MsgFlags::MSG_COPY) {
+        log::warn!("[sys_msgrcv] unsupported MSG_COPY");
+        return Err(SysError::ENOSYS);
+    }
+    let queue = current_ipc_ns().msg.get(msqid as usize).ok_or(SysError::EINVAL)?;
+    let (uid, cuid) = queue.owners();
+    if !ipc_access(uid, cuid, queue.stat().perm.mode, 0o4) {
+        return Err(SysError::EACCES);
+    }
+    let msg = UserSliceRaw::new(msgp as *mut u8, size_of::<isize>() + msgsz)
+        .ensure_write(&mut task.get_vm_space().lock())
+        .ok_or(SysError::EFAULT)?;
+    let recv_future = sysv::MsgRecvFuture::new(
+        queue, msgsz, msgtyp,
+        flags.contains(MsgFlags::MSG_EXCEPT),
+        flags.contains(MsgFlags::MSG_NOERROR),
+        task.pid() as i32,
+        flags.contains(MsgFlags::IPC_NOWAIT),
+    );
+    task.set_interruptable();
+    task.set_wake_up_sigs(task.with_sig_manager(|m| !m.blocked_sigs));
+    let intr_future = IntrBySignalFuture {
+        task: task.clone(),
+        mask: task.with_sig_manager(|m| m.blocked_sigs),
+    };
+    let ret = Select2Futures::new(recv_future, intr_future).await;
+    task.set_running();
+    let (mtype, text) = match ret {
+        SelectOutput::Output1(ret) => ret?,
+        SelectOutput::Output2(_) => return Err(SysError::EINTR),
+    };
+    let msg = msg.to_mut();
+    msg[..size_of::<isize>()].copy_from_slice(&mtype.to_ne_bytes());
+    msg[size_of::<isize>()..size_of::<isize>() + text.len()].copy_from_slice(&text);
+    Ok(text.len() as isize)
+}
+
+pub fn sys_msgctl(msqid: i32, cmd: i32, buf: usize) -> SysResult {
+    let task = current_task().unwrap();
+    let cmd = cmd & !IPC_64;
+    log::info!("[sys_msgctl] {} {} {:#x}", msqid, cmd, buf);
+    match cmd {
+        IPC_INFO | MSG_INFO => {
+            let (info, max_id) = current_ipc_ns().msg.info(cmd == MSG_INFO);
+            UserPtrRaw::new(buf as *mut sysv::MsgInfo)
+                .ensure_write(&mut task.get_vm_space().lock())
+                .ok_or(SysError::EFAULT)?
+                .write(info);
+            return Ok(max_id as isize);
+        }
+        _ => {}
+    }
+    if msqid < 0 {
+        return Err(SysError::EINVAL);
+    }
+    let queue = current_ipc_ns().msg.get(msqid as usize).ok_or(SysError::EINVAL)?;
+    let (uid, cuid) = queue.owners();
+    let me = task.uid() as u32;
+    match cmd {
+        IPC_STAT => {
+            if !ipc_access(uid, cuid, queue.stat().perm.mode, 0o4) {
+                return Err(SysError::EACCES);
+            }
+            UserPtrRaw::new(buf as *mut sysv::MsqIdDs)
+                .ensure_write(&mut task.get_vm_space().lock())
+                .ok_or(SysError::EFAULT)?
+                .write(queue.stat());
+            Ok(0)
+        }
+        IPC_SET => {
+            if me != 0 && me != uid && me != cuid {
+                return Err(SysError::EPERM);
+            }
+            let new = *UserPtrRaw::new(buf as *const sysv::MsqIdDs)
+                .ensure_read(&mut task.get_vm_space().lock())
+                .ok_or(SysError::EFAULT)?
+                .to_ref();
+            queue.set(&new, me == 0)?;
+            Ok(0)
+        }
+        IPC_RMID => {
+            if me != 0 && me != uid && me != cuid {
+                return Err(SysError::EPERM);
+            }
+            current_ipc_ns().msg.remove(msqid as usize).ok_or(SysError::EINVAL)?;
+            Ok(0)
+        }
+        _ => Err(SysError::EINVAL)
+    }
+}
+
+/// semctl commands
+const GETPID: i32 = 11;
+const GETVAL: i32 = 12;
+const GETALL: i32 = 13;
+const GETNCNT: i32 = 14;
+const GETZCNT: i32 = 15;
+const SETVAL: i32 = 16;
+const SETALL: i32 = 17;
+const SEM_INFO: i32 = 19;
+
+/// get or create a semaphore set
+pub fn sys_semget(key: i32, nsems: i32, semflg: i32) -> SysResult {
+    let task = current_task().unwrap();
+    let flags = ShmGetFlags::from_bits_truncate(semflg);
+    log::info!("[sys_semget] key {key} nsems {nsems} {:?}", flags);
+    if nsems < 0 || nsems as usize > sysv::SEMMSL {
+        return Err(SysError::EINVAL);
+    }
+    if let Some(set) = current_ipc_ns().sem.find_key(key) {
+        if flags.contains(ShmGetFlags::IPC_CREAT | ShmGetFlags::IPC_EXCL) {
+            return Err(SysError::EEXIST);
+        }
+        if nsems as usize > set.nsems() {
+            return Err(SysError::EINVAL);
+        }
+        let want = ((semflg as u32) >> 6) & 0o7;
+        let (uid, cuid) = set.owners();
+        if !ipc_access(uid, cuid, set.stat().perm.mode, want) {
+            return Err(SysError::EACCES);
+        }
+        return Ok(set.get_id() as isize);
+    }
+    if key != IPC_PRIVATE && !flags.contains(ShmGetFlags::IPC_CREAT) {
+        return Err(SysError::ENOENT);
+    }
+    if nsems == 0 {
+        return Err(SysError::EINVAL);
+    }
+    let uid = task.uid() as u32;
+    let set = current_ipc_ns().sem.alloc(key, nsems as usize, uid, 0, semflg as u32)?;
+    Ok(set.get_id() as isize)
+}
+
+/// semop is semtimedop without a timeout
+pub async fn sys_semop(semid: i32, sops: usize, nsops: usize) -> SysResult {
+    sys_semtimedop(semid, sops, nsops, 0).await
+}
+
+/// apply the operations of `sops` atomically, waiting at most `timeout`
+pub async fn sys_semtimedop(semid: i32, sops: usize, nsops: usize, timeout: usize) -> SysResult {
+    let task = current_task().unwrap().clone();
+    if semid < 0 || nsops == 0 {
+        return Err(SysError::EINVAL);
+    }
+    if nsops > sysv::SEMOPM {
+        return Err(SysError::E2BIG);
+    }
+    let ops = UserSliceRaw::new(sops as *const sysv::SemBuf, nsops)
+        .ensure_read(&mut task.get_vm_space().lock())
+        .ok_or(SysError::EFAULT)?
+        .to_ref()
+        .to_vec();
+    let timeout: Option<Duration> = if timeout == 0 {
+        None
+    } else {
+        let ts = *UserPtrRaw::new(timeout as *const TimeSpec)
+            .ensure_read(&mut task.get_vm_space().lock())
+            .ok_or(SysError::EFAULT)?
+            .to_ref();
+        if !ts.is_valid() {
+            return Err(SysError::EINVAL);
+        }
+        Some(ts.into())
+    };
+    let set = current_ipc_ns().sem.get(semid as usize).ok_or(SysError::EINVAL)?;
+    if ops.iter().any(|op| op.sem_num as usize >= set.nsems()) {
+        return Err(SysError::EFBIG);
+    }
+    let alter = ops.iter().any(|op| op.sem_op != 0);
+    let (uid, cuid) = set.owners();
+    if !ipc_access(uid, cuid, set.stat().perm.mode, if alter { 0o2 } else { 0o4 }) {
+        return Err(SysError::EACCES);
+    }
+    log::debug!("[sys_semtimedop] id {semid} ops {:?} timeout {:?}", ops, timeout);
+    let op_future = sysv::SemOpFuture::new(current_ipc_ns(), set, ops, task.pid() as i32);
+    task.set_interruptable();
+    task.set_wake_up_sigs(task.with_sig_manager(|m| !m.blocked_sigs));
+    let intr_future = IntrBySignalFuture {
+        task: task.clone(),
+        mask: task.with_sig_manager(|m| m.blocked_sigs),
+    };
+    let ret = if let Some(timeout) = timeout {
+        match Select2Futures::new(TimedTaskFuture::new(timeout, op_future), intr_future).await {
+            SelectOutput::Output1(TimedTaskOutput::OK(ret)) => ret,
+            SelectOutput::Output1(TimedTaskOutput::TimedOut) => Err(SysError::EAGAIN),
+            SelectOutput::Output2(_) => Err(SysError::EINTR),
+        }
+    } else {
+        match Select2Futures::new(op_future, intr_future).await {
+            SelectOutput::Output1(ret) => ret,
+            SelectOutput::Output2(_) => Err(SysError::EINTR),
+        }
+    };
+    task.set_running();
+    ret.map(|_| 0)
+}
+
+/// semaphore control, `arg` is the `union semun` passed by value
+pub fn sys_semctl(semid: i32, semnum: i32, cmd: i32, arg: usize) -> SysResult {
+    let task = current_task().unwrap();
+    let cmd = cmd & !IPC_64;
+    log::info!("[sys_semctl] {} {} {} {:#x}", semid, semnum, cmd, arg);
+    match cmd {
+        IPC_INFO | SEM_INFO => {
+            let (info, max_id) = current_ipc_ns().sem.info(cmd == SEM_INFO);
+            UserPtrRaw::new(arg as *mut sysv::SemInfo)
+                .ensure_write(&mut task.get_vm_space().lock())
+                .ok_or(SysError::EFAULT)?
+                .write(info);
+            return Ok(max_id as isize);
+        }
+        _ => {}
+    }
+    if semid < 0 {
+        return Err(SysError::EINVAL);
+    }
+    let set = current_ipc_ns().sem.get(semid as usize).ok_or(SysError::EINVAL)?;
+    let (uid, cuid) = set.owners();
+    let mode = set.stat().perm.mode;
+    let me = task.uid() as u32;
+    let want = match cmd {
+        IPC_STAT | GETPID | GETVAL | GETALL | GETNCNT | GETZCNT => 0o4,
+        SETVAL | SETALL => 0o2,
+        _ => 0,
+    };
+    if want != 0 && !ipc_access(uid, cuid, mode, want) {
+        return Err(SysError::EACCES);
+    }
+    match cmd {
+        IPC_STAT => {
+            UserPtrRaw::new(arg as *mut sysv::SemIdDs)
+                .ensure_write(&mut task.get_vm_space().lock())
+                .ok_or(SysError::EFAULT)?
+                .write(set.stat());
+            Ok(0)
+        }
+        IPC_SET => {
+            if me != 0 && me != uid && me != cuid {
+                return Err(SysError::EPERM);
+            }
+            let new = *UserPtrRaw::new(arg as *const sysv::SemIdDs)
+                .ensure_read(&mut task.get_vm_space().lock())
+                .ok_or(SysError::EFAULT)?
+                .to_ref();
+            set.set(&new);
+            Ok(0)
+        }
+        IPC_RMID => {
+            if me != 0 && me != uid && me != cuid {
+                return Err(SysError::EPERM);
+            }
+            current_ipc_ns().sem.remove(semid as usize).ok_or(SysError::EINVAL)?;
+            Ok(0)
+        }
+        GETVAL | GETPID | GETNCNT | GETZCNT => {
+            if semnum < 0 {
+                return Err(SysError::EINVAL);
+            }
+            let (val, pid, ncnt, zcnt) = set.get(semnum as usize)?;
+            Ok(match cmd {
+                GETVAL => val as isize,
+                GETPID => pid as isize,
+                GETNCNT => ncnt as isize,
+                _ => zcnt as isize,
+            })
+        }
+        GETALL => {
+            let vals = set.get_all();
+            UserSliceRaw::new(arg as *mut u16, vals.len())
+                .ensure_write(&mut task.get_vm_space().lock())
+                .ok_or(SysError::EFAULT)?
+                .to_mut()
+                .copy_from_slice(&vals);
+            Ok(0)
+        }
+        SETVAL => {
+            if semnum < 0 {
+                return Err(SysError::EINVAL);
+            }
+            // semun.val is an int in the low half of the register
+            set.set_val(&current_ipc_ns().sem, semnum as usize, arg as i32, task.pid() as i32)?;
+            Ok(0)
+        }
+        SETALL => {
+            let vals = UserSliceRaw::new(arg as *const u16, set.nsems())
+                .ensure_read(&mut task.get_vm_space().lock())
+                .ok_or(SysError::EFAULT)?
+                .to_ref()
+                .to_vec();
+            set.set_all(&current_ipc_ns().sem, &vals, task.pid() as i32)?;
+            Ok(0)
+        }
+        _ => Err(SysError::EINVAL)
+    }
+}
@@ -11,7 +11,9 @@ use lazy_static::lazy_static;
 use crate::mm::{UserPtrRaw, UserSliceRaw};
 use crate::sync::mutex::SpinNoIrqLock;
 use crate::syscall::SysError;
-use crate::{fs::devfs::urandom::RNG, task::{current_task, manager::TASK_MANAGER}, timer::{get_current_time,ffi::TimeVal}};
+use crate::sysctl::{register_sysctl, StrSysctl};
+use crate::{fs::devfs::urandom::RNG, task::{current_task, manager::TASK_MANAGER, ns::alloc_ns_id}, timer::{get_current_time_sec, ffi::TimeVal}};
+use crate::{config::PAGE_SIZE, mm::{allocator::{free_frames, total_frames}, swap}, processor::loadavg::{loadavg, FSHIFT}};
 
 use super::SysResult;
 
@@ -54,22 +56,23 @@ pub struct Sysinfo {
 }
 
 /// syscall: sysinfo
-/// TODO: unimlement
 pub fn sys_sysinfo(info: usize) -> SysResult {
+    // loads are fixed point with 16 fractional bits
+    const SI_LOAD_SHIFT: u32 = 16;
     let sysinfo = Sysinfo {
-        uptime: get_current_time() as i64,
-        loads: [0; 3],
-        totalram: 0,
-        freeram: 0,
+        uptime: get_current_time_sec() as i64,
+        loads: loadavg().map(|load| load << (SI_LOAD_SHIFT - FSHIFT)),
+        totalram: (total_frames() * PAGE_SIZE) as u64,
+        freeram: (free_frames() * PAGE_SIZE) as u64,
         sharedram: 0,
         bufferram: 0,
-        totalswap: 0,
-        freeswap: 0,
-        procs: 0,
+        totalswap: (swap::total_pages() * PAGE_SIZE) as u64,
+        freeswap: (swap::free_pages() * PAGE_SIZE) as u64,
+        procs: TASK_MANAGER.tasks_group().len() as u16,
         pad: 0,
         totalhigh: 0,
         freehigh: 0,
-        mem_uint: 0,
+        mem_uint: 1,
         _f: [0; _F_SIZE],
     };
     // unsafe {
@@ -368,6 +371,7 @@ impl UtsName {
     }
 }
 
+#[derive(Clone)]
 pub struct UtsManager {
     /// Name of the implementation of the operating system.
     pub sysname: String,
@@ -415,13 +419,56 @@ impl UtsManager {
     }
 }
 
+/// a uts namespace, the names seen by the tasks in it
+pub struct UtsNamespace {
+    pub id: usize,
+    pub uts: SpinNoIrqLock<UtsManager>,
+}
+
+impl UtsNamespace {
+    /// a new namespace starting with the names of this one
+    pub fn copy(&self) -> Arc<Self> {
+        let uts = self.uts.lock().clone();
+        Arc::new(Self { id: alloc_ns_id(), uts: SpinNoIrqLock::new(uts) })
+    }
+}
+
 lazy_static! {
-    pub static ref UTS: SpinNoIrqLock<UtsManager> = SpinNoIrqLock::new(UtsManager::new());
+    pub static ref INIT_UTS_NS: Arc<UtsNamespace> = Arc::new(UtsNamespace {
+        id: alloc_ns_id(),
+        uts: SpinNoIrqLock::new(UtsManager::new()),
+    });
+}
+
+/// uts namespace of the current task
+pub fn current_uts_ns() -> Arc<UtsNamespace> {
+    current_task().map_or_else(|| INIT_UTS_NS.clone(), |task| task.uts_ns())
+}
+
+/// longest hostname or domainname, the rest of the 65 bytes is the nul
+const UTS_NAME_MAX: usize = 64;
+
+/// kernel.hostname and kernel.domainname, shared with sethostname
+pub fn register_sysctls() {
+    fn check_name(name: &str) -> Result<&str, SysError> {
+        if name.len() > UTS_NAME_MAX {
+            return Err(SysError::EINVAL);
+        }
+        Ok(name)
+    }
+    register_sysctl("kernel.hostname", StrSysctl::new(
+        || current_uts_ns().uts.lock().nodename.clone(),
+        |name| Ok(current_uts_ns().uts.lock().set_nodename(check_name(name)?)),
+    ));
+    register_sysctl("kernel.domainname", StrSysctl::new(
+        || current_uts_ns().uts.lock().domainname.clone(),
+        |name| Ok(current_uts_ns().uts.lock().set_domainname(check_name(name)?)),
+    ));
 }
 
 /// syscall uname
 pub fn sys_uname(uname_buf: usize) -> SysResult {
-    let uname = UTS.lock().get_utsname();
+    let uname = current_uts_ns().uts.lock().get_utsname();
     // let uname_ptr = uname_buf as *mut UtsName;
     let task = current_task().unwrap();
     let uname_ptr = UserPtrRaw::new(uname_buf as *mut UtsName)
@@ -1,12 +1,13 @@
 //! memory related syscall
 #![allow(missing_docs)]
 
-use core::time::Duration;
+use core::{sync::atomic::Ordering, time::Duration};
 
+use alloc::sync::Arc;
 use hal::{addr::{VirtAddr, VirtAddrHal, VirtPageNumHal}, constant::{Constant, ConstantsHal}, pagetable::MapPerm, println};
 use log::info;
 
-use crate::{config::PAGE_SIZE, ipc::sysv::SHM_MANAGER, mm::vm::{self, MapFlags, UserVmArea, UserVmAreaType, UserVmFile, UserVmSpaceHal}, task::current_task, timer::get_current_time_duration, utils::timer::TimerGuard};
+use crate::{config::PAGE_SIZE, fs::{tmpfs::memfd::MemFdFile, vfs::File}, ipc::sysv::current_ipc_ns, mm::{vm_enough_memory, OVERCOMMIT_MEMORY, OVERCOMMIT_NEVER}, mm::vm::{self, MapFlags, UserVmArea, UserVmAreaType, UserVmFile, UserVmSpaceHal}, task::current_task, timer::get_current_time_duration, utils::timer::TimerGuard};
 
 use super::{SysError, SysResult};
 
@@ -78,6 +79,18 @@ bitflags! {
     }
 }
 
+bitflags! {
+    // Defined in <bits/mman-linux.h>
+    pub struct MsyncFlags: i32 {
+        /// Sync memory asynchronously.
+        const MS_ASYNC = 1;
+        /// Invalidate the caches.
+        const MS_INVALIDATE = 2;
+        /// Synchronous memory sync.
+        const MS_SYNC = 4;
+    }
+}
+
 impl From<MmapProt> for MapPerm {
     fn from(prot: MmapProt) -> Self {
         let mut ret = Self::U;
@@ -108,9 +121,11 @@ pub fn sys_mmap(
     let perm = MapPerm::from(prot);
     let task = current_task().unwrap().clone();
     // info!("[sys_mmap] addr: {:#x} length: {}, prot: {:?}, flags: {:?}, fd: {}, offset: {}", addr.0, length, prot, flags, fd, offset);
-    if !flags.contains(MmapFlags::MAP_ANONYMOUS) {
-        task.with_fd_table(|t| t.get_file(fd))?;
-    }
+    let file = if flags.contains(MmapFlags::MAP_ANONYMOUS) {
+        None
+    } else {
+        Some(task.with_fd_table(|t| t.get_file(fd))?)
+    };
     
     if length == 0 {
         return Err(SysError::EINVAL);
@@ -120,38 +135,53 @@ pub fn sys_mmap(
         return Err(SysError::EINVAL);
     }
 
+    // a private writable mapping may need a frame for each page
+    let private_write = flags.intersection(MmapFlags::MAP_TYPE_MASK) == MmapFlags::MAP_PRIVATE
+        && prot.contains(MmapProt::PROT_WRITE);
+    let noreserve = flags.contains(MmapFlags::MAP_NORESERVE)
+        && OVERCOMMIT_MEMORY.load(Ordering::Relaxed) != OVERCOMMIT_NEVER;
+    if private_write && !noreserve {
+        vm_enough_memory(length.div_ceil(PAGE_SIZE))?;
+    }
+
+    // refused before MAP_FIXED throws the old mapping away
+    if flags.intersection(MmapFlags::MAP_TYPE_MASK) == MmapFlags::MAP_SHARED {
+        if let Some(file) = &file {
+            check_sealed_shared(file, prot).map_err(|_| SysError::EPERM)?;
+        }
+    }
     if flags.contains(MmapFlags::MAP_FIXED) {
         task.with_mut_vm_space(|m| m.unmap(addr, length))?;
     }
 
     match flags.intersection(MmapFlags::MAP_TYPE_MASK) {
         MmapFlags::MAP_SHARED => {
-            if flags.contains(MmapFlags::MAP_ANONYMOUS) {
+            if let Some(file) = file {
                 let start_va = task.with_mut_vm_space(|m| {
-                    m.alloc_anon_area(addr, length, perm, flags, SHM_MANAGER.alloc(length, task.pid()))
+                    // again under the lock, F_ADD_SEALS may have come in
+                    check_sealed_shared(&file, prot).map_err(|_| SysError::EPERM)?;
+                    m.alloc_mmap_area(addr, length, perm, flags, file, offset)
                 })?;
                 Ok(start_va.0 as _)
             } else {
-                let file = task.with_fd_table(|t| t.get_file(fd))?;
                 let start_va = task.with_mut_vm_space(|m| {
-                    m.alloc_mmap_area(addr, length, perm, flags, file, offset)
+                    m.alloc_anon_area(addr, length, perm, flags, current_ipc_ns().shm.alloc(length, task.pid()))
                 })?;
                 Ok(start_va.0 as _)
             }
         }
         MmapFlags::MAP_PRIVATE => {
-            if flags.contains(MmapFlags::MAP_ANONYMOUS) {
+            if let Some(file) = file {
+                // TODO: private copy on write
                 let start_va = task.with_mut_vm_space(|m| {
-                    m.alloc_anon_area(addr, length, perm, flags, None)
+                    m.alloc_mmap_area(addr, length, perm, flags, file, offset)
                 })?;
-                // log::info!("[sys_mmap] private anonymous: {:?}", start_va);
                 Ok(start_va.0 as _)
             } else {
-                let file = task.with_fd_table(|t| t.get_file(fd))?;
-                // TODO: private copy on write
                 let start_va = task.with_mut_vm_space(|m| {
-                    m.alloc_mmap_area(addr, length, perm, flags, file, offset)
+                    m.alloc_anon_area(addr, length, perm, flags, None)
                 })?;
+                // log::info!("[sys_mmap] private anonymous: {:?}", start_va);
                 Ok(start_va.0 as _)
             }
         }
@@ -159,6 +189,15 @@ pub fn sys_mmap(
     }
 }
 
+/// a shared mapping of a memfd sealed against writes cannot be writable,
+/// private mappings only write to their own copy
+fn check_sealed_shared(file: &Arc<dyn File>, prot: MmapProt) -> Result<(), SysError> {
+    match file.clone().downcast_arc::<MemFdFile>() {
+        Ok(memfd) if prot.contains(MmapProt::PROT_WRITE) && !memfd.can_map_write() => Err(SysError::EACCES),
+        _ => Ok(()),
+    }
+}
+
 /// syscall munmap
 pub fn sys_munmap(addr: VirtAddr, mut length: usize) -> SysResult {
     let task = current_task().unwrap().clone();
@@ -193,6 +232,16 @@ pub fn sys_mprotect(addr: VirtAddr, mut length: usize, prot: i32) -> SysResult {
     // println!("[mprotect] {:#x} {:#x} {:?}", addr.0, length, prot);
     let task = current_task().unwrap().clone();
     task.with_mut_vm_space(|vm| -> SysResult {
+        for view in vm.area_views() {
+            if view.range_va.end.0 <= addr.0 || view.range_va.start.0 >= addr.0 + length {
+                continue;
+            }
+            if let UserVmFile::File(file) = &view.file {
+                if view.map_flags.contains(MapFlags::SHARED) {
+                    check_sealed_shared(file, prot)?;
+                }
+            }
+        }
         let end_vpn = (addr + length).ceil();
         let mut cur_vpn = addr.floor();
         while cur_vpn < end_vpn {
@@ -290,4 +339,34 @@ pub fn sys_mremap(
     }
 
     Ok(new_addr.0 as isize)
-}
\ No newline at end of file
+}
+
+/// syscall msync
+/// the pages written through shared file mappings in the range are set
+/// dirty, and with MS_SYNC written back before returning. MS_ASYNC leaves
+/// them to the flusher, the page cache is coherent so MS_INVALIDATE has
+/// nothing to do
+pub fn sys_msync(addr: VirtAddr, length: usize, flags: i32) -> SysResult {
+    let flags = MsyncFlags::from_bits(flags).ok_or(SysError::EINVAL)?;
+    if addr.page_offset() != 0 || flags.contains(MsyncFlags::MS_ASYNC | MsyncFlags::MS_SYNC) {
+        return Err(SysError::EINVAL);
+    }
+    if length == 0 {
+        return Ok(0);
+    }
+    let task = current_task().unwrap().clone();
+    let dirty = task.with_mut_vm_space(|vm| vm.sync_shared(addr, length))?;
+    if !flags.contains(MsyncFlags::MS_SYNC) {
+        return Ok(0);
+    }
+    let mut ret = Ok(());
+    for (cache, offset, page) in dirty {
+        let Some(inode) = cache.host() else {
+            continue;
+        };
+        if let Err(e) = cache.write_page(&*inode, offset, &page) {
+            ret = ret.and(Err(SysError::from_i32(e)));
+        }
+    }
+    ret.map(|_| 0)
+}
@@ -29,6 +29,7 @@ pub enum SyscallId {
     SYSCALL_LREMOVEXATTR = 15,
     SYSCALL_FREMOVEXATTR = 16,
     SYSCALL_GETCWD = 17,
+    SYSCALL_EVENTFD2 = 19,
     SYSCALL_EPOLL_CREATE1 = 20,
     SYSCALL_EPOLL_CTL = 21,
     SYSCALL_EPOLL_PWAIT = 22,
@@ -49,6 +50,7 @@ pub enum SyscallId {
     SYSCALL_LINKAT = 37,
     SYSCALL_UMOUNT2 = 39,
     SYSCALL_MOUNT = 40,
+    SYSCALL_PIVOT_ROOT = 41,
     SYSCALL_STATFS = 43,
     SYSCALL_FSTATFS = 44,
     SYSCALL_TRUNCATE = 45,
@@ -78,6 +80,7 @@ pub enum SyscallId {
     SYSCALL_SENDFILE = 71,
     SYSCALL_PSELECT6 = 72,
     SYSCALL_PPOLL = 73,
+    SYSCALL_SIGNALFD4 = 74,
     SYSCALL_VMSPLICE = 75,
     SYSCALL_SPLICE = 76,
     SYSCALL_TEE = 77,
@@ -87,6 +90,9 @@ pub enum SyscallId {
     SYSCALL_SYNC = 81,
     SYSCALL_FSYNC = 82,
     SYSCALL_FDATASYNC = 83,
+    SYSCALL_TIMERFD_CREATE = 85,
+    SYSCALL_TIMERFD_SETTIME = 86,
+    SYSCALL_TIMERFD_GETTIME = 87,
     SYSCALL_UTIMENSAT = 88,
     SYSCALL_ACCT = 89,
     SYSCALL_CAPGET = 90,
@@ -95,6 +101,7 @@ pub enum SyscallId {
     SYSCALL_EXIT = 93,
     SYSCALL_EXIT_GROUP = 94,
     SYSCALL_SET_TID_ADDRESS = 96,
+    SYSCALL_UNSHARE = 97,
     SYSCALL_FUTEX = 98,
     SYSCALL_SET_ROBUST_LIST = 99,
     SYSCALL_GET_ROBUST_LIST = 100,
@@ -156,10 +163,20 @@ pub enum SyscallId {
     SYSCALL_GETEGID = 177,
     SYSCALL_GETTID = 178,
     SYSCALL_SYSINFO = 179,
+    SYSCALL_MQ_OPEN = 180,
+    SYSCALL_MQ_UNLINK = 181,
+    SYSCALL_MQ_TIMEDSEND = 182,
+    SYSCALL_MQ_TIMEDRECEIVE = 183,
+    SYSCALL_MQ_NOTIFY = 184,
+    SYSCALL_MQ_GETSETATTR = 185,
     SYSCALL_MSGGET = 186,
     SYSCALL_MSGCTL = 187,
     SYSCALL_MSGRCV = 188,
     SYSCALL_MSGSND = 189,
+    SYSCALL_SEMGET = 190,
+    SYSCALL_SEMCTL = 191,
+    SYSCALL_SEMTIMEDOP = 192,
+    SYSCALL_SEMOP = 193,
     SYSCALL_SHMGET = 194,
     SYSCALL_SHMCTL = 195,
     SYSCALL_SHMAT = 196,
@@ -190,6 +207,8 @@ pub enum SyscallId {
     SYSCALL_EXEC = 221,
     SYSCALL_MMAP = 222,
     SYSCALL_FADVISE64 = 223,
+    SYSCALL_SWAPON = 224,
+    SYSCALL_SWAPOFF = 225,
     SYSCALL_MPROTECE = 226,
     SYSCALL_MSYNC = 227,
     SYSCALL_MLOCK = 228,
@@ -206,12 +225,15 @@ pub enum SyscallId {
     SYSCALL_NAME_TO_HANDLE_AT = 264,
     SYSCALL_OPEN_BY_HANDLE_AT = 265,
     SYSCALL_CLOCKADJTIME= 266,
+    SYSCALL_SYNCFS = 267,
+    SYSCALL_SETNS = 268,
     SYSCALL_SENDMMSG = 269,
     SYSCALL_KCMP = 272,
     SYSCALL_SCHED_SETATTR = 274,
     SYSCALL_SCHED_GETATTR = 275,
     SYSCALL_RENAMEAT2 = 276,
     SYSCALL_GETRANDOM = 278,
+    SYSCALL_MEMFD_CREATE = 279,
     SYSCALL_BPF = 280,
     SYSCALL_USERFAULTFD = 282,
     SYSCALL_MEMBARRIER = 283,
@@ -220,8 +242,11 @@ pub enum SyscallId {
     SYSCALL_PREADV2 = 286,
     SYSCALL_PWRITEV2 = 287,
     SYSCALL_STATX = 291,
+    SYSCALL_PIDFD_SEND_SIGNAL = 424,
     SYSCALL_IO_URING_SETUP = 425,
+    SYSCALL_PIDFD_OPEN = 434,
     SYSCALL_CLONE3 = 435,
+    SYSCALL_PIDFD_GETFD = 438,
     SYSCALL_FACCESSAT2 = 439,
 }
 
@@ -249,9 +274,10 @@ pub use fs::*;
 use futex::{sys_futex, sys_get_robust_list, sys_set_robust_list, FUTEX_OWNER_DIED, FUTEX_TID_MASK, FUTEX_WAITERS};
 use hal::{addr::VirtAddr, println};
 use io::*;
-use ipc::sysv::{sys_shmat, sys_shmctl, sys_shmdt, sys_shmget};
+use ipc::mqueue::{sys_mq_getsetattr, sys_mq_notify, sys_mq_open, sys_mq_timedreceive, sys_mq_timedsend, sys_mq_unlink};
+use ipc::sysv::{sys_msgctl, sys_msgget, sys_msgrcv, sys_msgsnd, sys_semctl, sys_semget, sys_semop, sys_semtimedop, sys_shmat, sys_shmctl, sys_shmdt, sys_shmget};
 use misc::*;
-use mm::{sys_mmap, sys_mprotect, sys_mremap, sys_munmap};
+use mm::{sys_mmap, sys_mprotect, sys_mremap, sys_msync, sys_munmap};
 use net::*;
 pub use process::*;
 use strum::FromRepr;
@@ -289,14 +315,15 @@ pub async fn syscall(syscall_id: usize, args: [usize; 6]) -> isize {
         SYSCALL_FREMOVEXATTR => sys_temp(syscall_id),
         SYSCALL_IO_GETEVENTS => sys_temp(syscall_id),
         SYSCALL_GETCWD => sys_getcwd(args[0] as usize, args[1] as usize),
-        SYSCALL_EPOLL_CREATE1 => sys_temp(syscall_id),
-        SYSCALL_EPOLL_CTL => sys_temp(syscall_id),
-        SYSCALL_EPOLL_PWAIT => sys_temp(syscall_id),
+        SYSCALL_EVENTFD2 => sys_eventfd2(args[0] as _, args[1] as _),
+        SYSCALL_EPOLL_CREATE1 => sys_epoll_create1(args[0]),
+        SYSCALL_EPOLL_CTL => sys_epoll_ctl(args[0], args[1], args[2], args[3]),
+        SYSCALL_EPOLL_PWAIT => sys_epoll_pwait(args[0], args[1], args[2], args[3], args[4]).await,
         SYSCALL_DUP => sys_dup(args[0] as usize),
         SYSCALL_DUP3 => sys_dup3(args[0] as usize, args[1] as usize, args[2] as u32),
-        SYSCALL_INOTIFY_INIT1 => sys_temp(syscall_id),
-        SYSCALL_INOTIFY_ADD_WATCH => sys_temp(syscall_id),
-        SYSCALL_INOTIFY_RM_WATCH => sys_temp(syscall_id),
+        SYSCALL_INOTIFY_INIT1 => sys_inotify_init1(args[0] as i32),
+        SYSCALL_INOTIFY_ADD_WATCH => sys_inotify_add_watch(args[0], args[1] as *const u8, args[2] as u32),
+        SYSCALL_INOTIFY_RM_WATCH => sys_inotify_rm_watch(args[0], args[1] as i32),
         SYSCALL_FCNTL => sys_fnctl(args[0], args[1] as isize, args[2]),
         SYSCALL_IOCTL => sys_ioctl(args[0], args[1], args[2]),
         SYSCALL_IOPRIO_SET => sys_temp(syscall_id),
@@ -309,16 +336,17 @@ pub async fn syscall(syscall_id: usize, args: [usize; 6]) -> isize {
         SYSCALL_SYMLINKAT => sys_symlinkat(args[0] as *const u8, args[1] as isize, args[2] as *const u8),
         SYSCALL_LINKAT => sys_linkat(args[0] as isize, args[1] as *const u8, args[2] as isize, args[3] as *const u8, args[4] as i32),
         SYSCALL_MOUNT => sys_mount(args[0] as *const u8, args[1] as *const u8, args[2] as *const u8, args[3] as u32, args[4] as usize),
+        SYSCALL_PIVOT_ROOT => sys_pivot_root(args[0] as *const u8, args[1] as *const u8),
         SYSCALL_STATFS => sys_statfs(args[0], args[1]),
         SYSCALL_FSTATFS => sys_temp(syscall_id),
         SYSCALL_TRUNCATE => sys_truncate(args[0] as *const u8, args[1]),
         SYSCALL_FTRUNCATE => sys_ftruncate(args[0], args[1]),
-        SYSCALL_FALLOCATE => sys_temp(syscall_id),
+        SYSCALL_FALLOCATE => sys_fallocate(args[0], args[1] as i32, args[2] as isize, args[3] as isize),
         SYSCALL_FACCESSAT => sys_faccessat(args[0] as isize, args[1] as *const u8, args[2], args[3] as i32),
         SYSCALL_UMOUNT2 => sys_umount2(args[0] as *const u8, args[1] as u32),
         SYSCALL_CHDIR => sys_chdir(args[0] as *const u8),
         SYSCALL_FCHDIR => sys_fchdir(args[0]),
-        SYSCALL_CHROOT => sys_temp(syscall_id),
+        SYSCALL_CHROOT => sys_chroot(args[0] as *const u8),
         SYSCALL_FCHMOD => sys_fchmod(args[0] as isize, args[1] as u32),
         SYSCALL_FCHMODAT => sys_fchmodat(args[0] as isize, args[1] as *const u8, args[2] as u32, args[3] as i32),
         SYSCALL_FCHOWNAT => sys_fchownat(args[0] as isize, args[1] as *const u8, args[2] as i32, args[3] as i32, args[4] as i32),
@@ -338,18 +366,24 @@ pub async fn syscall(syscall_id: usize, args: [usize; 6]) -> isize {
         SYSCALL_SENDFILE => sys_sendfile(args[0], args[1], args[2], args[3]).await,
         SYSCALL_PPOLL => sys_ppoll(args[0], args[1], args[2], args[3]).await,
         SYSCALL_PSELECT6 => sys_pselect6(args[0] as i32, args[1], args[2], args[3], args[4], args[5]).await,
+        SYSCALL_SIGNALFD4 => sys_signalfd4(args[0] as _, args[1], args[2], args[3] as _),
         SYSCALL_VMSPLICE => sys_vmsplice(args[0], args[1], args[2], args[3] as u32).await,
         SYSCALL_SPLICE => sys_splice(args[0], args[1], args[2], args[3], args[4], args[5] as i32).await,
         SYSCALL_TEE => sys_temp(syscall_id),
         SYSCALL_READLINKAT => sys_readlinkat(args[0] as isize, args[1] as *const u8, args[2], args[3]),
         SYSCALL_FSTATAT => sys_fstatat(args[0] as isize, args[1] as *const u8, args[2], args[3] as i32),
         SYSCALL_FSTAT => sys_fstat(args[0], args[1]),
+        SYSCALL_TIMERFD_CREATE => sys_timerfd_create(args[0], args[1] as _),
+        SYSCALL_TIMERFD_SETTIME => sys_timerfd_settime(args[0], args[1] as _, args[2], args[3]),
+        SYSCALL_TIMERFD_GETTIME => sys_timerfd_gettime(args[0], args[1]),
         SYSCALL_UTIMENSAT => sys_utimensat(args[0] as isize, args[1] as *const u8, args[2], args[3] as i32),
         SYSCALL_CAPGET => sys_temp(syscall_id),
         SYSCALL_CAPSET => sys_temp(syscall_id),
         SYSCALL_PERSONALITY => sys_temp(syscall_id),
         SYSCALL_EXIT => sys_exit(args[0] as i32),
         SYSCALL_SET_TID_ADDRESS => sys_set_tid_address(args[0]),
+        SYSCALL_UNSHARE => sys_unshare(args[0] as u64),
+        SYSCALL_SETNS => sys_setns(args[0], args[1] as i32),
         SYSCALL_EXIT_GROUP => sys_exit_group(args[0] as i32),
         SYSCALL_FUTEX => sys_futex(args[0], args[1] as _, args[2] as _, SendWrapper(args[3] as _), args[4], args[5] as _).await,
         SYSCALL_SET_ROBUST_LIST => sys_set_robust_list(args[0] as _, args[1]),
@@ -394,17 +428,27 @@ pub async fn syscall(syscall_id: usize, args: [usize; 6]) -> isize {
         SYSCALL_GETPPID => sys_getppid(),
         SYSCALL_GETUID => sys_getuid(),
         SYSCALL_GETEUID => sys_geteuid(),
-        SYSCALL_GETGID => sys_temp(syscall_id),
+        SYSCALL_GETGID => sys_getgid(),
         SYSCALL_GETEGID => sys_getegid(),
         SYSCALL_GETTID => sys_gettid(),
         SYSCALL_SETSID => sys_setsid(),
         SYSCALL_GETGROUPS => sys_temp(syscall_id),
         SYSCALL_SETGROUPS => sys_temp(syscall_id),
         SYSCALL_SYSINFO => sys_sysinfo(args[0]),
-        SYSCALL_MSGGET => sys_temp(syscall_id),
-        SYSCALL_MSGCTL => sys_temp(syscall_id),
-        SYSCALL_MSGRCV => sys_temp(syscall_id),
-        SYSCALL_MSGSND =>sys_temp(syscall_id),
+        SYSCALL_MQ_OPEN => sys_mq_open(args[0] as _, args[1] as _, args[2] as _, args[3]),
+        SYSCALL_MQ_UNLINK => sys_mq_unlink(args[0] as _),
+        SYSCALL_MQ_TIMEDSEND => sys_mq_timedsend(args[0], args[1], args[2], args[3] as _, args[4]).await,
+        SYSCALL_MQ_TIMEDRECEIVE => sys_mq_timedreceive(args[0], args[1], args[2], args[3], args[4]).await,
+        SYSCALL_MQ_NOTIFY => sys_mq_notify(args[0], args[1]),
+        SYSCALL_MQ_GETSETATTR => sys_mq_getsetattr(args[0], args[1], args[2]),
+        SYSCALL_MSGGET => sys_msgget(args[0] as _, args[1] as _),
+        SYSCALL_MSGCTL => sys_msgctl(args[0] as _, args[1] as _, args[2]),
+        SYSCALL_MSGRCV => sys_msgrcv(args[0] as _, args[1], args[2], args[3] as _, args[4] as _).await,
+        SYSCALL_MSGSND => sys_msgsnd(args[0] as _, args[1], args[2], args[3] as _).await,
+        SYSCALL_SEMGET => sys_semget(args[0] as _, args[1] as _, args[2] as _),
+        SYSCALL_SEMCTL => sys_semctl(args[0] as _, args[1] as _, args[2] as _, args[3]),
+        SYSCALL_SEMTIMEDOP => sys_semtimedop(args[0] as _, args[1], args[2], args[3]).await,
+        SYSCALL_SEMOP => sys_semop(args[0] as _, args[1], args[2]).await,
         SYSCALL_SHMGET => sys_shmget(args[0] as _, args[1] as _, args[2] as _),
         SYSCALL_SHMCTL => sys_shmctl(args[0] as _, args[1] as _, UserPtrRaw::new(args[2] as *mut _)),
         SYSCALL_SHMAT => sys_shmat(args[0] as _, VirtAddr::from(args[1]), args[2] as _),
@@ -413,6 +457,9 @@ pub async fn syscall(syscall_id: usize, args: [usize; 6]) -> isize {
         SYSCALL_GETPGID => sys_getpgid(args[0]),
         SYSCALL_CLONE => sys_clone(args[0] as u64, args[1].into(), args[2].into(), args[3].into(), args[4].into()),
         SYSCALL_CLONE3 => sys_clone3(args[0], args[1]),
+        SYSCALL_PIDFD_OPEN => sys_pidfd_open(args[0] as isize, args[1] as u32),
+        SYSCALL_PIDFD_GETFD => sys_pidfd_getfd(args[0], args[1], args[2] as u32),
+        SYSCALL_PIDFD_SEND_SIGNAL => sys_pidfd_send_signal(args[0], args[1] as i32, args[2], args[3] as u32),
         SYSCALL_WAITPID => sys_waitpid(args[0] as isize, args[1], args[2] as i32).await,
         SYSCALL_SETHOSTNAME => sys_sethostname(args[0], args[1]).await,
         SYSCALL_SETDOMAINNAME =>  sys_temp(syscall_id),
@@ -422,7 +469,9 @@ pub async fn syscall(syscall_id: usize, args: [usize; 6]) -> isize {
         SYSCALL_BRK => sys_brk(VirtAddr::from(args[0])),
         SYSCALL_MUNMAP => sys_munmap(VirtAddr::from(args[0]), args[1]),
         SYSCALL_MMAP => sys_mmap(VirtAddr::from(args[0]), args[1], args[2] as i32, args[3] as i32, args[4], args[5]),
-        SYSCALL_FADVISE64 => sys_temp(syscall_id),
+        SYSCALL_FADVISE64 => sys_fadvise64(args[0], args[1], args[2], args[3]),
+        SYSCALL_SWAPON => sys_swapon(args[0] as *const u8, args[1] as i32),
+        SYSCALL_SWAPOFF => sys_swapoff(args[0] as *const u8),
         SYSCALL_MREMAP => sys_mremap(VirtAddr::from(args[0]), args[1], args[2], args[3] as i32, args[4]),
         SYSCALL_FANOTIFY_INIT => sys_temp(syscall_id),
         SYSCALL_FANOTIFY_MARK => sys_temp(syscall_id),
@@ -435,6 +484,7 @@ pub async fn syscall(syscall_id: usize, args: [usize; 6]) -> isize {
         SYSCALL_SCHED_SETATTR => sys_temp(syscall_id),
         SYSCALL_RENAMEAT2 => sys_renameat2(args[0] as isize, args[1] as *const u8, args[2] as isize, args[3] as *const u8, args[4] as i32),
         SYSCALL_GETRANDOM => sys_getrandom(args[0], args[1], args[2]),
+        SYSCALL_MEMFD_CREATE => sys_memfd_create(args[0] as *const u8, args[1] as u32),
         SYSCALL_GETRLIMIT => sys_temp(syscall_id),
         SYSCALL_PREADV2 => sys_preadv2(args[0], args[1], args[2], args[3], args[4] as i32).await,
         SYSCALL_PWRITEV2 => sys_pwritev2(args[0], args[1], args[2], args[3], args[4] as i32).await,
@@ -462,17 +512,18 @@ pub async fn syscall(syscall_id: usize, args: [usize; 6]) -> isize {
         SYSCALL_MINCORE => sys_temp(syscall_id),
         SYSCALL_MADSIVE =>  sys_temp(syscall_id),
         SYSCALL_GET_MEMPOLICY => sys_temp(syscall_id),
-        SYSCALL_SYNC => sys_temp(syscall_id),
-        SYSCALL_FSYNC => sys_temp(syscall_id),
+        SYSCALL_SYNC => sys_sync(),
+        SYSCALL_SYNCFS => sys_syncfs(args[0]),
+        SYSCALL_FSYNC => sys_fsync(args[0]),
         SYSCALL_FDATASYNC => sys_fdatasync(args[0]),
-        SYSCALL_MSYNC => sys_temp(syscall_id),
+        SYSCALL_MSYNC => sys_msync(args[0].into(), args[1], args[2] as _),
         SYSCALL_MLOCK => sys_temp(syscall_id),
         SYSCALL_MEMBARRIER => sys_temp(syscall_id),
         SYSCALL_MLOCK2 => sys_temp(syscall_id),
-        SYSCALL_COPY_FILE_RANGE => sys_temp(syscall_id),
+        SYSCALL_COPY_FILE_RANGE => sys_copy_file_range(args[0], args[1], args[2], args[3], args[4], args[5] as u32).await,
         SYSCALL_IO_URING_SETUP => sys_temp(syscall_id),
         SYSCALL_SETREGID => sys_temp(syscall_id),
-        SYSCALL_SETGID => sys_temp(syscall_id),
+        SYSCALL_SETGID => sys_setgid(args[0] as i32),
         SYSCALL_SETREUID => sys_temp(syscall_id),
         SYSCALL_SETUID => sys_setuid(args[0] as i32),
         SYSCALL_ADDKEY => sys_temp(syscall_id),
@@ -6,7 +6,7 @@ use hal::{addr, instruction::{Instruction, InstructionHal}, println};
 use lwext4_rust::bindings::EXT4_SUPERBLOCK_FLAGS_TEST_FILESYS;
 use smoltcp::{socket::dns::Socket, time::Duration, wire::{IpAddress, Ipv4Address}};
 
-use crate::{config::PAGE_SIZE, fs::{pipefs, vfs::{file::open_file, File}, OpenFlags}, mm::{UserPtr, UserPtrRaw, UserSliceRaw}, net::{addr::{SockAddr, SockAddrIn4, SockAddrIn6, SockAddrUn}, crypto::{encode, AlgInstance, AlgType, SockAddrAlg}, socket::{self, Sock, SockResult}, socketpair::make_socketpair, tcp::TcpSocket, SaFamily, SOCKET_SET}, signal::SigSet, syscall::{misc::UTS, process}, task::{current_task, fs::{FdFlags, FdInfo}, task::TaskControlBlock}, timer::ffi::TimeSpec, utils::yield_now};
+use crate::{config::PAGE_SIZE, fs::{pipefs, vfs::{file::open_file, File}, OpenFlags}, mm::{UserPtr, UserPtrRaw, UserSliceRaw}, net::{addr::{SockAddr, SockAddrIn4, SockAddrIn6, SockAddrUn}, crypto::{encode, AlgInstance, AlgType, SockAddrAlg}, scm::{self, ScmData, UCred}, socket::{self, Sock, SockResult}, socketpair::make_socketpair, tcp::TcpSocket, unix::UnixAddr, SaFamily, SOCKET_SET}, signal::SigSet, syscall::{misc::current_uts_ns, process}, task::{current_task, fs::{FdFlags, FdInfo}, task::TaskControlBlock}, timer::ffi::TimeSpec, utils::yield_now};
 
 use super::{IoVec, SysError, SysResult};
 
@@ -184,7 +184,7 @@ pub fn sys_bind(fd: usize, addr: usize, addr_len: usize) -> SysResult {
     if (fd as isize) < 0 {
         return Err(SysError::EBADF);
     }
-    if addr_len <= 3 {
+    if addr_len < size_of::<u16>() {
         return Err(SysError::EINVAL);
     }
     let task = current_task().unwrap();
@@ -212,6 +212,10 @@ pub fn sys_bind(fd: usize, addr: usize, addr_len: usize) -> SysResult {
         *socket_file.alg_instance.lock() = Some(AlgInstance::new_without_key(alg_type, alg_name.to_string()));
         return Ok(0);
     }
+    if let Sock::Unix(unix) = &socket_file.sk {
+        unix.bind(UnixAddr::from_sockaddr(unsafe { &local_addr.unix }, addr_len)?)?;
+        return Ok(0);
+    }
     socket_file.sk.bind(fd, local_addr)?;
     Ok(0)
 }
@@ -219,7 +223,7 @@ pub fn sys_bind(fd: usize, addr: usize, addr_len: usize) -> SysResult {
 /// Mark the stream socket referenced by the file descriptor `sockfd` as
 /// passive. This socket will be used later to accept connections from other
 /// (active) sockets
-pub fn sys_listen(fd: usize, _backlog: usize) -> SysResult {
+pub fn sys_listen(fd: usize, backlog: usize) -> SysResult {
     if (fd as isize) < 0 {
         return Err(SysError::EBADF);
     }
@@ -228,7 +232,7 @@ pub fn sys_listen(fd: usize, _backlog: usize) -> SysResult {
         table.get_file(fd)})?
         .downcast_arc::<socket::Socket>()
         .map_err(|_| SysError::ENOTSOCK)?;
-    socket_file.sk.listen()?;
+    socket_file.sk.listen(backlog)?;
     Ok(0)
 }
 
@@ -251,6 +255,16 @@ pub async fn sys_connect(fd: usize, addr: usize, addr_len: usize) -> SysResult {
         .downcast_arc::<socket::Socket>()
         .map_err(|_| SysError::ENOTSOCK)?;
     log::info!("[sys_connect] socket_file_type {:#?}", socket_file.sk_type);
+    if let Sock::Unix(unix) = &socket_file.sk {
+        let remote_addr = UnixAddr::from_sockaddr(unsafe { &remote_addr.unix }, addr_len)?;
+        task.set_interruptable();
+        let old_mask = task.sig_manager.lock().blocked_sigs;
+        task.set_wake_up_sigs(!old_mask);
+        let ret = unix.connect(remote_addr).await;
+        task.set_running();
+        ret?;
+        return Ok(0);
+    }
     socket_file.sk.connect(remote_addr.into_endpoint()?).await?;
     // yield_now().await;
     Ok(0)
@@ -306,6 +320,20 @@ pub async fn sys_accept(fd: usize, addr: usize, addr_len: usize) -> SysResult {
     task.set_interruptable();
     let old_mask = task.sig_manager.lock().blocked_sigs;
     task.set_wake_up_sigs(!old_mask);
+    if let Sock::Unix(unix) = &socket_file.sk {
+        let accepted = unix.accept().await;
+        task.set_running();
+        let accepted = accepted?;
+        sockaddr_writer(task, addr, addr_len, accepted.peer_addr()?)?;
+        let accept_socket = Arc::new(socket::Socket::from_another(&socket_file, Sock::Unix(accepted)));
+        let fd_info = FdInfo {
+            file: accept_socket,
+            flags: OpenFlags::empty().into(),
+        };
+        let new_fd = task.with_mut_fd_table(|t|t.alloc_fd())?;
+        task.with_mut_fd_table(|t| t.put_file(new_fd, fd_info))?;
+        return Ok(new_fd as isize);
+    }
     let accept_sk = socket_file.sk.accept().await?;
     task.set_running();
     log::info!("get accept correct");
@@ -360,6 +388,16 @@ pub async fn sys_sendto(
         .downcast_arc::<socket::Socket>()
         .map_err(|_| SysError::ENOTSOCK)?;
     task.set_interruptable();
+    if let Sock::Unix(unix) = &socket_file.sk {
+        let remote_addr = if addr != 0 {
+            Some(unix_addr_reader(addr, addr_len, &task)?)
+        } else {
+            None
+        };
+        let bytes = unix.send_to(buf_slice, remote_addr).await;
+        task.set_running();
+        return Ok(bytes? as isize);
+    }
     let bytes=  match socket_file.sk_type {
         SocketType::DGRAM => {
             let remote_addr = if addr != 0 {  Some(sockaddr_reader(addr, addr_len, &task)?
@@ -368,7 +406,7 @@ pub async fn sys_sendto(
             };
             socket_file.sk.send(&buf_slice, remote_addr).await?    
         }
-        SocketType::STREAM => {
+        SocketType::STREAM | SocketType::SEQPACKET => {
             if addr != 0 {
                 return Err(SysError::EISCONN);
             }
@@ -406,10 +444,9 @@ pub async fn sys_recvfrom(
         inner_vec.set_len(len);
     }
     task.set_interruptable();
-    let (bytes, remote_endpoint) = socket_file.sk.recv(&mut inner_vec).await?;
+    let (bytes, remote_addr) = socket_file.sk.recv_from(&mut inner_vec).await?;
     // log::info!("first code recv:{} ",char::from(inner_vec[0]));
     // log::info!("recvfrom: bytes: {}, remote_endpoint: {:?}", bytes, remote_endpoint);
-    let remote_addr = SockAddr::from_endpoint(remote_endpoint);
     task.set_running();
     sockaddr_writer(&task,addr, addrlen, remote_addr)?;
     // write to pointer
@@ -739,6 +776,15 @@ impl SocketOption {
                 Ok(0)
             },
 
+            SocketOption::PASSCRED => {
+                if opt.len() < 4 {
+                    return Err(SysError::EINVAL);
+                }
+                let value = i32::from_ne_bytes(<[u8; 4]>::try_from(&opt[0..4]).unwrap());
+                socket.pass_cred.store(value != 0, core::sync::atomic::Ordering::Release);
+                Ok(0)
+            }
+
             _ => {
                 Ok(0)
             }
@@ -846,6 +892,33 @@ impl SocketOption {
                 Ok(0)
             }
 
+            SocketOption::PASSCRED => {
+                if buf_len < 4 {
+                    return Err(SysError::EINVAL)
+                }
+                let value: i32 = if socket.pass_cred.load(core::sync::atomic::Ordering::Acquire) {1} else {0};
+                let opt_w = UserSliceRaw::new(opt as *mut u8, 4)
+                    .ensure_write(&mut task.get_vm_space().lock())
+                    .ok_or(SysError::EFAULT)?;
+                opt_w.to_mut().copy_from_slice(&value.to_ne_bytes());
+                opt_len_w.write(4);
+                Ok(0)
+            }
+
+            SocketOption::PEERCRED => {
+                let cred = socket.sk.peer_cred()?;
+                let len = buf_len.min(size_of::<UCred>());
+                let opt_w = UserSliceRaw::new(opt as *mut u8, len)
+                    .ensure_write(&mut task.get_vm_space().lock())
+                    .ok_or(SysError::EFAULT)?;
+                let bytes = unsafe {
+                    core::slice::from_raw_parts(&cred as *const UCred as *const u8, size_of::<UCred>())
+                };
+                opt_w.to_mut().copy_from_slice(&bytes[..len]);
+                opt_len_w.write(len as u32);
+                Ok(0)
+            }
+
             _ => {
                 Ok(0)
             }
@@ -1369,11 +1442,11 @@ pub struct MsgHdr {
     /// iovecs ptr
     pub msg_iov: usize,
     /// iovecs len
-    pub msg_iovlen: u32,
+    pub msg_iovlen: usize,
     /// ancillary data ptr
     pub msg_control: usize,
     /// ancillary data len
-    pub msg_controllen: u32,
+    pub msg_controllen: usize,
     /// flags
     pub msg_flags: i32,
 }
@@ -1382,12 +1455,125 @@ pub struct MsgHdr {
 #[derive(Clone, Copy)]
 /// accillary data object information for recvmsg() and sendmsg() system calls
 pub struct CmsgHdr {
+    /// data len, including the header
+    pub cmsg_len: usize,
     /// level
-    pub cmsg_level: u32,
+    pub cmsg_level: i32,
     /// type
-    pub cmsg_type: u32,
-    /// data len
-    pub cmsg_len: u32,
+    pub cmsg_type: i32,
+}
+
+/// round a control message length up to the alignment of `CmsgHdr`
+const fn cmsg_align(len: usize) -> usize {
+    (len + size_of::<usize>() - 1) & !(size_of::<usize>() - 1)
+}
+
+/// parse the SOL_SOCKET control messages of a sendmsg on a unix socket
+fn scm_reader(task: &Arc<TaskControlBlock>, control: &[u8]) -> Result<Option<ScmData>, SysError> {
+    let hdr_len = size_of::<CmsgHdr>();
+    let mut scm = ScmData::default();
+    let mut offset = 0;
+    while offset + hdr_len <= control.len() {
+        let hdr = unsafe { ptr::read_unaligned(control[offset..].as_ptr() as *const CmsgHdr) };
+        if hdr.cmsg_len < hdr_len || hdr.cmsg_len > control.len() - offset {
+            return Err(SysError::EINVAL);
+        }
+        let data = &control[offset + hdr_len..offset + hdr.cmsg_len];
+        if hdr.cmsg_level == scm::SOL_SOCKET {
+            match hdr.cmsg_type {
+                scm::SCM_RIGHTS => {
+                    let nr = data.len() / size_of::<i32>();
+                    if nr == 0 || scm.files.len() + nr > scm::SCM_MAX_FD {
+                        return Err(SysError::EINVAL);
+                    }
+                    for raw in data.chunks_exact(size_of::<i32>()) {
+                        let fd = i32::from_ne_bytes(raw.try_into().unwrap());
+                        if fd < 0 {
+                            return Err(SysError::EBADF);
+                        }
+                        let file = task.with_fd_table(|table| table.get_file(fd as usize))?;
+                        scm.files.push(file);
+                    }
+                }
+                scm::SCM_CREDENTIALS => {
+                    if data.len() < size_of::<UCred>() {
+                        return Err(SysError::EINVAL);
+                    }
+                    let cred = unsafe { ptr::read_unaligned(data.as_ptr() as *const UCred) };
+                    // only root may claim to be someone else
+                    let current = UCred::current();
+                    if task.uid() != 0 && (cred.pid != current.pid || cred.uid != current.uid) {
+                        return Err(SysError::EPERM);
+                    }
+                    scm.creds = Some(cred);
+                }
+                _ => return Err(SysError::EINVAL),
+            }
+        }
+        offset += cmsg_align(hdr.cmsg_len);
+    }
+    Ok((!scm.is_empty()).then_some(scm))
+}
+
+/// write the control messages of a recvmsg on a unix socket into a buffer of
+/// at most `cap` bytes, installing passed files into the fd table.
+/// returns the buffer and whether anything was truncated
+fn scm_writer(task: &Arc<TaskControlBlock>, scm: ScmData, pass_cred: bool, cap: usize, cloexec: bool) -> Result<(Vec<u8>, bool), SysError> {
+    let hdr_len = size_of::<CmsgHdr>();
+    let mut buf = Vec::new();
+    let mut truncated = false;
+    let push_hdr = |buf: &mut Vec<u8>, ty: i32, data: &[u8]| {
+        let hdr = CmsgHdr {
+            cmsg_len: hdr_len + data.len(),
+            cmsg_level: scm::SOL_SOCKET,
+            cmsg_type: ty,
+        };
+        let hdr = unsafe { core::slice::from_raw_parts(&hdr as *const CmsgHdr as *const u8, hdr_len) };
+        buf.extend_from_slice(hdr);
+        buf.extend_from_slice(data);
+        buf.resize(cmsg_align(buf.len()).min(cap), 0);
+    };
+    if let Some(cred) = scm.creds.filter(|_| pass_cred) {
+        if cap - buf.len() < hdr_len + size_of::<UCred>() {
+            truncated = true;
+        } else {
+            let data = unsafe { core::slice::from_raw_parts(&cred as *const UCred as *const u8, size_of::<UCred>()) };
+            push_hdr(&mut buf, scm::SCM_CREDENTIALS, data);
+        }
+    }
+    if !scm.files.is_empty() {
+        let room = (cap - buf.len()).saturating_sub(hdr_len) / size_of::<i32>();
+        let nr = room.min(scm.files.len());
+        truncated |= nr < scm.files.len();
+        if nr > 0 {
+            let flags = if cloexec { OpenFlags::O_CLOEXEC.into() } else { FdFlags::empty() };
+            let mut data = Vec::with_capacity(nr * size_of::<i32>());
+            task.with_mut_fd_table(|table| -> Result<(), SysError> {
+                let mut installed = Vec::with_capacity(nr);
+                // files that do not fit are dropped here
+                for file in scm.files.into_iter().take(nr) {
+                    let res = table.alloc_fd()
+                        .and_then(|fd| table.put_file(fd, FdInfo { file, flags }).map(|_| fd));
+                    match res {
+                        Ok(fd) => installed.push(fd),
+                        Err(e) => {
+                            // undo the fds handed out so far
+                            for fd in installed {
+                                let _ = table.remove(fd);
+                            }
+                            return Err(e);
+                        }
+                    }
+                }
+                for fd in installed {
+                    data.extend_from_slice(&(fd as i32).to_ne_bytes());
+                }
+                Ok(())
+            })?;
+            push_hdr(&mut buf, scm::SCM_RIGHTS, &data);
+        }
+    }
+    Ok((buf, truncated))
 }
 
 /// send a message through a connection-mode or connectionless-mode socket. 
@@ -1417,7 +1603,12 @@ pub async fn sys_sendmsg(
     //     log::warn!("unsupported control data");
     // }
     let mut peer_addr = None;
-    if socket_file.domain != SaFamily::Alg && socket_file.domain != SaFamily::AfUnix {
+    let mut unix_peer_addr = None;
+    if let Sock::Unix(_) = &socket_file.sk {
+        if msg.msg_name != 0 && msg.msg_namelen > 0 {
+            unix_peer_addr = Some(unix_addr_reader(msg.msg_name, msg.msg_namelen as usize, task)?);
+        }
+    } else if socket_file.domain != SaFamily::Alg && socket_file.domain != SaFamily::AfUnix {
         if msg.msg_namelen > 0 {
             let addr = sockaddr_reader(msg.msg_name, msg.msg_namelen as usize, task)?;
             peer_addr = Some(addr.into_endpoint()?);
@@ -1455,6 +1646,13 @@ pub async fn sys_sendmsg(
         return encode(&socket_file, iovs, control_slice.to_ref())
     }
 
+    if let Sock::Unix(_) | Sock::SocketPair(_) = &socket_file.sk {
+        let scm = scm_reader(task, control_slice.to_ref())?;
+        if let Sock::Unix(unix) = &socket_file.sk {
+            return Ok(unix.send_to(kernel_iovec_buf.as_slice(), unix_peer_addr, scm).await? as isize);
+        }
+        return Ok(socket_file.sk.send_msg(kernel_iovec_buf.as_slice(), scm).await? as isize);
+    }
     match socket_file.sk.send(kernel_iovec_buf.as_slice(), peer_addr).await {
         Ok(len) => Ok(len as isize),
         Err(e) => Err(e),
@@ -1518,11 +1716,7 @@ pub async fn sys_recvmsg(
         .ensure_read(&mut task.get_vm_space().lock())
         .ok_or(SysError::EFAULT)?
         .to_ref();
-    
-    if inner_msg.msg_controllen != 0 {
-        log::warn!("unsupported control data");
-    }
-    if (inner_msg.msg_namelen as i32) < 0 || (inner_msg.msg_controllen as i32) < 0 {
+    if (inner_msg.msg_namelen as i32) < 0 || (inner_msg.msg_controllen as isize) < 0 {
         return Err(SysError::EINVAL);
     }
     // let iovs = unsafe {
@@ -1541,11 +1735,40 @@ pub async fn sys_recvmsg(
     for iov in iovs {
         total_len = total_len.saturating_add(iov.len);
     }
-    if total_len == 0 {
+    let is_unix = matches!(socket_file.sk, Sock::Unix(_) | Sock::SocketPair(_));
+    if total_len == 0 && !is_unix {
         return Ok(0);
     }
     let mut tmp_buf = vec![0u8; total_len];
-    let (recv_len,src_addr) = socket_file.sk.recv(&mut tmp_buf).await?;
+    let (recv_len, src_addr, scm) = socket_file.sk.recv_msg(&mut tmp_buf).await?;
+
+    let mut msg_flags = MsgFlags::empty();
+    let mut controllen = 0;
+    if is_unix && inner_msg.msg_control != 0 {
+        let pass_cred = socket_file.pass_cred.load(core::sync::atomic::Ordering::Acquire);
+        let cloexec = flags.contains(MsgFlags::MSG_CMSG_CLOEXEC);
+        let (control, truncated) = scm_writer(task, scm, pass_cred, inner_msg.msg_controllen, cloexec)?;
+        if !control.is_empty() {
+            let control_w = UserSliceRaw::new(inner_msg.msg_control as *mut u8, control.len())
+                .ensure_write(&mut task.get_vm_space().lock())
+                .ok_or(SysError::EFAULT)?;
+            control_w.to_mut().copy_from_slice(&control);
+        }
+        controllen = control.len();
+        if truncated {
+            msg_flags |= MsgFlags::MSG_CTRUNC;
+        }
+    } else if !scm.files.is_empty() {
+        msg_flags |= MsgFlags::MSG_CTRUNC;
+    }
+    let controllen_w = UserPtrRaw::new((msg + core::mem::offset_of!(MsgHdr, msg_controllen)) as *mut usize)
+        .ensure_write(&mut task.get_vm_space().lock())
+        .ok_or(SysError::EFAULT)?;
+    controllen_w.write(controllen);
+    let flags_w = UserPtrRaw::new((msg + core::mem::offset_of!(MsgHdr, msg_flags)) as *mut i32)
+        .ensure_write(&mut task.get_vm_space().lock())
+        .ok_or(SysError::EFAULT)?;
+    flags_w.write(msg_flags.bits() as i32);
     if recv_len == 0 {
         return Ok(0);
     }
@@ -1565,7 +1788,7 @@ pub async fn sys_recvmsg(
     }
 
     if inner_msg.msg_name != 0 {
-        let addr = SockAddr::from_endpoint(src_addr);
+        let addr = src_addr;
         // unsafe {
         //     match SaFamily::try_from(addr.family)? {
         //         SaFamily::AfInet => {
@@ -1589,7 +1812,8 @@ pub async fn sys_recvmsg(
         //         _ => todo!()
         //     }
         // }
-        sockaddr_writer(task, inner_msg.msg_name, inner_msg.msg_namelen as usize, addr)?;
+        let namelen = msg + core::mem::offset_of!(MsgHdr, msg_namelen);
+        sockaddr_writer(task, inner_msg.msg_name, namelen, addr)?;
     }      
     Ok(copied as isize)
 }
@@ -1617,7 +1841,8 @@ pub fn sockaddr_reader(addr: usize, addr_len: usize, task: &Arc<TaskControlBlock
             Ok(addr)
         },
         SaFamily::AfUnix => {
-            if addr_len < size_of::<SockAddrUn>() {
+            // the path is variable-length, abstract names even rely on addr_len
+            if addr_len < size_of::<u16>() || addr_len > size_of::<SockAddrUn>() {
                 log::info!("in this, size of SockAddrUn: {}",size_of::<SockAddrUn>());
                 return Err(SysError::EINVAL);
             }
@@ -1635,33 +1860,44 @@ pub fn sockaddr_reader(addr: usize, addr_len: usize, task: &Arc<TaskControlBlock
     }
 }
 
+/// read a `sockaddr_un` of `addr_len` bytes from user space
+pub fn unix_addr_reader(addr: usize, addr_len: usize, task: &Arc<TaskControlBlock>) -> Result<UnixAddr, SysError> {
+    let sock_addr = sockaddr_reader(addr, addr_len, task)?;
+    UnixAddr::from_sockaddr(unsafe { &sock_addr.unix }, addr_len)
+}
+
+/// write `sock_addr` into the user buffer of `*addr_len` bytes, truncated if
+/// it does not fit, and store its real length in `*addr_len`
 pub fn sockaddr_writer(task: &Arc<TaskControlBlock>, addr: usize, addr_len: usize, sock_addr: SockAddr) -> SockResult<()>{
     if addr == 0{
         return Ok(());
     }
-    let addr =  UserPtrRaw::new(addr as *const SockAddr)
-        .ensure_write(&mut task.get_vm_space().lock())
-        .ok_or(SysError::EFAULT)?;
     let addr_len = UserPtrRaw::new(addr_len as *const u32)
         .ensure_write(&mut task.get_vm_space().lock())
         .ok_or(SysError::EFAULT)?;
-    unsafe {
-        match SaFamily::try_from(sock_addr.family).unwrap() {
-            SaFamily::AfInet => {
-                addr.write(sock_addr);
-                addr_len.write(size_of::<SockAddrIn4>() as u32);
-            }
-            SaFamily::AfInet6 => {
-                addr.write(sock_addr);
-                addr_len.write(size_of::<SockAddrIn6>() as u32);
-            },
-            SaFamily::AfUnix => {
-                addr.write(sock_addr);
-                addr_len.write(size_of::<SockAddrUn>() as u32);
-            },
-            _ => todo!()
+    let buf_len = *addr_len.to_ref() as i32;
+    if buf_len < 0 {
+        return Err(SysError::EINVAL);
+    }
+    let len = unsafe {
+        match SaFamily::try_from(sock_addr.family) {
+            Ok(SaFamily::AfInet) => size_of::<SockAddrIn4>(),
+            Ok(SaFamily::AfInet6) => size_of::<SockAddrIn6>(),
+            Ok(SaFamily::AfUnix) => sock_addr.unix.len(),
+            _ => return Err(SysError::EINVAL),
         }
+    };
+    let copy_len = len.min(buf_len as usize);
+    if copy_len > 0 {
+        let bytes = unsafe {
+            core::slice::from_raw_parts(&sock_addr as *const SockAddr as *const u8, size_of::<SockAddr>())
+        };
+        let user_addr = UserSliceRaw::new(addr as *mut u8, copy_len)
+            .ensure_write(&mut task.get_vm_space().lock())
+            .ok_or(SysError::EFAULT)?;
+        user_addr.to_mut().copy_from_slice(&bytes[..copy_len]);
     }
+    addr_len.write(len as u32);
     Ok(())
 }
 
@@ -1682,7 +1918,7 @@ pub async fn sys_sethostname(hostname_ptr: usize, len: usize) -> SysResult {
         .ok_or(SysError::EFAULT)?;
     let hostname = String::from_utf8(hostname_buf.to_ref().to_vec()).map_err(|_| SysError::EINVAL)?;
     log::info!("[sys_hostname] hostname will be set {}", hostname);
-    UTS.lock().set_nodename(&hostname);
+    current_uts_ns().uts.lock().set_nodename(&hostname);
     Ok(0)
 }
 
@@ -1700,7 +1936,7 @@ pub async fn sys_gethostname(hostname_ptr: usize, len: usize) -> SysResult {
     let hostname_buf = UserSliceRaw::new(hostname_ptr as *mut u8, len)
         .ensure_write(&mut task.get_vm_space().lock())
         .ok_or(SysError::EFAULT)?;
-    let hostname = UTS.lock().get_utsname().nodename;
+    let hostname = current_uts_ns().uts.lock().get_utsname().nodename;
     hostname_buf.to_mut().copy_from_slice(&hostname[..len]);
     Ok(0)
 }
\ No newline at end of file
@@ -7,24 +7,31 @@ use crate::config::PAGE_SIZE;
 use crate::fs::fat32::dentry;
 use crate::fs::utils::FileReader;
 use crate::fs::vfs::dentry::global_find_dentry;
+use crate::fs::vfs::mount::check_exec;
 use crate::fs::vfs::DentryState;
 use crate::fs::AtFlags;
 use crate::fs::{
+    pidfd::{PidFdFile, PidFdFlags},
     vfs::file::open_file,
     OpenFlags,
 };
 use crate::mm::UserPtrRaw;
 use crate::processor::context::SumGuard;
 use crate::syscall::at_helper;
+use crate::task::fs::FdInfo;
 use crate::task::schedule::spawn_user_task;
+use crate::task::task::TaskControlBlock;
 use crate::task::INITPROC;
+use crate::task::ns::{find_task_by_vpid, pid_vnr, NsHandle};
+use crate::fs::nsfs::NsFile;
+use crate::ipc::sysv::IpcNamespace;
 use crate::task::manager::{TaskManager, PROCESS_GROUP_MANAGER, TASK_MANAGER};
 use crate::processor::processor::{current_processor, current_task, current_trap_cx, current_user_token, PROCESSORS};
 use crate::signal::{SigInfo, SigSet, SIGKILL};
 use crate::timer::get_current_time_duration;
 use crate::utils::{suspend_now, user_path_to_string};
 use alloc::string::ToString;
-use alloc::{sync::Arc, vec::Vec, string::String};
+use alloc::{sync::Arc, vec, vec::Vec, string::String};
 use fatfs::warn;
 use hal::addr::{PhysAddrHal, PhysPageNumHal, VirtAddr};
 use hal::instruction::{Instruction, InstructionHal};
@@ -54,6 +61,8 @@ bitflags! {
         const PARENT = 0x00008000;
         /// Set to add to same thread group.
         const THREAD = 0x00010000;
+        /// New mount namespace.
+        const NEWNS = 0x00020000;
         /// Set to shared SVID SEM_UNDO semantics.
         const SYSVSEM = 0x00040000;
         /// Set TLS info.
@@ -102,11 +111,11 @@ bitflags! {
 /// get the pid of the current process
 pub fn sys_getpid() -> SysResult {
     // log::info!("[sys_getpid]: in get pid");
-    Ok(current_task().unwrap().pid() as isize)
+    Ok(pid_vnr(current_task().unwrap().pid()) as isize)
 }
 /// get the tid of the current thread
 pub fn sys_gettid() -> SysResult {
-    Ok(current_task().unwrap().tid() as isize)
+    Ok(pid_vnr(current_task().unwrap().tid()) as isize)
 }
 
 pub fn sys_setuid(uid: i32) -> SysResult {
@@ -116,6 +125,18 @@ pub fn sys_setuid(uid: i32) -> SysResult {
     Ok(0)
 }
 
+/// set the group id of the current process
+pub fn sys_setgid(gid: i32) -> SysResult {
+    let task = current_task().unwrap();
+    task.set_gid(gid);
+    Ok(0)
+}
+
+/// get the group id of the current process
+pub fn sys_getgid() -> SysResult {
+    Ok(current_task().unwrap().gid() as isize)
+}
+
 /// exit the current process with the given exit code
 pub fn sys_exit(exit_code: i32) -> SysResult {
     let task = current_task().unwrap().clone();
@@ -131,15 +152,15 @@ pub fn sys_set_tid_address(tid_ptr: usize) -> SysResult {
     let task = current_task().unwrap().clone();
     info!("set task {} tid address: {tid_ptr:#x}", task.tid());
     task.tid_address().clear_child_tid = Some(tid_ptr);
-    Ok(task.tid() as isize)
+    Ok(pid_vnr(task.tid()) as isize)
 }
 
 /// fork a new process
-pub fn sys_fork() -> isize {
+pub fn sys_fork() -> SysResult {
     let current_task = current_task().unwrap();
-    let new_task = current_task.fork(CloneFlags { bits: 0 });
+    let new_task = current_task.fork(CloneFlags { bits: 0 })?;
     //info!("complete sys_fork, new_task = {:}",new_task.pid() );
-    let new_pid = new_task.pid();
+    let new_pid = pid_vnr(new_task.pid());
     // modify trap context of new_task, because it returns immediately after switching
     let trap_cx = new_task.get_trap_cx();
     // we do not have to move to next instruction since we have done it before
@@ -149,7 +170,7 @@ pub fn sys_fork() -> isize {
     // add new task to scheduler
     spawn_user_task(new_task);
     //info!("sys_fork: complete, new_pid = {}", new_pid);
-    new_pid as isize
+    Ok(new_pid as isize)
 }
 
 /// clone a new process/thread/ using clone flags
@@ -157,16 +178,34 @@ pub fn sys_fork() -> isize {
 pub fn sys_clone(flags: u64, stack: VirtAddr, parent_tid: VirtAddr, tls: VirtAddr, child_tid: VirtAddr) -> SysResult {
     // info!("[sys_clone]: into clone, stack addr: {:#x}, parent tid: {:?}", stack.0, parent_tid);
     let flags = CloneFlags::from_bits(flags & !0xff).ok_or(SysError::EINVAL)?;
+    // the pidfd goes to parent_tid, and a pidfd refers to a process
+    if flags.contains(CloneFlags::PIDFD) && flags.intersects(CloneFlags::PARENT_SETTID | CloneFlags::THREAD) {
+        return Err(SysError::EINVAL);
+    }
     let task = current_task().unwrap();
-    let new_task = task.fork(flags);
+    // nothing may fail once the child exists, so the pidfd comes first
+    let pidfd = reserve_pidfd(task, flags, parent_tid)?;
+    let new_task = match task.fork(flags) {
+        Ok(new_task) => new_task,
+        Err(e) => {
+            if let Some((fd, _)) = pidfd {
+                let _ = task.with_mut_fd_table(|table| table.remove(fd));
+            }
+            return Err(e);
+        }
+    };
     new_task.get_trap_cx().set_ret_nth(0, 0);
-    let new_tid = new_task.tid();
+    // the parent sees the child's number in its own pid namespace
+    let new_tid = pid_vnr(new_task.tid());
     task.get_trap_cx().set_ret_nth(0, new_tid);
     // set new stack
     if stack.0 != 0 {
         *new_task.get_trap_cx().sp() = stack.0;
     }
 
+    if let Some((fd, file)) = pidfd {
+        install_pidfd(task, &new_task, flags, parent_tid, fd, &file);
+    }
     // set parent tid and child tid
     if flags.contains(CloneFlags::PARENT_SETTID) {
         let user_ptr = UserPtrRaw::new(parent_tid.0 as *mut u32)
@@ -184,7 +223,7 @@ pub fn sys_clone(flags: u64, stack: VirtAddr, parent_tid: VirtAddr, tls: VirtAdd
         let user_ptr = UserPtrRaw::new(child_tid.0 as *mut u32)
             .ensure_write(&mut task.get_vm_space().lock())
             .ok_or(SysError::EINVAL)?;
-        user_ptr.write(new_tid as u32);
+        user_ptr.write(new_task.pid_ns().nr_of(new_task.tid()).unwrap_or(0) as u32);
     }
     if flags.contains(CloneFlags::CHILD_CLEARTID) {
         new_task.tid_address().clear_child_tid = Some(child_tid.0);
@@ -202,16 +241,34 @@ pub fn sys_clone(flags: u64, stack: VirtAddr, parent_tid: VirtAddr, tls: VirtAdd
 pub fn sys_clone(flags: u64, stack: VirtAddr, parent_tid: VirtAddr, child_tid: VirtAddr, tls: VirtAddr) -> SysResult {
     // info!("[sys_clone]: into clone, stack addr: {:#x}, parent tid: {:?}", stack.0, parent_tid);
     let flags = CloneFlags::from_bits(flags & !0xff).ok_or(SysError::EINVAL)?;
+    // the pidfd goes to parent_tid, and a pidfd refers to a process
+    if flags.contains(CloneFlags::PIDFD) && flags.intersects(CloneFlags::PARENT_SETTID | CloneFlags::THREAD) {
+        return Err(SysError::EINVAL);
+    }
     let task = current_task().unwrap();
-    let new_task = task.fork(flags);
+    // nothing may fail once the child exists, so the pidfd comes first
+    let pidfd = reserve_pidfd(task, flags, parent_tid)?;
+    let new_task = match task.fork(flags) {
+        Ok(new_task) => new_task,
+        Err(e) => {
+            if let Some((fd, _)) = pidfd {
+                let _ = task.with_mut_fd_table(|table| table.remove(fd));
+            }
+            return Err(e);
+        }
+    };
     new_task.get_trap_cx().set_ret_nth(0, 0);
-    let new_tid = new_task.tid();
+    // the parent sees the child's number in its own pid namespace
+    let new_tid = pid_vnr(new_task.tid());
     task.get_trap_cx().set_ret_nth(0, new_tid);
     // set new stack
     if stack.0 != 0 {
         *new_task.get_trap_cx().sp() = stack.0;
     }
 
+    if let Some((fd, file)) = pidfd {
+        install_pidfd(task, &new_task, flags, parent_tid, fd, &file);
+    }
     // set parent tid and child tid
     if flags.contains(CloneFlags::PARENT_SETTID) {
         let user_ptr = UserPtrRaw::new(parent_tid.0 as *mut u32)
@@ -229,7 +286,7 @@ pub fn sys_clone(flags: u64, stack: VirtAddr, parent_tid: VirtAddr, child_tid: V
         let user_ptr = UserPtrRaw::new(child_tid.0 as *mut u32)
             .ensure_write(&mut task.get_vm_space().lock())
             .ok_or(SysError::EFAULT)?;
-        user_ptr.write(new_tid as u32);
+        user_ptr.write(new_task.pid_ns().nr_of(new_task.tid()).unwrap_or(0) as u32);
     }
     if flags.contains(CloneFlags::CHILD_CLEARTID) {
         new_task.tid_address().clear_child_tid = Some(child_tid.0);
@@ -320,6 +377,7 @@ pub async fn sys_execve(pathname: usize, argv: usize, envp: usize) -> SysResult
     // open file
     log::info!("[sys_execve]: try to open file at path {}", dentry.path());
     if dentry.state() != DentryState::NEGATIVE {
+        check_exec(&dentry)?;
         let task = current_task().unwrap();
         let app = dentry.open(OpenFlags::empty()).unwrap();
         let reader = FileReader::new(app.clone()).map_err(|_| SysError::EINVAL)?;
@@ -355,6 +413,12 @@ pub async fn sys_waitpid(pid: isize, exit_code_ptr: usize, option: i32) -> SysRe
     let task = current_task().unwrap().clone();
     // println!("[sys_waitpid]: TCB: {}, pid: {}, exitcode_ptr: {:x}, option: {}", task.tid(), pid, exit_code_ptr, option);
     let option = WaitOptions::from_bits_truncate(option);
+    // children are keyed by global tid
+    let pid = if pid > 0 {
+        task.pid_ns().tid_of(pid as usize).ok_or(SysError::ECHILD)? as isize
+    } else {
+        pid
+    };
     // todo: now only support for pid == -1 and pid > 0
     // get the all target zombie process
     let res_task = {
@@ -409,7 +473,7 @@ pub async fn sys_waitpid(pid: isize, exit_code_ptr: usize, option: i32) -> SysRe
         let tid = res_task.tid();
         task.remove_child(tid);
         PROCESS_GROUP_MANAGER.remove(&task);
-        return Ok(tid as isize);
+        return Ok(pid_vnr(tid) as isize);
     } else if option.contains(WaitOptions::WNOHANG) {
         return Ok(0);
     } else {
@@ -487,7 +551,7 @@ pub async fn sys_waitpid(pid: isize, exit_code_ptr: usize, option: i32) -> SysRe
         let tid = res_task.tid();
         task.remove_child(tid);
         PROCESS_GROUP_MANAGER.remove(&task);
-        return Ok(tid as isize);
+        return Ok(pid_vnr(tid) as isize);
     }
 }
 /// yield immediatly to another process
@@ -506,22 +570,23 @@ pub fn sys_brk(addr: VirtAddr) -> SysResult {
 /// syscall: get_ppid
 pub fn sys_getppid() -> SysResult {
     let task = current_task().unwrap().clone();
+    // a parent outside the pid namespace shows up as 0
     if let Some(parent) = task.parent() {
         let parent = parent.upgrade().unwrap();
-        return Ok(parent.pid() as isize);
+        return Ok(pid_vnr(parent.pid()) as isize);
     } else {
-        return Ok(INITPROC.pid() as isize);
+        return Ok(pid_vnr(INITPROC.pid()) as isize);
     }
 }
 /// get the process group id of the specified process
 pub fn sys_getpgid(pid: usize) -> SysResult {
     log::debug!("[sys_getpgid]: caller pgid: {}, target pid: {}", current_task().unwrap().pgid(), pid);
     if pid == 0 {
-        Ok(current_task().unwrap().pgid() as isize)
+        Ok(pid_vnr(current_task().unwrap().pgid()) as isize)
     }else {
-        match TASK_MANAGER.get_task(pid){
+        match find_task_by_vpid(pid){
             Some(task) => {
-                Ok(task.pgid() as isize)
+                Ok(pid_vnr(task.pgid()) as isize)
             }
             None => {
                 Err(SysError::ESRCH)
@@ -534,7 +599,13 @@ pub fn sys_setpgid(pid: usize, pgid: usize) -> SysResult {
     let task =  if pid == 0{
         current_task().unwrap().clone()
     }else {
-        TASK_MANAGER.get_task(pid).unwrap()
+        find_task_by_vpid(pid).ok_or(SysError::ESRCH)?
+    };
+    // process groups are keyed by global ids
+    let pgid = if pgid == 0 {
+        0
+    } else {
+        task.pid_ns().tid_of(pgid).ok_or(SysError::EPERM)?
     };
 
     if pgid == 0 {
@@ -575,7 +646,7 @@ pub fn sys_geteuid() -> SysResult {
 /// getegid() returns the effective group ID of the calling process.
 /// todo
 pub fn sys_getegid() -> SysResult {
-    Ok(0)
+    Ok(current_task().unwrap().gid() as isize)
 }
 
 ///
@@ -604,8 +675,19 @@ pub fn sys_clone3(cl_args_ptr: usize, size: usize) -> SysResult {
     .ensure_read(&mut task.get_vm_space().lock())
     .ok_or(SysError::EFAULT)?
     .to_ref();
-    let flags = cl_args.flags;
+    let mut flags = cl_args.flags;
     // log::info!("[sys_clone3]: flags: {:x}", flags);
+    // clone3 has its own slot for the pidfd, install it after the clone
+    let want_pidfd = flags & CloneFlags::PIDFD.bits() != 0;
+    if want_pidfd {
+        if flags & CloneFlags::THREAD.bits() != 0 {
+            return Err(SysError::EINVAL);
+        }
+        UserPtrRaw::new(cl_args.pidfd as *mut i32)
+            .ensure_write(&mut task.get_vm_space().lock())
+            .ok_or(SysError::EFAULT)?;
+        flags &= !CloneFlags::PIDFD.bits();
+    }
     let stack = VirtAddr::from(cl_args.stack);
     // log::info!("[sys_clone3]: stack: {:x}", stack.0);
     let parent_tid = VirtAddr::from(cl_args.parent_tid);
@@ -616,13 +698,186 @@ pub fn sys_clone3(cl_args_ptr: usize, size: usize) -> SysResult {
     // log::info!("[sys_clone3]: child_tid: {:x}", child_tid.0);
     // log::info!("[sys_clone3]: stack_size: {}, set_tid_size: {}, cgroup: {}" , cl_args.stack_size, cl_args.set_tid_size, cl_args.cgroup);
     #[cfg(target_arch="riscv64")]
-    {
-        sys_clone(flags, stack + cl_args.stack_size, parent_tid, tls, child_tid)
-    } 
-    #[cfg(target_arch="loongarch64")] 
-    {
-        sys_clone(flags, stack + cl_args.stack_size, parent_tid, child_tid, tls)
+    let new_tid = sys_clone(flags, stack + cl_args.stack_size, parent_tid, tls, child_tid)?;
+    #[cfg(target_arch="loongarch64")]
+    let new_tid = sys_clone(flags, stack + cl_args.stack_size, parent_tid, child_tid, tls)?;
+    if want_pidfd {
+        // the child can't be reaped before we return, unless by another thread
+        let new_task = find_task_by_vpid(new_tid as usize).ok_or(SysError::ESRCH)?;
+        let pidfd = alloc_pidfd(task, &new_task, OpenFlags::O_RDWR)?;
+        UserPtrRaw::new(cl_args.pidfd as *mut i32)
+            .ensure_write(&mut task.get_vm_space().lock())
+            .ok_or(SysError::EFAULT)?
+            .write(pidfd as i32);
+    }
+    Ok(new_tid)
+}
+
+/// syscall: unshare
+/// the cwd, root and namespaces are shared by the whole thread group here,
+/// so a new namespace is entered by all the threads
+pub fn sys_unshare(flags: u64) -> SysResult {
+    let flags = CloneFlags::from_bits(flags).ok_or(SysError::EINVAL)?;
+    let new_ns = CloneFlags::NEWNS | CloneFlags::NEWUTS | CloneFlags::NEWIPC | CloneFlags::NEWPID;
+    if !(CloneFlags::FS | CloneFlags::FILES | CloneFlags::SYSVSEM | new_ns).contains(flags) {
+        return Err(SysError::EINVAL);
+    }
+    let task = current_task().unwrap();
+    if flags.intersects(new_ns) && task.uid() != 0 {
+        return Err(SysError::EPERM);
+    }
+    // the fs info and the fd table cannot be split off a task that shares
+    // them, unsharing is only a no-op when nobody else holds them
+    if (flags.contains(CloneFlags::FS) && Arc::strong_count(&task.cwd) > 1)
+        || (flags.contains(CloneFlags::FILES) && Arc::strong_count(&task.fd_table) > 1) {
+        return Err(SysError::EINVAL);
+    }
+    if flags.contains(CloneFlags::NEWPID) {
+        // only the children created from now on go into the new namespace
+        let ns = task.pid_ns_for_children().new_child()?;
+        task.set_pid_ns_for_children(ns);
+    }
+    if flags.contains(CloneFlags::NEWNS) {
+        let ns = task.mnt_ns().copy();
+        task.set_cwd(ns.translate(&task.cwd()));
+        task.set_root(ns.translate(&task.root()));
+        task.set_mnt_ns(ns);
+        log::info!("[sys_unshare] task {} enters a new mount namespace", task.tid());
+    }
+    if flags.contains(CloneFlags::NEWUTS) {
+        task.set_uts_ns(task.uts_ns().copy());
+    }
+    if flags.contains(CloneFlags::NEWIPC) {
+        task.set_ipc_ns(IpcNamespace::new());
+    }
+    Ok(0)
+}
+
+/// syscall: setns
+/// `fd` is a /proc/<pid>/ns file, or a pidfd to join several namespaces
+/// of that process at once
+pub fn sys_setns(fd: usize, nstype: i32) -> SysResult {
+    let task = current_task().unwrap();
+    if task.uid() != 0 {
+        return Err(SysError::EPERM);
+    }
+    let nstype = CloneFlags::from_bits(nstype as u32 as u64).ok_or(SysError::EINVAL)?;
+    let new_ns = CloneFlags::NEWNS | CloneFlags::NEWUTS | CloneFlags::NEWIPC | CloneFlags::NEWPID;
+    let file = task.with_fd_table(|table| table.get_file(fd))?;
+    let handles = if let Ok(ns) = file.clone().downcast_arc::<NsFile>() {
+        let handle = ns.handle().clone();
+        if !nstype.is_empty() && nstype != handle.flag() {
+            return Err(SysError::EINVAL);
+        }
+        vec![handle]
+    } else if let Ok(pidfd) = file.downcast_arc::<PidFdFile>() {
+        if nstype.is_empty() || !new_ns.contains(nstype) {
+            return Err(SysError::EINVAL);
+        }
+        let target = pidfd.task().ok_or(SysError::ESRCH)?;
+        if pidfd.exited() {
+            return Err(SysError::ESRCH);
+        }
+        [CloneFlags::NEWNS, CloneFlags::NEWUTS, CloneFlags::NEWIPC, CloneFlags::NEWPID]
+            .into_iter()
+            .filter(|flag| nstype.contains(*flag))
+            .filter_map(|flag| NsHandle::of(&target, flag))
+            .collect::<Vec<_>>()
+    } else {
+        return Err(SysError::EINVAL);
+    };
+    for handle in handles {
+        log::info!("[sys_setns] task {} enters {} namespace {}", task.tid(), handle.kind(), handle.id());
+        handle.enter(task)?;
+    }
+    Ok(0)
+}
+
+/// install a pidfd of `target` into the fd table of `task`
+fn alloc_pidfd(task: &Arc<TaskControlBlock>, target: &Arc<TaskControlBlock>, open_flags: OpenFlags) -> Result<usize, SysError> {
+    put_pidfd(task, PidFdFile::new(target, open_flags))
+}
+
+/// put a pidfd into the fd table of `task`
+fn put_pidfd(task: &Arc<TaskControlBlock>, file: Arc<PidFdFile>) -> Result<usize, SysError> {
+    task.with_mut_fd_table(|table| -> Result<usize, SysError> {
+        let fd = table.alloc_fd()?;
+        table.put_file(fd, FdInfo { file, flags: OpenFlags::O_CLOEXEC.into() })?;
+        Ok(fd)
+    })
+}
+
+/// CLONE_PIDFD: check the user pointer and install a detached pidfd
+/// before the fork
+fn reserve_pidfd(task: &Arc<TaskControlBlock>, flags: CloneFlags, parent_tid: VirtAddr) -> Result<Option<(usize, Arc<PidFdFile>)>, SysError> {
+    if !flags.contains(CloneFlags::PIDFD) {
+        return Ok(None);
+    }
+    UserPtrRaw::new(parent_tid.0 as *mut i32)
+        .ensure_write(&mut task.get_vm_space().lock())
+        .ok_or(SysError::EFAULT)?;
+    let file = PidFdFile::new_detached(OpenFlags::O_RDWR);
+    let fd = put_pidfd(task, file.clone())?;
+    Ok(Some((fd, file)))
+}
+
+/// point the reserved pidfd to the child and report it to the parent
+fn install_pidfd(task: &Arc<TaskControlBlock>, new_task: &Arc<TaskControlBlock>, flags: CloneFlags, parent_tid: VirtAddr, fd: usize, file: &PidFdFile) {
+    file.attach(new_task);
+    // the child copied the table with the reserved slot in it
+    if !flags.contains(CloneFlags::FILES) {
+        let _ = new_task.with_mut_fd_table(|table| table.remove(fd));
+    }
+    // checked before the fork, only a racing munmap gets here
+    if let Some(user_ptr) = UserPtrRaw::new(parent_tid.0 as *mut i32).ensure_write(&mut task.get_vm_space().lock()) {
+        user_ptr.write(fd as i32);
+    }
+}
+
+/// syscall: pidfd_open
+pub fn sys_pidfd_open(pid: isize, flags: u32) -> SysResult {
+    let flags = PidFdFlags::from_bits(flags).ok_or(SysError::EINVAL)?;
+    if pid <= 0 {
+        return Err(SysError::EINVAL);
+    }
+    let task = current_task().unwrap().clone();
+    let target = find_task_by_vpid(pid as usize).ok_or(SysError::ESRCH)?;
+    if !target.is_leader() {
+        return Err(SysError::EINVAL);
+    }
+    log::info!("[sys_pidfd_open] task {} opens pid {}", task.tid(), pid);
+    let mut open_flags = OpenFlags::O_RDWR;
+    if flags.contains(PidFdFlags::PIDFD_NONBLOCK) {
+        open_flags |= OpenFlags::O_NONBLOCK;
+    }
+    let fd = alloc_pidfd(&task, &target, open_flags)?;
+    Ok(fd as isize)
+}
+
+/// syscall: pidfd_getfd
+pub fn sys_pidfd_getfd(pidfd: usize, targetfd: usize, flags: u32) -> SysResult {
+    if flags != 0 {
+        return Err(SysError::EINVAL);
+    }
+    let task = current_task().unwrap().clone();
+    let pidfd = task.with_fd_table(|table| table.get_file(pidfd))?
+        .downcast_arc::<PidFdFile>()
+        .map_err(|_| SysError::EBADF)?;
+    let target = pidfd.task().ok_or(SysError::ESRCH)?;
+    if pidfd.exited() {
+        return Err(SysError::ESRCH);
+    }
+    // stands in for the ptrace access check
+    if task.uid() != 0 && task.uid() != target.uid() {
+        return Err(SysError::EPERM);
     }
+    let file = target.with_fd_table(|table| table.get_file(targetfd))?;
+    let fd = task.with_mut_fd_table(|table| -> Result<usize, SysError> {
+        let fd = table.alloc_fd()?;
+        table.put_file(fd, FdInfo { file, flags: OpenFlags::O_CLOEXEC.into() })?;
+        Ok(fd)
+    })?;
+    Ok(fd as isize)
 }
 
 //  * @flags:        Flags for the new process.
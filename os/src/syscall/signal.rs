@@ -1,6 +1,7 @@
 //! signal related syscall
 
 use core::time::Duration;
+use alloc::sync::Arc;
 
 use hal::instruction::{Instruction, InstructionHal};
 use hal::println;
@@ -15,12 +16,14 @@ use crate::{processor, timer};
 use crate::processor::context::SumGuard;
 use crate::processor::processor::current_processor;
 use crate::signal::*;
-use crate::task::{current_task,INITPROC_PID};
+use crate::task::{current_task,ns::find_task_by_vpid,task::TaskControlBlock,INITPROC_PID};
 use crate::processor::processor::current_trap_cx;
 use crate::task::manager::{PROCESS_GROUP_MANAGER, TASK_MANAGER};
 use crate::timer::ffi::TimeSpec;
 use crate::timer::timed_task::suspend_timeout;
 use crate::utils::suspend_now;
+use crate::fs::{pidfd::PidFdFile, signalfd::{SignalFdFile, SignalFdFlags}, OpenFlags};
+use crate::task::fs::FdInfo;
 
 /// syscall: kill
 pub fn sys_kill(pid: isize, signo: i32) -> SysResult {
@@ -55,11 +58,13 @@ pub fn sys_kill(pid: isize, signo: i32) -> SysResult {
         -1 => {
             // sent to every process which current process has permission ( except init proc )
             //panic!("[sys_kill] unsupport for sending signal to all process");
+            // only the processes in the caller's pid namespace and below
+            let ns = cur_task.pid_ns().clone();
             TASK_MANAGER.for_each_task(|task|{
-                if task.tid() == INITPROC_PID {
+                if task.tid() == INITPROC_PID || ns.nr_of(task.tid()) == Some(1) {
                     return;
                 }
-                if signo != 0 && task.is_leader(){
+                if signo != 0 && task.is_leader() && task.pid_ns().is_under(&ns) {
                     task.recv_sigs_process_level(
                         SigInfo { si_signo: signo as usize, si_code: SigInfo::USER, si_pid: Some(cur_task.pid()) },
                     );
@@ -69,7 +74,7 @@ pub fn sys_kill(pid: isize, signo: i32) -> SysResult {
         _ if pid < -1 => {
             // sent to every process in process group whose ID is -pid
             //panic!("[sys_kill] unsupport for sending signal to specific process group");
-            let inner_pid = -pid as usize;
+            let inner_pid = cur_task.pid_ns().tid_of(-pid as usize).ok_or(SysError::ESRCH)?;
             for task in PROCESS_GROUP_MANAGER
                 .get_group(pgid)
                 .ok_or_else(|| SysError::ESRCH)?
@@ -84,11 +89,9 @@ pub fn sys_kill(pid: isize, signo: i32) -> SysResult {
         _ if pid > 0 => {
             // sent to the process specified with pid
             //assert!(task.gettid() != pid as usize); // should not send to itself
-            if let Some(task) = TASK_MANAGER.get_task(pid as usize) {
+            if let Some(task) = find_task_by_vpid(pid as usize) {
                 if task.is_leader() {
-                    task.recv_sigs_process_level(
-                        SigInfo { si_signo: signo as usize, si_code: SigInfo::USER, si_pid: Some(cur_task.pid()) },
-                    );
+                    kill_process(&task, signo, &cur_task);
                 }else {
                     // todo standard error
                     return Err(SysError::ESRCH);
@@ -102,6 +105,41 @@ pub fn sys_kill(pid: isize, signo: i32) -> SysResult {
     Ok(0)
 }
 
+/// send `signo` from `sender` to the process led by `task`
+fn kill_process(task: &Arc<TaskControlBlock>, signo: i32, sender: &Arc<TaskControlBlock>) {
+    if !task.ns_init_accepts(signo as usize, sender) {
+        return;
+    }
+    task.recv_sigs_process_level(
+        SigInfo { si_signo: signo as usize, si_code: SigInfo::USER, si_pid: Some(sender.pid()) },
+    );
+}
+
+/// syscall: pidfd_send_signal
+pub fn sys_pidfd_send_signal(pidfd: usize, signo: i32, info: usize, flags: u32) -> SysResult {
+    if flags != 0 {
+        return Err(SysError::EINVAL);
+    }
+    if info != 0 {
+        log::warn!("[sys_pidfd_send_signal] siginfo is not supported, ignored");
+    }
+    if signo < 0 || signo as usize > SIGRTMAX {
+        return Err(SysError::EINVAL);
+    }
+    let cur_task = current_task().unwrap().clone();
+    let file = cur_task.with_fd_table(|table| table.get_file(pidfd))?
+        .downcast_arc::<PidFdFile>()
+        .map_err(|_| SysError::EBADF)?;
+    log::info!("[sys_pidfd_send_signal]: task {} sending signo: {} to pid: {}", cur_task.tid(), signo, file.pid());
+    let task = file.task().ok_or(SysError::ESRCH)?;
+    if file.exited() {
+        return Err(SysError::ESRCH);
+    }
+    if signo != 0 {
+        kill_process(&task, signo, &cur_task);
+    }
+    Ok(0)
+}
 
 /// syscall: rt_sigaction
 pub fn sys_rt_sigaction(signo: i32, action: *const SigAction, old_action: *mut SigAction) -> SysResult {
@@ -318,7 +356,7 @@ pub async fn sys_rt_sigtimedwait(
         log::warn!("[sys_rt_sigtimedwait] task {} woken by {:#?}", task.tid(), si);
         if info_ptr != 0 {
             unsafe {
-                (info_ptr as *mut SigInfo).write(si);
+                (info_ptr as *mut SigInfo).write(si.seen_from(task.pid_ns()));
             }
         }
         return  Ok(si.si_signo as isize);
@@ -386,7 +424,7 @@ pub fn sys_tkill(tid: isize, sig: i32) -> SysResult {
         return Err(SysError::EINVAL);
     }
     let cur_task = current_task().unwrap();
-    let task = TASK_MANAGER.get_task(tid as usize)
+    let task = find_task_by_vpid(tid as usize)
         .ok_or(SysError::ESRCH)?;
     task.recv_sigs(
         SigInfo {
@@ -412,11 +450,12 @@ pub fn sys_tgkill(tgid: isize, tid: isize, signo: i32) -> SysResult {
         return Err(SysError::EINVAL);
     }
     let cur_task = current_task().unwrap();
-    let task = TASK_MANAGER.get_task(tgid as usize).ok_or(SysError::ESRCH)?;
+    let task = find_task_by_vpid(tgid as usize).ok_or(SysError::ESRCH)?;
+    let tid = cur_task.pid_ns().tid_of(tid as usize).ok_or(SysError::ESRCH)?;
     if task.is_leader() {
         task.with_mut_thread_group(|thread_group| -> SysResult {
             for thread in thread_group.iter() {
-                if thread.tid() == tid as usize {
+                if thread.tid() == tid {
                     thread.recv_sigs(SigInfo { si_signo: signo as usize, si_code: SigInfo::TKILL, si_pid: Some(cur_task.pid())});
                     return Ok(0)
                 }
@@ -426,4 +465,33 @@ pub fn sys_tgkill(tgid: isize, tid: isize, signo: i32) -> SysResult {
     }else {
         return Err(SysError::ESRCH);
     }
-}
\ No newline at end of file
+}
+/// syscall: signalfd4
+/// create a signalfd when `fd` is -1, otherwise change the mask of `fd`
+pub fn sys_signalfd4(fd: isize, mask: usize, sizemask: usize, flags: i32) -> SysResult {
+    let flags = SignalFdFlags::from_bits(flags).ok_or(SysError::EINVAL)?;
+    if sizemask != size_of::<SigSet>() {
+        return Err(SysError::EINVAL);
+    }
+    let task = current_task().unwrap();
+    let mask = *UserPtrRaw::new(mask as *const SigSet)
+        .ensure_read(&mut task.get_vm_space().lock())
+        .ok_or(SysError::EFAULT)?
+        .to_ref();
+    if fd != -1 {
+        let signalfd = task.with_fd_table(|t| t.get_file(fd as usize))?
+            .downcast_arc::<SignalFdFile>()
+            .map_err(|_| SysError::EINVAL)?;
+        signalfd.set_mask(mask);
+        return Ok(fd);
+    }
+    let signalfd = SignalFdFile::new(mask, flags);
+    let fd_flags = if flags.contains(SignalFdFlags::SFD_CLOEXEC) { OpenFlags::O_CLOEXEC } else { OpenFlags::empty() };
+    let fd = task.with_mut_fd_table(|table| -> Result<usize, SysError> {
+        let fd = table.alloc_fd()?;
+        table.put_file(fd, FdInfo { file: signalfd, flags: fd_flags.into() })?;
+        Ok(fd)
+    })?;
+    log::info!("[sys_signalfd4] new signalfd {} mask {:?}", fd, mask);
+    Ok(fd as isize)
+}
@@ -84,12 +84,20 @@ pub enum SysError {
     ENOTEMPTY = 39,
     /// Too many symbolic links encountered
     ELOOP = 40,
+    /// No message of desired type
+    ENOMSG = 42,
+    /// Identifier removed
+    EIDRM = 43,
     /// Timer expired   
     ETIME = 62,
     /// Socket operation on non-socket
     ENOTSOCK = 88,
+    /// Destination address required
+    EDESTADDRREQ = 89,
     /// sendmsg bigger than biggest message
     EMSGSIZE = 90,
+    /// Protocol wrong type for socket
+    EPROTOTYPE = 91,
     // sock opt not support
     ENOPROTOOPT=92,
     /// Protocol not supported
@@ -7,7 +7,7 @@ use fatfs::{info, Time};
 use hal::instruction::{Instruction, InstructionHal};
 use xmas_elf::program::Flags;
 
-use crate::{mm::UserPtrRaw, processor::context::SumGuard, task::current_task, timer::{clock::{CLOCK_DEVIATION, CLOCK_MONOTONIC, CLOCK_MONOTONIC_COARSE, CLOCK_MONOTONIC_RAW, CLOCK_PROCESS_CPUTIME_ID, CLOCK_REALTIME, CLOCK_REALTIME_COARSE, CLOCK_THREAD_CPUTIME_ID}, ffi::{TimeSpec, TimeVal}, get_current_time_duration, get_current_time_ms, get_current_time_us, timed_task::{ksleep,suspend_timeout}, timer::{alloc_timer_id, ITimerVal, RealITimer, Timer, TIMER_MANAGER}}, utils::Select2Futures
+use crate::{fs::{timerfd::{TimerFdFile, TimerFdFlags, TimerFdSetFlags}, OpenFlags}, mm::UserPtrRaw, processor::context::SumGuard, task::{current_task, fs::FdInfo}, timer::{clock::{CLOCK_DEVIATION, CLOCK_MONOTONIC, CLOCK_MONOTONIC_COARSE, CLOCK_MONOTONIC_RAW, CLOCK_PROCESS_CPUTIME_ID, CLOCK_REALTIME, CLOCK_REALTIME_COARSE, CLOCK_THREAD_CPUTIME_ID}, ffi::{TimeSpec, TimeVal}, get_current_time_duration, get_current_time_ms, get_current_time_us, timed_task::{ksleep,suspend_timeout}, timer::{alloc_timer_id, ITimerSpec, ITimerVal, RealITimer, Timer, TIMER_MANAGER}}, utils::Select2Futures
 };
 use super::{SysError, SysResult};
 /// get current time of day
@@ -523,4 +523,55 @@ pub fn sys_clock_adjtime(clock_id: usize, timex: usize) -> SysResult {
             Ok(0)
         }
     }
-}
\ No newline at end of file
+}
+/// syscall: timerfd_create
+pub fn sys_timerfd_create(clockid: usize, flags: i32) -> SysResult {
+    let flags = TimerFdFlags::from_bits(flags).ok_or(SysError::EINVAL)?;
+    let task = current_task().unwrap();
+    let timerfd = TimerFdFile::new(clockid, flags)?;
+    let fd_flags = if flags.contains(TimerFdFlags::TFD_CLOEXEC) { OpenFlags::O_CLOEXEC } else { OpenFlags::empty() };
+    let fd = task.with_mut_fd_table(|table| -> Result<usize, SysError> {
+        let fd = table.alloc_fd()?;
+        table.put_file(fd, FdInfo { file: timerfd, flags: fd_flags.into() })?;
+        Ok(fd)
+    })?;
+    Ok(fd as isize)
+}
+
+/// syscall: timerfd_settime
+pub fn sys_timerfd_settime(fd: usize, flags: i32, new_value: usize, old_value: usize) -> SysResult {
+    let flags = TimerFdSetFlags::from_bits(flags).ok_or(SysError::EINVAL)?;
+    let task = current_task().unwrap();
+    let timerfd = task.with_fd_table(|t| t.get_file(fd))?
+        .downcast_arc::<TimerFdFile>()
+        .map_err(|_| SysError::EINVAL)?;
+    let new = *UserPtrRaw::new(new_value as *const ITimerSpec)
+        .ensure_read(&mut task.get_vm_space().lock())
+        .ok_or(SysError::EFAULT)?
+        .to_ref();
+    if !new.is_valid() {
+        return Err(SysError::EINVAL);
+    }
+    log::info!("[sys_timerfd_settime] fd {} {:?} {:?}", fd, flags, new);
+    let old = timerfd.set_time(&new, flags);
+    if old_value != 0 {
+        UserPtrRaw::new(old_value as *mut ITimerSpec)
+            .ensure_write(&mut task.get_vm_space().lock())
+            .ok_or(SysError::EFAULT)?
+            .write(old);
+    }
+    Ok(0)
+}
+
+/// syscall: timerfd_gettime
+pub fn sys_timerfd_gettime(fd: usize, curr_value: usize) -> SysResult {
+    let task = current_task().unwrap();
+    let timerfd = task.with_fd_table(|t| t.get_file(fd))?
+        .downcast_arc::<TimerFdFile>()
+        .map_err(|_| SysError::EINVAL)?;
+    UserPtrRaw::new(curr_value as *mut ITimerSpec)
+        .ensure_write(&mut task.get_vm_space().lock())
+        .ok_or(SysError::EFAULT)?
+        .write(timerfd.get_time());
+    Ok(0)
+}
@@ -0,0 +1,123 @@
+//! sysctl: kernel tunables shown under /proc/sys
+//!
+//! a subsystem owns the storage of its tunables and registers them here by
+//! dotted name, e.g. `net.core.somaxconn`. procfs turns each dot into a
+//! directory, reads show the value and writes store it.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use alloc::{collections::btree_map::BTreeMap, format, string::{String, ToString}, sync::Arc, vec::Vec};
+
+use crate::{sync::mutex::SpinNoIrqLock, syscall::SysError};
+
+/// a kernel tunable
+pub trait Sysctl: Send + Sync {
+    /// current value as text, without the trailing newline
+    fn show(&self) -> String;
+    /// parse and set a new value
+    fn store(&self, val: &str) -> Result<(), SysError>;
+}
+
+/// an integer tunable within [min, max]
+pub struct IntSysctl {
+    value: &'static AtomicUsize,
+    min: usize,
+    max: usize,
+}
+
+impl IntSysctl {
+    pub fn new(value: &'static AtomicUsize, min: usize, max: usize) -> Arc<Self> {
+        Arc::new(Self { value, min, max })
+    }
+}
+
+impl Sysctl for IntSysctl {
+    fn show(&self) -> String {
+        self.value.load(Ordering::Relaxed).to_string()
+    }
+
+    fn store(&self, val: &str) -> Result<(), SysError> {
+        let val = val.trim().parse::<usize>().map_err(|_| SysError::EINVAL)?;
+        if val < self.min || val > self.max {
+            return Err(SysError::EINVAL);
+        }
+        self.value.store(val, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+/// a pair of integers within [min, max], the first no more than the second
+pub struct RangeSysctl {
+    value: &'static SpinNoIrqLock<(usize, usize)>,
+    min: usize,
+    max: usize,
+}
+
+impl RangeSysctl {
+    pub fn new(value: &'static SpinNoIrqLock<(usize, usize)>, min: usize, max: usize) -> Arc<Self> {
+        Arc::new(Self { value, min, max })
+    }
+}
+
+impl Sysctl for RangeSysctl {
+    fn show(&self) -> String {
+        let (low, high) = *self.value.lock();
+        format!("{}\t{}", low, high)
+    }
+
+    fn store(&self, val: &str) -> Result<(), SysError> {
+        let mut nums = val.split_whitespace().map(|s| s.parse::<usize>());
+        let (Some(Ok(low)), Some(Ok(high)), None) = (nums.next(), nums.next(), nums.next()) else {
+            return Err(SysError::EINVAL);
+        };
+        if low < self.min || high > self.max || low > high {
+            return Err(SysError::EINVAL);
+        }
+        *self.value.lock() = (low, high);
+        Ok(())
+    }
+}
+
+/// a string tunable stored elsewhere, e.g. the hostname in the uts
+pub struct StrSysctl {
+    get: fn() -> String,
+    set: fn(&str) -> Result<(), SysError>,
+}
+
+impl StrSysctl {
+    pub fn new(get: fn() -> String, set: fn(&str) -> Result<(), SysError>) -> Arc<Self> {
+        Arc::new(Self { get, set })
+    }
+}
+
+impl Sysctl for StrSysctl {
+    fn show(&self) -> String {
+        (self.get)()
+    }
+
+    fn store(&self, val: &str) -> Result<(), SysError> {
+        (self.set)(val)
+    }
+}
+
+/// all registered tunables by dotted name
+static SYSCTLS: SpinNoIrqLock<BTreeMap<&'static str, Arc<dyn Sysctl>>> = SpinNoIrqLock::new(BTreeMap::new());
+
+/// register a tunable, must be done before procfs is initialized
+pub fn register_sysctl(name: &'static str, sysctl: Arc<dyn Sysctl>) {
+    SYSCTLS.lock().insert(name, sysctl);
+}
+
+/// all registered tunables, sorted by name
+pub fn sysctls() -> Vec<(&'static str, Arc<dyn Sysctl>)> {
+    SYSCTLS.lock().iter().map(|(name, sysctl)| (*name, sysctl.clone())).collect()
+}
+
+/// register the tunables of every subsystem
+pub fn init() {
+    crate::syscall::misc::register_sysctls();
+    crate::task::register_sysctls();
+    crate::mm::register_sysctls();
+    crate::fs::register_sysctls();
+    crate::net::register_sysctls();
+}
@@ -1,14 +1,15 @@
 //! file system support for Task
 
-use alloc::{sync::Arc, vec::Vec};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use alloc::{string::{String, ToString}, sync::Arc, vec::Vec};
 use fatfs::info;
 use log::warn;
 
-use crate::{fs::{devfs::tty::TTY, vfs::{Dentry, File}, OpenFlags, Stdin}, syscall::{misc::RLimit, SysError}, task::current_task};
+use crate::{fs::{devfs::tty::TTY, vfs::{mount::MntNamespace, Dentry, File}, OpenFlags, Stdin}, syscall::{misc::RLimit, SysError}, task::current_task, utils::path::rel_path_to_abs};
 
 use super::task::TaskControlBlock;
 
-#[derive(Clone)]
 /// the fd table
 pub struct FdTable {
     /// the inner table
@@ -20,6 +21,29 @@ pub struct FdTable {
 /// Max file descriptors counts
 pub const MAX_FDS: usize = 1024;
 
+/// system wide limit of open fds, tunable as fs.file-max
+pub static FILE_MAX: AtomicUsize = AtomicUsize::new(MAX_FDS * 64);
+
+/// fds open in all fd tables
+static NR_FILES: AtomicUsize = AtomicUsize::new(0);
+
+/// number of fds open in all fd tables
+pub fn nr_files() -> usize {
+    NR_FILES.load(Ordering::Relaxed)
+}
+
+/// count a new open fd, ENFILE past fs.file-max
+fn charge_file() -> Result<(), SysError> {
+    let file_max = FILE_MAX.load(Ordering::Relaxed);
+    NR_FILES.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |nr| (nr < file_max).then_some(nr + 1))
+        .map(|_| ())
+        .map_err(|_| SysError::ENFILE)
+}
+
+fn uncharge_files(n: usize) {
+    NR_FILES.fetch_sub(n, Ordering::Relaxed);
+}
+
 impl FdTable {
     /// new and init fd table
     pub fn new() -> Self {
@@ -38,7 +62,8 @@ impl FdTable {
         table.push(Some(FdInfo { file: stdin, flags: FdFlags::empty() }));
         table.push(Some(FdInfo { file: stdout, flags: FdFlags::empty() }));
         table.push(Some(FdInfo { file: stderr, flags: FdFlags::empty() }));
-        
+        NR_FILES.fetch_add(table.len(), Ordering::Relaxed);
+
         Self { 
             fd_table: table,
             rlimit: RLimit { rlim_cur: MAX_FDS, rlim_max: MAX_FDS }
@@ -121,6 +146,9 @@ impl FdTable {
             log::warn!("[put_file] fd {} is not valid",fd);
             return Err(SysError::EBADF);
         }
+        if self.fd_table[fd].is_none() {
+            charge_file()?;
+        }
         self.fd_table[fd] = Some(fd_info);
         Ok(())
     }
@@ -134,6 +162,7 @@ impl FdTable {
             return Err(SysError::EBADF);
         } else {
             self.fd_table[fd] = None;
+            uncharge_files(1);
             Ok(())
         } 
     }
@@ -165,6 +194,9 @@ impl FdTable {
         if self.fd_table.len() <= new_fd {
             self.fd_table.resize(new_fd.checked_add(1).ok_or(SysError::EMFILE)?, None);
         }
+        if self.fd_table[new_fd].is_none() {
+            charge_file()?;
+        }
         self.fd_table[new_fd] = Some(FdInfo {file, flags});
         Ok(new_fd)
     }
@@ -175,6 +207,9 @@ impl FdTable {
         if self.fd_table.len() <= new_fd {
             self.fd_table.resize(new_fd.checked_add(1).ok_or(SysError::EMFILE)?, None);
         }
+        if self.fd_table[new_fd].is_none() {
+            charge_file()?;
+        }
         self.fd_table[new_fd] = Some(fd_info);
         Ok(new_fd)
     }
@@ -187,6 +222,7 @@ impl FdTable {
         self.rlimit = rlimit;
         if rlimit.rlim_max <= self.fd_table.len() {
             warn!("[Fd table]: set limit smaller than table size, truncate");
+            uncharge_files(self.fd_table[rlimit.rlim_max..].iter().filter(|f| f.is_some()).count());
             self.fd_table.truncate(rlimit.rlim_max);
         }
     }
@@ -199,10 +235,36 @@ impl FdTable {
                     //     panic!("Failed to downcast to socket::Socket")});
                     // log::info!("[do_close_on_exec]: file {:?} close on exec", socket_file.sk_type);
                     *fd_info = None;
+                    uncharge_files(1);
                 }
             }
         }
     }
+    /// close all fds, called when the task exits
+    pub fn close_all(&mut self) {
+        uncharge_files(self.open_count());
+        self.fd_table.clear();
+    }
+    fn open_count(&self) -> usize {
+        self.fd_table.iter().filter(|f| f.is_some()).count()
+    }
+}
+
+impl Clone for FdTable {
+    fn clone(&self) -> Self {
+        // fds inherited by fork are not checked against fs.file-max
+        NR_FILES.fetch_add(self.open_count(), Ordering::Relaxed);
+        Self {
+            fd_table: self.fd_table.clone(),
+            rlimit: self.rlimit,
+        }
+    }
+}
+
+impl Drop for FdTable {
+    fn drop(&mut self) {
+        uncharge_files(self.open_count());
+    }
 }
 
 
@@ -256,6 +318,58 @@ impl TaskControlBlock {
         log::info!("switching task {}'s cwd to {}", self.gettid(), dentry.path());
         *self.cwd.lock() = dentry;
     }
+    /// get the root dir
+    pub fn root(&self) -> Arc<dyn Dentry> {
+        self.root.lock().clone()
+    }
+    /// change the root dir
+    pub fn set_root(&self, dentry: Arc<dyn Dentry>) {
+        log::info!("switching task {}'s root to {}", self.gettid(), dentry.path());
+        *self.root.lock() = dentry;
+    }
+    /// whether the root dir is below the root of the mount namespace
+    pub fn is_chrooted(&self) -> bool {
+        self.root().parent().is_some()
+    }
+    /// get the mount namespace
+    pub fn mnt_ns(&self) -> Arc<MntNamespace> {
+        self.mnt_ns.lock().clone()
+    }
+    /// move to another mount namespace
+    pub fn set_mnt_ns(&self, ns: Arc<MntNamespace>) {
+        *self.mnt_ns.lock() = ns;
+    }
+    /// turn a path absolute to the root dir into one absolute to the
+    /// mount namespace, ".." stops at the root dir
+    pub fn path_from_root(&self, path: &str) -> String {
+        if !self.is_chrooted() {
+            return path.to_string();
+        }
+        let mut names = Vec::new();
+        for name in path.split('/') {
+            match name {
+                "" | "." => {}
+                ".." => {
+                    names.pop();
+                }
+                name => names.push(name),
+            }
+        }
+        rel_path_to_abs(&self.root().path(), &names.join("/")).unwrap()
+    }
+    /// the path of `dentry` seen from the root dir, None if it lies outside
+    pub fn path_in_root(&self, dentry: &Arc<dyn Dentry>) -> Option<String> {
+        let path = dentry.path();
+        if !self.is_chrooted() {
+            return Some(path);
+        }
+        let root = self.root().path();
+        match path.strip_prefix(root.as_str()) {
+            Some("") => Some("/".to_string()),
+            Some(rest) if rest.starts_with('/') => Some(rest.to_string()),
+            _ => None,
+        }
+    }
     
     
 }
\ No newline at end of file
@@ -45,6 +45,10 @@ impl TaskManager {
         .map(|task| task.clone())
         .collect()
     }
+    /// tasks_group, None if the manager is locked
+    pub fn try_tasks_group(&self) -> Option<Vec<Arc<TaskControlBlock>>> {
+        self.0.try_lock().map(|tasks| tasks.values().cloned().collect())
+    }
     /// do something for each task
     pub fn for_each_task<F: FnMut(&Arc<TaskControlBlock>)>(&self, mut f: F) {
         for task in self.tasks_group() {
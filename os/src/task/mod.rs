@@ -23,6 +23,7 @@ pub mod manager;
 pub mod utils;
 pub mod fs;
 pub mod signal;
+pub mod ns;
 
 #[allow(clippy::module_inception)]
 #[allow(rustdoc::private_intra_doc_links)]
@@ -52,23 +53,38 @@ pub fn suspend_current_and_run_next() {
 /// pid of initproc (must > 0)
 pub const INITPROC_PID: usize = 1;
 
+/// kernel.pid_max, fs.file-max and fs.file-nr
+pub fn register_sysctls() {
+    use alloc::format;
+    use crate::{syscall::SysError, sysctl::{register_sysctl, IntSysctl, StrSysctl}};
+    register_sysctl("kernel.pid_max", IntSysctl::new(&tid::PID_MAX, 301, 4194304));
+    register_sysctl("fs.file-max", IntSysctl::new(&fs::FILE_MAX, 0, usize::MAX));
+    register_sysctl("fs.file-nr", StrSysctl::new(
+        || format!("{}\t0\t{}", fs::nr_files(), fs::FILE_MAX.load(Ordering::Relaxed)),
+        |_| Err(SysError::EACCES),
+    ));
+}
+
 lazy_static! {
     ///Globle process that init user shell
     pub static ref INITPROC: Arc<TaskControlBlock> = {
         //info!("trying to open initproc");
         
         #[cfg(all(target_arch = "riscv64", feature = "autotest"))]
-        let file = open_file("/riscv/autotest", OpenFlags::O_WRONLY).unwrap();
+        let path = "/riscv/autotest";
 
         #[cfg(all(target_arch = "riscv64", not(feature = "autotest")))]
-        let file = open_file("/riscv/initproc", OpenFlags::O_WRONLY).unwrap();
+        let path = "/riscv/initproc";
 
         #[cfg(all(target_arch = "loongarch64", feature = "autotest"))]
-        let file = open_file("/loongarch/autotest", OpenFlags::O_WRONLY).unwrap();
+        let path = "/loongarch/autotest";
 
         #[cfg(all(target_arch = "loongarch64", not(feature = "autotest")))]
-        let file = open_file("/loongarch/initproc", OpenFlags::O_WRONLY).unwrap();
-        
+        let path = "/loongarch/initproc";
+
+        // booted from an initramfs, its /init comes first
+        let path = if crate::fs::initramfs::is_rootfs() { "/init" } else { path };
+        let file = open_file(path, OpenFlags::O_WRONLY).unwrap();
 
         let reader = FileReader::new(file.clone()).unwrap();
         let elf = xmas_elf::ElfFile::new(&reader).unwrap();
@@ -204,7 +220,8 @@ macro_rules! generate_state_methods {
                 }
                 #[allow(unused)]
                 pub fn [<set_ $state:lower>](&self) {
-                    *self.task_status.lock() = TaskStatus::$state
+                    let old = core::mem::replace(&mut *self.task_status.lock(), TaskStatus::$state);
+                    $crate::processor::loadavg::update_nr_running(Some(old), Some(TaskStatus::$state));
                 }
             }
         )+
@@ -0,0 +1,354 @@
+//! pid namespaces and the namespaces of a task
+//!
+//! every task keeps its global tid, which is what the kernel uses
+//! everywhere. a task in a nested pid namespace also gets a number in
+//! its namespace and in every namespace above it, those numbers only
+//! show up at the syscall boundary. the init namespace numbers tasks
+//! by their global tid.
+
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use alloc::{collections::btree_map::BTreeMap, sync::Arc, vec::Vec};
+use spin::Lazy;
+
+use crate::{fs::vfs::mount::MntNamespace, ipc::sysv::IpcNamespace, signal::{SigInfo, SIGKILL, SIGSTOP}, sync::mutex::SpinNoIrqLock, syscall::{misc::UtsNamespace, process::CloneFlags, SysError}};
+
+use super::{current_task, manager::TASK_MANAGER, task::TaskControlBlock, tid::{Tid, PID_MAX}, INITPROC, INITPROC_PID};
+
+/// namespace id allocator, shared by all kinds of namespaces
+static NEXT_NS_ID: AtomicUsize = AtomicUsize::new(1);
+
+/// allocate a namespace id
+pub fn alloc_ns_id() -> usize {
+    NEXT_NS_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// pid namespaces nest at most this deep, like linux
+const MAX_PID_NS_LEVEL: usize = 32;
+
+/// a pid namespace
+pub struct PidNamespace {
+    pub id: usize,
+    /// the namespace this one is nested in
+    pub parent: Option<Arc<PidNamespace>>,
+    /// nesting depth, 0 for the init namespace
+    pub level: usize,
+    /// set once the init of the namespace exits, no task can join then
+    dead: AtomicBool,
+    inner: SpinNoIrqLock<PidNsInner>,
+}
+
+struct PidNsInner {
+    /// next number to hand out
+    next: usize,
+    /// global tid -> number here
+    nrs: BTreeMap<Tid, usize>,
+    /// number here -> global tid
+    tids: BTreeMap<usize, Tid>,
+}
+
+impl PidNamespace {
+    fn new(parent: Option<Arc<PidNamespace>>) -> Arc<Self> {
+        let level = parent.as_ref().map_or(0, |p| p.level + 1);
+        Arc::new(Self {
+            id: alloc_ns_id(),
+            parent,
+            level,
+            dead: AtomicBool::new(false),
+            inner: SpinNoIrqLock::new(PidNsInner {
+                next: 1,
+                nrs: BTreeMap::new(),
+                tids: BTreeMap::new(),
+            }),
+        })
+    }
+
+    /// a new namespace nested in this one
+    pub fn new_child(self: &Arc<Self>) -> Result<Arc<Self>, SysError> {
+        if self.level >= MAX_PID_NS_LEVEL {
+            return Err(SysError::ENOSPC);
+        }
+        Ok(Self::new(Some(self.clone())))
+    }
+
+    /// number of the task `tid` here, None if it lives outside
+    pub fn nr_of(&self, tid: Tid) -> Option<usize> {
+        if self.level == 0 {
+            return Some(tid);
+        }
+        self.inner.lock().nrs.get(&tid).copied()
+    }
+
+    /// global tid of the task numbered `nr` here
+    pub fn tid_of(&self, nr: usize) -> Option<Tid> {
+        if self.level == 0 {
+            return Some(nr);
+        }
+        self.inner.lock().tids.get(&nr).copied()
+    }
+
+    /// global tids of the tasks here and in the namespaces below
+    pub fn tids(&self) -> Vec<Tid> {
+        self.inner.lock().nrs.keys().copied().collect()
+    }
+
+    /// whether this is `other` or nested somewhere below it
+    pub fn is_under(&self, other: &PidNamespace) -> bool {
+        let mut ns = self;
+        loop {
+            if ns.id == other.id {
+                return true;
+            }
+            match &ns.parent {
+                Some(parent) => ns = parent,
+                None => return false,
+            }
+        }
+    }
+
+    /// whether the init of the namespace has exited
+    pub fn is_dead(&self) -> bool {
+        self.dead.load(Ordering::Acquire)
+    }
+
+    fn alloc(&self, tid: Tid) -> Result<usize, SysError> {
+        let pid_max = PID_MAX.load(Ordering::Relaxed);
+        let mut inner = self.inner.lock();
+        // numbers are handed out in a cycle like linux, 1 only goes to init,
+        // one round over all of them without a free one means it is full
+        for _ in 0..pid_max {
+            let nr = inner.next;
+            inner.next = if nr + 1 >= pid_max { 2 } else { nr + 1 };
+            if !inner.tids.contains_key(&nr) {
+                inner.tids.insert(nr, tid);
+                inner.nrs.insert(tid, nr);
+                return Ok(nr);
+            }
+        }
+        Err(SysError::EAGAIN)
+    }
+
+    fn free(&self, tid: Tid, nr: usize) {
+        let mut inner = self.inner.lock();
+        if inner.tids.get(&nr) == Some(&tid) {
+            inner.tids.remove(&nr);
+        }
+        if inner.nrs.get(&tid) == Some(&nr) {
+            inner.nrs.remove(&tid);
+        }
+    }
+}
+
+/// the init pid namespace
+pub static INIT_PID_NS: Lazy<Arc<PidNamespace>> = Lazy::new(|| PidNamespace::new(None));
+
+/// the numbers of a task in its pid namespace and the ones above it,
+/// given back when the task is freed
+pub struct PidLink {
+    /// pid namespace of the task
+    pub ns: Arc<PidNamespace>,
+    tid: Tid,
+    nrs: Vec<(Arc<PidNamespace>, usize)>,
+}
+
+impl PidLink {
+    /// number the task `tid` in `ns` and all of its ancestors,
+    /// EAGAIN if one of them has no number left
+    pub fn new(ns: Arc<PidNamespace>, tid: Tid) -> Result<Self, SysError> {
+        // the numbers taken so far are given back on drop if one fails
+        let mut link = Self { ns: ns.clone(), tid, nrs: Vec::new() };
+        let mut cur = Some(ns);
+        while let Some(n) = cur {
+            if n.level > 0 {
+                let nr = n.alloc(tid)?;
+                link.nrs.push((n.clone(), nr));
+            }
+            cur = n.parent.clone();
+        }
+        Ok(link)
+    }
+}
+
+impl Drop for PidLink {
+    fn drop(&mut self) {
+        for (ns, nr) in self.nrs.iter() {
+            ns.free(self.tid, *nr);
+        }
+    }
+}
+
+/// number of `tid` in the pid namespace of the current task, 0 if the
+/// task is not visible from there
+pub fn pid_vnr(tid: Tid) -> usize {
+    match current_task() {
+        Some(task) => task.pid_ns().nr_of(tid).unwrap_or(0),
+        None => tid,
+    }
+}
+
+/// find the task numbered `nr` in the pid namespace of the current task
+pub fn find_task_by_vpid(nr: usize) -> Option<Arc<TaskControlBlock>> {
+    let tid = match current_task() {
+        Some(task) => task.pid_ns().tid_of(nr)?,
+        None => nr,
+    };
+    TASK_MANAGER.get_task(tid)
+}
+
+/// a namespace of some kind, what a /proc/<pid>/ns file refers to
+#[derive(Clone)]
+pub enum NsHandle {
+    Mnt(Arc<MntNamespace>),
+    Uts(Arc<UtsNamespace>),
+    Ipc(Arc<IpcNamespace>),
+    Pid(Arc<PidNamespace>),
+}
+
+impl NsHandle {
+    /// the clone flag of this kind of namespace
+    pub fn flag(&self) -> CloneFlags {
+        match self {
+            NsHandle::Mnt(_) => CloneFlags::NEWNS,
+            NsHandle::Uts(_) => CloneFlags::NEWUTS,
+            NsHandle::Ipc(_) => CloneFlags::NEWIPC,
+            NsHandle::Pid(_) => CloneFlags::NEWPID,
+        }
+    }
+
+    /// name of the kind, as shown in /proc/<pid>/ns
+    pub fn kind(&self) -> &'static str {
+        match self {
+            NsHandle::Mnt(_) => "mnt",
+            NsHandle::Uts(_) => "uts",
+            NsHandle::Ipc(_) => "ipc",
+            NsHandle::Pid(_) => "pid",
+        }
+    }
+
+    /// namespace id
+    pub fn id(&self) -> usize {
+        match self {
+            NsHandle::Mnt(ns) => ns.id,
+            NsHandle::Uts(ns) => ns.id,
+            NsHandle::Ipc(ns) => ns.id,
+            NsHandle::Pid(ns) => ns.id,
+        }
+    }
+
+    /// the namespace of kind `flag` that `task` lives in
+    pub fn of(task: &Arc<TaskControlBlock>, flag: CloneFlags) -> Option<Self> {
+        Some(match flag {
+            CloneFlags::NEWNS => NsHandle::Mnt(task.mnt_ns()),
+            CloneFlags::NEWUTS => NsHandle::Uts(task.uts_ns()),
+            CloneFlags::NEWIPC => NsHandle::Ipc(task.ipc_ns()),
+            CloneFlags::NEWPID => NsHandle::Pid(task.pid_ns().clone()),
+            _ => return None,
+        })
+    }
+
+    /// move `task` into this namespace, a pid namespace only applies to
+    /// the children created afterwards
+    pub fn enter(&self, task: &Arc<TaskControlBlock>) -> Result<(), SysError> {
+        match self {
+            NsHandle::Mnt(ns) => {
+                // the cwd stays at its path if the new tree has it
+                task.set_cwd(ns.translate(&task.cwd()));
+                task.set_root(ns.root());
+                task.set_mnt_ns(ns.clone());
+            }
+            NsHandle::Uts(ns) => task.set_uts_ns(ns.clone()),
+            NsHandle::Ipc(ns) => task.set_ipc_ns(ns.clone()),
+            NsHandle::Pid(ns) => {
+                // only the own namespace or one nested below it
+                if !ns.is_under(task.pid_ns()) {
+                    return Err(SysError::EINVAL);
+                }
+                task.set_pid_ns_for_children(ns.clone());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// namespaces of a task
+impl TaskControlBlock {
+    /// get the uts namespace
+    pub fn uts_ns(&self) -> Arc<UtsNamespace> {
+        self.uts_ns.lock().clone()
+    }
+    /// move to another uts namespace
+    pub fn set_uts_ns(&self, ns: Arc<UtsNamespace>) {
+        *self.uts_ns.lock() = ns;
+    }
+    /// get the ipc namespace
+    pub fn ipc_ns(&self) -> Arc<IpcNamespace> {
+        self.ipc_ns.lock().clone()
+    }
+    /// move to another ipc namespace, the SEM_UNDO adjustments made in
+    /// the old one are applied like on exit
+    pub fn set_ipc_ns(self: &Arc<Self>, ns: Arc<IpcNamespace>) {
+        let old = core::mem::replace(&mut *self.ipc_ns.lock(), ns);
+        old.sem.exit(self.pid());
+    }
+    /// get the pid namespace, fixed for the life of the task
+    pub fn pid_ns(&self) -> &Arc<PidNamespace> {
+        &self.pid_link.ns
+    }
+    /// get the pid namespace new children are created in
+    pub fn pid_ns_for_children(&self) -> Arc<PidNamespace> {
+        self.pid_ns_for_children.lock().clone()
+    }
+    /// create new children in another pid namespace
+    pub fn set_pid_ns_for_children(&self, ns: Arc<PidNamespace>) {
+        *self.pid_ns_for_children.lock() = ns;
+    }
+    /// whether the task is the init of a nested pid namespace
+    pub fn is_ns_init(self: &Arc<Self>) -> bool {
+        let ns = self.pid_ns();
+        ns.level > 0 && ns.nr_of(self.pid()) == Some(1)
+    }
+    /// the process that adopts the orphans of this one: the init of its
+    /// pid namespace, or of the one above if this is the init
+    pub fn find_reaper(self: &Arc<Self>) -> Arc<TaskControlBlock> {
+        let mut ns = Some(self.pid_ns().clone());
+        while let Some(n) = ns {
+            let reaper = n.tid_of(1)
+                .filter(|tid| *tid != self.pid())
+                .and_then(|tid| TASK_MANAGER.get_task(tid))
+                .filter(|task| !task.is_zombie());
+            if let Some(reaper) = reaper {
+                return reaper;
+            }
+            ns = n.parent.clone();
+        }
+        INITPROC.clone()
+    }
+    /// the init of a pid namespace exited: kill everyone else in it
+    pub fn zap_pid_ns(self: &Arc<Self>) {
+        let ns = self.pid_ns();
+        ns.dead.store(true, Ordering::Release);
+        for tid in ns.tids() {
+            let Some(task) = TASK_MANAGER.get_task(tid) else {
+                continue;
+            };
+            if task.is_leader() && task.pid() != self.pid() && !task.is_zombie() {
+                task.recv_sigs_process_level(
+                    SigInfo { si_signo: SIGKILL, si_code: SigInfo::KERNEL, si_pid: None }
+                );
+            }
+        }
+    }
+    /// whether `signo` from `sender` reaches this process: the init of a
+    /// pid namespace only gets the signals it has a handler for, and
+    /// from an ancestor namespace also SIGKILL and SIGSTOP
+    pub fn ns_init_accepts(self: &Arc<Self>, signo: usize, sender: &Arc<TaskControlBlock>) -> bool {
+        if self.pid() == INITPROC_PID || !self.is_ns_init() {
+            return true;
+        }
+        let from_outside = !Arc::ptr_eq(sender.pid_ns(), self.pid_ns());
+        if signo == SIGKILL || signo == SIGSTOP {
+            return from_outside;
+        }
+        self.sig_manager.lock().sig_handler[signo].is_user
+    }
+}
@@ -143,7 +143,7 @@ impl TaskControlBlock {
                         let mut siginfo_v = LinuxSigInfo::default();
                         siginfo_v.si_signo = sig.si_signo as _;
                         siginfo_v.si_code = sig.si_code;
-                        siginfo_v._pad[1] = sig.si_pid.unwrap_or(0) as i32;
+                        siginfo_v._pad[1] = sig.seen_from(self.pid_ns()).si_pid.unwrap_or(0) as i32;
                         new_sp -= size_of::<LinuxSigInfo>();
                         let dst = 
                             UserPtrRaw::new(new_sp as *mut LinuxSigInfo).ensure_write(&mut self.get_vm_space().lock()).unwrap();
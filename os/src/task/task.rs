@@ -4,19 +4,23 @@
 
 use super::fs::FdTable;
 use super::manager::{PROCESS_GROUP_MANAGER, TASK_MANAGER};
+use super::ns::{PidLink, PidNamespace, INIT_PID_NS};
 use super::{tid_alloc, schedule, INITPROC};
 use crate::fs::devfs::tty::TTY;
+use crate::fs::pidfd::pidfd_notify_exit;
+use crate::ipc::sysv::{IpcNamespace, INIT_IPC_NS};
 use crate::processor::context::{EnvContext,SumGuard};
-use crate::fs::vfs::{Dentry, DCACHE};
+use crate::fs::vfs::{mount::{MntNamespace, INIT_MNT_NS}, Dentry, DCACHE};
 use crate::fs::{Stdin, Stdout, vfs::File};
 use crate::mm::{copy_out_str, translate_uva_checked, UserPtr, UserPtrRaw, UserPtrRead, UserVmSpace, KVMSPACE};
-use crate::processor::processor::{current_processor, PROCESSORS};
+use crate::processor::{loadavg::update_nr_running, processor::{current_processor, PROCESSORS}};
 #[cfg(feature = "smp")]
 use crate::processor::schedule::TaskLoadTracker;
 use crate::sync::mutex::spin_mutex::MutexGuard;
 use crate::sync::mutex::{MutexSupport, SpinNoIrq, SpinNoIrqLock};
 use crate::sync::UPSafeCell;
 use crate::syscall::futex::{futex_manager, FutexHashKey, RobustList, RobustListHead, FUTEX_OWNER_DIED, FUTEX_TID_MASK, FUTEX_WAITERS};
+use crate::syscall::misc::{UtsNamespace, INIT_UTS_NS};
 use crate::syscall::process::CloneFlags;
 use crate::signal::{KSigAction, SigInfo, SigManager, SigSet, SIGCHLD, SIGKILL, SIGSTOP};
 use crate::syscall::SysError;
@@ -64,6 +68,9 @@ pub type Shared<T> = Arc<SpinNoIrqLock<T>>;
 /// pack Option<Arc<Spin> into a struct
 pub type SharedOption<T> = Option<Arc<SpinNoIrqLock<T>>>;
 
+/// tasks forked since boot, the processes line of /proc/stat
+pub static TOTAL_FORKS: AtomicUsize = AtomicUsize::new(0);
+
 /// new a shared object
 pub fn new_shared<T>(data: T) -> Shared<T> {
     Arc::new(SpinNoIrqLock::new(data))
@@ -80,6 +87,9 @@ pub fn new_shared_option<T>(data: Option<T>) -> SharedOption<T> {
 /// Task 
 pub struct TaskControlBlock {
     // ! immutable
+    /// numbers of the task in its pid namespace and the ones above,
+    /// declared before `tid` so they are given back first
+    pub pid_link: PidLink,
     /// task id
     pub tid: TidHandle,
     /// leader of the thread group
@@ -101,6 +111,8 @@ pub struct TaskControlBlock {
     pub exit_code: AtomicUsize,
     /// ELF file the task executes
     pub elf: Shared<Option<Arc<dyn File>>>,
+    /// argv and envp of the last execve, for /proc/<pid>/cmdline and environ
+    pub exec_args: Shared<(Vec<String>, Vec<String>)>,
     #[allow(unused)]
     /// base address of the user stack, can be used in thread create
     pub base_size: AtomicUsize,
@@ -125,6 +137,16 @@ pub struct TaskControlBlock {
     pub sig_ucontext_ptr: AtomicUsize, 
     /// current working dentry
     pub cwd: Shared<Arc<dyn Dentry>>,
+    /// root dentry, changed by chroot
+    pub root: Shared<Arc<dyn Dentry>>,
+    /// mount namespace
+    pub mnt_ns: Shared<Arc<MntNamespace>>,
+    /// uts namespace
+    pub uts_ns: Shared<Arc<UtsNamespace>>,
+    /// ipc namespace
+    pub ipc_ns: Shared<Arc<IpcNamespace>>,
+    /// pid namespace new children are created in
+    pub pid_ns_for_children: Shared<Arc<PidNamespace>>,
     /// Interval timers for the task.
     pub itimers: Shared<[ITimer; 3]>,
     #[cfg(feature = "smp")]
@@ -138,6 +160,10 @@ pub struct TaskControlBlock {
     pub priority: AtomicI32,
     /// effective user ID
     pub uid: AtomicI32,
+    /// effective group ID
+    pub gid: AtomicI32,
+    /// /proc/<pid>/oom_score_adj, kept by the leader
+    oom_score_adj: AtomicI32,
 }
 
 /// Hold a group of threads which belongs to the same process.
@@ -210,6 +236,7 @@ impl ThreadGroup {
 impl Drop for TaskControlBlock {
     fn drop(&mut self) {
         // info!("Dropping TCB {}", self.tid.0);
+        update_nr_running(Some(self.get_status()), None);
     }
 }
 
@@ -239,7 +266,8 @@ impl TaskControlBlock {
         sig_ucontext_ptr: usize,
         cpu_allowed: usize,
         processor_id: usize,
-        uid: i32
+        uid: i32,
+        gid: i32
     );
     generate_state_methods!(
         Ready,
@@ -343,6 +371,17 @@ impl TaskControlBlock {
     pub fn set_priority(&self, priority: i32) {
         self.priority.store(priority, Ordering::SeqCst);
     }
+
+    /// how much more or less likely the process is to be killed when out of
+    /// memory, -1000 to 1000
+    pub fn oom_score_adj(self: &Arc<Self>) -> i32 {
+        self.get_leader().oom_score_adj.load(Ordering::Relaxed)
+    }
+
+    /// set oom_score_adj of the process
+    pub fn set_oom_score_adj(self: &Arc<Self>, adj: i32) {
+        self.get_leader().oom_score_adj.store(adj, Ordering::Relaxed);
+    }
 }
 
 impl TaskControlBlock {
@@ -350,7 +389,7 @@ impl TaskControlBlock {
     pub fn new<T: Reader + ?Sized>(elf: &xmas_elf::ElfFile<'_, T>, elf_file: Option<Arc<dyn File>>) -> Result<Arc<Self>, SysError> {
         // note: the kernel stack must be allocated before the user page table is created
         // alloc a pid and a kernel stack in kernel space
-        let tid_handle = tid_alloc();
+        let tid_handle = tid_alloc()?;
         let pgid = tid_handle.0;
         // memory_set with elf program headers/trampoline/trap context/user stack
         let (
@@ -372,6 +411,7 @@ impl TaskControlBlock {
         };
 
         let task_control_block = Arc::new(Self {
+            pid_link: PidLink::new(INIT_PID_NS.clone(), pgid).unwrap(),
             tid: tid_handle,
             leader: None,
             is_leader: true,
@@ -398,8 +438,14 @@ impl TaskControlBlock {
             pgid: new_shared(pgid),
             sig_manager: new_shared(SigManager::new()),
             sig_ucontext_ptr: AtomicUsize::new(0),
-            cwd: new_shared(root_dentry), 
+            cwd: new_shared(root_dentry.clone()), 
+            root: new_shared(root_dentry),
+            mnt_ns: new_shared(INIT_MNT_NS.clone()),
+            uts_ns: new_shared(INIT_UTS_NS.clone()),
+            ipc_ns: new_shared(INIT_IPC_NS.clone()),
+            pid_ns_for_children: new_shared(INIT_PID_NS.clone()),
             elf: new_shared(elf_file),
+            exec_args: new_shared((Vec::new(), Vec::new())),
             itimers: new_shared([ITimer::ZERO; 3]),
             robust: UPSafeCell::new(UserPtrRaw::new(null_mut())),
             #[cfg(feature = "smp")]
@@ -408,7 +454,10 @@ impl TaskControlBlock {
             processor_id: AtomicUsize::new(current_processor().id()),
             priority: AtomicI32::new(20),
             uid: AtomicI32::new(0),
+            gid: AtomicI32::new(0),
+            oom_score_adj: AtomicI32::new(0),
         });
+        update_nr_running(None, Some(task_control_block.get_status()));
         // info!("in new");
         // task_control_block.get_trap_cx().set_arg_nth(0, user_sp); // set a0 to user_sp
         task_control_block.with_mut_thread_group(|thread_group|thread_group.push(Arc::clone(&task_control_block)));
@@ -443,6 +492,7 @@ impl TaskControlBlock {
 
         // update the executing elf file
         *self.elf.lock() = elf_file;
+        *self.exec_args.lock() = (argv.clone(), envp.clone());
         // NOTE: should do termination before switching page table, so that other
         // threads will trap in by page fault and be handled by handle_zombie
         // info!("terminating all threads except main");
@@ -464,7 +514,9 @@ impl TaskControlBlock {
 
         // substitute memory_set
         // self.with_mut_vm_space(|m| *m = vm_space);
-        *self.vm_space.exclusive_access() = new_shared(vm_space);
+        let vm_space = new_shared(vm_space);
+        current_processor().set_active_space(Some(&vm_space));
+        *self.vm_space.exclusive_access() = vm_space;
         // close fd on exec
         self.with_mut_fd_table(|fd_table|fd_table.do_close_on_exec());
 
@@ -485,9 +537,37 @@ impl TaskControlBlock {
         Ok(())
     }
     /// 
-    pub fn fork(self: &Arc<TaskControlBlock>, flag: CloneFlags) -> Arc<TaskControlBlock> {
+    pub fn fork(self: &Arc<TaskControlBlock>, flag: CloneFlags) -> Result<Arc<TaskControlBlock>, SysError> {
+        let new_ns = CloneFlags::NEWNS | CloneFlags::NEWUTS | CloneFlags::NEWIPC | CloneFlags::NEWPID;
+        if flag.intersects(new_ns) {
+            // one fs context can't live in two trees
+            if flag.contains(CloneFlags::NEWNS) && flag.intersects(CloneFlags::FS | CloneFlags::THREAD) {
+                return Err(SysError::EINVAL);
+            }
+            // threads of a process share its pid namespace and undo list
+            if flag.contains(CloneFlags::NEWPID) && flag.contains(CloneFlags::THREAD)
+                || flag.contains(CloneFlags::NEWIPC) && flag.contains(CloneFlags::SYSVSEM)
+            {
+                return Err(SysError::EINVAL);
+            }
+            if self.uid() != 0 {
+                return Err(SysError::EPERM);
+            }
+        }
+        let pid_ns = if flag.contains(CloneFlags::NEWPID) {
+            self.pid_ns_for_children().new_child()?
+        } else if flag.contains(CloneFlags::THREAD) {
+            self.pid_ns().clone()
+        } else {
+            self.pid_ns_for_children()
+        };
+        // no one joins a pid namespace whose init is gone
+        if pid_ns.is_dead() {
+            return Err(SysError::ENOMEM);
+        }
         // alloc a pid and a kernel stack in kernel space
-        let tid_handle = tid_alloc();
+        let tid_handle = tid_alloc()?;
+        let pid_link = PidLink::new(pid_ns.clone(), tid_handle.0)?;
         // ---- hold parent PCB lock
         let status = SpinNoIrqLock::new(self.get_status());
         let leader;
@@ -497,8 +577,14 @@ impl TaskControlBlock {
         let thread_group;
         let pgid;
         let cwd;
+        let root;
+        let mnt_ns;
+        let mut uts_ns;
+        let mut ipc_ns;
+        let pid_ns_for_children;
         let itimers;
         let elf;
+        let exec_args;
         let sig_manager = new_shared(
             match flag.contains(CloneFlags::SIGHAND) {
             true => SigManager::from_another(&self.sig_manager.lock()),
@@ -513,8 +599,14 @@ impl TaskControlBlock {
             thread_group = self.thread_group.clone();
             pgid = self.pgid.clone();
             cwd = self.cwd.clone();
+            root = self.root.clone();
+            mnt_ns = self.mnt_ns.clone();
+            uts_ns = self.uts_ns.clone();
+            ipc_ns = self.ipc_ns.clone();
+            pid_ns_for_children = self.pid_ns_for_children.clone();
             itimers = self.itimers.clone();
             elf = self.elf.clone();
+            exec_args = self.exec_args.clone();
         } else {
             is_leader = true;
             leader = None;
@@ -522,9 +614,31 @@ impl TaskControlBlock {
             children = new_shared(BTreeMap::new());
             thread_group = new_shared(ThreadGroup::new());
             pgid = new_shared(*self.pgid.lock());
-            cwd = new_shared(self.cwd());
+            if flag.contains(CloneFlags::NEWNS) {
+                // the child walks the same paths in its copy of the tree
+                let ns = self.mnt_ns().copy();
+                cwd = new_shared(ns.translate(&self.cwd()));
+                root = new_shared(ns.translate(&self.root()));
+                mnt_ns = new_shared(ns);
+            } else {
+                cwd = new_shared(self.cwd());
+                root = new_shared(self.root());
+                mnt_ns = new_shared(self.mnt_ns());
+            }
+            uts_ns = new_shared(self.uts_ns());
+            ipc_ns = new_shared(self.ipc_ns());
+            // the child's children land in the child's namespace
+            pid_ns_for_children = new_shared(pid_ns);
             itimers = new_shared([ITimer::ZERO; 3]);
-            elf = new_shared(self.elf.lock().clone())
+            elf = new_shared(self.elf.lock().clone());
+            exec_args = new_shared(self.exec_args.lock().clone());
+        }
+        if flag.contains(CloneFlags::NEWUTS) {
+            let ns = self.uts_ns().copy();
+            uts_ns = new_shared(ns);
+        }
+        if flag.contains(CloneFlags::NEWIPC) {
+            ipc_ns = new_shared(IpcNamespace::new());
         }
         let vm_space;
         if flag.contains(CloneFlags::VM){
@@ -546,6 +660,7 @@ impl TaskControlBlock {
         };
         let task_control_block = Arc::new(TaskControlBlock {
             tid: tid_handle,
+            pid_link,
             leader,
             is_leader,
             trap_context: UPSafeCell::new(self.get_trap_cx().clone()),
@@ -564,7 +679,13 @@ impl TaskControlBlock {
             sig_manager,
             sig_ucontext_ptr: AtomicUsize::new(0),
             cwd,
+            root,
+            mnt_ns,
+            uts_ns,
+            ipc_ns,
+            pid_ns_for_children,
             elf,
+            exec_args,
             itimers,
             robust: UPSafeCell::new(UserPtrRaw::new(null_mut())),
             #[cfg(feature = "smp")]
@@ -572,8 +693,12 @@ impl TaskControlBlock {
             cpu_allowed: AtomicUsize::new(15),
             processor_id: AtomicUsize::new(self.processor_id()),
             priority: self.priority(),
-            uid: AtomicI32::new(self.uid())
+            uid: AtomicI32::new(self.uid()),
+            gid: AtomicI32::new(self.gid()),
+            oom_score_adj: AtomicI32::new(self.oom_score_adj()),
         });
+        update_nr_running(None, Some(task_control_block.get_status()));
+        TOTAL_FORKS.fetch_add(1, Ordering::Relaxed);
         // add child except when creating a thread
         if !flag.contains(CloneFlags::THREAD) {
             //info!("fork should in this ");
@@ -584,12 +709,13 @@ impl TaskControlBlock {
         }
         // update user start 
         task_control_block.time_recorder().update_user_start(get_current_time_duration());
+        task_control_block.time_recorder().set_start_time(get_current_time_duration());
         task_control_block.thread_group.lock().push(task_control_block.clone());
         if task_control_block.is_leader() {
             PROCESS_GROUP_MANAGER.add_task_to_group(task_control_block.pgid(), &task_control_block);
         }
         TASK_MANAGER.add_task(&task_control_block);
-        task_control_block
+        Ok(task_control_block)
     }
 
     fn futex_wake(&self, addr: usize, shared: bool, vm: &mut UserVmSpace) {
@@ -741,23 +867,29 @@ impl TaskControlBlock {
         self.set_zombie();
         
         if is_last {
+            if self.is_ns_init() {
+                self.zap_pid_ns();
+            }
             self.with_mut_children(|children|{
                 if children.is_empty() {
                     return;
                 }
-                let initproc = &INITPROC;
+                let reaper = self.find_reaper();
                 for child in children.values() {
                     if child.is_zombie() {
-                        initproc.recv_sigs_process_level(
+                        reaper.recv_sigs_process_level(
                             SigInfo { si_signo: SIGCHLD, si_code: SigInfo::CLD_EXITED, si_pid: None }
                         );
                     }
-                    *child.parent.lock() = Some(Arc::downgrade(initproc));
+                    *child.parent.lock() = Some(Arc::downgrade(&reaper));
                 }
-                initproc.children.lock().extend(children.clone()); 
+                reaper.children.lock().extend(children.clone()); 
                 children.clear();
             });
-            self.with_mut_fd_table(|table|table.fd_table.clear());
+            self.with_mut_fd_table(|table|table.close_all());
+            // SEM_UNDO adjustments belong to the process
+            self.ipc_ns().sem.exit(self.pid());
+            pidfd_notify_exit(self.pid());
             self.notify_parent();
         }
     }
@@ -832,7 +964,7 @@ impl TaskControlBlock {
         });
 
         // leader will be removed by parent calling sys_waitpid
-        self.with_mut_fd_table(|table|table.fd_table.clear());
+        self.with_mut_fd_table(|table|table.close_all());
         if self.is_leader() {
             self.set_zombie();
         }else {
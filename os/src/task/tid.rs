@@ -3,6 +3,8 @@ use crate::sync::UPSafeCell;
 use alloc::vec::Vec;
 use lazy_static::*;
 use crate::sync::mutex::SpinNoIrqLock;
+use crate::syscall::SysError;
+use core::sync::atomic::{AtomicUsize, Ordering};
 
 use super::INITPROC_PID;
 
@@ -12,6 +14,9 @@ pub type Tid = usize;
 pub type Pid = Tid;
 /// main thread' tid of a thread group
 pub type PGid = Tid;
+/// tids are below this bound, tunable as kernel.pid_max
+pub static PID_MAX: AtomicUsize = AtomicUsize::new(4194304);
+
 ///Tid Allocator struct
 pub struct TidAllocator {
     current: usize,
@@ -26,13 +31,16 @@ impl TidAllocator {
             recycled: Vec::new(),
         }
     }
-    ///Allocate a tid
-    pub fn alloc(&mut self) -> TidHandle {
-        if let Some(tid) = self.recycled.pop() {
-            TidHandle(tid)  
-        } else {
+    ///Allocate a tid, EAGAIN if all tids below pid_max are in use
+    pub fn alloc(&mut self) -> Result<TidHandle, SysError> {
+        let pid_max = PID_MAX.load(Ordering::Relaxed);
+        if let Some(i) = self.recycled.iter().rposition(|tid| *tid < pid_max) {
+            Ok(TidHandle(self.recycled.swap_remove(i)))
+        } else if self.current < pid_max {
             self.current += 1;
-            TidHandle(self.current - 1)
+            Ok(TidHandle(self.current - 1))
+        } else {
+            Err(SysError::EAGAIN)
         }
     }
     ///Recycle a pid
@@ -61,7 +69,7 @@ impl Drop for TidHandle {
     }
 }
 ///Allocate a pid from PID_ALLOCATOR
-pub fn tid_alloc() -> TidHandle {
+pub fn tid_alloc() -> Result<TidHandle, SysError> {
     TID_ALLOCATOR.lock().alloc()
 }
 
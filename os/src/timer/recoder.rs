@@ -19,6 +19,8 @@ pub struct TimeRecorder {
     child_user_time: Duration,
     /// child kernel time Duration
     child_kernel_time: Duration,
+    /// time when the task was created
+    start_time: Duration,
 }
 
 impl TimeRecorder {
@@ -31,6 +33,7 @@ impl TimeRecorder {
             user_start: Duration::ZERO,
             child_user_time: Duration::ZERO,
             child_kernel_time: Duration::ZERO,
+            start_time: Duration::ZERO,
         }
     }
     /// return a pair for user and kernel time
@@ -56,6 +59,14 @@ impl TimeRecorder {
     pub fn processor_time(&self) -> Duration {
         self.kernel_time + self.user_time
     }
+    /// time when the task was created
+    pub fn start_time(&self) -> Duration {
+        self.start_time
+    }
+    /// record the creation time
+    pub fn set_start_time(&mut self, start_time: Duration) {
+        self.start_time = start_time;
+    }
     /// update user time start
     pub fn update_user_start(&mut self, user_start: Duration) {
         self.user_start = user_start;
@@ -3,7 +3,7 @@ extern crate alloc;
 use alloc::{boxed::Box, collections::BinaryHeap, sync::{Arc, Weak}};
 use log::info;
 
-use super::{ffi::TimeVal, get_current_time_duration};
+use super::{ffi::{TimeSpec, TimeVal}, get_current_time_duration};
 use spin::Lazy;
 use crate::{processor::processor::current_processor, signal::{SigInfo, SIGALRM}, sync::mutex::SpinNoIrqLock, task::task::TaskControlBlock};
 use hal::{board::MAX_PROCESSORS, instruction::{Instruction, InstructionHal}};
@@ -184,6 +184,23 @@ impl ITimerVal {
     }
 }
 
+/// `struct itimerspec`, used by timerfd
+#[derive(Debug, Clone, Copy, Default)]
+#[repr(C)]
+pub struct ITimerSpec {
+    /// Interval for periodic timer
+    pub it_interval: TimeSpec,
+    /// Time until next expiration
+    pub it_value: TimeSpec,
+}
+
+impl ITimerSpec {
+    /// check the val is valid
+    pub fn is_valid(&self) -> bool {
+        self.it_interval.is_valid() && self.it_value.is_valid()
+    }
+}
+
 #[derive (Default, Debug)]
 /// based on real time no matter the task is running the timer will work
 /// poll by SIGALRM
@@ -21,7 +21,7 @@ use hal::println;
 use hal::trap::{set_kernel_trap_entry, set_user_trap_entry, TrapContext, TrapContextHal, TrapType, TrapTypeHal};
 use hal::util::backtrace;
 use crate::mm::vm::{KernVmSpaceHal, PageFaultAccessType, UserVmSpaceHal};
-use crate::mm::KVMSPACE;
+use crate::mm::{oom, KVMSPACE};
 use crate::signal::{SigInfo, SIGILL, SIGKILL, SIGSEGV, SIGTRAP};
 use crate::utils::timer::TimerGuard;
 use hal::addr::VirtAddr;
@@ -100,10 +100,14 @@ pub async fn user_trap_handler() -> bool {
                 _ => unreachable!(),
             };
 
+            // frames may be needed, reclaim now that no address space is locked
+            crate::mm::swap::balance();
             let task = current_task().unwrap();
             let res = task.with_mut_vm_space(|vm_space| vm_space.handle_page_fault(VirtAddr::from(stval), access_type));
             match res {
                 Ok(()) => {}
+                // out of frames, take the fault again once some process is killed
+                Err(()) if oom::take_pending() && oom::pagefault_out_of_memory() => {}
                 Err(()) => {
                     log::warn!(
                         "[user_trap_handler] task pid {}, tid {}, cannot handle page fault, addr {stval:#x} access_type: {access_type:?} epc: {epc:#x}",
@@ -121,6 +125,7 @@ pub async fn user_trap_handler() -> bool {
         }
         TrapType::Timer => {
             crate::timer::timer::TIMER_MANAGER.check();
+            crate::processor::loadavg::calc_global_load();
             #[cfg(feature = "smp")]
             crate::processor::processor::current_processor().update_load_avg();
             set_next_trigger();
@@ -217,6 +222,7 @@ fn kernel_trap_handler() {
         TrapType::Timer => {
             // println!("interrupt: supervisor timer");
             crate::timer::timer::TIMER_MANAGER.check();
+            crate::processor::loadavg::calc_global_load();
             set_next_trigger();
         }
         TrapType::ExternalInterrupt => {
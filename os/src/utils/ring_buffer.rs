@@ -38,6 +38,30 @@ impl RingBuffer {
         self.state == RingBufferState::FULL
     }
 
+    pub fn capacity(&self) -> usize {
+        self.arr.len()
+    }
+
+    /// Bytes currently stored in the buffer.
+    pub fn len(&self) -> usize {
+        match self.state {
+            RingBufferState::EMPTY => 0,
+            RingBufferState::FULL => self.arr.len(),
+            RingBufferState::NORMAL => {
+                if self.tail > self.head {
+                    self.tail - self.head
+                } else {
+                    self.arr.len() - self.head + self.tail
+                }
+            }
+        }
+    }
+
+    /// Bytes that can still be written without overwriting.
+    pub fn available(&self) -> usize {
+        self.arr.len() - self.len()
+    }
+
     /// Read as much as possible to fill `buf`.
     pub fn read(&mut self, buf: &mut [u8]) -> usize {
         if self.state == RingBufferState::EMPTY || buf.is_empty() {